@@ -0,0 +1,95 @@
+use crate::auth::AuthUser;
+use crate::config::{AuthHeaderStyle, ProjectConfig};
+use crate::error::{Result, SupamigrateError};
+use crate::http::{SendLimited, ServiceKeyAuth};
+use reqwest::Client;
+use serde::Serialize;
+use tracing::debug;
+
+/// Client for the GoTrue admin API (`/auth/v1/admin`)
+#[derive(Debug, Clone)]
+pub struct AuthAdminClient {
+    client: Client,
+    api_url: String,
+    service_key: String,
+    auth_style: AuthHeaderStyle,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateUserRequest<'a> {
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phone: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password_hash: Option<&'a str>,
+    email_confirm: bool,
+    phone_confirm: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_metadata: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_metadata: Option<&'a serde_json::Value>,
+}
+
+/// Outcome of creating a single user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateUserOutcome {
+    Created,
+    AlreadyExists,
+}
+
+impl AuthAdminClient {
+    pub fn for_project(project: &ProjectConfig, service_key: String) -> Self {
+        Self {
+            client: crate::http::client(),
+            api_url: project.api_url(),
+            service_key,
+            auth_style: project.auth_header_style,
+        }
+    }
+
+    /// Create a user, preserving its id, password hash and confirmation state
+    pub async fn create_user(&self, user: &AuthUser) -> Result<CreateUserOutcome> {
+        let url = format!("{}/auth/v1/admin/users", self.api_url);
+        debug!("Creating auth user: {}", user.id);
+
+        let request = CreateUserRequest {
+            id: &user.id,
+            email: user.email.as_deref(),
+            phone: user.phone.as_deref(),
+            password_hash: user
+                .encrypted_password
+                .as_deref()
+                .filter(|hash| !hash.is_empty()),
+            email_confirm: user.email_confirmed_at.is_some(),
+            phone_confirm: user.phone_confirmed_at.is_some(),
+            user_metadata: user.raw_user_meta_data.as_ref(),
+            app_metadata: user.raw_app_meta_data.as_ref(),
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .service_key_auth(&self.service_key, self.auth_style)
+            .json(&request)
+            .send_limited()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            if status == reqwest::StatusCode::UNPROCESSABLE_ENTITY
+                && body.contains("already been registered")
+            {
+                return Ok(CreateUserOutcome::AlreadyExists);
+            }
+            return Err(SupamigrateError::Auth(format!(
+                "Failed to create user '{}': {} - {}",
+                user.id, status, body
+            )));
+        }
+
+        Ok(CreateUserOutcome::Created)
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config::ProjectConfig;
+use crate::error::{Result, SupamigrateError};
+use crate::http::SendLimited;
+use reqwest::Client;
+use serde_json::{Map, Value};
+use tracing::debug;
+
+/// Prefix shared by all auth hook settings in the project auth config
+const HOOK_KEY_PREFIX: &str = "hook_";
+
+/// Client for the project auth configuration via the Management API
+#[derive(Debug, Clone)]
+pub struct AuthConfigClient {
+    client: Client,
+    api_url: String,
+    project_ref: String,
+    access_token: String,
+}
+
+/// Auth hook settings extracted from a project's auth config
+#[derive(Debug, Clone, Default)]
+pub struct AuthHooks {
+    pub settings: Map<String, Value>,
+}
+
+impl AuthConfigClient {
+    pub fn for_project(project: &ProjectConfig, access_token: String) -> Self {
+        Self {
+            client: crate::http::client(),
+            api_url: project.management_api_url(),
+            project_ref: project.project_ref.clone(),
+            access_token,
+        }
+    }
+
+    fn auth_header(&self) -> String {
+        format!("Bearer {}", self.access_token)
+    }
+
+    fn config_url(&self) -> String {
+        format!(
+            "{}/v1/projects/{}/config/auth",
+            self.api_url, self.project_ref
+        )
+    }
+
+    /// Fetch the full auth configuration
+    pub async fn get_config(&self) -> Result<Map<String, Value>> {
+        let url = self.config_url();
+        debug!("Fetching auth config: {}", url);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("Authorization", self.auth_header())
+            .send_limited()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SupamigrateError::Auth(format!(
+                "Failed to fetch auth config: {} - {}",
+                status, body
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Apply a partial auth configuration update
+    pub async fn update_config(&self, changes: &Map<String, Value>) -> Result<()> {
+        let url = self.config_url();
+        debug!("Updating {} auth config keys", changes.len());
+
+        let response = self
+            .client
+            .patch(&url)
+            .header("Authorization", self.auth_header())
+            .json(changes)
+            .send_limited()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SupamigrateError::Auth(format!(
+                "Failed to update auth config: {} - {}",
+                status, body
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Read the configured auth hooks
+    pub async fn get_hooks(&self) -> Result<AuthHooks> {
+        Ok(AuthHooks::from_config(&self.get_config().await?))
+    }
+}
+
+impl AuthHooks {
+    /// Collect all `hook_*` settings that carry a value
+    pub fn from_config(config: &Map<String, Value>) -> Self {
+        let settings = config
+            .iter()
+            .filter(|(key, value)| key.starts_with(HOOK_KEY_PREFIX) && !value.is_null())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Self { settings }
+    }
+
+    /// Names of enabled hooks (e.g. "custom_access_token")
+    pub fn enabled(&self) -> Vec<String> {
+        self.settings
+            .iter()
+            .filter(|(_, value)| value.as_bool() == Some(true))
+            .filter_map(|(key, _)| {
+                key.strip_prefix(HOOK_KEY_PREFIX)?
+                    .strip_suffix("_enabled")
+                    .map(String::from)
+            })
+            .collect()
+    }
+
+    /// Enabled hooks whose secrets were not returned by the API and must be set by hand
+    pub fn missing_secrets(&self) -> Vec<String> {
+        self.enabled()
+            .into_iter()
+            .filter(|hook| {
+                let uri = self.settings.get(&format!("{HOOK_KEY_PREFIX}{hook}_uri"));
+                let is_http = uri
+                    .and_then(Value::as_str)
+                    .is_some_and(|u| u.starts_with("http"));
+                let has_secret = self
+                    .settings
+                    .get(&format!("{HOOK_KEY_PREFIX}{hook}_secrets"))
+                    .and_then(Value::as_str)
+                    .is_some_and(|s| !s.is_empty());
+                is_http && !has_secret
+            })
+            .collect()
+    }
+
+    /// Rewrite hook URIs that point at the source project so they target the new project
+    pub fn rewrite_urls(&mut self, from: &ProjectUrls, to: &ProjectUrls) {
+        for (key, value) in &mut self.settings {
+            if !key.ends_with("_uri") {
+                continue;
+            }
+            if let Some(uri) = value.as_str() {
+                *value = Value::String(rewrite_project_url(uri, from, to));
+            }
+        }
+    }
+}
+
+/// The URL identity of a project, used when rewriting references between projects
+#[derive(Debug, Clone)]
+pub struct ProjectUrls {
+    pub project_ref: String,
+    pub api_url: String,
+}
+
+/// Replace references to one project's API URL or ref-based hosts with another's
+pub fn rewrite_project_url(value: &str, from: &ProjectUrls, to: &ProjectUrls) -> String {
+    let value = value.replace(&from.api_url, &to.api_url);
+    // A local stack has no ref, and every host would match
+    if from.project_ref.is_empty() {
+        return value;
+    }
+    value.replace(
+        &format!("{}.supabase.", from.project_ref),
+        &format!("{}.supabase.", to.project_ref),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(project_ref: &str) -> ProjectUrls {
+        ProjectUrls {
+            project_ref: project_ref.to_string(),
+            api_url: format!("https://{}.supabase.co", project_ref),
+        }
+    }
+
+    #[test]
+    fn test_hooks_rewrite_source_urls() {
+        let config: Map<String, Value> = serde_json::from_value(serde_json::json!({
+            "hook_send_email_enabled": true,
+            "hook_send_email_uri": "https://prodref.supabase.co/functions/v1/send-email",
+            "hook_custom_access_token_enabled": true,
+            "hook_custom_access_token_uri": "pg-functions://postgres/public/access_token_hook",
+            "site_url": "https://prodref.supabase.co",
+        }))
+        .unwrap();
+
+        let mut hooks = AuthHooks::from_config(&config);
+        hooks.rewrite_urls(&urls("prodref"), &urls("stagingref"));
+
+        assert!(!hooks.settings.contains_key("site_url"));
+        assert_eq!(
+            hooks.settings["hook_send_email_uri"],
+            "https://stagingref.supabase.co/functions/v1/send-email"
+        );
+        assert_eq!(
+            hooks.settings["hook_custom_access_token_uri"],
+            "pg-functions://postgres/public/access_token_hook"
+        );
+        assert_eq!(hooks.missing_secrets(), vec!["send_email".to_string()]);
+    }
+}
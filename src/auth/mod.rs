@@ -0,0 +1,9 @@
+mod admin;
+mod hooks;
+mod sso;
+mod users;
+
+pub use admin::{AuthAdminClient, CreateUserOutcome};
+pub use hooks::{rewrite_project_url, AuthConfigClient, ProjectUrls};
+pub use sso::SsoClient;
+pub use users::{users_to_csv, AuthExport, AuthUser, AuthUsersClient};
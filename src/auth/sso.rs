@@ -0,0 +1,199 @@
+use crate::config::ProjectConfig;
+use crate::error::{Result, SupamigrateError};
+use crate::http::SendLimited;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// Client for SSO (SAML) provider configuration via the Management API
+#[derive(Debug, Clone)]
+pub struct SsoClient {
+    client: Client,
+    api_url: String,
+    project_ref: String,
+    access_token: String,
+}
+
+/// A configured SAML identity provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoProvider {
+    pub id: String,
+    #[serde(default)]
+    pub saml: Option<SamlConfig>,
+    #[serde(default)]
+    pub domains: Vec<SsoDomain>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamlConfig {
+    pub entity_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_xml: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute_mapping: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoDomain {
+    pub domain: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListProvidersResponse {
+    #[serde(default)]
+    items: Vec<SsoProvider>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateProviderRequest<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata_xml: Option<&'a str>,
+    domains: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attribute_mapping: Option<&'a serde_json::Value>,
+}
+
+impl SsoProvider {
+    /// Domain names routed to this provider
+    pub fn domain_names(&self) -> Vec<&str> {
+        self.domains.iter().map(|d| d.domain.as_str()).collect()
+    }
+}
+
+impl SsoClient {
+    pub fn for_project(project: &ProjectConfig, access_token: String) -> Self {
+        Self {
+            client: crate::http::client(),
+            api_url: project.management_api_url(),
+            project_ref: project.project_ref.clone(),
+            access_token,
+        }
+    }
+
+    fn auth_header(&self) -> String {
+        format!("Bearer {}", self.access_token)
+    }
+
+    fn providers_url(&self) -> String {
+        format!(
+            "{}/v1/projects/{}/config/auth/sso/providers",
+            self.api_url, self.project_ref
+        )
+    }
+
+    /// List configured SSO providers
+    pub async fn list_providers(&self) -> Result<Vec<SsoProvider>> {
+        let url = self.providers_url();
+        debug!("Listing SSO providers: {}", url);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("Authorization", self.auth_header())
+            .send_limited()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SupamigrateError::Auth(format!(
+                "Failed to list SSO providers: {} - {}",
+                status, body
+            )));
+        }
+
+        let providers: ListProvidersResponse = response.json().await?;
+        Ok(providers.items)
+    }
+
+    /// Create a SAML provider with the same metadata, domains and attribute
+    /// mapping, returning it as created (with its new id)
+    pub async fn create_provider(&self, provider: &SsoProvider) -> Result<SsoProvider> {
+        let saml = provider.saml.as_ref().ok_or_else(|| {
+            SupamigrateError::Auth(format!(
+                "SSO provider '{}' has no SAML configuration",
+                provider.id
+            ))
+        })?;
+
+        let url = self.providers_url();
+        debug!("Creating SSO provider for entity: {}", saml.entity_id);
+
+        let request = CreateProviderRequest {
+            kind: "saml",
+            // Prefer the metadata URL so the target keeps following IdP rotations
+            metadata_url: saml.metadata_url.as_deref(),
+            metadata_xml: if saml.metadata_url.is_some() {
+                None
+            } else {
+                saml.metadata_xml.as_deref()
+            },
+            domains: provider.domain_names(),
+            attribute_mapping: saml.attribute_mapping.as_ref(),
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .header("Authorization", self.auth_header())
+            .json(&request)
+            .send_limited()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SupamigrateError::Auth(format!(
+                "Failed to create SSO provider '{}': {} - {}",
+                saml.entity_id, status, body
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Create providers missing on this project (matched by SAML entity id).
+    /// Providers get new ids here, so the result maps each source id to the
+    /// id of its provider on this project.
+    pub async fn restore(&self, providers: &[SsoProvider]) -> Result<RestoredProviders> {
+        let existing = self.list_providers().await?;
+        let mut restored = RestoredProviders::default();
+        for provider in providers {
+            let Some(saml) = provider.saml.as_ref() else {
+                continue;
+            };
+            let matching = existing
+                .iter()
+                .find(|p| p.saml.as_ref().map(|s| &s.entity_id) == Some(&saml.entity_id));
+            let id = match matching {
+                Some(existing) => {
+                    debug!("SSO provider '{}' already exists, skipping", saml.entity_id);
+                    existing.id.clone()
+                }
+                None => {
+                    restored.created += 1;
+                    self.create_provider(provider).await?.id
+                }
+            };
+            restored.ids.insert(provider.id.clone(), id);
+        }
+
+        Ok(restored)
+    }
+}
+
+/// SSO providers restored on a project
+#[derive(Debug, Default)]
+pub struct RestoredProviders {
+    /// Providers that didn't exist yet
+    pub created: usize,
+    /// Id on the project of each source provider, by source id
+    pub ids: BTreeMap<String, String>,
+}
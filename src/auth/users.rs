@@ -0,0 +1,587 @@
+use crate::auth::sso::SsoProvider;
+use crate::db::PgRestore;
+use crate::error::{Result, SupamigrateError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// Dollar-quote tag used to embed JSON payloads in SQL
+const JSON_QUOTE_TAG: &str = "$supamigrate_json$";
+
+/// A user row from `auth.users`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthUser {
+    pub id: String,
+    /// Audience and database role of the user's tokens; older exports lack
+    /// them
+    #[serde(default)]
+    pub aud: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// bcrypt password hash (only present when exported with hashes)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_password: Option<String>,
+    pub email_confirmed_at: Option<String>,
+    pub phone_confirmed_at: Option<String>,
+    #[serde(default)]
+    pub raw_user_meta_data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub raw_app_meta_data: Option<serde_json::Value>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub last_sign_in_at: Option<String>,
+    #[serde(default)]
+    pub identities: Vec<AuthIdentity>,
+}
+
+/// A provider identity row from `auth.identities`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthIdentity {
+    pub id: String,
+    pub provider_id: String,
+    pub provider: String,
+    #[serde(default)]
+    pub identity_data: Option<serde_json::Value>,
+    pub last_sign_in_at: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Portable export of auth users
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthExport {
+    pub users: Vec<AuthUser>,
+    /// SAML providers, so SSO users can still sign in on the target
+    #[serde(default)]
+    pub sso_providers: Vec<SsoProvider>,
+    /// `auth.sessions` rows as stored, imported after their users
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<serde_json::Value>,
+    /// `auth.refresh_tokens` rows as stored, imported with the sessions
+    /// they renew
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refresh_tokens: Vec<serde_json::Value>,
+    pub includes_password_hashes: bool,
+    pub exported_at: String,
+}
+
+/// Prefix of the provider of identities signed in through an SSO provider,
+/// followed by the provider's id
+const SSO_PROVIDER_PREFIX: &str = "sso:";
+
+impl AuthExport {
+    /// Point identities signed in through SSO providers at the ids the
+    /// providers got on the target (`ids`, by source id). Fails if one of
+    /// them has no provider there, since its user couldn't sign in.
+    pub fn remap_sso_providers(&mut self, ids: &BTreeMap<String, String>) -> Result<()> {
+        let remap = |provider: &str| -> Result<Option<String>> {
+            let Some(id) = provider.strip_prefix(SSO_PROVIDER_PREFIX) else {
+                return Ok(None);
+            };
+            let target = ids.get(id).ok_or_else(|| {
+                SupamigrateError::Auth(format!(
+                    "SSO provider {} wasn't restored on the target; its users couldn't sign in",
+                    id
+                ))
+            })?;
+            Ok(Some(format!("{}{}", SSO_PROVIDER_PREFIX, target)))
+        };
+        for user in &mut self.users {
+            for identity in &mut user.identities {
+                if let Some(provider) = remap(&identity.provider)? {
+                    identity.provider = provider;
+                }
+            }
+            // GoTrue also lists the providers in the app metadata
+            let Some(meta) = user.raw_app_meta_data.as_mut() else {
+                continue;
+            };
+            if let Some(provider) = meta.get_mut("provider") {
+                if let Some(remapped) = provider.as_str().map(remap).transpose()?.flatten() {
+                    *provider = remapped.into();
+                }
+            }
+            if let Some(providers) = meta.get_mut("providers").and_then(|p| p.as_array_mut()) {
+                for provider in providers {
+                    if let Some(remapped) = provider.as_str().map(remap).transpose()?.flatten() {
+                        *provider = remapped.into();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether some users sign in through SSO providers
+    pub fn has_sso_identities(&self) -> bool {
+        self.users.iter().any(|user| {
+            user.identities
+                .iter()
+                .any(|identity| identity.provider.starts_with(SSO_PROVIDER_PREFIX))
+        })
+    }
+}
+
+/// Client for reading and writing auth users via SQL
+pub struct AuthUsersClient {
+    db: PgRestore,
+}
+
+impl AuthUsersClient {
+    pub fn new(db_url: String) -> Self {
+        Self {
+            db: PgRestore::new(db_url),
+        }
+    }
+
+    /// List all users with their identities
+    pub fn list_users(&self, include_password_hashes: bool) -> Result<Vec<AuthUser>> {
+        let password_expr = if include_password_hashes {
+            "u.encrypted_password"
+        } else {
+            "NULL"
+        };
+
+        let sql = format!(
+            r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'id', u.id::text,
+                        'aud', u.aud,
+                        'role', u.role,
+                        'email', u.email,
+                        'phone', u.phone,
+                        'encrypted_password', {},
+                        'email_confirmed_at', u.email_confirmed_at::text,
+                        'phone_confirmed_at', u.phone_confirmed_at::text,
+                        'raw_user_meta_data', u.raw_user_meta_data,
+                        'raw_app_meta_data', u.raw_app_meta_data,
+                        'created_at', u.created_at::text,
+                        'updated_at', u.updated_at::text,
+                        'last_sign_in_at', u.last_sign_in_at::text,
+                        'identities', COALESCE((
+                            SELECT json_agg(
+                                json_build_object(
+                                    'id', i.id::text,
+                                    'provider_id', i.provider_id,
+                                    'provider', i.provider,
+                                    'identity_data', i.identity_data,
+                                    'last_sign_in_at', i.last_sign_in_at::text,
+                                    'created_at', i.created_at::text,
+                                    'updated_at', i.updated_at::text
+                                )
+                            )
+                            FROM auth.identities i
+                            WHERE i.user_id = u.id
+                        ), '[]'::json)
+                    )
+                    ORDER BY u.created_at
+                ),
+                '[]'::json
+            )::text
+            FROM auth.users u
+            ",
+            password_expr
+        );
+
+        self.db
+            .query_json(&sql)
+            .map_err(|e| SupamigrateError::Auth(format!("Failed to list users: {}", e)))
+    }
+
+    /// Export all users
+    pub fn export(&self, include_password_hashes: bool) -> Result<AuthExport> {
+        let users = self.list_users(include_password_hashes)?;
+        Ok(AuthExport {
+            users,
+            sso_providers: Vec::new(),
+            sessions: Vec::new(),
+            refresh_tokens: Vec::new(),
+            includes_password_hashes: include_password_hashes,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Export users with their password hashes, identities, sessions and
+    /// refresh tokens, to move accounts to another project as they are
+    pub fn export_with_sessions(&self) -> Result<AuthExport> {
+        let mut export = self.export(true)?;
+        export.sessions = self.list_sessions()?;
+        export.refresh_tokens = self.list_refresh_tokens()?;
+        Ok(export)
+    }
+
+    /// Sessions as stored in `auth.sessions`, whatever its columns
+    pub fn list_sessions(&self) -> Result<Vec<serde_json::Value>> {
+        let sql = r"
+            SELECT COALESCE(json_agg(s ORDER BY s.created_at), '[]'::json)::text
+            FROM auth.sessions s
+        ";
+
+        self.db
+            .query_json(sql)
+            .map_err(|e| SupamigrateError::Auth(format!("Failed to list sessions: {}", e)))
+    }
+
+    /// Refresh tokens of sessions as stored in `auth.refresh_tokens`. A
+    /// session can't be renewed without them.
+    pub fn list_refresh_tokens(&self) -> Result<Vec<serde_json::Value>> {
+        let sql = r"
+            SELECT COALESCE(json_agg(r ORDER BY r.id), '[]'::json)::text
+            FROM auth.refresh_tokens r
+            WHERE r.session_id IS NOT NULL
+        ";
+
+        self.db
+            .query_json(sql)
+            .map_err(|e| SupamigrateError::Auth(format!("Failed to list refresh tokens: {}", e)))
+    }
+
+    /// Import users with their original IDs, then their provider identities
+    /// and sessions, in one transaction so a failure leaves none of them
+    /// behind. Existing users (by id) are left untouched. Returns the number
+    /// of users inserted.
+    pub fn import(&self, export: &AuthExport) -> Result<usize> {
+        if export.users.is_empty() {
+            return Ok(0);
+        }
+
+        debug!("Importing {} auth users via SQL", export.users.len());
+        let mut sql = users_import_sql(export)?;
+        sql.push_str(&links_import_sql(export)?);
+        let output = self
+            .db
+            .apply(&sql)
+            .map_err(|e| SupamigrateError::Auth(format!("Failed to import users: {}", e)))?;
+
+        // One count per insert: users, identities, then sessions and
+        // refresh tokens
+        let counts = parse_counts(&output);
+        debug!(
+            "Imported {} auth identities and {} sessions",
+            counts.get(1).copied().unwrap_or(0),
+            counts.get(2).copied().unwrap_or(0)
+        );
+        Ok(counts.first().copied().unwrap_or(0))
+    }
+
+    /// Link OAuth/SSO provider identities, and sessions with their refresh
+    /// tokens, to users that exist on the target, in one transaction.
+    /// Existing ones are skipped. Returns the number of identities inserted.
+    pub fn import_identities(&self, export: &AuthExport) -> Result<usize> {
+        let output = self
+            .db
+            .apply(&links_import_sql(export)?)
+            .map_err(|e| SupamigrateError::Auth(format!("Failed to import identities: {}", e)))?;
+
+        // One count per insert: identities, then sessions and refresh tokens
+        let counts = parse_counts(&output);
+        let identities = counts.first().copied().unwrap_or(0);
+        debug!(
+            "Imported {} auth identities and {} sessions",
+            identities,
+            counts.get(1).copied().unwrap_or(0)
+        );
+        Ok(identities)
+    }
+}
+
+/// Embed a JSON payload as a dollar-quoted SQL literal
+fn json_literal(json: &str) -> Result<String> {
+    if json.contains(JSON_QUOTE_TAG) {
+        return Err(SupamigrateError::Auth(
+            "User data contains a reserved quoting sequence".to_string(),
+        ));
+    }
+    Ok(format!("{tag}{json}{tag}", tag = JSON_QUOTE_TAG))
+}
+
+/// Build the SQL that inserts exported users
+fn users_import_sql(export: &AuthExport) -> Result<String> {
+    let users = json_literal(&serde_json::to_string(&export.users)?)?;
+
+    Ok(format!(
+        r"
+        WITH inserted AS (
+            INSERT INTO auth.users (
+                instance_id, id, aud, role, email, phone, encrypted_password,
+                email_confirmed_at, phone_confirmed_at, raw_user_meta_data,
+                raw_app_meta_data, created_at, updated_at, last_sign_in_at
+            )
+            SELECT
+                '00000000-0000-0000-0000-000000000000', id,
+                COALESCE(aud, 'authenticated'), COALESCE(role, 'authenticated'), email, phone, COALESCE(encrypted_password, ''), email_confirmed_at,
+                phone_confirmed_at, raw_user_meta_data, raw_app_meta_data,
+                COALESCE(created_at, now()), COALESCE(updated_at, now()), last_sign_in_at
+            FROM json_populate_recordset(NULL::auth.users, {users})
+            ON CONFLICT (id) DO NOTHING
+            RETURNING id
+        )
+        SELECT count(*) FROM inserted;
+        "
+    ))
+}
+
+/// Build the SQL that links identities, then sessions when the export has
+/// any, to users present on the target
+fn links_import_sql(export: &AuthExport) -> Result<String> {
+    let mut sql = identities_import_sql(export)?;
+    if !export.sessions.is_empty() {
+        sql.push_str(&sessions_import_sql(export)?);
+    }
+    Ok(sql)
+}
+
+/// Build the SQL that links provider identities to users present on the target
+fn identities_import_sql(export: &AuthExport) -> Result<String> {
+    let identities: Vec<serde_json::Value> = export
+        .users
+        .iter()
+        .flat_map(|user| {
+            user.identities.iter().map(move |identity| {
+                let mut value = serde_json::to_value(identity).unwrap_or_default();
+                value["user_id"] = serde_json::json!(user.id);
+                value
+            })
+        })
+        .collect();
+    let identities = json_literal(&serde_json::to_string(&identities)?)?;
+
+    Ok(format!(
+        r"
+        WITH inserted AS (
+            INSERT INTO auth.identities (
+                id, provider_id, user_id, provider, identity_data,
+                last_sign_in_at, created_at, updated_at
+            )
+            SELECT
+                i.id, i.provider_id, i.user_id, i.provider, i.identity_data,
+                i.last_sign_in_at, COALESCE(i.created_at, now()), COALESCE(i.updated_at, now())
+            FROM json_populate_recordset(NULL::auth.identities, {identities}) i
+            WHERE EXISTS (SELECT 1 FROM auth.users u WHERE u.id = i.user_id)
+            ON CONFLICT DO NOTHING
+            RETURNING id
+        )
+        SELECT count(*) FROM inserted;
+        "
+    ))
+}
+
+/// Build the SQL that inserts sessions of users present on the target, then
+/// the refresh tokens of the sessions inserted, and moves the tokens' id
+/// sequence past the copied ids. Rows take the target's columns, so those
+/// from other auth versions fit.
+fn sessions_import_sql(export: &AuthExport) -> Result<String> {
+    let sessions = json_literal(&serde_json::to_string(&export.sessions)?)?;
+    let refresh_tokens = json_literal(&serde_json::to_string(&export.refresh_tokens)?)?;
+
+    Ok(format!(
+        r"
+        CREATE TEMP TABLE supamigrate_sessions ON COMMIT DROP AS
+        SELECT s.* FROM json_populate_recordset(NULL::auth.sessions, {sessions}) s
+        WHERE EXISTS (SELECT 1 FROM auth.users u WHERE u.id = s.user_id)
+          AND NOT EXISTS (SELECT 1 FROM auth.sessions t WHERE t.id = s.id);
+
+        WITH inserted AS (
+            INSERT INTO auth.sessions
+            SELECT * FROM supamigrate_sessions
+            ON CONFLICT DO NOTHING
+            RETURNING id
+        )
+        SELECT count(*) FROM inserted;
+
+        WITH inserted AS (
+            INSERT INTO auth.refresh_tokens
+            SELECT r.*
+            FROM json_populate_recordset(NULL::auth.refresh_tokens, {refresh_tokens}) r
+            WHERE r.session_id IN (SELECT id FROM supamigrate_sessions)
+            ON CONFLICT DO NOTHING
+            RETURNING id
+        )
+        SELECT count(*) FROM inserted;
+
+        DO $$
+        BEGIN
+            PERFORM setval(
+                pg_get_serial_sequence('auth.refresh_tokens', 'id'),
+                GREATEST((SELECT max(id) FROM auth.refresh_tokens), 1)
+            );
+        END
+        $$;
+        "
+    ))
+}
+
+/// Parse the counts returned by the statements of an import, in order
+fn parse_counts(output: &str) -> Vec<usize> {
+    output
+        .lines()
+        .filter_map(|l| l.trim().parse().ok())
+        .collect()
+}
+
+/// Render users as CSV (identities are summarized as a provider list)
+pub fn users_to_csv(users: &[AuthUser]) -> String {
+    let mut output = String::from(
+        "id,email,phone,encrypted_password,email_confirmed_at,phone_confirmed_at,created_at,last_sign_in_at,providers,raw_user_meta_data,raw_app_meta_data\n",
+    );
+
+    for user in users {
+        let providers = user
+            .identities
+            .iter()
+            .map(|i| i.provider.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let json_field = |value: &Option<serde_json::Value>| {
+            value.as_ref().map(ToString::to_string).unwrap_or_default()
+        };
+
+        let fields = [
+            user.id.clone(),
+            user.email.clone().unwrap_or_default(),
+            user.phone.clone().unwrap_or_default(),
+            user.encrypted_password.clone().unwrap_or_default(),
+            user.email_confirmed_at.clone().unwrap_or_default(),
+            user.phone_confirmed_at.clone().unwrap_or_default(),
+            user.created_at.clone().unwrap_or_default(),
+            user.last_sign_in_at.clone().unwrap_or_default(),
+            providers,
+            json_field(&user.raw_user_meta_data),
+            json_field(&user.raw_app_meta_data),
+        ];
+
+        let line = fields
+            .iter()
+            .map(|f| csv_escape(f))
+            .collect::<Vec<_>>()
+            .join(",");
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Quote a CSV field when it contains separators, quotes or newlines
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_user() -> AuthUser {
+        AuthUser {
+            id: "8d1f0a6e-0000-0000-0000-000000000001".to_string(),
+            aud: Some("authenticated".to_string()),
+            role: Some("authenticated".to_string()),
+            email: Some("jane@example.com".to_string()),
+            phone: None,
+            encrypted_password: Some("$2a$10$abc".to_string()),
+            email_confirmed_at: Some("2024-01-01 00:00:00+00".to_string()),
+            phone_confirmed_at: None,
+            raw_user_meta_data: Some(serde_json::json!({"name": "Jane, Doe"})),
+            raw_app_meta_data: None,
+            created_at: None,
+            updated_at: None,
+            last_sign_in_at: None,
+            identities: vec![AuthIdentity {
+                id: "8d1f0a6e-0000-0000-0000-000000000002".to_string(),
+                provider_id: "12345".to_string(),
+                provider: "github".to_string(),
+                identity_data: None,
+                last_sign_in_at: None,
+                created_at: None,
+                updated_at: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_users_to_csv_escapes_fields() {
+        let csv = users_to_csv(&[sample_user()]);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("8d1f0a6e-0000-0000-0000-000000000001,jane@example.com,,"));
+        assert!(row.contains(",github,"));
+        assert!(row.contains(r#""{""name"":""Jane, Doe""}""#));
+    }
+
+    #[test]
+    fn test_import_sql_links_identities_to_users() {
+        let export = AuthExport {
+            users: vec![sample_user()],
+            sso_providers: Vec::new(),
+            sessions: Vec::new(),
+            refresh_tokens: Vec::new(),
+            includes_password_hashes: true,
+            exported_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+        let sql = users_import_sql(&export).unwrap();
+        assert!(sql.contains("\"aud\":\"authenticated\""));
+        assert!(sql.contains("COALESCE(role, 'authenticated')"));
+
+        let sql = identities_import_sql(&export).unwrap();
+        assert!(sql.contains("\"user_id\":\"8d1f0a6e-0000-0000-0000-000000000001\""));
+        assert!(sql.contains("\"provider\":\"github\""));
+
+        let export = AuthExport {
+            sessions: vec![serde_json::json!({
+                "id": "8d1f0a6e-0000-0000-0000-000000000003",
+                "user_id": "8d1f0a6e-0000-0000-0000-000000000001",
+                "aal": "aal1",
+            })],
+            ..export
+        };
+        // Sessions follow the identities in the same script
+        let sql = links_import_sql(&export).unwrap();
+        assert!(sql.contains("INSERT INTO auth.identities"));
+        assert!(sql.contains("json_populate_recordset(NULL::auth.sessions"));
+        assert!(sql.contains("\"aal\":\"aal1\""));
+        // Only tokens of sessions copied in the same transaction
+        assert!(sql.contains("WHERE r.session_id IN (SELECT id FROM supamigrate_sessions)"));
+    }
+
+    #[test]
+    fn test_remap_sso_providers() {
+        let mut user = sample_user();
+        user.identities[0].provider = "sso:old-id".to_string();
+        user.raw_app_meta_data = Some(serde_json::json!({
+            "provider": "sso:old-id",
+            "providers": ["email", "sso:old-id"],
+        }));
+        let mut export = AuthExport {
+            users: vec![user],
+            sso_providers: Vec::new(),
+            sessions: Vec::new(),
+            refresh_tokens: Vec::new(),
+            includes_password_hashes: true,
+            exported_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+        assert!(export.has_sso_identities());
+        assert!(export
+            .clone()
+            .remap_sso_providers(&BTreeMap::new())
+            .is_err());
+
+        let ids = [("old-id".to_string(), "new-id".to_string())].into();
+        export.remap_sso_providers(&ids).unwrap();
+        let user = &export.users[0];
+        assert_eq!(user.identities[0].provider, "sso:new-id");
+        assert_eq!(
+            user.raw_app_meta_data,
+            Some(serde_json::json!({
+                "provider": "sso:new-id",
+                "providers": ["email", "sso:new-id"],
+            }))
+        );
+    }
+}
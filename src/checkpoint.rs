@@ -0,0 +1,137 @@
+use crate::error::{Result, SupamigrateError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const CHECKPOINTS_DIR: &str = "~/.config/supamigrate/checkpoints";
+
+/// Steps of a migration that have completed, so `migrate --resume <id>`
+/// can skip them after a crash or network failure. Steps are named
+/// `database`, `bucket:<name>`, `function:<slug>` and `auth_hooks`. The
+/// file is saved after every step and removed once the migration succeeds.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    completed: Mutex<BTreeSet<String>>,
+}
+
+impl Checkpoint {
+    fn dir() -> PathBuf {
+        PathBuf::from(shellexpand::tilde(CHECKPOINTS_DIR).as_ref())
+    }
+
+    fn path(id: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", id))
+    }
+
+    /// Start tracking a new migration between `from` and `to` (display names)
+    pub fn start(from: &str, to: &str) -> Result<Self> {
+        let started_at = Utc::now();
+        let checkpoint = Self {
+            id: started_at.format("%Y%m%d_%H%M%S").to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            started_at,
+            completed: Mutex::new(BTreeSet::new()),
+        };
+        checkpoint.save()?;
+        Ok(checkpoint)
+    }
+
+    /// Load the checkpoint of an interrupted migration, checking it was
+    /// between the same projects
+    pub fn resume(id: &str, from: &str, to: &str) -> Result<Self> {
+        let path = Self::path(id);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            SupamigrateError::Config(format!(
+                "No checkpoint '{}' to resume ({}: {})",
+                id,
+                path.display(),
+                e
+            ))
+        })?;
+        let checkpoint: Self = serde_json::from_str(&content)?;
+        if checkpoint.from != from || checkpoint.to != to {
+            return Err(SupamigrateError::Config(format!(
+                "Checkpoint '{}' is for {} -> {}, not {} -> {}",
+                id, checkpoint.from, checkpoint.to, from, to
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn is_done(&self, step: &str) -> bool {
+        self.completed
+            .lock()
+            .is_ok_and(|completed| completed.contains(step))
+    }
+
+    /// Steps completed so far
+    pub fn completed(&self) -> Vec<String> {
+        self.completed
+            .lock()
+            .map(|completed| completed.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record `step` as done and save the checkpoint
+    pub fn complete(&self, step: &str) -> Result<()> {
+        if let Ok(mut completed) = self.completed.lock() {
+            completed.insert(step.to_string());
+        }
+        self.save()
+    }
+
+    /// Delete the checkpoint once the migration has finished
+    pub fn remove(&self) -> Result<()> {
+        let path = Self::path(&self.id);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Write via a temporary file, so a crash mid-write can't leave a
+    /// truncated checkpoint behind
+    fn save(&self) -> Result<()> {
+        std::fs::create_dir_all(Self::dir())?;
+        let path = Self::path(&self.id);
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(partial, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_steps_roundtrip() {
+        let checkpoint = Checkpoint {
+            id: "20240115_120000".to_string(),
+            from: "production".to_string(),
+            to: "staging".to_string(),
+            started_at: Utc::now(),
+            completed: Mutex::new(BTreeSet::new()),
+        };
+        checkpoint
+            .completed
+            .lock()
+            .unwrap()
+            .extend(["database".to_string(), "bucket:avatars".to_string()]);
+
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let loaded: Checkpoint = serde_json::from_str(&json).unwrap();
+        assert!(loaded.is_done("bucket:avatars"));
+        assert!(!loaded.is_done("bucket:documents"));
+        assert_eq!(loaded.completed(), vec!["bucket:avatars", "database"]);
+    }
+}
@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -21,6 +23,24 @@ pub struct Cli {
     /// Enable verbose output
     #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    /// Load environment variables from this dotenv file (default: ./.env if present)
+    #[arg(long, global = true, env = "SUPAMIGRATE_ENV_FILE")]
+    pub env_file: Option<PathBuf>,
+
+    /// Write a machine-readable JSON summary of the run to this path (for CI)
+    #[arg(long, global = true, env = "SUPAMIGRATE_SUMMARY_FILE")]
+    pub summary_file: Option<PathBuf>,
+
+    /// Connect to databases through the Supavisor session pooler (needs
+    /// pooler_region in each project's config)
+    #[arg(long, global = true, env = "SUPAMIGRATE_POOLER")]
+    pub pooler: bool,
+
+    /// Database to connect to on every project (overrides db_name; default:
+    /// postgres)
+    #[arg(long, global = true, env = "SUPAMIGRATE_DATABASE")]
+    pub database: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -28,6 +48,9 @@ pub enum Commands {
     /// Migrate between two Supabase projects
     Migrate(MigrateArgs),
 
+    /// Write a reviewable plan file for `migrate --plan`
+    Plan(PlanArgs),
+
     /// Backup a Supabase project
     Backup(BackupArgs),
 
@@ -43,11 +66,65 @@ pub enum Commands {
     /// Manage Supabase Vault secrets (encrypted database secrets)
     Vault(VaultArgs),
 
+    /// Export and import auth users
+    Auth(AuthArgs),
+
     /// Manage configuration
     Config(ConfigArgs),
 
+    /// Generate a Markdown or HTML report of a recorded run
+    Report(ReportArgs),
+
+    /// Compare database objects between projects
+    Diff(DiffArgs),
+
+    /// Run SQL or open psql against a configured project
+    Db(DbArgs),
+
+    /// Swap a shadow schema from `migrate --shadow-schema` into place
+    Cutover(CutoverArgs),
+
     /// Check system dependencies and show installation instructions
     Doctor(DoctorArgs),
+
+    /// Update supamigrate to the latest GitHub release
+    Upgrade(UpgradeArgs),
+}
+
+impl Commands {
+    /// Whether the command reads projects or settings from the config file;
+    /// the others run without one, even a broken one
+    pub fn reads_config(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Report(_)
+                | Commands::Doctor(_)
+                | Commands::Config(ConfigArgs {
+                    command: ConfigCommands::Init { .. } | ConfigCommands::Add { .. },
+                })
+        )
+    }
+
+    /// Top-level command name as typed on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Migrate(_) => "migrate",
+            Commands::Plan(_) => "plan",
+            Commands::Backup(_) => "backup",
+            Commands::Restore(_) => "restore",
+            Commands::Storage(_) => "storage",
+            Commands::Secrets(_) => "secrets",
+            Commands::Vault(_) => "vault",
+            Commands::Auth(_) => "auth",
+            Commands::Config(_) => "config",
+            Commands::Report(_) => "report",
+            Commands::Diff(_) => "diff",
+            Commands::Db(_) => "db",
+            Commands::Cutover(_) => "cutover",
+            Commands::Doctor(_) => "doctor",
+            Commands::Upgrade(_) => "upgrade",
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -57,24 +134,99 @@ pub struct DoctorArgs {
     pub fix: bool,
 }
 
-#[derive(Parser)]
+#[derive(Parser, Serialize, Deserialize)]
 pub struct MigrateArgs {
-    /// Source project reference or alias
-    #[arg(long, env = "SUPAMIGRATE_SOURCE")]
+    /// Source project reference, alias or postgres:// connection string
+    #[arg(
+        long,
+        env = "SUPAMIGRATE_SOURCE",
+        required_unless_present = "plan",
+        default_value = ""
+    )]
     pub from: String,
 
-    /// Target project reference or alias
-    #[arg(long, env = "SUPAMIGRATE_TARGET")]
+    /// Target project reference, alias or postgres:// connection string
+    #[arg(
+        long,
+        env = "SUPAMIGRATE_TARGET",
+        required_unless_present = "plan",
+        default_value = ""
+    )]
     pub to: String,
 
     /// Include storage objects
     #[arg(long, default_value = "false")]
     pub include_storage: bool,
 
+    /// Give the target's storage.objects rows the source's ids, owners and
+    /// timestamps, so tables referencing objects by id keep matching
+    #[arg(long, default_value = "false", requires = "include_storage")]
+    pub preserve_object_ids: bool,
+
     /// Include edge functions
     #[arg(long, default_value = "false")]
     pub include_functions: bool,
 
+    /// Include auth hook configuration (requires access_token on both projects)
+    #[arg(long, default_value = "false")]
+    pub include_auth_hooks: bool,
+
+    /// Include Supabase Vault secrets, decrypted on the source and recreated
+    /// on the target. Without access to the key, lists what to recreate.
+    #[arg(long, default_value = "false")]
+    pub include_vault: bool,
+
+    /// Include auth users (with their ids and password hashes), identities
+    /// and sessions, copied before the database so foreign keys to
+    /// auth.users hold
+    #[arg(long, default_value = "false", conflicts_with = "schema_only")]
+    pub include_auth: bool,
+
+    /// How auth users are created on the target: rows inserted over SQL, or
+    /// the GoTrue admin API, which copes with auth schemas of other versions
+    /// (needs the target's service_key)
+    #[arg(long, value_enum, default_value = "sql", requires = "include_auth")]
+    pub auth_mode: AuthImportMode,
+
+    /// Don't add the tables the source streams through Realtime to the
+    /// target's supabase_realtime publication
+    #[arg(long, default_value = "false")]
+    pub no_realtime: bool,
+
+    /// Don't recreate the source's Database Webhooks on the target (they're
+    /// pointed at the target project either way)
+    #[arg(long, default_value = "false")]
+    pub no_webhooks: bool,
+
+    /// Recreate the source's pg_cron jobs on the target
+    #[arg(long, default_value = "false")]
+    pub include_cron: bool,
+
+    /// Import pg_cron jobs disabled, to be enabled after cutover
+    #[arg(long, default_value = "false", requires = "include_cron")]
+    pub cron_disabled: bool,
+
+    /// Create the source's custom database roles on the target before the
+    /// database (pg_dumpall --roles-only; Supabase-managed roles are skipped)
+    #[arg(long, default_value = "false")]
+    pub include_roles: bool,
+
+    /// Abort storage migration if estimated egress exceeds this size (e.g. 50GB)
+    #[arg(long, value_parser = parse_byte_size)]
+    pub max_egress: Option<u64>,
+
+    /// Kind of database being migrated from
+    #[arg(long, value_enum, default_value = "supabase")]
+    pub source_kind: SourceKind,
+
+    /// Kind of database being migrated into
+    #[arg(long, value_enum, default_value = "supabase")]
+    pub target_kind: TargetKind,
+
+    /// Run storage and edge functions phases concurrently with the database phase
+    #[arg(long, default_value = "false")]
+    pub parallel_phases: bool,
+
     /// Schema only (no data)
     #[arg(long, default_value = "false")]
     pub schema_only: bool,
@@ -83,26 +235,184 @@ pub struct MigrateArgs {
     #[arg(long, default_value = "false")]
     pub data_only: bool,
 
+    /// Restore the public schema into this shadow schema (e.g. public_next) for
+    /// validation before `cutover`; other schemas are left untouched
+    #[arg(long, conflicts_with_all = ["data_only", "remap_schema"])]
+    pub shadow_schema: Option<String>,
+
+    /// Stream rows table by table with COPY from source straight into the
+    /// target, without a dump in between (with --data-only)
+    #[arg(
+        long,
+        default_value = "false",
+        requires = "data_only",
+        conflicts_with_all = ["remap_schema", "output_sql"]
+    )]
+    pub direct_copy: bool,
+
+    /// Empty the target tables being loaded before loading them (with
+    /// --data-only)
+    #[arg(
+        long,
+        default_value = "false",
+        requires = "data_only",
+        conflicts_with_all = ["remap_schema", "output_sql"]
+    )]
+    pub truncate: bool,
+
+    /// Stop at the first error and restore in a single transaction, rolling
+    /// the database back on failure
+    #[arg(long, default_value = "false", conflicts_with = "direct_copy")]
+    pub atomic: bool,
+
+    /// Move sequences on the target past the highest id in their column after
+    /// loading data (always done with --data-only)
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["schema_only", "shadow_schema", "remap_schema"]
+    )]
+    pub sync_sequences: bool,
+
+    /// Point Storage URLs in migrated rows (https://<source-ref>.supabase.co/storage/v1/...)
+    /// at the target project
+    #[arg(long, default_value = "false")]
+    pub rewrite_storage_urls: bool,
+
+    /// Load data even if source and target table definitions differ (with --data-only)
+    #[arg(long, default_value = "false")]
+    pub skip_drift_check: bool,
+
+    /// Restore even if the target can't install extensions the source has
+    #[arg(long, default_value = "false")]
+    pub skip_extension_check: bool,
+
+    /// Don't create the source's extensions on the target before the
+    /// restore (they're created in the source's schema by default)
+    #[arg(long, default_value = "false")]
+    pub no_create_extensions: bool,
+
+    /// Only migrate these tables (comma-separated, wildcards allowed, e.g.
+    /// public.orders,public.order_*) [default: `included_tables` from config]
+    #[arg(long, value_delimiter = ',', conflicts_with = "shadow_schema")]
+    pub include_tables: Option<Vec<String>>,
+
     /// Exclude specific tables (comma-separated)
     #[arg(long, value_delimiter = ',')]
     pub exclude_tables: Option<Vec<String>>,
 
+    /// Migrate excluded tables anyway when kept objects depend on them
+    #[arg(long, default_value = "false")]
+    pub include_dependencies: bool,
+
+    /// Keep the definition but skip the rows of these tables (comma-separated,
+    /// wildcards allowed, e.g. public.audit_*,*.events)
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_table_data: Option<Vec<String>>,
+
+    /// Extra argument passed through to pg_dump (repeatable, e.g. --dump-arg=--no-comments)
+    #[arg(long = "dump-arg", allow_hyphen_values = true)]
+    pub pg_dump_args: Vec<String>,
+
+    /// How to dump the database: pg-dump, native (no client tools needed)
+    /// or docker [default: `engine` from config, else pg-dump]
+    #[arg(long, value_enum)]
+    pub engine: Option<DumpEngine>,
+
+    /// Extra argument passed through to psql on restore (repeatable)
+    #[arg(long = "restore-arg", allow_hyphen_values = true)]
+    pub psql_args: Vec<String>,
+
+    /// What to do with ownership and grants naming roles the target lacks:
+    /// remap, drop or keep [default: `unknown_roles` from config, else remap]
+    #[arg(long, value_enum)]
+    pub unknown_roles: Option<UnknownRoles>,
+
+    #[command(flatten)]
+    pub hooks: HookArgs,
+
+    #[command(flatten)]
+    pub transform: TransformArgs,
+
+    #[command(flatten)]
+    pub timeouts: TimeoutArgs,
+
+    /// Only migrate these schemas (comma-separated, e.g. public,private_api)
+    /// instead of every schema not excluded
+    #[arg(long, value_delimiter = ',', conflicts_with = "shadow_schema")]
+    pub schemas: Option<Vec<String>>,
+
     /// Exclude specific schemas (comma-separated)
     #[arg(long, value_delimiter = ',')]
     pub exclude_schemas: Option<Vec<String>>,
 
     /// Dry run - show what would be done
     #[arg(long, default_value = "false")]
+    #[serde(skip)]
     pub dry_run: bool,
 
+    /// How to print the plan: text, or json on stdout for CI approval
+    /// steps (implies --dry-run)
+    #[arg(long, value_enum, default_value = "text")]
+    #[serde(skip)]
+    pub plan_format: PlanFormat,
+
+    /// Apply a plan file written by `supamigrate plan`, with its options,
+    /// refusing if the source schema changed since (no confirmation prompt)
+    #[arg(long, value_name = "FILE", conflicts_with = "resume")]
+    #[serde(skip)]
+    pub plan: Option<PathBuf>,
+
+    /// Where `supamigrate plan` writes the plan instead of migrating
+    #[arg(skip)]
+    #[serde(skip)]
+    pub write_plan: Option<PathBuf>,
+
+    /// Don't record the migration in supamigrate.migrations on the target
+    #[arg(long, default_value = "false")]
+    pub no_history: bool,
+
+    /// Continue an interrupted migration from its checkpoint, skipping the
+    /// steps already done (pass the same options as the original run)
+    #[arg(long, value_name = "ID")]
+    #[serde(skip)]
+    pub resume: Option<String>,
+
+    /// Write to the target even if it's marked `protected` in the config
+    #[arg(long, default_value = "false")]
+    #[serde(skip)]
+    pub allow_protected: bool,
+
     /// Skip confirmation prompt
     #[arg(short = 'y', long, default_value = "false")]
+    #[serde(skip)]
     pub yes: bool,
+
+    /// Project ref of a target that looks like production, confirming the
+    /// write without the typed prompt; required with --yes for such targets
+    #[arg(long, value_name = "REF")]
+    #[serde(skip)]
+    pub confirm_ref: Option<String>,
+
+    /// Fail instead of retrying through the session pooler when the direct
+    /// database host is unreachable
+    #[arg(long, default_value = "false")]
+    pub no_fallback: bool,
+}
+
+#[derive(Parser)]
+pub struct PlanArgs {
+    #[command(flatten)]
+    pub migrate: MigrateArgs,
+
+    /// Plan file to write
+    #[arg(short, long, default_value = "supamigrate-plan.json")]
+    pub output: PathBuf,
 }
 
 #[derive(Parser)]
 pub struct BackupArgs {
-    /// Project reference or alias to backup
+    /// Project reference, alias or postgres:// connection string to backup
     #[arg(long, env = "SUPAMIGRATE_PROJECT")]
     pub project: String,
 
@@ -114,10 +424,18 @@ pub struct BackupArgs {
     #[arg(long, default_value = "false")]
     pub include_storage: bool,
 
+    /// Layout of storage objects in the backup
+    #[arg(long, value_enum, default_value = "files")]
+    pub storage_format: StorageFormat,
+
     /// Include Supabase Vault secrets (with actual values)
     #[arg(long, default_value = "false")]
     pub include_vault: bool,
 
+    /// Include auth users (with password hashes), identities and sessions
+    #[arg(long, default_value = "false")]
+    pub include_auth: bool,
+
     /// Exclude edge functions from backup (functions included by default)
     #[arg(long, default_value = "false")]
     pub no_functions: bool,
@@ -126,9 +444,235 @@ pub struct BackupArgs {
     #[arg(long, default_value = "false")]
     pub schema_only: bool,
 
+    /// Only back up these schemas (comma-separated, e.g. public,private_api)
+    #[arg(long, value_delimiter = ',')]
+    pub schemas: Option<Vec<String>>,
+
+    /// Only back up these tables (comma-separated, wildcards allowed, e.g.
+    /// public.orders,public.order_*) [default: `included_tables` from config]
+    #[arg(long, value_delimiter = ',')]
+    pub include_tables: Option<Vec<String>>,
+
+    /// Keep the definition but skip the rows of these tables (comma-separated,
+    /// wildcards allowed, e.g. public.audit_*,*.events)
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_table_data: Option<Vec<String>>,
+
+    /// Extra argument passed through to pg_dump (repeatable, e.g. --dump-arg=--no-comments)
+    #[arg(long = "dump-arg", allow_hyphen_values = true)]
+    pub pg_dump_args: Vec<String>,
+
+    /// How to dump the database: pg-dump, native (no client tools needed)
+    /// or docker [default: `engine` from config, else pg-dump]
+    #[arg(long, value_enum)]
+    pub engine: Option<DumpEngine>,
+
+    /// Database dump format: plain SQL, or a custom- or directory-format
+    /// archive that `restore --jobs` loads in parallel with pg_restore
+    #[arg(long, value_enum, default_value = "plain")]
+    pub format: DumpFormat,
+
+    /// Parallel pg_dump jobs (needs --format directory)
+    #[arg(long, default_value = "1")]
+    pub jobs: usize,
+
     /// Compress output with gzip
     #[arg(long, default_value = "true")]
     pub compress: bool,
+
+    /// Fail instead of retrying through the session pooler when the direct
+    /// database host is unreachable
+    #[arg(long, default_value = "false")]
+    pub no_fallback: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PlanFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StorageFormat {
+    /// One file per object under storage/<bucket>/
+    Files,
+    /// One storage/<bucket>.tar.zst per bucket, with a JSON index
+    Archive,
+}
+
+/// Session timeouts for the restore, as Postgres durations (override the
+/// config defaults)
+// The fields are named after the Postgres settings they set
+#[allow(clippy::struct_field_names)]
+#[derive(Args, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TimeoutArgs {
+    /// statement_timeout while restoring (e.g. 30min; 0 disables it)
+    #[arg(long)]
+    pub statement_timeout: Option<String>,
+
+    /// lock_timeout while restoring (e.g. 10s), so DDL on a busy target fails
+    /// instead of blocking other queries
+    #[arg(long)]
+    pub lock_timeout: Option<String>,
+
+    /// idle_in_transaction_session_timeout while restoring
+    #[arg(long)]
+    pub idle_in_transaction_timeout: Option<String>,
+}
+
+/// SQL files run on the target around the database restore, after the ones
+/// in config
+#[derive(Args, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HookArgs {
+    /// SQL file to run on the target before the database is restored, e.g.
+    /// to disable triggers (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub pre_sql: Vec<PathBuf>,
+
+    /// SQL file to run on the target after the database is restored, e.g.
+    /// to refresh materialized views or re-grant roles (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub post_sql: Vec<PathBuf>,
+}
+
+/// Control over how a plain dump is transformed for the target
+#[derive(Args, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TransformArgs {
+    /// Restore the dump without the built-in transformations; rewrites and
+    /// `[transforms]` from config still apply
+    #[arg(long, default_value = "false")]
+    pub no_transform: bool,
+
+    /// Leave out one built-in transformation (repeatable)
+    #[arg(long, value_enum, value_name = "RULE", conflicts_with = "no_transform")]
+    pub transform_skip: Vec<TransformRule>,
+
+    /// Write every line the transformations changed (commented out or
+    /// rewritten) to this JSON file [default: transform-report.json]
+    #[arg(long, num_args = 0..=1, default_missing_value = "transform-report.json")]
+    pub transform_report: Option<PathBuf>,
+
+    /// Restore schema OLD as NEW, e.g. legacy=public to consolidate projects
+    /// (repeatable or comma-separated)
+    #[arg(
+        long,
+        value_name = "OLD=NEW",
+        value_delimiter = ',',
+        value_parser = parse_schema_remap
+    )]
+    pub remap_schema: Vec<(String, String)>,
+
+    /// Write the transformed dump to this file instead of restoring it, to
+    /// review or apply separately; the target is left untouched
+    #[arg(long, value_name = "FILE")]
+    pub output_sql: Option<PathBuf>,
+}
+
+impl TransformArgs {
+    /// The built-in transformations left out
+    pub fn skipped(&self) -> Vec<TransformRule> {
+        if self.no_transform {
+            TransformRule::value_variants().to_vec()
+        } else {
+            self.transform_skip.clone()
+        }
+    }
+}
+
+/// A built-in transformation of dumps
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransformRule {
+    /// Comment out ownership, grants, policies, event triggers and platform
+    /// extensions for a plain Postgres target
+    ForPostgres,
+    /// Drop a plain Postgres source's roles, grants, event triggers and
+    /// foreign extensions, and move Heroku's extension schema, for Supabase
+    FromPostgres,
+    /// Comment out what Supabase manages: the auth and storage schemas, event
+    /// triggers, extension comments, the realtime publication and anything
+    /// owned by a platform role
+    SupabaseManaged,
+    /// Drop Supabase's roles from grants and default privileges the target
+    /// can't take: platform roles always, and the API roles (anon,
+    /// authenticated, service_role) for a plain Postgres target
+    SupabaseRoles,
+    /// Rename roles from role_map and remap or drop roles the target lacks
+    Roles,
+}
+
+impl TransformRule {
+    /// Name in transform reports, as typed on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ForPostgres => "for-postgres",
+            Self::FromPostgres => "from-postgres",
+            Self::SupabaseManaged => "supabase-managed",
+            Self::SupabaseRoles => "supabase-roles",
+            Self::Roles => "roles",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DumpEngine {
+    /// The pg_dump binary
+    #[default]
+    PgDump,
+    /// Catalog queries and COPY over a direct connection; needs no client
+    /// tools, but skips grants, ownership and less common object kinds
+    Native,
+    /// pg_dump and psql from the postgres Docker image, for machines with
+    /// Docker but no client tools
+    Docker,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownRoles {
+    /// Hand the ownership or grant to the fallback role (`postgres` unless
+    /// configured otherwise)
+    #[default]
+    Remap,
+    /// Leave the statement out of the restore
+    Drop,
+    /// Restore the statement as-is and let it fail
+    Keep,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DumpFormat {
+    /// Plain SQL, restored with psql
+    #[default]
+    Plain,
+    /// pg_dump's compressed archive format, restored with pg_restore
+    Custom,
+    /// A directory with one compressed file per table; pg_dump writes it
+    /// with `--jobs` parallel connections
+    Directory,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// A Supabase project
+    Supabase,
+    /// Plain Postgres (Heroku, RDS, ...): its roles, grants and unsupported
+    /// extensions are stripped, and storage/functions phases are skipped
+    Postgres,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    /// A Supabase project
+    Supabase,
+    /// Plain Postgres (Neon, RDS, ...): Supabase roles, policies and platform
+    /// extensions are stripped, and storage/functions phases are skipped
+    Postgres,
 }
 
 #[derive(Parser)]
@@ -137,7 +681,7 @@ pub struct RestoreArgs {
     #[arg(long)]
     pub from: PathBuf,
 
-    /// Target project reference or alias
+    /// Target project reference, alias or postgres:// connection string
     #[arg(long, env = "SUPAMIGRATE_TARGET")]
     pub to: String,
 
@@ -161,9 +705,64 @@ pub struct RestoreArgs {
     #[arg(long, default_value = "false")]
     pub include_vault: bool,
 
+    /// Include auth users, identities and sessions from backup, restored
+    /// before the database
+    #[arg(long, default_value = "false")]
+    pub include_auth: bool,
+
+    /// How auth users are created: SQL inserts or the GoTrue admin API
+    #[arg(long, value_enum, default_value = "sql", requires = "include_auth")]
+    pub auth_mode: AuthImportMode,
+
+    /// Kind of database being restored into
+    #[arg(long, value_enum, default_value = "supabase")]
+    pub target_kind: TargetKind,
+
+    /// Extra argument passed through to psql on restore (repeatable)
+    #[arg(long = "restore-arg", allow_hyphen_values = true)]
+    pub psql_args: Vec<String>,
+
+    /// What to do with ownership and grants naming roles the target lacks:
+    /// remap, drop or keep [default: `unknown_roles` from config, else remap]
+    #[arg(long, value_enum)]
+    pub unknown_roles: Option<UnknownRoles>,
+
+    #[command(flatten)]
+    pub hooks: HookArgs,
+
+    #[command(flatten)]
+    pub transform: TransformArgs,
+
+    #[command(flatten)]
+    pub timeouts: TimeoutArgs,
+
+    /// Parallel pg_restore jobs for custom- and directory-format backups
+    #[arg(long, default_value = "4")]
+    pub jobs: usize,
+
+    /// Stop at the first error and restore the database in a single
+    /// transaction, rolling it back on failure (archives then restore with
+    /// one job). Auth users are imported in a transaction of their own, so
+    /// it can't be combined with --include-auth.
+    #[arg(long, default_value = "false", conflicts_with = "include_auth")]
+    pub atomic: bool,
+
+    /// Restore even if the target can't install extensions the backup creates
+    #[arg(long, default_value = "false")]
+    pub skip_extension_check: bool,
+
+    /// Write to the target even if it's marked `protected` in the config
+    #[arg(long, default_value = "false")]
+    pub allow_protected: bool,
+
     /// Skip confirmation prompt
     #[arg(short = 'y', long, default_value = "false")]
     pub yes: bool,
+
+    /// Project ref of a target that looks like production, confirming the
+    /// write without the typed prompt; required with --yes for such targets
+    #[arg(long, value_name = "REF")]
+    pub confirm_ref: Option<String>,
 }
 
 #[derive(Parser)]
@@ -198,6 +797,26 @@ pub enum StorageCommands {
         /// Number of parallel transfers
         #[arg(long, default_value = "4")]
         parallel: usize,
+
+        /// Only sync objects updated after this time (RFC 3339, e.g. 2024-06-01T00:00:00Z)
+        #[arg(long, conflicts_with = "since_last_run")]
+        since: Option<DateTime<Utc>>,
+
+        /// Only sync objects updated since the last successful sync between these projects
+        #[arg(long, default_value = "false")]
+        since_last_run: bool,
+
+        /// Abort if estimated egress exceeds this size (e.g. 50GB)
+        #[arg(long, value_parser = parse_byte_size)]
+        max_egress: Option<u64>,
+
+        /// Don't prompt when the egress budget is exceeded; abort instead
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
     },
 
     /// Download storage to local directory
@@ -228,6 +847,10 @@ pub enum StorageCommands {
         /// Target bucket
         #[arg(long)]
         bucket: String,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
     },
 }
 
@@ -266,6 +889,10 @@ pub enum SecretsCommands {
         /// Env file with secrets (NAME=value format)
         #[arg(long)]
         file: PathBuf,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
     },
 
     /// Copy secrets between projects (prompts for values)
@@ -277,6 +904,10 @@ pub enum SecretsCommands {
         /// Target project
         #[arg(long)]
         to: String,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
     },
 }
 
@@ -315,6 +946,10 @@ pub enum VaultCommands {
         /// JSON file with vault secrets
         #[arg(long)]
         file: PathBuf,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
     },
 
     /// Copy vault secrets between projects
@@ -326,9 +961,258 @@ pub enum VaultCommands {
         /// Target project
         #[arg(long)]
         to: String,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
     },
 }
 
+#[derive(Parser)]
+pub struct AuthArgs {
+    #[command(subcommand)]
+    pub command: AuthCommands,
+}
+
+#[derive(Subcommand)]
+pub enum AuthCommands {
+    /// Export auth users (with identities) to a JSON or CSV file
+    Export {
+        /// Project reference or alias
+        #[arg(long)]
+        project: String,
+
+        /// Output file path
+        #[arg(short, long, default_value = "./auth-users.json")]
+        output: PathBuf,
+
+        /// Output format [default: csv for a .csv output, else json]
+        #[arg(long, value_enum)]
+        format: Option<AuthExportFormat>,
+
+        /// Include bcrypt password hashes (handle the file as a secret!)
+        #[arg(long, default_value = "false")]
+        include_password_hashes: bool,
+    },
+
+    /// Import auth users from a JSON export
+    Import {
+        /// Target project reference or alias
+        #[arg(long)]
+        project: String,
+
+        /// JSON file produced by `auth export`
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Import mechanism
+        #[arg(long, value_enum, default_value = "sql")]
+        mode: AuthImportMode,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+
+        /// Project ref of a target that looks like production, confirming the
+        /// write without the typed prompt; required with --yes for such targets
+        #[arg(long, value_name = "REF")]
+        confirm_ref: Option<String>,
+    },
+
+    /// Copy auth hook configuration between projects (rewrites source URLs)
+    Hooks {
+        /// Source project
+        #[arg(long)]
+        from: String,
+
+        /// Target project
+        #[arg(long)]
+        to: String,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+
+        /// Project ref of a target that looks like production, confirming the
+        /// write without the typed prompt; required with --yes for such targets
+        #[arg(long, value_name = "REF")]
+        confirm_ref: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AuthExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthImportMode {
+    /// Insert rows into auth.users / auth.identities directly
+    Sql,
+    /// Create users through the GoTrue admin API (hash-preserving)
+    Api,
+}
+
+#[derive(Parser)]
+pub struct ReportArgs {
+    /// Run summary file (defaults to the most recent run)
+    #[arg(long)]
+    pub run: Option<PathBuf>,
+
+    /// Report format
+    #[arg(long, value_enum, default_value = "markdown")]
+    pub format: ReportFormat,
+
+    /// Write the report to a file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Parser)]
+pub struct DiffArgs {
+    #[command(subcommand)]
+    pub command: DiffCommands,
+}
+
+#[derive(Subcommand)]
+pub enum DiffCommands {
+    /// Compare RLS policies per table (expression, roles, command)
+    Policies {
+        /// Source project (the desired state)
+        #[arg(long)]
+        from: String,
+
+        /// Target project (compared against the source)
+        #[arg(long)]
+        to: String,
+
+        /// Only compare these schemas (comma-separated, all if omitted)
+        #[arg(long, value_delimiter = ',')]
+        schemas: Option<Vec<String>>,
+
+        /// Write SQL that makes the target match the source to this file
+        #[arg(long)]
+        sql: Option<PathBuf>,
+    },
+}
+
+#[derive(Parser)]
+pub struct DbArgs {
+    #[command(subcommand)]
+    pub command: DbCommands,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Execute a SQL file or command (stops at the first error)
+    #[command(alias = "exec")]
+    Execute {
+        /// Project reference, alias or postgres:// connection string
+        #[arg(long)]
+        project: String,
+
+        /// SQL file to run
+        #[arg(long, required_unless_present = "command", conflicts_with = "command")]
+        file: Option<PathBuf>,
+
+        /// SQL command to run (no -c: that's the global --config)
+        #[arg(long)]
+        command: Option<String>,
+
+        /// How to print the results of --command
+        #[arg(long, value_enum, default_value = "table")]
+        output: DbOutputFormat,
+
+        /// Write to the target even if it's marked `protected` in the config
+        #[arg(long, default_value = "false")]
+        allow_protected: bool,
+
+        /// Fail instead of retrying through the session pooler when the
+        /// direct database host is unreachable
+        #[arg(long, default_value = "false")]
+        no_fallback: bool,
+    },
+
+    /// Open an interactive psql session
+    Shell {
+        /// Project reference, alias or postgres:// connection string
+        #[arg(long)]
+        project: String,
+
+        /// Fail instead of retrying through the session pooler when the
+        /// direct database host is unreachable
+        #[arg(long, default_value = "false")]
+        no_fallback: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DbOutputFormat {
+    /// psql's aligned table output
+    Table,
+    /// A JSON array with one object per row (queries only)
+    Json,
+}
+
+#[derive(Parser)]
+pub struct CutoverArgs {
+    /// Project reference or alias
+    #[arg(long)]
+    pub project: String,
+
+    /// Shadow schema to promote
+    #[arg(long, default_value = "public_next")]
+    pub shadow: String,
+
+    /// Live schema to replace (renamed to <schema>_old_<timestamp>)
+    #[arg(long, default_value = "public")]
+    pub schema: String,
+
+    /// Write to the project even if it's marked `protected` in the config
+    #[arg(long, default_value = "false")]
+    pub allow_protected: bool,
+
+    /// Skip confirmation prompt
+    #[arg(short = 'y', long, default_value = "false")]
+    pub yes: bool,
+
+    /// Project ref of a project that looks like production, confirming the
+    /// write without the typed prompt; required with --yes for such projects
+    #[arg(long, value_name = "REF")]
+    pub confirm_ref: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct UpgradeArgs {
+    /// Only report whether a newer release is available
+    #[arg(long, default_value = "false")]
+    pub check: bool,
+
+    /// Install this release tag instead of the latest (e.g. v0.4.0)
+    #[arg(long)]
+    pub version: Option<String>,
+
+    /// Skip confirmation prompt
+    #[arg(short = 'y', long, default_value = "false")]
+    pub yes: bool,
+}
+
 #[derive(Parser)]
 pub struct ConfigArgs {
     #[command(subcommand)]
@@ -365,6 +1249,14 @@ pub enum ConfigCommands {
         /// Personal access token (for secrets operations)
         #[arg(long)]
         access_token: Option<String>,
+
+        /// Connect through the Supavisor pooler in this region (e.g., eu-central-1)
+        #[arg(long)]
+        pooler_region: Option<String>,
+
+        /// Database name, for self-hosted clusters (default: postgres)
+        #[arg(long)]
+        db_name: Option<String>,
     },
 
     /// List configured projects
@@ -373,3 +1265,58 @@ pub enum ConfigCommands {
     /// Show current config
     Show,
 }
+
+/// Parse an `OLD=NEW` schema remap
+fn parse_schema_remap(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        }
+        _ => Err(format!(
+            "expected OLD=NEW, e.g. legacy=public, got '{}'",
+            value
+        )),
+    }
+}
+
+/// Parse a human byte size like `50GB`, `512MiB` or `1048576` (binary units)
+fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        other => return Err(format!("unknown size unit '{}'", other)),
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1024"), Ok(1024));
+        assert_eq!(parse_byte_size("50GB"), Ok(50 << 30));
+        assert_eq!(parse_byte_size("1.5 MiB"), Ok(3 << 19));
+        assert!(parse_byte_size("10 parsecs").is_err());
+        assert!(parse_byte_size("GB").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use super::migrate::confirm_target;
+use crate::auth::{
+    users_to_csv, AuthAdminClient, AuthConfigClient, AuthExport, AuthUsersClient,
+    CreateUserOutcome, ProjectUrls, SsoClient,
+};
+use crate::cli::{AuthArgs, AuthCommands, AuthExportFormat, AuthImportMode};
+use crate::config::{Config, ProjectConfig};
+use anyhow::{Context, Result};
+use console::style;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub async fn run(config: &Config, args: AuthArgs) -> Result<()> {
+    match args.command {
+        AuthCommands::Export {
+            project,
+            output,
+            format,
+            include_password_hashes,
+        } => export_users(config, &project, &output, format, include_password_hashes).await,
+        AuthCommands::Import {
+            project,
+            file,
+            mode,
+            allow_protected,
+            yes,
+            confirm_ref,
+        } => {
+            import_users(
+                config,
+                &project,
+                &file,
+                mode,
+                allow_protected,
+                yes,
+                confirm_ref.as_deref(),
+            )
+            .await
+        }
+        AuthCommands::Hooks {
+            from,
+            to,
+            allow_protected,
+            yes,
+            confirm_ref,
+        } => {
+            copy_hooks(
+                config,
+                &from,
+                &to,
+                allow_protected,
+                yes,
+                confirm_ref.as_deref(),
+            )
+            .await
+        }
+    }
+}
+
+async fn export_users(
+    config: &Config,
+    project_name: &str,
+    output: &Path,
+    format: Option<AuthExportFormat>,
+    include_password_hashes: bool,
+) -> Result<()> {
+    let format = format.unwrap_or_else(|| {
+        if output
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        {
+            AuthExportFormat::Csv
+        } else {
+            AuthExportFormat::Json
+        }
+    });
+    let project = config.get_project(project_name)?;
+
+    let client = AuthUsersClient::new(project.db_url());
+    let mut export =
+        tokio::task::spawn_blocking(move || client.export(include_password_hashes)).await??;
+
+    // SSO providers live in the Management API, not the database
+    if let Some(access_token) = project.access_token.as_ref() {
+        let sso = SsoClient::for_project(&project, access_token.clone());
+        match sso.list_providers().await {
+            Ok(providers) => export.sso_providers = providers,
+            Err(e) => println!(
+                "{} Could not export SSO providers: {}",
+                style("⚠").yellow(),
+                e
+            ),
+        }
+    } else {
+        println!(
+            "{} Skipping SSO providers (no access_token configured)",
+            style("ℹ").blue()
+        );
+    }
+
+    if export.users.is_empty() {
+        println!(
+            "{} No auth users found in {}",
+            style("ℹ").blue(),
+            project_name
+        );
+        return Ok(());
+    }
+
+    let content = match format {
+        AuthExportFormat::Json => serde_json::to_string_pretty(&export)?,
+        AuthExportFormat::Csv => users_to_csv(&export.users),
+    };
+    fs::write(output, content)?;
+
+    println!(
+        "{} Exported {} auth users ({} identities, {} SSO providers) to {}",
+        style("✓").green(),
+        export.users.len(),
+        export
+            .users
+            .iter()
+            .map(|u| u.identities.len())
+            .sum::<usize>(),
+        export.sso_providers.len(),
+        output.display()
+    );
+    if format == AuthExportFormat::Csv && !export.sso_providers.is_empty() {
+        println!(
+            "  {} CSV exports list identity providers only; use JSON to carry SSO configuration",
+            style("ℹ").blue()
+        );
+    }
+    if include_password_hashes {
+        println!(
+            "  {} The export contains password hashes - store it securely!",
+            style("⚠").yellow()
+        );
+    }
+
+    Ok(())
+}
+
+async fn import_users(
+    config: &Config,
+    project_name: &str,
+    file: &Path,
+    mode: AuthImportMode,
+    allow_protected: bool,
+    yes: bool,
+    confirm_ref: Option<&str>,
+) -> Result<()> {
+    let project = config.get_project(project_name)?;
+    config.ensure_writable(project_name, &project, allow_protected)?;
+
+    let content = fs::read_to_string(file)?;
+    let mut export: AuthExport = serde_json::from_str(&content)?;
+
+    if export.users.is_empty() {
+        println!("{} No users found in file", style("ℹ").blue());
+        return Ok(());
+    }
+
+    println!(
+        "\n{} Importing {} auth users to {} (mode: {:?})",
+        style("👤").bold(),
+        export.users.len(),
+        project_name,
+        mode
+    );
+    if !export.includes_password_hashes {
+        println!(
+            "  {} Export has no password hashes - users will need to reset their passwords",
+            style("⚠").yellow()
+        );
+    }
+
+    if !confirm_target(config, project_name, &project, "Proceed?", yes, confirm_ref)? {
+        println!("{} Import cancelled", style("✗").red());
+        return Ok(());
+    }
+
+    // Providers come first: they get new ids, which SSO identities must
+    // point at before they're imported
+    let mut sso_ids = BTreeMap::new();
+    if !export.sso_providers.is_empty() {
+        if let Some(access_token) = project.access_token.as_ref() {
+            let sso = SsoClient::for_project(&project, access_token.clone());
+            let restored = sso.restore(&export.sso_providers).await?;
+            println!(
+                "{} Created {} SSO providers (skipped {} existing)",
+                style("✓").green(),
+                restored.created,
+                restored.ids.len() - restored.created
+            );
+            sso_ids = restored.ids;
+        } else if !export.has_sso_identities() {
+            println!(
+                "{} Skipping {} SSO providers (target has no access_token configured)",
+                style("⚠").yellow(),
+                export.sso_providers.len()
+            );
+        }
+    }
+    if export.has_sso_identities() {
+        export
+            .remap_sso_providers(&sso_ids)
+            .context("SSO users need their providers on the target; configure its access_token")?;
+    }
+
+    let created = import_auth_users(&export, &project, project.db_url(), mode).await?;
+    let skipped = export.users.len() - created;
+
+    println!(
+        "\n{} Imported {} auth users (skipped {} existing)",
+        style("✓").green(),
+        created,
+        skipped
+    );
+
+    Ok(())
+}
+
+/// Create the users of `export` on `project` (connected to over `db_url`),
+/// then link their identities and sessions, which only SQL can. Returns the
+/// number of users created; existing ones are skipped.
+pub async fn import_auth_users(
+    export: &AuthExport,
+    project: &ProjectConfig,
+    db_url: String,
+    mode: AuthImportMode,
+) -> Result<usize> {
+    let sql = AuthUsersClient::new(db_url);
+    match mode {
+        AuthImportMode::Sql => {
+            // psql blocks, so it runs off the async runtime
+            let export = export.clone();
+            Ok(tokio::task::spawn_blocking(move || sql.import(&export)).await??)
+        }
+        AuthImportMode::Api => {
+            let service_key = project.service_key.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("Project requires service_key for admin API imports")
+            })?;
+            let client = AuthAdminClient::for_project(project, service_key.clone());
+
+            let mut created = 0;
+            for user in &export.users {
+                if client.create_user(user).await? == CreateUserOutcome::Created {
+                    created += 1;
+                }
+            }
+
+            // The admin API only creates email/phone identities; link OAuth ones via SQL
+            let export = export.clone();
+            let identities =
+                tokio::task::spawn_blocking(move || sql.import_identities(&export)).await??;
+            println!(
+                "{} Linked {} provider identities",
+                style("✓").green(),
+                identities
+            );
+
+            Ok(created)
+        }
+    }
+}
+
+async fn copy_hooks(
+    config: &Config,
+    from_name: &str,
+    to_name: &str,
+    allow_protected: bool,
+    yes: bool,
+    confirm_ref: Option<&str>,
+) -> Result<()> {
+    let source = config.get_project(from_name)?;
+    let target = config.get_project(to_name)?;
+    config.ensure_writable(to_name, &target, allow_protected)?;
+
+    let question = format!(
+        "Copy auth hook configuration from {} to {}?",
+        from_name, to_name
+    );
+    if !confirm_target(config, to_name, &target, &question, yes, confirm_ref)? {
+        println!("{} Cancelled", style("✗").red());
+        return Ok(());
+    }
+
+    let count = copy_auth_hooks(&source, &target).await?;
+    println!(
+        "\n{} Copied {} auth hook settings to {}",
+        style("✓").green(),
+        count,
+        to_name
+    );
+
+    Ok(())
+}
+
+/// Copy auth hook settings between projects (called by migrate command).
+/// Returns the number of settings applied.
+pub async fn copy_auth_hooks(source: &ProjectConfig, target: &ProjectConfig) -> Result<usize> {
+    let source_token = source
+        .access_token
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Source project requires access_token for auth hooks"))?;
+    let target_token = target
+        .access_token
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Target project requires access_token for auth hooks"))?;
+
+    let source_client = AuthConfigClient::for_project(source, source_token.clone());
+    let mut hooks = source_client.get_hooks().await?;
+
+    if hooks.settings.is_empty() {
+        println!("{} No auth hooks configured on source", style("ℹ").blue());
+        return Ok(0);
+    }
+
+    hooks.rewrite_urls(
+        &ProjectUrls {
+            project_ref: source.project_ref.clone(),
+            api_url: source.api_url(),
+        },
+        &ProjectUrls {
+            project_ref: target.project_ref.clone(),
+            api_url: target.api_url(),
+        },
+    );
+
+    for hook in hooks.enabled() {
+        println!("  {} {}", style("•").cyan(), hook);
+    }
+
+    let target_client = AuthConfigClient::for_project(target, target_token.clone());
+    target_client.update_config(&hooks.settings).await?;
+
+    for hook in hooks.missing_secrets() {
+        println!(
+            "  {} Hook '{}' needs its secret set manually on the target (hook_{}_secrets)",
+            style("⚠").yellow(),
+            hook,
+            hook
+        );
+    }
+
+    Ok(hooks.settings.len())
+}
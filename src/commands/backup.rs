@@ -1,10 +1,14 @@
-use crate::cli::BackupArgs;
+use crate::auth::AuthUsersClient;
+use crate::branches::{resolve_project, split_branch};
+use crate::cli::{BackupArgs, DumpFormat, StorageFormat};
 use crate::commands::secrets::backup_secrets;
 use crate::commands::vault::backup_vault;
-use crate::config::Config;
-use crate::db::PgDump;
+use crate::config::{display_name, is_connection_url, Config};
+use crate::db::{connect_url, PgDump};
 use crate::functions::FunctionsClient;
+use crate::hooks::{self, Hook};
 use crate::storage::{StorageClient, StorageTransfer};
+use crate::summary::RunSummary;
 use anyhow::Result;
 use chrono::Utc;
 use console::style;
@@ -12,64 +16,154 @@ use std::fs;
 use std::io::Write;
 use tracing::info;
 
-pub async fn run(args: BackupArgs) -> Result<()> {
-    let config = Config::load(None)?;
-    let project = config.get_project(&args.project)?;
+pub async fn run(config: &Config, args: BackupArgs, summary: &mut RunSummary) -> Result<()> {
+    let project = resolve_project(config, &args.project).await?;
 
-    // Create output directory with timestamp
-    let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-    let backup_dir = args.output.join(format!("{}_{}", args.project, timestamp));
+    if args.jobs > 1 && args.format != DumpFormat::Directory {
+        anyhow::bail!("--jobs needs --format directory; pg_dump only parallelizes that format");
+    }
+
+    // Create output directory named by the configured template. Connection
+    // strings have no alias, so the project ref stands in for it; branches
+    // are named <alias>-<branch>.
+    let alias = if is_connection_url(&args.project) {
+        project.project_ref.clone()
+    } else if let Some((parent, branch)) = split_branch(&args.project) {
+        format!("{}-{}", parent, branch.replace('/', "-"))
+    } else {
+        args.project.clone()
+    };
+    let backup_dir = args.output.join(config.defaults.backup_path(
+        &alias,
+        &project.project_ref,
+        Utc::now(),
+    )?);
     fs::create_dir_all(&backup_dir)?;
 
     let include_functions = !args.no_functions;
+    let included_tables = config
+        .defaults
+        .included_tables(args.include_tables.as_deref());
+    let masks = config.masks()?;
+
+    let plan = [
+        (
+            "Project",
+            format!("{} ({})", display_name(&args.project), project.project_ref),
+        ),
+        ("Output", backup_dir.display().to_string()),
+        ("Schema only", args.schema_only.to_string()),
+        (
+            "Schemas",
+            args.schemas
+                .as_ref()
+                .map_or_else(|| "all".to_string(), |schemas| schemas.join(", ")),
+        ),
+        (
+            "Tables",
+            if included_tables.is_empty() {
+                "all".to_string()
+            } else {
+                included_tables.join(", ")
+            },
+        ),
+        (
+            "Masked tables",
+            if masks.is_empty() {
+                "-".to_string()
+            } else {
+                masks.keys().cloned().collect::<Vec<_>>().join(", ")
+            },
+        ),
+        ("Include storage", args.include_storage.to_string()),
+        (
+            "Storage format",
+            format!("{:?}", args.storage_format).to_lowercase(),
+        ),
+        ("Include functions", include_functions.to_string()),
+        ("Include vault", args.include_vault.to_string()),
+        ("Include auth", args.include_auth.to_string()),
+        ("Format", format!("{:?}", args.format).to_lowercase()),
+        ("Compress", args.compress.to_string()),
+    ];
 
     println!("\n{} Backup Plan", style("📋").bold());
-    println!("  Project: {} ({})", args.project, project.project_ref);
-    println!("  Output: {}", backup_dir.display());
-    println!("  Schema only: {}", args.schema_only);
-    println!("  Include storage: {}", args.include_storage);
-    println!("  Include functions: {}", include_functions);
-    println!("  Include vault: {}", args.include_vault);
-    println!("  Compress: {}", args.compress);
+    for (key, value) in &plan {
+        println!("  {}: {}", key, value);
+        summary.plan(key, value);
+    }
+    summary.artifact(&backup_dir);
+
+    hooks::context("project", display_name(&args.project));
+    hooks::context("project_ref", &project.project_ref);
+    hooks::context("backup_path", backup_dir.display());
+    hooks::run(Hook::BeforeBackup, summary).await?;
 
     // Database backup
     println!("\n{} Backing up database...", style("🗄️").bold());
-
-    let dump_file = if args.compress {
-        backup_dir.join("database.sql.gz")
-    } else {
-        backup_dir.join("database.sql")
+    summary.begin_phase("database");
+
+    let db_url = connect_url(&project, !args.no_fallback)?;
+    let dump = PgDump::new(db_url.clone())
+        .engine(config.defaults.engine(args.engine))
+        .format(args.format)
+        .jobs(args.jobs)
+        .schemas(args.schemas.clone().unwrap_or_default())
+        .exclude_schemas(config.defaults.excluded_schemas.clone())
+        .include_tables(included_tables.clone())
+        .exclude_table_data(
+            config
+                .defaults
+                .excluded_table_data(args.exclude_table_data.as_deref()),
+        )
+        .always_exclude_table_data(config.defaults.always_excluded_table_data.clone())
+        .row_filters(config.filters.clone())
+        .masks(masks)
+        .extra_args(config.defaults.pg_dump_args(&args.pg_dump_args))
+        .schema_only(args.schema_only);
+
+    // Archives are compressed by pg_dump itself
+    let compressed = args.compress && args.format == DumpFormat::Plain;
+    let dump_file = match args.format {
+        DumpFormat::Custom => backup_dir.join("database.dump"),
+        DumpFormat::Directory => backup_dir.join("database"),
+        DumpFormat::Plain if compressed => backup_dir.join("database.sql.gz"),
+        DumpFormat::Plain => backup_dir.join("database.sql"),
     };
 
-    let dump = PgDump::new(project.db_url())
-        .exclude_schemas(config.defaults.excluded_schemas.clone())
-        .schema_only(args.schema_only)
-        .dump_to_string()?;
-
-    if args.compress {
-        use std::io::BufWriter;
-        let file = fs::File::create(&dump_file)?;
-        let mut encoder =
-            flate2::write::GzEncoder::new(BufWriter::new(file), flate2::Compression::default());
-        encoder.write_all(dump.as_bytes())?;
-        encoder.finish()?;
+    let dump_bytes = if args.format == DumpFormat::Plain {
+        let dump = dump.dump_to_string()?;
+        if compressed {
+            use std::io::BufWriter;
+            let file = fs::File::create(&dump_file)?;
+            let mut encoder =
+                flate2::write::GzEncoder::new(BufWriter::new(file), flate2::Compression::default());
+            encoder.write_all(dump.as_bytes())?;
+            encoder.finish()?;
+        } else {
+            fs::write(&dump_file, &dump)?;
+        }
+        dump.len() as u64
     } else {
-        fs::write(&dump_file, &dump)?;
-    }
+        dump.dump_to_file(&dump_file)?;
+        artifact_size(&dump_file)?
+    };
 
     info!("Database backup saved to: {}", dump_file.display());
     println!("{} Database backup complete!", style("✓").green());
+    summary.stat("dump_bytes", dump_bytes);
+    summary.end_phase(dump_file.display());
 
     // Edge Functions backup (included by default)
     if include_functions {
         println!("\n{} Backing up edge functions...", style("⚡").bold());
+        summary.begin_phase("functions");
 
         let service_key = project.service_key.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Project requires service_key for edge functions backup")
         })?;
 
-        let functions_client =
-            FunctionsClient::new(project.project_ref.clone(), service_key.clone());
+        let functions_client = FunctionsClient::for_project(&project, service_key.clone());
 
         let functions = functions_client.backup_all().await?;
         let functions_dir = backup_dir.join("functions");
@@ -80,12 +174,14 @@ pub async fn run(args: BackupArgs) -> Result<()> {
             fs::create_dir_all(&func_dir)?;
 
             // Save function metadata
+            let bundle_file = func.bundle_file_name();
             let metadata = serde_json::json!({
                 "slug": func.slug,
                 "name": func.name,
                 "verify_jwt": func.verify_jwt,
                 "entrypoint_path": func.entrypoint_path,
                 "import_map_path": func.import_map_path,
+                "bundle": bundle_file,
             });
             fs::write(
                 func_dir.join("metadata.json"),
@@ -101,6 +197,11 @@ pub async fn run(args: BackupArgs) -> Result<()> {
                 fs::write(&file_path, &file.content)?;
             }
 
+            // Keep the original bundle so restores can deploy it unchanged
+            if let (Some(name), Some(bundle)) = (bundle_file, &func.bundle) {
+                fs::write(func_dir.join(name), bundle)?;
+            }
+
             info!("Backed up function: {}", func.slug);
         }
 
@@ -109,14 +210,17 @@ pub async fn run(args: BackupArgs) -> Result<()> {
             style("✓").green(),
             functions.len()
         );
+        summary.stat("functions", functions.len() as u64);
+        summary.end_phase(format!("{} functions", functions.len()));
     }
 
     // Secrets backup (if access_token available)
     let mut secrets_count = 0;
     if project.has_secrets_access() {
         println!("\n{} Backing up secrets...", style("🔐").bold());
+        summary.begin_phase("secrets");
 
-        match backup_secrets(&args.project).await? {
+        match backup_secrets(&project).await? {
             Some(secrets_backup) => {
                 secrets_count = secrets_backup.secrets.len();
                 let secrets_file = backup_dir.join("secrets.json");
@@ -138,6 +242,8 @@ pub async fn run(args: BackupArgs) -> Result<()> {
                 );
             }
         }
+        summary.stat("secrets", secrets_count as u64);
+        summary.end_phase(format!("{} secret names", secrets_count));
     } else {
         println!(
             "\n{} Skipping secrets backup (no access_token configured)",
@@ -152,15 +258,16 @@ pub async fn run(args: BackupArgs) -> Result<()> {
     let mut vault_count = 0;
     if args.include_vault {
         println!("\n{} Backing up vault secrets...", style("🔐").bold());
+        summary.begin_phase("vault");
 
-        match backup_vault(&args.project) {
+        match backup_vault(&project) {
             Ok(Some(vault_backup)) => {
                 vault_count = vault_backup.secrets.len();
                 let vault_file = backup_dir.join("vault_secrets.json");
                 fs::write(&vault_file, serde_json::to_string_pretty(&vault_backup)?)?;
                 info!("Vault backup saved to: {}", vault_file.display());
                 println!(
-                    "{} Vault backup complete: {} secrets (with values)",
+                    "{} Vault backup complete: {} vault secrets (with values)",
                     style("✓").green(),
                     vault_count
                 );
@@ -177,27 +284,71 @@ pub async fn run(args: BackupArgs) -> Result<()> {
             }
             Err(e) => {
                 println!("{} Vault backup failed: {}", style("⚠").yellow(), e);
+                summary.warn(format!("Vault backup failed: {}", e));
             }
         }
+        summary.stat("vault_secrets", vault_count as u64);
+        summary.end_phase(format!("{} vault secrets", vault_count));
+    }
+
+    // Auth users backup (if --include-auth flag is set)
+    let mut auth_users = 0;
+    if args.include_auth {
+        println!("\n{} Backing up auth users...", style("👤").bold());
+        summary.begin_phase("auth");
+
+        // psql blocks, so it runs off the async runtime
+        let client = AuthUsersClient::new(db_url);
+        let export = tokio::task::spawn_blocking(move || client.export_with_sessions()).await??;
+        auth_users = export.users.len();
+        let auth_file = backup_dir.join("auth_users.json");
+        fs::write(&auth_file, serde_json::to_string_pretty(&export)?)?;
+        info!("Auth backup saved to: {}", auth_file.display());
+        println!(
+            "{} Auth backup complete: {} users ({} sessions, {} refresh tokens)",
+            style("✓").green(),
+            auth_users,
+            export.sessions.len(),
+            export.refresh_tokens.len()
+        );
+        println!(
+            "  {} auth_users.json contains password hashes - store securely!",
+            style("⚠").yellow()
+        );
+        summary.stat("users", auth_users as u64);
+        summary.end_phase(format!("{} users", auth_users));
     }
 
     // Storage backup
     if args.include_storage {
         println!("\n{} Backing up storage...", style("📦").bold());
+        summary.begin_phase("storage");
 
         let service_key = project
             .service_key
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Project requires service_key for storage backup"))?;
 
-        let storage = StorageClient::new(project.api_url(), service_key.clone());
+        let storage = StorageClient::for_project(&project, service_key.clone());
         let storage_dir = backup_dir.join("storage");
         fs::create_dir_all(&storage_dir)?;
 
-        let transfer = StorageTransfer::new(storage).parallel(config.defaults.parallel_transfers);
+        // Record bucket settings so restore can recreate them faithfully
+        let buckets = storage.list_buckets().await?;
+        fs::write(
+            backup_dir.join("buckets.json"),
+            serde_json::to_string_pretty(&buckets)?,
+        )?;
+
+        let transfer = StorageTransfer::new(storage)
+            .parallel(config.defaults.parallel_transfers)
+            .ranged_threshold(config.defaults.ranged_download_threshold())
+            .archive(args.storage_format == StorageFormat::Archive);
 
         let stats = transfer.download_all(&storage_dir).await?;
         println!("{} Storage backup complete: {}", style("✓").green(), stats);
+        stats.record(summary);
+        summary.end_phase(&stats);
     }
 
     // Write metadata
@@ -211,18 +362,31 @@ pub async fn run(args: BackupArgs) -> Result<()> {
         secrets_count,
         include_vault: vault_count > 0,
         vault_count,
-        compressed: args.compress,
+        include_auth: args.include_auth,
+        auth_users,
+        compressed,
+        format: args.format,
     };
 
     let metadata_file = backup_dir.join("metadata.json");
     fs::write(&metadata_file, serde_json::to_string_pretty(&metadata)?)?;
 
+    hooks::run(Hook::AfterBackup, summary).await?;
+
     println!("\n{} Backup completed successfully!", style("🎉").bold());
     println!("  Location: {}", backup_dir.display());
 
     Ok(())
 }
 
+/// Bytes in a dump file, or in all files of a directory-format dump
+fn artifact_size(path: &std::path::Path) -> std::io::Result<u64> {
+    if !path.is_dir() {
+        return Ok(fs::metadata(path)?.len());
+    }
+    fs::read_dir(path)?.try_fold(0, |total, entry| Ok(total + artifact_size(&entry?.path())?))
+}
+
 #[derive(serde::Serialize)]
 struct BackupMetadata {
     project_ref: String,
@@ -234,5 +398,8 @@ struct BackupMetadata {
     secrets_count: usize,
     include_vault: bool,
     vault_count: usize,
+    include_auth: bool,
+    auth_users: usize,
     compressed: bool,
+    format: DumpFormat,
 }
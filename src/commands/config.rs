@@ -1,10 +1,10 @@
 use crate::cli::{ConfigArgs, ConfigCommands};
-use crate::config::{generate_sample_config, Config, ProjectConfig};
+use crate::config::{generate_sample_config, AuthHeaderStyle, Config, ProjectConfig};
 use anyhow::Result;
 use console::style;
 use std::fs;
 
-pub fn run(args: ConfigArgs) -> Result<()> {
+pub fn run(config: &Config, args: ConfigArgs) -> Result<()> {
     match args.command {
         ConfigCommands::Init { output } => init_config(&output),
         ConfigCommands::Add {
@@ -13,15 +13,25 @@ pub fn run(args: ConfigArgs) -> Result<()> {
             db_password,
             service_key,
             access_token,
+            pooler_region,
+            db_name,
         } => add_project(
             &alias,
             &project_ref,
             &db_password,
             service_key,
             access_token,
+            pooler_region,
+            db_name,
         ),
-        ConfigCommands::List => list_projects(),
-        ConfigCommands::Show => show_config(),
+        ConfigCommands::List => {
+            list_projects(config);
+            Ok(())
+        }
+        ConfigCommands::Show => {
+            show_config(config);
+            Ok(())
+        }
     }
 }
 
@@ -54,6 +64,8 @@ fn add_project(
     db_password: &str,
     service_key: Option<String>,
     access_token: Option<String>,
+    pooler_region: Option<String>,
+    db_name: Option<String>,
 ) -> Result<()> {
     let config_path = std::path::Path::new("./supamigrate.toml");
 
@@ -66,11 +78,23 @@ fn add_project(
     let project = ProjectConfig {
         project_ref: project_ref.to_string(),
         db_password: db_password.to_string(),
+        local: false,
+        protected: false,
+        typed_confirmation: None,
         service_key,
         db_host: None,
         db_port: None,
+        db_name,
         api_url: None,
+        storage_url: None,
+        management_api_url: None,
+        auth_header_style: AuthHeaderStyle::default(),
         access_token,
+        pooler: pooler_region.is_some(),
+        pooler_region,
+        sslmode: None,
+        sslrootcert: None,
+        connection_url: None,
     };
 
     config.add_project(alias.to_string(), project);
@@ -86,9 +110,7 @@ fn add_project(
     Ok(())
 }
 
-fn list_projects() -> Result<()> {
-    let config = Config::load(None)?;
-
+fn list_projects(config: &Config) {
     println!("\n{} Configured Projects", style("📋").bold());
     println!("{:-<50}", "");
 
@@ -117,13 +139,9 @@ fn list_projects() -> Result<()> {
             );
         }
     }
-
-    Ok(())
 }
 
-fn show_config() -> Result<()> {
-    let config = Config::load(None)?;
-
+fn show_config(config: &Config) {
     println!("\n{} Current Configuration", style("⚙️").bold());
     println!("{:-<50}", "");
 
@@ -141,6 +159,12 @@ fn show_config() -> Result<()> {
     println!("\nProjects:");
     for (alias, project) in &config.projects {
         println!("  [{}]", alias);
+        if project.local {
+            println!("    local: true");
+        }
+        if project.protected {
+            println!("    protected: true");
+        }
         println!("    project_ref: {}", project.project_ref);
         println!("    db_password: ****");
         println!(
@@ -165,7 +189,14 @@ fn show_config() -> Result<()> {
         if let Some(port) = &project.db_port {
             println!("    db_port: {}", port);
         }
+        if let Some(name) = &project.db_name {
+            println!("    db_name: {}", name);
+        }
+        if project.pooler {
+            println!(
+                "    pooler: {}",
+                project.pooler_region.as_deref().unwrap_or("(no region)")
+            );
+        }
     }
-
-    Ok(())
 }
@@ -0,0 +1,77 @@
+use crate::cli::CutoverArgs;
+use crate::commands::migrate::confirm_target;
+use crate::config::{display_name, Config};
+use crate::db::sql::{quote_ident, quote_literal};
+use crate::db::PgRestore;
+use anyhow::Result;
+use chrono::Utc;
+use console::style;
+
+pub fn run(config: &Config, args: &CutoverArgs) -> Result<()> {
+    let project = config.get_project(&args.project)?;
+    config.ensure_writable(&args.project, &project, args.allow_protected)?;
+    let db = PgRestore::new(project.db_url());
+
+    let exists = db.query(&format!(
+        "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = {})",
+        quote_literal(&args.shadow)
+    ))?;
+    if exists != "t" {
+        anyhow::bail!(
+            "Shadow schema '{}' not found in {}. Run `migrate --shadow-schema {}` first.",
+            args.shadow,
+            display_name(&args.project),
+            args.shadow
+        );
+    }
+
+    let retired = format!("{}_old_{}", args.schema, Utc::now().format("%Y%m%d_%H%M%S"));
+
+    println!("\n{} Cutover Plan", style("📋").bold());
+    println!(
+        "  Project: {} ({})",
+        display_name(&args.project),
+        project.project_ref
+    );
+    println!("  {} → {}", args.schema, retired);
+    println!("  {} → {}", args.shadow, args.schema);
+
+    if !confirm_target(
+        config,
+        &args.project,
+        &project,
+        "Swap schemas now?",
+        args.yes,
+        args.confirm_ref.as_deref(),
+    )? {
+        println!("Cutover cancelled.");
+        return Ok(());
+    }
+
+    // Both renames commit together; the lock timeout keeps a busy table from
+    // stalling live traffic behind the ACCESS EXCLUSIVE locks
+    db.query(&format!(
+        "BEGIN; \
+         SET LOCAL lock_timeout = '10s'; \
+         ALTER SCHEMA {live} RENAME TO {retired}; \
+         ALTER SCHEMA {shadow} RENAME TO {live}; \
+         COMMIT; \
+         NOTIFY pgrst, 'reload schema';",
+        live = quote_ident(&args.schema),
+        retired = quote_ident(&retired),
+        shadow = quote_ident(&args.shadow),
+    ))?;
+
+    println!(
+        "{} Cutover complete: {} is live, previous schema kept as {}",
+        style("✓").green(),
+        args.schema,
+        retired
+    );
+    println!(
+        "  Roll back with: ALTER SCHEMA \"{0}\" RENAME TO \"{1}\"; ALTER SCHEMA \"{2}\" RENAME TO \"{0}\";",
+        args.schema, args.shadow, retired
+    );
+
+    Ok(())
+}
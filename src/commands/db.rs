@@ -0,0 +1,189 @@
+use crate::branches::resolve_project;
+use crate::cli::{DbArgs, DbCommands, DbOutputFormat};
+use crate::config::{display_name, Config, ProjectConfig};
+use crate::db::{connect_project, tools, PgRestore, ResultSet};
+use anyhow::Result;
+use console::style;
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+
+pub async fn run(config: &Config, args: DbArgs) -> Result<()> {
+    match args.command {
+        DbCommands::Execute {
+            project,
+            file,
+            command,
+            output,
+            allow_protected,
+            no_fallback,
+        } => {
+            execute(
+                config,
+                &project,
+                file.as_deref(),
+                command.as_deref(),
+                output,
+                allow_protected,
+                no_fallback,
+            )
+            .await
+        }
+        DbCommands::Shell {
+            project,
+            no_fallback,
+        } => shell(config, &project, no_fallback).await,
+    }
+}
+
+/// Resolve a project (including `alias#branch`) and the settings to connect
+/// with, falling back to the session pooler like migrate and backup
+async fn connect(config: &Config, project_name: &str, no_fallback: bool) -> Result<ProjectConfig> {
+    let project = resolve_project(config, project_name).await?;
+    Ok(connect_project(&project, !no_fallback)?)
+}
+
+/// psql connected to the project, with credentials passed via environment
+fn psql(project: &ProjectConfig, mut cmd: Command) -> Result<Command> {
+    PgRestore::check_available()?;
+    cmd.envs(project.pg_env());
+    Ok(cmd)
+}
+
+async fn execute(
+    config: &Config,
+    project_name: &str,
+    file: Option<&Path>,
+    command: Option<&str>,
+    output: DbOutputFormat,
+    allow_protected: bool,
+    no_fallback: bool,
+) -> Result<()> {
+    let project = resolve_project(config, project_name).await?;
+    config.ensure_writable(project_name, &project, allow_protected)?;
+    let project = connect_project(&project, !no_fallback)?;
+
+    let Some(path) = file else {
+        let sql = command.ok_or_else(|| anyhow::anyhow!("Provide --file or --command"))?;
+        let restore = PgRestore::new(project.db_url());
+        match output {
+            DbOutputFormat::Table => {
+                for result in restore.execute(sql).await? {
+                    print!("{}", render_table(&result));
+                }
+            }
+            DbOutputFormat::Json => {
+                let rows = restore.query_rows(sql).await?;
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            }
+        }
+        return Ok(());
+    };
+
+    if output == DbOutputFormat::Json {
+        anyhow::bail!("--output json needs a single query passed with --command");
+    }
+
+    println!(
+        "{} Executing {} on {}",
+        style("▶").cyan(),
+        path.display(),
+        display_name(project_name)
+    );
+    let (cmd, path) = tools::psql_reading(path)?;
+    let status = psql(&project, cmd)?
+        .arg("-v")
+        .arg("ON_ERROR_STOP=1")
+        .arg("--file")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("psql exited with {}", status);
+    }
+
+    Ok(())
+}
+
+/// Aligned table like psql's default output, or the affected row count for
+/// statements that return no rows
+fn render_table(result: &ResultSet) -> String {
+    if result.columns.is_empty() {
+        return format!("{} {} rows affected\n", style("✓").green(), result.affected);
+    }
+
+    let cell = |value: &Option<String>| value.as_deref().unwrap_or("").to_string();
+    let widths: Vec<usize> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            result
+                .rows
+                .iter()
+                .map(|row| cell(&row[i]).chars().count())
+                .chain(std::iter::once(name.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |values: Vec<String>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!(" {:<width$} ", value, width = width))
+            .collect::<Vec<_>>()
+            .join("|")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut out = String::new();
+    out.push_str(&line(result.columns.clone()));
+    out.push('\n');
+    out.push_str(
+        &widths
+            .iter()
+            .map(|width| "-".repeat(width + 2))
+            .collect::<Vec<_>>()
+            .join("+"),
+    );
+    out.push('\n');
+    for row in &result.rows {
+        out.push_str(&line(row.iter().map(cell).collect()));
+        out.push('\n');
+    }
+    let count = result.rows.len();
+    let _ = writeln!(
+        out,
+        "({} {})\n",
+        count,
+        if count == 1 { "row" } else { "rows" }
+    );
+    out
+}
+
+async fn shell(config: &Config, project_name: &str, no_fallback: bool) -> Result<()> {
+    let project = connect(config, project_name, no_fallback).await?;
+
+    println!(
+        "{} Connecting to {} ({})",
+        style("🐘").bold(),
+        display_name(project_name),
+        project.db_host()
+    );
+
+    // Let psql handle Ctrl+C (cancel the running query) without it also
+    // killing this process and leaving psql attached to the terminal
+    #[cfg(unix)]
+    // SAFETY: ignoring SIGINT has no preconditions; psql installs its own handler
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+    }
+
+    let status = psql(&project, tools::psql_shell())?.status()?;
+    if !status.success() {
+        anyhow::bail!("psql exited with {}", status);
+    }
+
+    Ok(())
+}
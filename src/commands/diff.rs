@@ -0,0 +1,84 @@
+use crate::cli::{DiffArgs, DiffCommands};
+use crate::config::Config;
+use crate::db::{diff_policies, reconcile_sql, render_diff, PolicySet};
+use anyhow::Result;
+use console::style;
+use std::fs;
+use std::path::Path;
+
+pub fn run(config: &Config, args: DiffArgs) -> Result<()> {
+    match args.command {
+        DiffCommands::Policies {
+            from,
+            to,
+            schemas,
+            sql,
+        } => diff_policies_cmd(
+            config,
+            &from,
+            &to,
+            &schemas.unwrap_or_default(),
+            sql.as_deref(),
+        ),
+    }
+}
+
+fn diff_policies_cmd(
+    config: &Config,
+    from: &str,
+    to: &str,
+    schemas: &[String],
+    sql: Option<&Path>,
+) -> Result<()> {
+    let source = config.get_project(from)?;
+    let target = config.get_project(to)?;
+
+    println!(
+        "\n{} Comparing RLS policies: {} → {}",
+        style("🔍").bold(),
+        from,
+        to
+    );
+
+    let mut source_set = PolicySet::fetch(&source.db_url())?;
+    let mut target_set = PolicySet::fetch(&target.db_url())?;
+    source_set.retain_schemas(schemas);
+    target_set.retain_schemas(schemas);
+
+    let changes = diff_policies(&source_set, &target_set);
+
+    if changes.is_empty() {
+        println!(
+            "{} Policies match ({} policies compared)",
+            style("✓").green(),
+            source_set.policies.len()
+        );
+        return Ok(());
+    }
+
+    print!("{}", render_diff(&changes));
+    println!(
+        "\n{} {} differences (+ only in {}, - only in {}, ~ changed)",
+        style("⚠").yellow(),
+        changes.len(),
+        from,
+        to
+    );
+
+    if let Some(path) = sql {
+        let script = format!(
+            "-- Reconcile RLS policies on {} to match {}\nBEGIN;\n{}COMMIT;\n",
+            to,
+            from,
+            reconcile_sql(&changes)
+        );
+        fs::write(path, script)?;
+        println!(
+            "{} Reconciliation SQL written to {}",
+            style("✓").green(),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
@@ -3,6 +3,9 @@ use console::{style, Emoji};
 use std::io::{self, Write};
 use std::process::Command;
 
+/// Oldest pg_dump that can dump the Postgres versions Supabase runs
+const MIN_PG_DUMP_MAJOR: u32 = 15;
+
 // Beautiful emoji icons
 static CHECKMARK: Emoji<'_, '_> = Emoji("✔ ", "√ ");
 static CROSS: Emoji<'_, '_> = Emoji("✖ ", "x ");
@@ -122,8 +125,7 @@ fn is_root() -> bool {
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
             .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+            .is_ok_and(|s| s.success())
     }
     #[cfg(not(any(unix, windows)))]
     {
@@ -139,22 +141,20 @@ fn command_exists(cmd: &str) -> bool {
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
             .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+            .is_ok_and(|s| s.success())
     } else {
         Command::new("which")
             .arg(cmd)
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
             .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+            .is_ok_and(|s| s.success())
     }
 }
 
-/// Check if a command exists and get its version
-fn check_tool(name: &'static str, version_args: &[&str]) -> ToolStatus {
-    let path = if cfg!(target_os = "windows") {
+/// Location of a command on PATH
+fn find_in_path(name: &str) -> Option<String> {
+    if cfg!(target_os = "windows") {
         Command::new("where").arg(name).output().ok()
     } else {
         Command::new("which").arg(name).output().ok()
@@ -168,12 +168,17 @@ fn check_tool(name: &'static str, version_args: &[&str]) -> ToolStatus {
         } else {
             None
         }
-    });
+    })
+}
+
+/// Check if a command exists and get its version
+fn check_tool(name: &'static str, version_args: &[&str]) -> ToolStatus {
+    let path = find_in_path(name);
 
     let found = path.is_some();
 
-    let version = if found {
-        Command::new(name)
+    let version = if let Some(path) = &path {
+        Command::new(path)
             .args(version_args)
             .output()
             .ok()
@@ -272,6 +277,11 @@ fn print_system_info(os: Os, distro: Option<&str>, pkg_manager: Option<&str>) {
     println!();
 }
 
+/// Major version from `pg_dump --version` style output
+fn major_version(version: &str) -> Option<u32> {
+    extract_version(version).split('.').next()?.parse().ok()
+}
+
 /// Print tool status with beautiful formatting
 fn print_tool_status(tool: &ToolStatus, required: bool) {
     if tool.found {
@@ -830,9 +840,18 @@ pub fn run(args: DoctorArgs) -> Result<()> {
     let psql = check_tool("psql", &["--version"]);
     let gzip = check_tool("gzip", &["--version"]);
     let gunzip = check_tool("gunzip", &["--version"]);
+    let docker = check_tool("docker", &["--version"]);
+    let docker_found = docker.found;
+
+    // Supabase runs Postgres 15+, which older pg_dump refuses to dump
+    let outdated = pg_dump
+        .version
+        .as_deref()
+        .and_then(major_version)
+        .is_some_and(|major| major < MIN_PG_DUMP_MAJOR);
 
     let required = vec![pg_dump, psql];
-    let optional = vec![gzip, gunzip];
+    let optional = vec![gzip, gunzip, docker];
 
     print_tools(&required, &optional);
 
@@ -843,17 +862,24 @@ pub fn run(args: DoctorArgs) -> Result<()> {
         .map(|t| t.name)
         .collect();
 
-    if missing.is_empty() {
+    if missing.is_empty() && !outdated {
         print_success();
         return Ok(());
     }
 
-    // Missing tools
-    print_failure(&missing);
+    if missing.is_empty() {
+        println!(
+            "     {}pg_dump is older than {} and cannot dump Supabase databases.",
+            WARNING, MIN_PG_DUMP_MAJOR
+        );
+        println!();
+    } else {
+        print_failure(&missing);
+    }
 
     // Try to auto-install
     if args.fix {
-        if get_install_command(os, distro_ref).is_some() {
+        if get_install_command(os, distro_ref).is_some() && !outdated {
             if install_pg_tools(os, distro_ref)? {
                 println!();
                 println!(
@@ -871,8 +897,11 @@ pub fn run(args: DoctorArgs) -> Result<()> {
             println!("     {}No supported package manager detected.", WARNING);
         }
         println!();
-    } else if get_install_command(os, distro_ref).is_some() {
-        if confirm("Install missing dependencies now?") {
+    } else {
+        if get_install_command(os, distro_ref).is_some()
+            && !outdated
+            && confirm("Install missing dependencies now?")
+        {
             println!();
             if install_pg_tools(os, distro_ref)? {
                 println!();
@@ -894,6 +923,12 @@ pub fn run(args: DoctorArgs) -> Result<()> {
     // Show manual instructions
     print_install_instructions(os, distro_ref);
 
+    if docker_found {
+        print_tip(
+            "Docker is available: pass --engine docker or set engine = \"docker\" under [defaults] to run pg_dump and psql in containers.",
+        );
+    }
+
     if !args.fix && get_install_command(os, distro_ref).is_some() {
         print_tip("Run 'supamigrate doctor --fix' for automatic installation.");
     }
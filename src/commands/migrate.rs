@@ -1,104 +1,2058 @@
-use crate::cli::MigrateArgs;
-use crate::config::Config;
-use crate::db::{PgDump, PgRestore, SqlTransformer};
-use crate::storage::{StorageClient, StorageTransfer};
+use crate::auth::{rewrite_project_url, AuthUsersClient, ProjectUrls};
+use crate::branches::resolve_project;
+use crate::checkpoint::Checkpoint;
+use crate::cli::{
+    AuthImportMode, DumpEngine, MigrateArgs, PlanFormat, SourceKind, TargetKind, TransformRule,
+    UnknownRoles,
+};
+use crate::commands::auth::{copy_auth_hooks, import_auth_users};
+use crate::commands::plan::{PlanFile, SourceSnapshot, PLAN_VERSION};
+use crate::commands::storage::{check_egress, EgressBudget};
+use crate::config::{display_name, Config, ProjectConfig};
+use crate::db::{
+    check_database, connect_url, schema_drift, sync_cron_jobs, sync_object_rows, sync_publication,
+    sync_sequences, sync_webhooks, DatabaseSize, DependencyCheck, Extension, ExtensionCheck,
+    LineTransformer, MaskRule, MigrationRecord, PgDump, PgRestore, Rewrite, RoleDump, SchemaScope,
+    SchemaSize, SchemaSnapshot, SequenceAdjustment, TargetLock, TransformKind, TransformScript,
+    UserTransforms, VaultClient, VaultEntry, REALTIME_PUBLICATION,
+};
+use crate::functions::FunctionsClient;
+use crate::hooks::{self, Hook};
+use crate::storage::{human_bytes, StorageClient, StorageTransfer, TransferEstimate};
+use crate::summary::RunSummary;
 use anyhow::Result;
 use console::style;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::io::{self, Write};
-use tempfile::NamedTempFile;
-use tracing::info;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
 
-pub async fn run(args: MigrateArgs) -> Result<()> {
-    let config = Config::load(None)?;
+/// `println!`, or `eprintln!` while stdout carries a JSON plan
+macro_rules! say {
+    ($json:expr, $($arg:tt)*) => {
+        if $json {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
-    let source = config.get_project(&args.from)?;
-    let target = config.get_project(&args.to)?;
+pub async fn run(config: &Config, mut args: MigrateArgs, summary: &mut RunSummary) -> Result<()> {
+    // An approved plan replaces the command-line options
+    let applied = match &args.plan {
+        Some(path) => {
+            let plan = PlanFile::load(path)?;
+            args = plan.migrate_args(&args)?;
+            Some(plan)
+        }
+        None => None,
+    };
+    // A JSON plan is only a preview; it's printed instead of migrating
+    let json_plan = args.plan_format == PlanFormat::Json;
+    args.dry_run |= json_plan;
 
-    println!("\n{} Migration Plan", style("📋").bold());
-    println!("  Source: {} ({})", args.from, source.project_ref);
-    println!("  Target: {} ({})", args.to, target.project_ref);
-    println!("  Schema only: {}", args.schema_only);
-    println!("  Data only: {}", args.data_only);
-    println!("  Include storage: {}", args.include_storage);
+    // Plain Postgres has no storage, functions or auth APIs to migrate
+    if args.source_kind == SourceKind::Postgres || args.target_kind == TargetKind::Postgres {
+        for (flag, enabled) in [
+            ("--include-storage", &mut args.include_storage),
+            ("--preserve-object-ids", &mut args.preserve_object_ids),
+            ("--include-functions", &mut args.include_functions),
+            ("--include-auth-hooks", &mut args.include_auth_hooks),
+            ("--include-vault", &mut args.include_vault),
+            ("--include-auth", &mut args.include_auth),
+        ] {
+            if std::mem::take(enabled) {
+                say!(
+                    json_plan,
+                    "{} Ignoring {} for a plain Postgres database",
+                    style("⚠").yellow(),
+                    flag
+                );
+                summary.warn(format!("{} ignored for a plain Postgres database", flag));
+            }
+        }
+    }
+    // Only the database goes into the SQL file; the other phases write to
+    // the target directly
+    if args.transform.output_sql.is_some() {
+        for (flag, enabled) in [
+            ("--include-roles", &mut args.include_roles),
+            ("--include-auth", &mut args.include_auth),
+            ("--include-storage", &mut args.include_storage),
+            ("--preserve-object-ids", &mut args.preserve_object_ids),
+            ("--include-functions", &mut args.include_functions),
+            ("--include-auth-hooks", &mut args.include_auth_hooks),
+            ("--include-cron", &mut args.include_cron),
+            ("--include-vault", &mut args.include_vault),
+        ] {
+            if std::mem::take(enabled) {
+                say!(
+                    json_plan,
+                    "{} Ignoring {} with --output-sql",
+                    style("⚠").yellow(),
+                    flag
+                );
+                summary.warn(format!("{} ignored with --output-sql", flag));
+            }
+        }
+    }
 
-    if args.dry_run {
-        println!("\n{} Dry run - no changes will be made", style("ℹ️").cyan());
-        return Ok(());
+    let source = resolve_project(config, &args.from).await?;
+    let target = resolve_project(config, &args.to).await?;
+    // Previews, plans and SQL files don't write, so they still work against
+    // production
+    if !args.dry_run && args.transform.output_sql.is_none() {
+        config.ensure_writable(&args.to, &target, args.allow_protected)?;
+    }
+    // Probing for the session pooler fallback blocks on TCP connects
+    let (source_url, target_url) = {
+        let (source, target) = (source.clone().into_owned(), target.clone().into_owned());
+        let allow_fallback = !args.no_fallback;
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            Ok((
+                connect_url(&source, allow_fallback)?,
+                connect_url(&target, allow_fallback)?,
+            ))
+        })
+        .await??
+    };
+
+    // Catch unreachable hosts and bad credentials before the plan is confirmed
+    preflight(&source, &source_url, &target_url, json_plan).await?;
+
+    let included_tables = config
+        .defaults
+        .included_tables(args.include_tables.as_deref());
+    let masks = config.masks()?;
+    let (pre_sql, post_sql) = config.defaults.hooks(&args.hooks);
+    let mut rewrites = config.rewrites()?;
+    let transforms = config.transforms()?;
+    let script = config.transform_script()?;
+    if args.rewrite_storage_urls {
+        if args.source_kind == SourceKind::Postgres {
+            anyhow::bail!("--rewrite-storage-urls needs a Supabase source");
+        }
+        rewrites.splice(0..0, storage_url_rewrites(&source, &target));
     }
 
-    if !args.yes {
-        print!("\nProceed with migration? [y/N] ");
-        io::stdout().flush()?;
+    let resumed = args
+        .resume
+        .as_deref()
+        .map(|id| Checkpoint::resume(id, &display_name(&args.from), &display_name(&args.to)))
+        .transpose()?;
+
+    let plan = [
+        (
+            "Source",
+            format!("{} ({})", display_name(&args.from), source.project_ref),
+        ),
+        (
+            "Target",
+            format!("{} ({})", display_name(&args.to), target.project_ref),
+        ),
+        (
+            "Source kind",
+            format!("{:?}", args.source_kind).to_lowercase(),
+        ),
+        (
+            "Target kind",
+            format!("{:?}", args.target_kind).to_lowercase(),
+        ),
+        ("Schema only", args.schema_only.to_string()),
+        ("Data only", args.data_only.to_string()),
+        (
+            "Schemas",
+            args.schemas
+                .as_ref()
+                .map_or_else(|| "all".to_string(), |schemas| schemas.join(", ")),
+        ),
+        (
+            "Tables",
+            if included_tables.is_empty() {
+                "all".to_string()
+            } else {
+                included_tables.join(", ")
+            },
+        ),
+        (
+            "Masked tables",
+            if masks.is_empty() {
+                "-".to_string()
+            } else {
+                masks.keys().cloned().collect::<Vec<_>>().join(", ")
+            },
+        ),
+        ("Direct copy", args.direct_copy.to_string()),
+        ("Truncate target tables", args.truncate.to_string()),
+        ("Atomic restore", args.atomic.to_string()),
+        (
+            "Skipped transforms",
+            transform_list(&args.transform.skipped()),
+        ),
+        ("Remap schemas", remap_list(&args.transform.remap_schema)),
+        (
+            "Transform script",
+            script
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |s| s.path().display().to_string()),
+        ),
+        (
+            "Output SQL",
+            args.transform
+                .output_sql
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |path| path.display().to_string()),
+        ),
+        ("Sync sequences", sequences_synced(&args).to_string()),
+        (
+            "Rewrite storage URLs",
+            args.rewrite_storage_urls.to_string(),
+        ),
+        ("Pre-SQL", file_list(&pre_sql)),
+        ("Post-SQL", file_list(&post_sql)),
+        ("Include storage", args.include_storage.to_string()),
+        ("Preserve object ids", args.preserve_object_ids.to_string()),
+        ("Include functions", args.include_functions.to_string()),
+        ("Include auth hooks", args.include_auth_hooks.to_string()),
+        ("Include vault", args.include_vault.to_string()),
+        (
+            "Include auth",
+            if args.include_auth {
+                format!("true ({:?})", args.auth_mode).to_lowercase()
+            } else {
+                "false".to_string()
+            },
+        ),
+        (
+            "Create extensions",
+            (!args.no_create_extensions && !args.data_only).to_string(),
+        ),
+        ("Include roles", args.include_roles.to_string()),
+        (
+            "Include cron jobs",
+            match (args.include_cron, args.cron_disabled) {
+                (false, _) => "false".to_string(),
+                (true, false) => "true".to_string(),
+                (true, true) => "true (disabled)".to_string(),
+            },
+        ),
+        (
+            "Renamed roles",
+            if config.role_map.is_empty() {
+                "-".to_string()
+            } else {
+                config
+                    .role_map
+                    .iter()
+                    .map(|(from, to)| format!("{} -> {}", from, to))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            },
+        ),
+        (
+            "Unknown roles",
+            match config.defaults.unknown_roles(args.unknown_roles) {
+                UnknownRoles::Remap => format!("remap to {}", config.defaults.fallback_role),
+                UnknownRoles::Drop => "drop".to_string(),
+                UnknownRoles::Keep => "keep".to_string(),
+            },
+        ),
+        ("Parallel phases", args.parallel_phases.to_string()),
+        (
+            "Shadow schema",
+            args.shadow_schema
+                .clone()
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        (
+            "Resume",
+            resumed.as_ref().map_or_else(
+                || "-".to_string(),
+                |checkpoint| {
+                    let completed = checkpoint.completed();
+                    if completed.is_empty() {
+                        checkpoint.id.clone()
+                    } else {
+                        format!("{} (done: {})", checkpoint.id, completed.join(", "))
+                    }
+                },
+            ),
+        ),
+    ];
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    say!(json_plan, "\n{} Migration Plan", style("📋").bold());
+    for (key, value) in &plan {
+        say!(json_plan, "  {}: {}", key, value);
+        summary.plan(key, value);
+    }
+    if !args.transform.remap_schema.is_empty() && !args.data_only && !args.atomic {
+        say!(json_plan, "  {} {}", style("⚠").yellow(), REMAP_CONFLICTS);
+        summary.warn(REMAP_CONFLICTS);
+    }
 
-        if !input.trim().eq_ignore_ascii_case("y") {
-            println!("Migration cancelled.");
+    let mut storage_estimate = None;
+    if args.include_storage {
+        let source_key = source.service_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Source project requires service_key for storage migration")
+        })?;
+        let transfer =
+            StorageTransfer::new(StorageClient::for_project(&source, source_key.clone()));
+        let budget = EgressBudget {
+            max_bytes: args.max_egress,
+            assume_yes: args.yes,
+        };
+        storage_estimate = if json_plan {
+            let estimate = transfer.estimate(None).await?;
+            if args.max_egress.is_some_and(|max| estimate.bytes > max) {
+                summary.warn("Estimated egress exceeds --max-egress");
+            }
+            Some(estimate)
+        } else {
+            check_egress(&transfer, None, &budget, config.defaults.egress_cost_per_gb).await?
+        };
+        if storage_estimate.is_none() {
+            say!(json_plan, "Migration cancelled.");
+            summary.cancel();
             return Ok(());
         }
     }
 
-    // Database migration
-    println!("\n{} Starting database migration...", style("🗄️").bold());
-
     let excluded_schemas = args
         .exclude_schemas
+        .clone()
         .unwrap_or_else(|| config.defaults.excluded_schemas.clone());
+    let mut excluded_tables = args.exclude_tables.clone().unwrap_or_default();
+    let mut dependencies = (Vec::new(), Vec::new());
 
-    let excluded_tables = args.exclude_tables.unwrap_or_default();
+    // Supabase-managed schemas exist on every Supabase project, so there
+    // only the schemas the user left out can leave kept objects without
+    // their dependencies. Plain Postgres has none of them.
+    let schema_scope = SchemaScope {
+        only: args.schemas.clone().unwrap_or_default(),
+        excluded: excluded_schemas.clone(),
+        on_target: match args.target_kind {
+            TargetKind::Supabase => config
+                .defaults
+                .excluded_schemas
+                .iter()
+                .cloned()
+                .chain(["auth".to_string()])
+                .collect(),
+            TargetKind::Postgres => Vec::new(),
+        },
+    };
 
-    // Dump source database
-    info!("Dumping source database...");
-    let dump = PgDump::new(source.db_url())
-        .exclude_schemas(excluded_schemas)
-        .exclude_tables(excluded_tables)
-        .schema_only(args.schema_only)
-        .data_only(args.data_only)
-        .dump_to_string()?;
+    if !excluded_tables.is_empty() || schema_scope.is_partial() {
+        say!(
+            json_plan,
+            "\n{} Checking dependencies of the partial migration...",
+            style("🔍").bold()
+        );
+        let check = DependencyCheck::new(source_url.clone());
+        let (tables, scope) = (excluded_tables.clone(), schema_scope.clone());
+        let include_dependencies = args.include_dependencies;
+        let report =
+            tokio::task::spawn_blocking(move || check.check(&tables, &scope, include_dependencies))
+                .await??;
 
-    // Transform SQL for Supabase compatibility
-    info!("Transforming SQL...");
-    let transformed = SqlTransformer::transform(&dump);
+        for name in &report.auto_included {
+            say!(
+                json_plan,
+                "  {} Including {} (needed by migrated objects)",
+                style("+").green(),
+                name
+            );
+            summary.plan("Included dependency", name);
+        }
+        for dep in &report.missing {
+            say!(json_plan, "  {} {}", style("⚠").yellow(), dep);
+            summary.warn(format!("Missing dependency: {}", dep));
+        }
+        if !report.missing_schemas.is_empty() {
+            say!(
+                json_plan,
+                "  {} The target won't have schema {}; migrate it too or expect these objects to fail to restore",
+                style("ℹ").blue(),
+                report.missing_schemas.join(", ")
+            );
+        }
+        if report.missing.is_empty() {
+            say!(
+                json_plan,
+                "  {} No missing dependencies",
+                style("✓").green()
+            );
+        } else if !args.include_dependencies && report.needs_excluded_tables() {
+            say!(
+                json_plan,
+                "  {} Use --include-dependencies to migrate the excluded tables these need",
+                style("ℹ").blue()
+            );
+        }
 
-    // Write to temp file
-    let temp_file = NamedTempFile::new()?;
-    std::fs::write(temp_file.path(), &transformed)?;
+        excluded_tables = report.quoted_exclusions();
+        dependencies = (
+            report.auto_included,
+            report.missing.iter().map(ToString::to_string).collect(),
+        );
+    }
 
-    // Restore to target
-    info!("Restoring to target database...");
-    let restore = PgRestore::new(target.db_url());
-    restore.restore_from_file(temp_file.path())?;
+    // Loading rows into a drifted schema fails midway, so check first
+    if args.data_only && !args.skip_drift_check {
+        say!(
+            json_plan,
+            "\n{} Comparing source and target table definitions...",
+            style("🔍").bold()
+        );
+        let table_patterns = args.exclude_tables.clone().unwrap_or_default();
+        let (source, target) = (source_url.clone(), target_url.clone());
+        let schemas = excluded_schemas.clone();
+        let (source_schema, target_schema) = tokio::task::spawn_blocking(move || -> Result<_> {
+            Ok((
+                SchemaSnapshot::fetch(&source, &schemas, &table_patterns)?,
+                SchemaSnapshot::fetch(&target, &schemas, &table_patterns)?,
+            ))
+        })
+        .await??;
+        let drift = schema_drift(&source_schema, &target_schema);
 
-    println!("{} Database migration complete!", style("✓").green());
+        if !drift.is_empty() {
+            for line in &drift {
+                say!(json_plan, "  {} {}", style("✗").red(), line);
+            }
+            anyhow::bail!(
+                "Target schema differs from source in {} place(s); migrate the schema first \
+                 or pass --skip-drift-check",
+                drift.len()
+            );
+        }
+        say!(
+            json_plan,
+            "  {} {} tables match",
+            style("✓").green(),
+            source_schema.tables.len()
+        );
+    }
 
-    // Storage migration
-    if args.include_storage {
-        println!("\n{} Starting storage migration...", style("📦").bold());
+    // Migrated objects use the source's extensions: the native engine
+    // creates them, pg_dump's schema-scoped dumps expect them to exist
+    let kind = transform_kind(args.source_kind, args.target_kind)
+        .unless_skipped(&args.transform.skipped());
+    let extensions: Vec<Extension> = if args.data_only {
+        Vec::new()
+    } else {
+        Extension::installed(&source_url)
+            .await?
+            .into_iter()
+            .filter(|extension| kind.keeps_extension(&extension.name))
+            .map(|extension| Extension {
+                schema: extension
+                    .schema
+                    .as_deref()
+                    .map(|schema| kind.extension_schema(schema).to_string()),
+                ..extension
+            })
+            .collect()
+    };
+    if !args.skip_extension_check {
+        check_extensions(&target_url, &extensions, json_plan, summary).await?;
+    }
 
-        let source_key = source.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Source project requires service_key for storage migration")
-        })?;
-        let target_key = target.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Target project requires service_key for storage migration")
-        })?;
+    // The plan and the source snapshot are checked or recorded against the
+    // same tables a data-only drift check would compare
+    let snapshot = if applied.is_some() || args.write_plan.is_some() {
+        let table_patterns = args.exclude_tables.clone().unwrap_or_default();
+        let (source_url, schemas) = (source_url.clone(), excluded_schemas.clone());
+        let schema = tokio::task::spawn_blocking(move || {
+            SchemaSnapshot::fetch(&source_url, &schemas, &table_patterns)
+        })
+        .await??;
+        Some(SourceSnapshot {
+            project_ref: source.project_ref.clone(),
+            tables: schema.fingerprints(),
+        })
+    } else {
+        None
+    };
+    let options: BTreeMap<String, String> = plan
+        .iter()
+        .map(|(key, value)| ((*key).to_string(), value.clone()))
+        .collect();
+
+    if let (Some(applied), Some(snapshot)) = (&applied, &snapshot) {
+        say!(
+            json_plan,
+            "\n{} Checking the plan against the source...",
+            style("🔍").bold()
+        );
+        let mut changes = applied.option_changes(&options, &excluded_schemas, &excluded_tables);
+        changes.extend(applied.source.changes(snapshot));
+        if !changes.is_empty() {
+            for line in &changes {
+                say!(json_plan, "  {} {}", style("✗").red(), line);
+            }
+            anyhow::bail!(
+                "The source or options changed in {} place(s) since the plan was made; \
+                 run `supamigrate plan` again and review the new plan",
+                changes.len()
+            );
+        }
+        say!(
+            json_plan,
+            "  {} Plan matches ({} tables)",
+            style("✓").green(),
+            snapshot.tables.len()
+        );
+    }
+
+    if args.dry_run {
+        let dump = PgDump::new(source_url.clone())
+            .schemas(args.schemas.clone().unwrap_or_default())
+            .exclude_schemas(excluded_schemas.clone())
+            .include_tables(included_tables.clone())
+            .exclude_tables(args.exclude_tables.clone().unwrap_or_default())
+            .exclude_table_data(
+                config
+                    .defaults
+                    .excluded_table_data(args.exclude_table_data.as_deref()),
+            )
+            .always_exclude_table_data(config.defaults.always_excluded_table_data.clone())
+            .schema_only(args.schema_only);
+        let estimate = estimate(&args, config, &source, &dump, storage_estimate).await?;
+        if let (Some(path), Some(snapshot)) = (args.write_plan.clone(), snapshot) {
+            let mut plan = PlanFile {
+                version: PLAN_VERSION,
+                created_at: chrono::Utc::now(),
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                phases: planned_phases(&args)
+                    .into_iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                options: options.clone(),
+                excluded_schemas: excluded_schemas.clone(),
+                excluded_tables: excluded_tables.clone(),
+                estimate: serde_json::to_value(&estimate)?,
+                source: snapshot,
+                signature: String::new(),
+                args,
+            };
+            plan.save(&path)?;
+            say!(
+                json_plan,
+                "\n{} Plan written to {} ({} tables). Review it, then apply it with:\n  \
+                 supamigrate migrate --plan {}",
+                style("✓").green(),
+                path.display(),
+                plan.source.tables.len(),
+                path.display()
+            );
+            return Ok(());
+        }
+        if json_plan {
+            let (included_dependencies, missing_dependencies) = dependencies;
+            let plan = JsonPlan {
+                options,
+                phases: planned_phases(&args),
+                excluded_schemas,
+                excluded_tables,
+                included_dependencies,
+                missing_dependencies,
+                warnings: summary.warnings.clone(),
+                estimate,
+            };
+            println!("{}", serde_json::to_string_pretty(&plan)?);
+        } else {
+            say!(
+                json_plan,
+                "\n{} Dry run - no changes will be made",
+                style("ℹ️").cyan()
+            );
+        }
+        return Ok(());
+    }
+
+    let database = DatabasePhase {
+        source_url: source_url.clone(),
+        target_url: target_url.clone(),
+        schemas: args.schemas.clone().unwrap_or_default(),
+        excluded_schemas,
+        included_tables,
+        excluded_tables,
+        excluded_table_data: config
+            .defaults
+            .excluded_table_data(args.exclude_table_data.as_deref()),
+        always_excluded_table_data: config.defaults.always_excluded_table_data.clone(),
+        row_filters: config.filters.clone(),
+        masks,
+        rewrites,
+        transforms,
+        script,
+        transform_report: args.transform.transform_report.clone(),
+        skipped_transforms: args.transform.skipped(),
+        output_sql: args.transform.output_sql.clone(),
+        schema_remaps: args.transform.remap_schema.clone(),
+        dump_args: config.defaults.pg_dump_args(&args.pg_dump_args),
+        engine: config.defaults.engine(args.engine),
+        restore_args: config.defaults.psql_args(&args.psql_args),
+        restore_timeouts: config.defaults.restore_timeouts(&args.timeouts),
+        pre_sql,
+        post_sql,
+        schema_only: args.schema_only,
+        data_only: args.data_only,
+        direct_copy: args.direct_copy,
+        truncate: args.truncate,
+        atomic: args.atomic,
+        sync_sequences: sequences_synced(&args),
+        shadow_schema: args.shadow_schema.clone(),
+        role_map: config.role_map.clone(),
+        unknown_roles: config.defaults.unknown_roles(args.unknown_roles),
+        fallback_role: config.defaults.fallback_role.clone(),
+        source_kind: args.source_kind,
+        target_kind: args.target_kind,
+    };
+
+    // Writing the SQL file leaves the target alone, so it isn't confirmed
+    if let Some(path) = args.transform.output_sql.clone() {
+        println!(
+            "\n{} Writing the database to {}...",
+            style("🗄️").bold(),
+            path.display()
+        );
+        summary.begin_phase("database");
+        let detail = transfer_database(database, summary).await?;
+        summary.end_phase(detail);
+        println!(
+            "\n{} Review it, then apply it with:\n  psql \"$TARGET_DB_URL\" --file {}",
+            style("ℹ").blue(),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if !confirm_target(
+        config,
+        &args.to,
+        &target,
+        "Proceed with migration?",
+        args.yes,
+        args.confirm_ref.as_deref(),
+    )? {
+        println!("Migration cancelled.");
+        summary.cancel();
+        return Ok(());
+    }
+
+    // Held until the migration returns, so concurrent runs into the same
+    // target fail fast instead of interleaving
+    let _lock = TargetLock::acquire(&target_url, "migrate").await?;
+
+    let checkpoint = match resumed {
+        Some(checkpoint) => checkpoint,
+        None => Checkpoint::start(&display_name(&args.from), &display_name(&args.to))?,
+    };
+    println!(
+        "\n{} Checkpoint {}: if the migration is interrupted, re-run it with --resume {}",
+        style("ℹ").blue(),
+        checkpoint.id,
+        checkpoint.id
+    );
+    summary.plan("Checkpoint", &checkpoint.id);
+
+    hooks::context("source", display_name(&args.from));
+    hooks::context("source_ref", &source.project_ref);
+    hooks::context("target", display_name(&args.to));
+    hooks::context("target_ref", &target.project_ref);
+    hooks::run(Hook::BeforeMigrate, summary).await?;
+
+    // Grants and policies in the dump refer to roles, so they go first
+    if args.include_roles && !checkpoint.is_done("roles") {
+        migrate_roles(
+            config,
+            &source_url,
+            &target_url,
+            args.target_kind,
+            config.defaults.engine(args.engine),
+            summary,
+        )
+        .await?;
+        checkpoint.complete("roles")?;
+    }
+
+    // Migrated tables may reference auth.users, and their foreign keys are
+    // added once their rows are in
+    if args.include_auth && !checkpoint.is_done("auth") {
+        migrate_auth(&source_url, &target, &target_url, args.auth_mode, summary).await?;
+        checkpoint.complete("auth")?;
+    }
+
+    if !args.no_create_extensions && !extensions.is_empty() {
+        create_extensions(&target_url, &extensions, summary).await?;
+    }
+
+    // Publication tables and webhooks are chosen like the dumped tables
+    let dump_filter = || {
+        PgDump::new(database.source_url.clone())
+            .schemas(database.schemas.clone())
+            .exclude_schemas(database.excluded_schemas.clone())
+            .include_tables(database.included_tables.clone())
+            .exclude_tables(database.excluded_tables.clone())
+    };
+    let realtime_filter = realtime_enabled(&args).then(dump_filter);
+    let webhooks_filter = webhooks_enabled(&args).then(dump_filter);
+
+    let object_ids_source = args.preserve_object_ids.then(|| source_url.clone());
+    let cron_source = args.include_cron.then(|| source_url.clone());
+    let vault_source = args.include_vault.then(|| source_url.clone());
+
+    if args.parallel_phases {
+        // Storage objects and edge functions don't depend on the database,
+        // so they run alongside it. Each phase records into its own summary
+        // and is merged afterwards so timings stay per-phase.
+        println!(
+            "\n{} Running database, storage and functions phases concurrently...",
+            style("⚡").bold()
+        );
+
+        let mut db_summary = RunSummary::start("database");
+        let mut storage_summary = RunSummary::start("storage");
+        let mut functions_summary = RunSummary::start("functions");
+
+        let (db_result, storage_result, functions_result) = tokio::join!(
+            migrate_database(database, &checkpoint, &mut db_summary),
+            async {
+                if args.include_storage {
+                    migrate_storage(config, &source, &target, &checkpoint, &mut storage_summary)
+                        .await
+                } else {
+                    Ok(())
+                }
+            },
+            async {
+                if args.include_functions {
+                    migrate_functions(&source, &target, &checkpoint, &mut functions_summary).await
+                } else {
+                    Ok(())
+                }
+            },
+        );
+
+        let mut first_error = None;
+        for (mut phase_summary, result) in [
+            (db_summary, db_result),
+            (storage_summary, storage_result),
+            (functions_summary, functions_result),
+        ] {
+            phase_summary.finish(&result);
+            summary.merge(phase_summary);
+            if let Err(e) = result {
+                if first_error.is_some() {
+                    println!("{} {:#}", style("✗").red(), e);
+                } else {
+                    first_error = Some(e);
+                }
+            }
+        }
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+    } else {
+        migrate_database(database, &checkpoint, summary).await?;
+
+        if args.include_storage {
+            migrate_storage(config, &source, &target, &checkpoint, summary).await?;
+        }
+
+        if args.include_functions {
+            migrate_functions(&source, &target, &checkpoint, summary).await?;
+        }
+    }
+
+    // Object rows exist once their files were uploaded
+    if let Some(source_url) = object_ids_source {
+        if !checkpoint.is_done("object_ids") {
+            migrate_object_ids(&source_url, &target_url, summary).await?;
+            checkpoint.complete("object_ids")?;
+        }
+    }
+
+    // Publications list tables, so they wait for the database
+    if let Some(filter) = realtime_filter {
+        if !checkpoint.is_done("realtime") {
+            migrate_realtime(&target_url, &filter, summary).await?;
+            checkpoint.complete("realtime")?;
+        }
+    }
+
+    // Webhooks are triggers on migrated tables
+    if let Some(filter) = webhooks_filter {
+        if !checkpoint.is_done("webhooks") {
+            migrate_webhooks(&source, &target, &target_url, &filter, summary).await?;
+            checkpoint.complete("webhooks")?;
+        }
+    }
 
-        let source_storage = StorageClient::new(source.api_url(), source_key.clone());
-        let target_storage = StorageClient::new(target.api_url(), target_key.clone());
+    // Jobs run SQL against migrated objects, so they wait for the database
+    if let Some(source_url) = cron_source {
+        if !checkpoint.is_done("cron") {
+            migrate_cron(&source_url, &target_url, args.cron_disabled, summary).await?;
+            checkpoint.complete("cron")?;
+        }
+    }
 
-        let transfer = StorageTransfer::new(source_storage)
-            .with_target(target_storage)
-            .parallel(config.defaults.parallel_transfers);
+    if let Some(source_url) = vault_source {
+        if !checkpoint.is_done("vault") {
+            migrate_vault(&source_url, &target_url, summary).await?;
+            checkpoint.complete("vault")?;
+        }
+    }
 
-        let stats = transfer.sync_all().await?;
+    // Hooks may point at Postgres functions, so they always wait for the database
+    if args.include_auth_hooks && !checkpoint.is_done("auth_hooks") {
+        println!("\n{} Migrating auth hooks...", style("🪝").bold());
+        summary.begin_phase("auth_hooks");
+        let count = copy_auth_hooks(&source, &target).await?;
         println!(
-            "{} Storage migration complete: {}",
+            "{} Auth hooks migration complete: {} settings",
             style("✓").green(),
-            stats
+            count
         );
+        summary.stat("settings", count as u64);
+        summary.end_phase(format!("{} settings", count));
+        checkpoint.complete("auth_hooks")?;
     }
 
+    if !args.no_history {
+        let record = MigrationRecord {
+            source_ref: source.project_ref.clone(),
+            source: display_name(&args.from),
+            started_at: summary.started_at,
+            finished_at: chrono::Utc::now(),
+            options: summary
+                .plan
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into()))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+            stats: serde_json::to_value(summary.phase_stats())?,
+        };
+        // The migration itself is done, so a failed write only warns
+        if let Err(e) = record.save(&target_url).await {
+            println!(
+                "{} Could not record the migration in supamigrate.migrations: {}",
+                style("⚠").yellow(),
+                e
+            );
+            summary.warn(format!("Migration history not recorded: {}", e));
+        }
+    }
+
+    hooks::run(Hook::AfterMigrate, summary).await?;
+    checkpoint.remove()?;
+
     println!("\n{} Migration completed successfully!", style("🎉").bold());
 
+    if let Some(shadow) = &args.shadow_schema {
+        println!(
+            "\n{} Validate {} on {}, then swap it in with:\n  supamigrate cutover --project {} --shadow {}",
+            style("ℹ").blue(),
+            shadow,
+            display_name(&args.to),
+            display_name(&args.to),
+            shadow
+        );
+    }
+
+    Ok(())
+}
+
+/// Rough throughputs behind dry-run duration estimates: table data through
+/// dump and restore, one storage object transfer, and one function deploy
+const DATABASE_BYTES_PER_SEC: u64 = 20 * 1024 * 1024;
+const STORAGE_BYTES_PER_SEC: u64 = 5 * 1024 * 1024;
+const FUNCTION_DEPLOY_SECS: u64 = 3;
+
+/// Sizes a migration would transfer and roughly how long it would take
+#[derive(Serialize)]
+struct Estimate {
+    /// On-disk size of the whole source database
+    database_bytes: u64,
+    /// Tables and table data to be migrated, by schema
+    schemas: BTreeMap<String, SchemaSize>,
+    storage: Option<TransferEstimate>,
+    functions: Option<usize>,
+    transfer_bytes: u64,
+    duration_secs: u64,
+}
+
+/// The plan of a dry run, printed by `--plan-format json`
+#[derive(Serialize)]
+struct JsonPlan {
+    options: BTreeMap<String, String>,
+    phases: Vec<&'static str>,
+    excluded_schemas: Vec<String>,
+    excluded_tables: Vec<String>,
+    included_dependencies: Vec<String>,
+    missing_dependencies: Vec<String>,
+    warnings: Vec<String>,
+    estimate: Estimate,
+}
+
+/// Phases the migration would run, in order
+fn planned_phases(args: &MigrateArgs) -> Vec<&'static str> {
+    let mut phases = Vec::new();
+    if args.include_roles {
+        phases.push("roles");
+    }
+    if args.include_auth {
+        phases.push("auth");
+    }
+    phases.push("database");
+    for (name, enabled) in [
+        ("storage", args.include_storage),
+        ("object_ids", args.preserve_object_ids),
+        ("functions", args.include_functions),
+        ("realtime", realtime_enabled(args)),
+        ("webhooks", webhooks_enabled(args)),
+        ("cron", args.include_cron),
+        ("vault", args.include_vault),
+        ("auth_hooks", args.include_auth_hooks),
+    ] {
+        if enabled {
+            phases.push(name);
+        }
+    }
+    phases
+}
+
+/// Whether the source's Realtime tables are published on the target: only
+/// between Supabase projects, and not into a shadow schema, whose tables
+/// aren't the ones clients subscribe to, or remapped schemas, whose tables
+/// the target knows by other names
+fn realtime_enabled(args: &MigrateArgs) -> bool {
+    !args.no_realtime
+        && args.source_kind == SourceKind::Supabase
+        && args.target_kind == TargetKind::Supabase
+        && args.shadow_schema.is_none()
+        && args.transform.remap_schema.is_empty()
+}
+
+/// Whether the source's Database Webhooks are recreated on the target: only
+/// between Supabase projects, and not into a shadow schema, whose tables
+/// aren't the ones clients write to, or remapped schemas
+fn webhooks_enabled(args: &MigrateArgs) -> bool {
+    !args.no_webhooks
+        && args.source_kind == SourceKind::Supabase
+        && args.target_kind == TargetKind::Supabase
+        && args.shadow_schema.is_none()
+        && args.transform.remap_schema.is_empty()
+}
+
+/// Whether target sequences are moved past the loaded ids: asked for, or
+/// always with --data-only, unless schemas are remapped, since the loaded
+/// tables are looked up by their source names
+fn sequences_synced(args: &MigrateArgs) -> bool {
+    (args.sync_sequences || args.data_only) && args.transform.remap_schema.is_empty()
+}
+
+/// Estimate and print the sizes a migration would transfer and roughly how
+/// long it would take. Storage was already estimated by the egress check.
+async fn estimate(
+    args: &MigrateArgs,
+    config: &Config,
+    source: &ProjectConfig,
+    dump: &PgDump,
+    storage: Option<TransferEstimate>,
+) -> Result<Estimate> {
+    let json_plan = args.plan_format == PlanFormat::Json;
+    say!(json_plan, "\n{} Estimated transfer", style("📏").bold());
+
+    let size = DatabaseSize::fetch(dump).await?;
+    say!(
+        json_plan,
+        "  Source database: {} on disk",
+        human_bytes(size.database_bytes)
+    );
+    let schemas = size.by_schema(dump);
+    for (schema, SchemaSize { tables, bytes }) in &schemas {
+        say!(
+            json_plan,
+            "  {}: {} tables, {} of data",
+            schema,
+            tables,
+            human_bytes(*bytes)
+        );
+    }
+    let database_bytes: u64 = schemas.values().map(|s| s.bytes).sum();
+    let database_secs = database_bytes / DATABASE_BYTES_PER_SEC;
+
+    let storage_bytes = storage.as_ref().map_or(0, |estimate| estimate.bytes);
+    let parallel = config.defaults.parallel_transfers.max(1) as u64;
+    let storage_secs = storage_bytes / (STORAGE_BYTES_PER_SEC * parallel);
+
+    let mut functions = None;
+    if args.include_functions {
+        let service_key = source.service_key.clone().unwrap_or_default();
+        match FunctionsClient::for_project(source, service_key)
+            .list_functions()
+            .await
+        {
+            Ok(list) => {
+                say!(json_plan, "  Edge functions: {}", list.len());
+                functions = Some(list.len());
+            }
+            Err(e) => say!(json_plan, "  Edge functions: unknown ({})", e),
+        }
+    }
+    let functions_secs = functions.unwrap_or(0) as u64 * FUNCTION_DEPLOY_SECS;
+
+    let duration_secs = if args.parallel_phases {
+        database_secs.max(storage_secs).max(functions_secs)
+    } else {
+        database_secs + storage_secs + functions_secs
+    };
+    let transfer_bytes = database_bytes + storage_bytes;
+    say!(
+        json_plan,
+        "  Total: {}, taking {}",
+        human_bytes(transfer_bytes),
+        human_duration(duration_secs)
+    );
+
+    Ok(Estimate {
+        database_bytes: size.database_bytes,
+        schemas,
+        storage,
+        functions,
+        transfer_bytes,
+        duration_secs,
+    })
+}
+
+/// Rounded duration like "about 2h 5m", "about 12m" or "under a minute"
+fn human_duration(secs: u64) -> String {
+    match secs / 60 {
+        0 => "under a minute".to_string(),
+        minutes if minutes < 60 => format!("about {}m", minutes),
+        minutes => format!("about {}h {}m", minutes / 60, minutes % 60),
+    }
+}
+
+/// Test both database connections and the source API, reporting every
+/// failure at once
+async fn preflight(
+    source: &ProjectConfig,
+    source_url: &str,
+    target_url: &str,
+    json_plan: bool,
+) -> Result<()> {
+    say!(
+        json_plan,
+        "\n{} Checking connections...",
+        style("🔌").bold()
+    );
+
+    let mut failures = Vec::new();
+
+    for (label, url) in [
+        ("Source database", source_url),
+        ("Target database", target_url),
+    ] {
+        match check_database(url).await {
+            Ok(()) => say!(json_plan, "  {} {}", style("✓").green(), label),
+            Err(e) => {
+                say!(json_plan, "  {} {}", style("✗").red(), label);
+                failures.push(format!("{}: {}", label, e));
+            }
+        }
+    }
+
+    if let Some(service_key) = &source.service_key {
+        let storage = StorageClient::for_project(source, service_key.clone());
+        match storage.list_buckets().await {
+            Ok(_) => say!(json_plan, "  {} Source API", style("✓").green()),
+            Err(e) => {
+                say!(json_plan, "  {} Source API", style("✗").red());
+                let hint = if e.to_string().contains("401") || e.to_string().contains("403") {
+                    " (check service_key: Project Settings → API → service_role key)"
+                } else {
+                    ""
+                };
+                failures.push(format!("Source API {}: {}{}", source.api_url(), e, hint));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!("Preflight checks failed:\n  {}", failures.join("\n  "));
+    }
+
+    Ok(())
+}
+
+/// How a dump is adapted between these kinds of source and target
+fn transform_kind(source: SourceKind, target: TargetKind) -> TransformKind {
+    match (source, target) {
+        (_, TargetKind::Postgres) => TransformKind::ForPostgres,
+        (SourceKind::Postgres, _) => TransformKind::FromPostgres,
+        (SourceKind::Supabase, TargetKind::Supabase) => TransformKind::Supabase,
+    }
+}
+
+/// Fail before anything is written when the target can't install extensions
+/// the restored objects need. Extensions that would come out in another version only
+/// warn.
+pub async fn check_extensions(
+    target_url: &str,
+    wanted: &[Extension],
+    json_plan: bool,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    if wanted.is_empty() {
+        return Ok(());
+    }
+    say!(
+        json_plan,
+        "\n{} Checking extensions on the target...",
+        style("🔍").bold()
+    );
+    let check = ExtensionCheck::run(target_url, wanted).await?;
+    for line in &check.other_versions {
+        say!(json_plan, "  {} {}", style("⚠").yellow(), line);
+        summary.warn(format!("Extension version differs: {}", line));
+    }
+    if !check.missing.is_empty() {
+        for name in &check.missing {
+            say!(
+                json_plan,
+                "  {} {} isn't available on the target",
+                style("✗").red(),
+                name
+            );
+        }
+        anyhow::bail!(
+            "The target can't install {} extension(s) the restored objects need ({}); install them \
+             or pass --skip-extension-check",
+            check.missing.len(),
+            check.missing.join(", ")
+        );
+    }
+    say!(
+        json_plan,
+        "  {} {} extensions available",
+        style("✓").green(),
+        wanted.len()
+    );
+    Ok(())
+}
+
+/// Warning for remapped schemas restored without `--atomic`
+pub const REMAP_CONFLICTS: &str =
+    "Remapped schemas keep what the target already has: objects that \
+     exist there fail to restore. Pass --atomic to roll back at the first conflict";
+
+/// Schema that blue/green migrations restore into a shadow copy
+const SHADOW_SOURCE_SCHEMA: &str = "public";
+
+/// Owned inputs for the database phase, which runs on a blocking thread
+struct DatabasePhase {
+    source_url: String,
+    target_url: String,
+    /// Only these schemas (all if empty)
+    schemas: Vec<String>,
+    excluded_schemas: Vec<String>,
+    included_tables: Vec<String>,
+    excluded_tables: Vec<String>,
+    excluded_table_data: Vec<String>,
+    always_excluded_table_data: Vec<String>,
+    /// Row filters from config, by table
+    row_filters: BTreeMap<String, String>,
+    /// Masking rules from config, by table and column
+    masks: BTreeMap<String, BTreeMap<String, MaskRule>>,
+    /// Search/replace rules from config for the migrated rows
+    rewrites: Vec<Rewrite>,
+    /// `[transforms]` from config for the dump's statements
+    transforms: UserTransforms,
+    /// `transform_script` from config, run on each statement last
+    script: Option<TransformScript>,
+    /// Where to write the lines the transformations changed
+    transform_report: Option<PathBuf>,
+    /// Built-in transformations left out
+    skipped_transforms: Vec<TransformRule>,
+    /// Write the transformed dump here instead of restoring it
+    output_sql: Option<PathBuf>,
+    /// Schemas restored under another name, from source to target
+    schema_remaps: Vec<(String, String)>,
+    dump_args: Vec<String>,
+    engine: DumpEngine,
+    restore_args: Vec<String>,
+    /// Settings for the restore session: timeouts, and the replica role
+    /// when loading data only
+    restore_timeouts: Vec<(&'static str, String)>,
+    /// SQL files run on the target before and after the restore
+    pre_sql: Vec<PathBuf>,
+    post_sql: Vec<PathBuf>,
+    schema_only: bool,
+    data_only: bool,
+    /// Pipe rows with COPY instead of dumping (data only)
+    direct_copy: bool,
+    /// Empty the loaded tables on the target first (data only)
+    truncate: bool,
+    /// Restore in one transaction, stopping at the first error
+    atomic: bool,
+    /// Move target sequences past the loaded ids afterwards
+    sync_sequences: bool,
+    /// Restore `public` into this schema instead (blue/green)
+    shadow_schema: Option<String>,
+    /// Roles renamed in ownership, grants and policies, from config
+    role_map: BTreeMap<String, String>,
+    /// Ownership and grants naming roles the target lacks, and who takes
+    /// them over when remapped
+    unknown_roles: UnknownRoles,
+    fallback_role: String,
+    source_kind: SourceKind,
+    target_kind: TargetKind,
+}
+
+/// Rewrites pointing the source's Storage URLs at the target, both the
+/// `<ref>.supabase.co` form and a custom domain set as `api_url`
+fn storage_url_rewrites(source: &ProjectConfig, target: &ProjectConfig) -> Vec<Rewrite> {
+    let to = target.storage_url();
+    let mut from = vec![
+        format!("https://{}.supabase.co/storage/v1", source.project_ref),
+        source.storage_url(),
+    ];
+    from.dedup();
+    from.into_iter()
+        .filter(|url| *url != to)
+        .map(|url| {
+            info!("Rewriting {} to {} in migrated rows", url, to);
+            Rewrite::literal(&url, &to)
+        })
+        .collect()
+}
+
+async fn migrate_database(
+    mut phase: DatabasePhase,
+    checkpoint: &Checkpoint,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    if checkpoint.is_done("database") {
+        println!(
+            "\n{} Skipping database migration (completed before the interruption)",
+            style("ℹ").blue()
+        );
+        return Ok(());
+    }
+    println!("\n{} Starting database migration...", style("🗄️").bold());
+    summary.begin_phase("database");
+
+    let hook_runner = || {
+        PgRestore::new(phase.target_url.clone())
+            .extra_args(phase.restore_args.clone())
+            .session_settings(phase.restore_timeouts.clone())
+    };
+    let (pre, post) = (hook_runner(), hook_runner());
+    let post_sql = std::mem::take(&mut phase.post_sql);
+    run_hooks(pre, std::mem::take(&mut phase.pre_sql)).await?;
+    // Tables whose sequences are synced, chosen like the dumped ones
+    let sequence_filter = phase.sync_sequences.then(|| {
+        PgDump::new(phase.source_url.clone())
+            .schemas(phase.schemas.clone())
+            .exclude_schemas(phase.excluded_schemas.clone())
+            .include_tables(phase.included_tables.clone())
+            .exclude_tables(phase.excluded_tables.clone())
+    });
+    if phase.truncate {
+        let dump = PgDump::new(phase.source_url.clone())
+            .schemas(phase.schemas.clone())
+            .exclude_schemas(phase.excluded_schemas.clone())
+            .include_tables(phase.included_tables.clone())
+            .exclude_tables(phase.excluded_tables.clone())
+            .exclude_table_data(phase.excluded_table_data.clone())
+            .always_exclude_table_data(phase.always_excluded_table_data.clone());
+        let truncated = PgRestore::new(phase.target_url.clone())
+            .truncate_for(&dump)
+            .await?;
+        println!(
+            "{} Truncated {} target table(s)",
+            style("✓").green(),
+            truncated.len()
+        );
+        summary.stat("tables_truncated", truncated.len() as u64);
+    }
+    let target_url = phase.target_url.clone();
+    let detail = transfer_database(phase, summary).await?;
+    if let Some(filter) = sequence_filter {
+        report_sequences(&sync_sequences(&target_url, &filter).await?, summary);
+    }
+    run_hooks(post, post_sql).await?;
+    summary.end_phase(detail);
+    checkpoint.complete("database")?;
+    Ok(())
+}
+
+/// Print how many lines each transformation changed, and write every change
+/// to `path` as JSON when given (the transformer kept them with
+/// `keep_changes`)
+pub fn report_transforms(
+    transformer: &LineTransformer,
+    path: Option<&Path>,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    let by_rule = transformer.counts();
+    let total: u64 = by_rule.values().sum();
+    if total > 0 {
+        println!(
+            "{} Transformations changed {} line(s): {}",
+            style("ℹ").blue(),
+            total,
+            by_rule
+                .iter()
+                .map(|(rule, count)| format!("{} {}", rule, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    summary.stat("lines_transformed", total);
+
+    match path {
+        Some(path) => {
+            let report = serde_json::json!({
+                "total": total,
+                "by_rule": by_rule,
+                "changes": transformer.changes(),
+            });
+            std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+            println!("  Transform report written to {}", path.display());
+            summary.artifact(path);
+        }
+        None if total > 0 => {
+            println!("  Pass --transform-report to list every change");
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Print the sequences `sync_sequences` moved forward
+fn report_sequences(adjustments: &[SequenceAdjustment], summary: &mut RunSummary) {
+    if adjustments.is_empty() {
+        println!("{} Sequences are up to date", style("✓").green());
+        return;
+    }
+    println!(
+        "{} Advanced {} sequence(s) past the migrated ids:",
+        style("✓").green(),
+        adjustments.len()
+    );
+    for adjustment in adjustments {
+        println!(
+            "  {} ({}): {} → {}",
+            adjustment.sequence,
+            adjustment.column,
+            adjustment
+                .previous
+                .map_or_else(|| "unused".to_string(), |value| value.to_string()),
+            adjustment.value
+        );
+    }
+    summary.stat("sequences_synced", adjustments.len() as u64);
+}
+
+/// Ask `question` before writing to a target. When `typed_ref` is set (the
+/// target looks like production), only typing that project ref proceeds.
+pub fn confirm(question: &str, typed_ref: Option<&str>) -> Result<bool> {
+    match typed_ref {
+        Some(project_ref) => print!(
+            "\n{} {}\n  This target looks like production. Type its project ref ({}) to confirm: ",
+            style("⚠").yellow(),
+            question,
+            style(project_ref).bold()
+        ),
+        None => print!("\n{} [y/N] ", question),
+    }
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+    Ok(match typed_ref {
+        Some(project_ref) => input == project_ref,
+        None => input.eq_ignore_ascii_case("y"),
+    })
+}
+
+/// Confirm writing to `target` (`name` on the command line) by asking
+/// `question`, unless `yes`. A target that looks like production needs its
+/// project ref even then: typed at the prompt, or passed as `confirm_ref`.
+pub fn confirm_target(
+    config: &Config,
+    name: &str,
+    target: &ProjectConfig,
+    question: &str,
+    yes: bool,
+    confirm_ref: Option<&str>,
+) -> Result<bool> {
+    if let Some(typed) = confirm_ref {
+        if typed != target.project_ref {
+            anyhow::bail!(
+                "--confirm-ref {} doesn't match the project ref of {} ({})",
+                typed,
+                display_name(name),
+                target.project_ref
+            );
+        }
+        return Ok(true);
+    }
+    let production = config.looks_like_production(name, target);
+    if yes && production {
+        anyhow::bail!(
+            "{} looks like production; pass --confirm-ref {} to write to it with --yes",
+            display_name(name),
+            target.project_ref
+        );
+    }
+    if yes {
+        return Ok(true);
+    }
+    confirm(question, production.then_some(target.project_ref.as_str()))
+}
+
+/// Comma-separated names of transformations for the plan, or "-"
+pub fn transform_list(rules: &[TransformRule]) -> String {
+    if rules.is_empty() {
+        return "-".to_string();
+    }
+    rules
+        .iter()
+        .map(|rule| rule.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Comma-separated `old=new` schema remaps for the plan, or "-"
+pub fn remap_list(remaps: &[(String, String)]) -> String {
+    if remaps.is_empty() {
+        return "-".to_string();
+    }
+    remaps
+        .iter()
+        .map(|(from, to)| format!("{}={}", from, to))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Comma-separated paths for the plan, or "-"
+pub fn file_list(files: &[PathBuf]) -> String {
+    if files.is_empty() {
+        return "-".to_string();
+    }
+    files
+        .iter()
+        .map(|file| file.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Run SQL hook files on the target in order, off the async workers
+async fn run_hooks(restore: PgRestore, files: Vec<PathBuf>) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    tokio::task::spawn_blocking(move || files.iter().try_for_each(|file| restore.run_hook(file)))
+        .await??;
+    Ok(())
+}
+
+/// Dump and restore (or copy) the database, returning the phase's detail
+async fn transfer_database(mut phase: DatabasePhase, summary: &mut RunSummary) -> Result<String> {
+    // Rows load with foreign keys and triggers off, so tables of a data-only
+    // dump can go in any order (the direct copy does this itself)
+    if phase.data_only && !phase.direct_copy {
+        let role = ("session_replication_role", "replica".to_string());
+        match PgRestore::new(phase.target_url.clone())
+            .execute("SET session_replication_role = replica")
+            .await
+        {
+            Ok(_) => phase.restore_timeouts.push(role),
+            Err(e) => warn!(
+                "Foreign keys and triggers stay on while loading, so tables load in dump order: {}",
+                e
+            ),
+        }
+    }
+    if phase.direct_copy {
+        let dump = PgDump::new(phase.source_url)
+            .schemas(phase.schemas)
+            .exclude_schemas(phase.excluded_schemas)
+            .include_tables(phase.included_tables)
+            .exclude_tables(phase.excluded_tables)
+            .exclude_table_data(phase.excluded_table_data)
+            .always_exclude_table_data(phase.always_excluded_table_data)
+            .row_filters(phase.row_filters)
+            .masks(phase.masks)
+            .data_only(true);
+        let stats = PgRestore::new(phase.target_url)
+            .session_settings(phase.restore_timeouts)
+            .copy_from(&dump, &phase.rewrites)
+            .await?;
+
+        println!(
+            "{} Database migration complete: {} rows in {} tables",
+            style("✓").green(),
+            stats.rows,
+            stats.tables
+        );
+        summary.stat("tables", stats.tables);
+        summary.stat("rows", stats.rows);
+        summary.stat("copied_bytes", stats.bytes);
+        return Ok(format!("{} rows in {} tables", stats.rows, stats.tables));
+    }
+
+    // pg_dump/psql block, so keep them off the async workers that drive
+    // concurrent storage and functions transfers
+    let report_path = phase.transform_report.take();
+    let keep_changes = report_path.is_some();
+    let output = phase.output_sql.take();
+    let output_path = output.clone();
+    let (dump_bytes, transformer) = tokio::task::spawn_blocking(move || -> Result<_> {
+        // Dump source database
+        let schemas = if phase.shadow_schema.is_some() {
+            vec![SHADOW_SOURCE_SCHEMA.to_string()]
+        } else {
+            phase.schemas
+        };
+        let dump = PgDump::new(phase.source_url)
+            .engine(phase.engine)
+            .schemas(schemas)
+            .exclude_schemas(phase.excluded_schemas)
+            .include_tables(phase.included_tables)
+            .exclude_tables(phase.excluded_tables)
+            .exclude_table_data(phase.excluded_table_data)
+            .always_exclude_table_data(phase.always_excluded_table_data)
+            .row_filters(phase.row_filters)
+            .masks(phase.masks)
+            .extra_args(phase.dump_args)
+            .schema_only(phase.schema_only)
+            .data_only(phase.data_only);
+
+        // Transform SQL for the target
+        let mut transformer =
+            LineTransformer::new(transform_kind(phase.source_kind, phase.target_kind))
+                .rewrite(phase.rewrites)
+                .custom(phase.transforms)
+                .script(phase.script)
+                .skip(phase.skipped_transforms)
+                .keep_changes(keep_changes)
+                .rename_roles(phase.role_map);
+        if let Some(shadow) = &phase.shadow_schema {
+            info!("Remapping {} to {}...", SHADOW_SOURCE_SCHEMA, shadow);
+            transformer = transformer.shadow_schema(SHADOW_SOURCE_SCHEMA, shadow);
+        }
+        for (from, to) in &phase.schema_remaps {
+            info!("Remapping {} to {}...", from, to);
+            transformer = transformer.remap_schema(from, to);
+        }
+
+        let restore = PgRestore::new(phase.target_url)
+            .extra_args(phase.restore_args)
+            .session_settings(phase.restore_timeouts)
+            .atomic(phase.atomic);
+        if phase.unknown_roles != UnknownRoles::Keep && !phase.data_only {
+            transformer = transformer.known_roles(
+                restore.role_names()?,
+                phase.unknown_roles,
+                &phase.fallback_role,
+            );
+        }
+
+        // Either engine streams through the transformer into psql
+        let bytes = match &output_path {
+            Some(path) => restore.write_dump(&dump, &mut transformer, path)?,
+            None => restore.restore_from_dump(&dump, &mut transformer)?,
+        };
+        Ok((bytes, transformer))
+    })
+    .await??;
+
+    summary.stat("dump_bytes", dump_bytes);
+    let detail = match output {
+        Some(path) => {
+            println!(
+                "{} Transformed SQL written to {} ({})",
+                style("✓").green(),
+                path.display(),
+                human_bytes(dump_bytes)
+            );
+            summary.artifact(&path);
+            format!("written to {}", path.display())
+        }
+        None => {
+            println!("{} Database migration complete!", style("✓").green());
+            "restored".to_string()
+        }
+    };
+    report_transforms(&transformer, report_path.as_deref(), summary)?;
+    Ok(detail)
+}
+
+async fn migrate_storage(
+    config: &Config,
+    source: &ProjectConfig,
+    target: &ProjectConfig,
+    checkpoint: &Checkpoint,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Starting storage migration...", style("📦").bold());
+    summary.begin_phase("storage");
+
+    let source_key = source.service_key.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("Source project requires service_key for storage migration")
+    })?;
+    let target_key = target.service_key.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("Target project requires service_key for storage migration")
+    })?;
+
+    let source_storage = StorageClient::for_project(source, source_key.clone());
+    let target_storage = StorageClient::for_project(target, target_key.clone());
+
+    let transfer = StorageTransfer::new(source_storage)
+        .with_target(target_storage)
+        .parallel(config.defaults.parallel_transfers)
+        .ranged_threshold(config.defaults.ranged_download_threshold());
+
+    let stats = transfer.sync_all(Some(checkpoint)).await?;
+    println!(
+        "{} Storage migration complete: {}",
+        style("✓").green(),
+        stats
+    );
+    stats.record(summary);
+    summary.end_phase(&stats);
+    hooks::run(Hook::AfterStorageSync, summary).await
+}
+
+async fn migrate_object_ids(
+    source_url: &str,
+    target_url: &str,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Preserving storage object ids...", style("🔗").bold());
+    summary.begin_phase("object_ids");
+
+    let sync = sync_object_rows(source_url, target_url).await?;
+    if sync.missing > 0 {
+        println!(
+            "  {} {} source objects have no file on the target",
+            style("⚠").yellow(),
+            sync.missing
+        );
+        summary.warn(format!(
+            "{} storage objects missing on target",
+            sync.missing
+        ));
+    }
+    println!(
+        "{} Object ids preserved: {} objects",
+        style("✓").green(),
+        sync.updated
+    );
+    summary.stat("objects", sync.updated);
+    summary.end_phase(format!("{} objects", sync.updated));
+    Ok(())
+}
+
+async fn migrate_roles(
+    config: &Config,
+    source_url: &str,
+    target_url: &str,
+    target_kind: TargetKind,
+    engine: DumpEngine,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Migrating database roles...", style("👤").bold());
+    summary.begin_phase("roles");
+
+    let (source_url, target_url) = (source_url.to_string(), target_url.to_string());
+    let excluded = config.defaults.excluded_roles.clone();
+    let roles = tokio::task::spawn_blocking(move || -> Result<RoleDump> {
+        let roles = RoleDump::fetch(
+            &source_url,
+            engine,
+            &excluded,
+            target_kind == TargetKind::Supabase,
+        )?;
+        if !roles.sql.is_empty() {
+            PgRestore::new(target_url).restore_from_string(&roles.sql)?;
+        }
+        Ok(roles)
+    })
+    .await??;
+
+    for dropped in &roles.dropped_attributes {
+        summary.warn(format!("Role attribute not granted on target: {}", dropped));
+        println!(
+            "  {} Dropped {} (needs a superuser on the target)",
+            style("⚠").yellow(),
+            dropped
+        );
+    }
+    if !roles.login_roles.is_empty() {
+        let login_roles = roles.login_roles.join(", ");
+        summary.warn(format!("Login roles need a password: {}", login_roles));
+        println!(
+            "  {} Passwords aren't migrated; set one for: {}",
+            style("ℹ").blue(),
+            login_roles
+        );
+    }
+    println!(
+        "{} Roles migration complete: {} roles",
+        style("✓").green(),
+        roles.roles.len()
+    );
+    summary.stat("roles", roles.roles.len() as u64);
+    summary.end_phase(format!("{} roles", roles.roles.len()));
     Ok(())
 }
+
+async fn migrate_auth(
+    source_url: &str,
+    target: &ProjectConfig,
+    target_url: &str,
+    mode: AuthImportMode,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Migrating auth users...", style("👤").bold());
+    summary.begin_phase("auth");
+
+    let source_url = source_url.to_string();
+    let export = tokio::task::spawn_blocking(move || {
+        AuthUsersClient::new(source_url).export_with_sessions()
+    })
+    .await??;
+    let created = import_auth_users(&export, target, target_url.to_string(), mode).await?;
+
+    let identities: usize = export.users.iter().map(|u| u.identities.len()).sum();
+    println!(
+        "{} Auth migration complete: {} users (skipped {} existing); the source had {} identities \
+         and {} sessions",
+        style("✓").green(),
+        created,
+        export.users.len() - created,
+        identities,
+        export.sessions.len()
+    );
+    summary.stat("users", created as u64);
+    summary.end_phase(format!("{} users", created));
+    Ok(())
+}
+
+/// Create the source's extensions the target lacks, so the dump's objects
+/// find their types and functions
+async fn create_extensions(
+    target_url: &str,
+    extensions: &[Extension],
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Enabling extensions...", style("🧩").bold());
+    let created = Extension::create_missing(target_url, extensions).await?;
+    if created.is_empty() {
+        println!(
+            "  {} All {} extensions already enabled",
+            style("✓").green(),
+            extensions.len()
+        );
+    } else {
+        println!("  {} Enabled {}", style("✓").green(), created.join(", "));
+    }
+    summary.stat("extensions_created", created.len() as u64);
+    Ok(())
+}
+
+async fn migrate_realtime(
+    target_url: &str,
+    filter: &PgDump,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Migrating Realtime publication...", style("📡").bold());
+    summary.begin_phase("realtime");
+
+    let sync = sync_publication(filter, target_url, REALTIME_PUBLICATION).await?;
+    if sync.all_tables {
+        println!(
+            "  {} The target's {} publishes all tables already",
+            style("ℹ").blue(),
+            REALTIME_PUBLICATION
+        );
+    }
+    for table in &sync.added {
+        println!("  {} {}", style("+").green(), table);
+    }
+    for table in &sync.missing {
+        println!(
+            "  {} {} isn't on the target; not published",
+            style("⚠").yellow(),
+            table
+        );
+        summary.warn(format!("Realtime table missing on target: {}", table));
+    }
+    println!(
+        "{} Realtime migration complete: {} tables added",
+        style("✓").green(),
+        sync.added.len()
+    );
+    summary.stat("tables", sync.added.len() as u64);
+    summary.end_phase(format!("{} tables added", sync.added.len()));
+    Ok(())
+}
+
+async fn migrate_webhooks(
+    source: &ProjectConfig,
+    target: &ProjectConfig,
+    target_url: &str,
+    filter: &PgDump,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Migrating database webhooks...", style("🔔").bold());
+    summary.begin_phase("webhooks");
+
+    let from = ProjectUrls {
+        project_ref: source.project_ref.clone(),
+        api_url: source.api_url(),
+    };
+    let to = ProjectUrls {
+        project_ref: target.project_ref.clone(),
+        api_url: target.api_url(),
+    };
+    // Webhooks calling edge functions authenticate with the project's key
+    let keys = source
+        .service_key
+        .as_deref()
+        .zip(target.service_key.as_deref());
+    let sync = sync_webhooks(filter, target_url, |definition| {
+        let definition = rewrite_project_url(definition, &from, &to);
+        match keys {
+            Some((from, to)) => definition.replace(from, to),
+            None => definition,
+        }
+    })
+    .await?;
+
+    for webhook in &sync.created {
+        println!("  {} {}", style("+").green(), webhook);
+    }
+    for webhook in &sync.missing {
+        println!(
+            "  {} {}: table isn't on the target; skipped",
+            style("⚠").yellow(),
+            webhook
+        );
+        summary.warn(format!("Webhook table missing on target: {}", webhook));
+    }
+    println!(
+        "{} Webhooks migration complete: {} webhooks",
+        style("✓").green(),
+        sync.created.len()
+    );
+    summary.stat("webhooks", sync.created.len() as u64);
+    summary.end_phase(format!("{} webhooks", sync.created.len()));
+    Ok(())
+}
+
+async fn migrate_cron(
+    source_url: &str,
+    target_url: &str,
+    disable: bool,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!("\n{} Migrating pg_cron jobs...", style("⏰").bold());
+    summary.begin_phase("cron");
+
+    let sync = sync_cron_jobs(source_url, target_url, disable).await?;
+    for job in &sync.scheduled {
+        let state = if sync.disabled.contains(job) {
+            " (disabled)"
+        } else {
+            ""
+        };
+        println!("  {} {}{}", style("+").green(), job, state);
+    }
+    for job in &sync.existing {
+        println!("  {} {} (already scheduled)", style("=").dim(), job);
+    }
+    for job in &sync.other_databases {
+        println!(
+            "  {} {} runs in another database; not migrated",
+            style("⚠").yellow(),
+            job
+        );
+        summary.warn(format!(
+            "Cron job for another database not migrated: {}",
+            job
+        ));
+    }
+    println!(
+        "{} Cron migration complete: {} jobs",
+        style("✓").green(),
+        sync.scheduled.len()
+    );
+    summary.stat("jobs", sync.scheduled.len() as u64);
+    summary.stat("jobs_disabled", sync.disabled.len() as u64);
+    summary.end_phase(format!("{} jobs", sync.scheduled.len()));
+    Ok(())
+}
+
+/// Vault secrets as copied, or only listed when the source's key doesn't
+/// allow decrypting them
+enum VaultMigration {
+    NotEnabled,
+    Copied { created: usize, existing: usize },
+    Listed(Vec<VaultEntry>),
+}
+
+async fn migrate_vault(source_url: &str, target_url: &str, summary: &mut RunSummary) -> Result<()> {
+    println!("\n{} Migrating vault secrets...", style("🔐").bold());
+    summary.begin_phase("vault");
+
+    let (source_url, target_url) = (source_url.to_string(), target_url.to_string());
+    let migration = tokio::task::spawn_blocking(move || -> Result<VaultMigration> {
+        let source = VaultClient::new(source_url);
+        if !source.is_vault_enabled()? {
+            return Ok(VaultMigration::NotEnabled);
+        }
+        let backup = match source.backup() {
+            Ok(backup) => backup,
+            Err(e) => {
+                warn!("Could not decrypt the source's vault secrets: {}", e);
+                return Ok(VaultMigration::Listed(source.inventory()?));
+            }
+        };
+        let target = VaultClient::new(target_url);
+        if !target.is_vault_enabled()? {
+            anyhow::bail!(
+                "Vault extension is not enabled in the target project. Enable it with: \
+                 CREATE EXTENSION IF NOT EXISTS supabase_vault"
+            );
+        }
+        let created = target.restore(&backup)?;
+        Ok(VaultMigration::Copied {
+            created,
+            existing: backup.secrets.len() - created,
+        })
+    })
+    .await??;
+
+    let detail = match migration {
+        VaultMigration::NotEnabled => {
+            println!(
+                "  {} Vault isn't enabled on the source; nothing to migrate",
+                style("ℹ").blue()
+            );
+            "vault not enabled".to_string()
+        }
+        VaultMigration::Copied { created, existing } => {
+            println!(
+                "{} Vault migration complete: {} vault secrets (skipped {} existing)",
+                style("✓").green(),
+                created,
+                existing
+            );
+            summary.stat("vault_secrets", created as u64);
+            format!("{} vault secrets", created)
+        }
+        VaultMigration::Listed(entries) => {
+            println!(
+                "  {} The source's secrets can't be decrypted with this connection; recreate \
+                 these on the target:",
+                style("⚠").yellow()
+            );
+            for entry in &entries {
+                let description = entry.description.as_deref().unwrap_or("(no description)");
+                println!("    {} {} - {}", style("•").cyan(), entry.name, description);
+                summary.warn(format!("Vault secret to recreate manually: {}", entry.name));
+            }
+            summary.stat("vault_secrets_listed", entries.len() as u64);
+            format!("{} secrets listed", entries.len())
+        }
+    };
+    summary.end_phase(detail);
+    Ok(())
+}
+
+async fn migrate_functions(
+    source: &ProjectConfig,
+    target: &ProjectConfig,
+    checkpoint: &Checkpoint,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    println!(
+        "\n{} Starting edge functions migration...",
+        style("⚡").bold()
+    );
+    summary.begin_phase("functions");
+
+    let source_key = source.service_key.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("Source project requires service_key for edge functions migration")
+    })?;
+    let target_key = target.service_key.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("Target project requires service_key for edge functions migration")
+    })?;
+
+    let source_functions = FunctionsClient::for_project(source, source_key.clone());
+    let target_functions = FunctionsClient::for_project(target, target_key.clone());
+
+    let functions = source_functions.backup_all().await?;
+    for func in &functions {
+        let step = format!("function:{}", func.slug);
+        if checkpoint.is_done(&step) {
+            info!("Skipping function {} (deployed before)", func.slug);
+            continue;
+        }
+        target_functions.deploy_function(func).await?;
+        checkpoint.complete(&step)?;
+        info!("Deployed function: {}", func.slug);
+    }
+
+    println!(
+        "{} Edge functions migration complete: {} functions deployed",
+        style("✓").green(),
+        functions.len()
+    );
+    summary.stat("functions", functions.len() as u64);
+    summary.end_phase(format!("{} functions", functions.len()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_target_with_yes() {
+        let config: Config = toml::from_str(
+            r#"
+[projects.prod]
+project_ref = "abcdefghijklmnop"
+db_password = "secret"
+
+[projects.staging]
+project_ref = "qrstuvwxyzabcdef"
+db_password = "secret"
+"#,
+        )
+        .unwrap();
+        let confirm = |name: &str, confirm_ref: Option<&str>| {
+            let project = config.get_project(name).unwrap();
+            confirm_target(&config, name, &project, "Proceed?", true, confirm_ref)
+        };
+
+        assert!(confirm("staging", None).unwrap());
+        // --yes alone never writes to production
+        assert!(confirm("prod", None).is_err());
+        assert!(confirm("prod", Some("qrstuvwxyzabcdef")).is_err());
+        assert!(confirm("prod", Some("abcdefghijklmnop")).unwrap());
+    }
+}
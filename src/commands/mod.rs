@@ -1,8 +1,15 @@
+pub mod auth;
 pub mod backup;
 pub mod config;
+pub mod cutover;
+pub mod db;
+pub mod diff;
 pub mod doctor;
 pub mod migrate;
+pub mod plan;
+pub mod report;
 pub mod restore;
 pub mod secrets;
 pub mod storage;
+pub mod upgrade;
 pub mod vault;
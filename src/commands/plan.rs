@@ -0,0 +1,260 @@
+use crate::cli::{MigrateArgs, PlanArgs};
+use crate::commands::migrate;
+use crate::config::{display_name, Config};
+use crate::summary::RunSummary;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const PLAN_VERSION: u32 = 1;
+
+/// Secret that signs plans with HMAC-SHA256, so only holders of the key can
+/// produce a plan `migrate --plan` accepts. Without it plans carry a plain
+/// SHA-256 checksum, which only catches accidental edits.
+const KEY_VAR: &str = "SUPAMIGRATE_PLAN_KEY";
+
+pub async fn run(config: &Config, args: PlanArgs, summary: &mut RunSummary) -> Result<()> {
+    let mut migrate = args.migrate;
+    if migrate.plan.is_some() {
+        bail!("--plan applies a plan; use `supamigrate migrate --plan` instead");
+    }
+    migrate.write_plan = Some(args.output);
+    migrate.dry_run = true;
+    Box::pin(migrate::run(config, migrate, summary)).await
+}
+
+/// What the source looked like when the plan was made
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSnapshot {
+    pub project_ref: String,
+    /// Fingerprint of each migrated table's definition, by qualified name
+    pub tables: BTreeMap<String, String>,
+}
+
+impl SourceSnapshot {
+    /// Material differences between this snapshot and `current`, one line each
+    pub fn changes(&self, current: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        if self.project_ref != current.project_ref {
+            changes.push(format!(
+                "source project is {}, plan was made against {}",
+                current.project_ref, self.project_ref
+            ));
+            return changes;
+        }
+        for (table, fingerprint) in &self.tables {
+            match current.tables.get(table) {
+                None => changes.push(format!("{}: table was dropped", table)),
+                Some(current) if current != fingerprint => {
+                    changes.push(format!("{}: columns or constraints changed", table));
+                }
+                Some(_) => {}
+            }
+        }
+        for table in current.tables.keys() {
+            if !self.tables.contains_key(table) {
+                changes.push(format!("{}: table was added", table));
+            }
+        }
+        changes
+    }
+}
+
+/// A reviewed migration, written by `supamigrate plan` and executed as-is by
+/// `migrate --plan`
+#[derive(Serialize, Deserialize)]
+pub struct PlanFile {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub tool_version: String,
+    /// Options the plan was made with. Source and target hold display names,
+    /// so connection strings never carry their password into the file.
+    pub args: MigrateArgs,
+    /// The plan as printed, after config defaults were resolved
+    pub options: BTreeMap<String, String>,
+    pub phases: Vec<String>,
+    pub excluded_schemas: Vec<String>,
+    pub excluded_tables: Vec<String>,
+    pub estimate: serde_json::Value,
+    pub source: SourceSnapshot,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl PlanFile {
+    /// Sign the plan and write it to `path`
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.args.from = display_name(&self.args.from);
+        self.args.to = display_name(&self.args.to);
+        self.signature = self.sign()?;
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write plan {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Read a plan, refusing one that was edited after it was signed
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read plan {}", path.display()))?;
+        let plan: Self = serde_json::from_str(&content)
+            .with_context(|| format!("{} is not a supamigrate plan", path.display()))?;
+        if plan.version != PLAN_VERSION {
+            bail!(
+                "Plan {} has version {}, this supamigrate reads version {}",
+                path.display(),
+                plan.version,
+                PLAN_VERSION
+            );
+        }
+
+        let keyed = std::env::var_os(KEY_VAR).is_some();
+        if plan.signature.starts_with("hmac-sha256:") && !keyed {
+            bail!(
+                "Plan {} is signed with a key; set {} to apply it",
+                path.display(),
+                KEY_VAR
+            );
+        }
+        if keyed && !plan.signature.starts_with("hmac-sha256:") {
+            bail!(
+                "Plan {} isn't signed with {}; regenerate it with the key set",
+                path.display(),
+                KEY_VAR
+            );
+        }
+        if plan.sign()? != plan.signature {
+            bail!(
+                "Plan {} was modified after it was written (signature mismatch)",
+                path.display()
+            );
+        }
+        Ok(plan)
+    }
+
+    /// Signature over the plan with the signature field left empty
+    fn sign(&self) -> Result<String> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..serde_json::from_value::<Self>(serde_json::to_value(self)?)?
+        };
+        let body = serde_json::to_vec(&unsigned)?;
+        Ok(match std::env::var(KEY_VAR) {
+            Ok(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+                    .context("Invalid plan signing key")?;
+                mac.update(&body);
+                format!("hmac-sha256:{:x}", mac.finalize().into_bytes())
+            }
+            Err(_) => format!("sha256:{:x}", Sha256::digest(&body)),
+        })
+    }
+
+    /// The plan's options, with the source and target connection strings from
+    /// the command line when given. They must name the same databases.
+    pub fn migrate_args(&self, cli: &MigrateArgs) -> Result<MigrateArgs> {
+        let mut args: MigrateArgs = serde_json::from_value(serde_json::to_value(&self.args)?)?;
+        for (flag, planned, given) in [
+            ("--from", &mut args.from, &cli.from),
+            ("--to", &mut args.to, &cli.to),
+        ] {
+            if given.is_empty() {
+                // A masked password can't connect, so the caller supplies it
+                if planned.contains(":****@") {
+                    bail!(
+                        "The plan was made for {}; pass {} with its connection string",
+                        planned,
+                        flag
+                    );
+                }
+                continue;
+            }
+            if display_name(given) != *planned {
+                bail!(
+                    "{} {} doesn't match the plan, which was made for {}",
+                    flag,
+                    display_name(given),
+                    planned
+                );
+            }
+            planned.clone_from(given);
+        }
+        args.plan.clone_from(&cli.plan);
+        args.allow_protected = cli.allow_protected;
+        args.yes = cli.yes;
+        args.confirm_ref.clone_from(&cli.confirm_ref);
+        Ok(args)
+    }
+
+    /// Options and exclusions that resolve differently now, e.g. because the
+    /// config changed since the plan was made
+    pub fn option_changes(
+        &self,
+        options: &BTreeMap<String, String>,
+        excluded_schemas: &[String],
+        excluded_tables: &[String],
+    ) -> Vec<String> {
+        let mut changes: Vec<String> = self
+            .options
+            .iter()
+            .filter(|(key, value)| options.get(*key) != Some(*value))
+            .map(|(key, value)| {
+                format!(
+                    "{}: planned {}, now {}",
+                    key,
+                    value,
+                    options.get(key).map_or("-", String::as_str)
+                )
+            })
+            .collect();
+        if self.excluded_schemas != excluded_schemas {
+            changes.push(format!(
+                "Excluded schemas: planned {}, now {}",
+                self.excluded_schemas.join(", "),
+                excluded_schemas.join(", ")
+            ));
+        }
+        if self.excluded_tables != excluded_tables {
+            changes.push(format!(
+                "Excluded tables: planned {}, now {}",
+                self.excluded_tables.join(", "),
+                excluded_tables.join(", ")
+            ));
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tables: &[(&str, &str)]) -> SourceSnapshot {
+        SourceSnapshot {
+            project_ref: "abcdefghijklmnop".to_string(),
+            tables: tables
+                .iter()
+                .map(|(name, fingerprint)| ((*name).to_string(), (*fingerprint).to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_source_snapshot_changes() {
+        let planned = snapshot(&[("public.orders", "aaaa"), ("public.users", "bbbb")]);
+        assert!(planned.changes(&planned.clone()).is_empty());
+
+        let current = snapshot(&[("public.orders", "cccc"), ("public.audit", "dddd")]);
+        assert_eq!(
+            planned.changes(&current),
+            vec![
+                "public.orders: columns or constraints changed",
+                "public.users: table was dropped",
+                "public.audit: table was added",
+            ]
+        );
+    }
+}
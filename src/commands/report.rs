@@ -0,0 +1,36 @@
+use crate::cli::{ReportArgs, ReportFormat};
+use crate::summary::{render_html, render_markdown, RunSummary};
+use anyhow::Result;
+use console::style;
+use std::fs;
+
+pub fn run(args: &ReportArgs) -> Result<()> {
+    let path = match &args.run {
+        Some(path) => path.clone(),
+        None => RunSummary::latest_path()?.ok_or_else(|| {
+            anyhow::anyhow!("No recorded runs found. Run migrate, backup or restore first.")
+        })?,
+    };
+
+    let summary = RunSummary::load(&path)?;
+
+    let report = match args.format {
+        ReportFormat::Markdown => render_markdown(&summary),
+        ReportFormat::Html => render_html(&summary),
+    };
+
+    match &args.output {
+        Some(output) => {
+            fs::write(output, report)?;
+            println!(
+                "{} Report for {} written to {}",
+                style("✓").green(),
+                path.display(),
+                output.display()
+            );
+        }
+        None => print!("{}", report),
+    }
+
+    Ok(())
+}
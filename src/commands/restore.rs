@@ -1,16 +1,31 @@
-use crate::cli::RestoreArgs;
+use crate::auth::AuthExport;
+use crate::branches::resolve_project;
+use crate::cli::{DumpFormat, RestoreArgs, TargetKind, TransformRule, UnknownRoles};
+use crate::commands::auth::import_auth_users;
+use crate::commands::migrate::{
+    check_extensions, confirm_target, file_list, remap_list, report_transforms, transform_list,
+    REMAP_CONFLICTS,
+};
 use crate::commands::secrets::restore_secrets;
 use crate::commands::vault::restore_vault;
-use crate::config::Config;
-use crate::db::{PgRestore, SqlTransformer, VaultBackup};
+use crate::config::{display_name, Config};
+use crate::db::{
+    Extension, LineTransformer, PgRestore, SqlTransformer, TargetLock, TransformKind, VaultBackup,
+};
 use crate::error::SupamigrateError;
 use crate::functions::secrets::SecretsBackup;
-use crate::functions::{FunctionBackup, FunctionFile, FunctionsClient};
-use crate::storage::StorageClient;
+use crate::functions::{
+    FunctionBackup, FunctionFile, FunctionsClient, ESZIP_BUNDLE_FILE, TARBALL_BUNDLE_FILE,
+};
+use crate::hooks;
+use crate::storage::archive::{archive_bucket_name, read_bucket_archive};
+use crate::storage::manifest::read_manifest;
+use crate::storage::{Bucket, NewBucket, StorageClient};
+use crate::summary::RunSummary;
 use anyhow::Result;
 use console::style;
 use std::fs;
-use std::io::{self, Read, Write};
+use std::io::Read;
 use tracing::info;
 
 #[derive(serde::Deserialize)]
@@ -32,12 +47,68 @@ struct BackupMetadata {
     include_vault: bool,
     #[serde(default)]
     vault_count: usize,
+    #[serde(default)]
+    include_auth: bool,
+    #[serde(default)]
+    auth_users: usize,
     compressed: bool,
+    /// Missing in backups made before custom-format dumps
+    #[serde(default)]
+    format: DumpFormat,
 }
 
-pub async fn run(args: RestoreArgs) -> Result<()> {
-    let config = Config::load(None)?;
-    let target = config.get_project(&args.to)?;
+pub async fn run(config: &Config, mut args: RestoreArgs, summary: &mut RunSummary) -> Result<()> {
+    let target = resolve_project(config, &args.to).await?;
+    let output_sql = args.transform.output_sql.clone();
+    // Writing the SQL file leaves the target alone
+    if output_sql.is_none() {
+        config.ensure_writable(&args.to, &target, args.allow_protected)?;
+    }
+
+    // Plain Postgres has no storage, functions, secrets, vault or auth to
+    // restore into
+    if args.target_kind == TargetKind::Postgres {
+        for (flag, enabled) in [
+            ("--include-storage", &mut args.include_storage),
+            ("--include-functions", &mut args.include_functions),
+            ("--include-secrets", &mut args.include_secrets),
+            ("--include-vault", &mut args.include_vault),
+            ("--include-auth", &mut args.include_auth),
+        ] {
+            if std::mem::take(enabled) {
+                println!(
+                    "{} Ignoring {} for a plain Postgres target",
+                    style("⚠").yellow(),
+                    flag
+                );
+                summary.warn(format!("{} ignored for a plain Postgres target", flag));
+            }
+        }
+    }
+    // Only the database goes into the SQL file
+    if output_sql.is_some() {
+        for (flag, enabled) in [
+            ("--include-storage", &mut args.include_storage),
+            ("--include-functions", &mut args.include_functions),
+            ("--include-secrets", &mut args.include_secrets),
+            ("--include-vault", &mut args.include_vault),
+            ("--include-auth", &mut args.include_auth),
+        ] {
+            if std::mem::take(enabled) {
+                println!(
+                    "{} Ignoring {} with --output-sql",
+                    style("⚠").yellow(),
+                    flag
+                );
+                summary.warn(format!("{} ignored with --output-sql", flag));
+            }
+        }
+    }
+
+    // on_failure hooks learn what was being restored where
+    hooks::context("target", display_name(&args.to));
+    hooks::context("target_ref", &target.project_ref);
+    hooks::context("backup_path", args.from.display());
 
     // Validate backup exists
     if !args.from.exists() {
@@ -55,7 +126,22 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
 
     println!("\n{} Restore Plan", style("📋").bold());
     println!("  From: {}", args.from.display());
-    println!("  Target: {} ({})", args.to, target.project_ref);
+    println!(
+        "  Target: {} ({})",
+        display_name(&args.to),
+        target.project_ref
+    );
+    println!(
+        "  Target kind: {}",
+        format!("{:?}", args.target_kind).to_lowercase()
+    );
+    if metadata.format != DumpFormat::Plain {
+        println!(
+            "  Database: {}-format archive ({} jobs)",
+            format!("{:?}", metadata.format).to_lowercase(),
+            if args.atomic { 1 } else { args.jobs }
+        );
+    }
     println!(
         "  Include storage: {}",
         args.include_storage && metadata.include_storage
@@ -89,27 +175,130 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
             "no vault secrets in backup".to_string()
         }
     );
+    println!(
+        "  Include auth: {} ({})",
+        args.include_auth && metadata.include_auth,
+        if metadata.include_auth {
+            format!("{} auth users in backup", metadata.auth_users)
+        } else {
+            "no auth users in backup".to_string()
+        }
+    );
+
+    let (pre_sql, post_sql) = config.defaults.hooks(&args.hooks);
+    for (key, files) in [("Pre-SQL", &pre_sql), ("Post-SQL", &post_sql)] {
+        if !files.is_empty() {
+            println!("  {}: {}", key, file_list(files));
+            summary.plan(key, file_list(files));
+        }
+    }
+    let skipped = args.transform.skipped();
+    if !skipped.is_empty() {
+        println!("  Skipped transforms: {}", transform_list(&skipped));
+        summary.plan("Skipped transforms", transform_list(&skipped));
+    }
+    let remaps = &args.transform.remap_schema;
+    if !remaps.is_empty() {
+        println!("  Remap schemas: {}", remap_list(remaps));
+        summary.plan("Remap schemas", remap_list(remaps));
+        if !args.atomic {
+            println!("  {} {}", style("⚠").yellow(), REMAP_CONFLICTS);
+            summary.warn(REMAP_CONFLICTS);
+        }
+    }
+    let script = config.transform_script()?;
+    if let Some(script) = &script {
+        println!("  Transform script: {}", script.path().display());
+        summary.plan("Transform script", script.path().display());
+    }
+    if let Some(path) = &output_sql {
+        println!("  Output SQL: {}", path.display());
+        summary.plan("Output SQL", path.display());
+    }
 
-    if !args.yes {
-        print!("\n⚠️  This will overwrite data in the target project. Proceed? [y/N] ");
-        io::stdout().flush()?;
+    summary.plan("From", args.from.display());
+    summary.plan(
+        "Target",
+        format!("{} ({})", display_name(&args.to), target.project_ref),
+    );
+    summary.plan(
+        "Target kind",
+        format!("{:?}", args.target_kind).to_lowercase(),
+    );
+    summary.plan(
+        "Include storage",
+        args.include_storage && metadata.include_storage,
+    );
+    summary.plan(
+        "Include functions",
+        args.include_functions && metadata.include_functions,
+    );
+    summary.plan(
+        "Include secrets",
+        args.include_secrets && metadata.include_secrets,
+    );
+    summary.plan(
+        "Include vault",
+        args.include_vault && metadata.include_vault,
+    );
+    summary.plan("Include auth", args.include_auth && metadata.include_auth);
+
+    if output_sql.is_none()
+        && !confirm_target(
+            config,
+            &args.to,
+            &target,
+            "⚠️  This will overwrite data in the target project. Proceed?",
+            args.yes,
+            args.confirm_ref.as_deref(),
+        )?
+    {
+        println!("Restore cancelled.");
+        summary.cancel();
+        return Ok(());
+    }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    // Held until the restore returns, so concurrent runs into the same
+    // target fail fast instead of interleaving
+    let _lock = match output_sql {
+        Some(_) => None,
+        None => Some(TargetLock::acquire(&target.db_url(), "restore").await?),
+    };
 
-        if !input.trim().eq_ignore_ascii_case("y") {
-            println!("Restore cancelled.");
-            return Ok(());
+    // Restored tables may reference auth.users, and their foreign keys are
+    // added once their rows are in
+    if args.include_auth && metadata.include_auth {
+        println!("\n{} Restoring auth users...", style("👤").bold());
+        summary.begin_phase("auth");
+
+        let auth_file = args.from.join("auth_users.json");
+        if auth_file.exists() {
+            let export: AuthExport = serde_json::from_str(&fs::read_to_string(&auth_file)?)?;
+            let created =
+                import_auth_users(&export, &target, target.db_url(), args.auth_mode).await?;
+            summary.stat("users", created as u64);
+            println!(
+                "{} Auth restore complete: {} users created (skipped {} existing)",
+                style("✓").green(),
+                created,
+                export.users.len() - created
+            );
+            summary.end_phase(format!("{} users", created));
+        } else {
+            println!("{} No auth backup found, skipping", style("⚠️").yellow());
+            summary.end_phase("done");
         }
     }
 
     // Database restore
     println!("\n{} Restoring database...", style("🗄️").bold());
+    summary.begin_phase("database");
 
-    let dump_file = if metadata.compressed {
-        args.from.join("database.sql.gz")
-    } else {
-        args.from.join("database.sql")
+    let dump_file = match metadata.format {
+        DumpFormat::Custom => args.from.join("database.dump"),
+        DumpFormat::Directory => args.from.join("database"),
+        DumpFormat::Plain if metadata.compressed => args.from.join("database.sql.gz"),
+        DumpFormat::Plain => args.from.join("database.sql"),
     };
 
     if !dump_file.exists() {
@@ -120,56 +309,228 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
         .into());
     }
 
-    let sql = if metadata.compressed {
-        let file = fs::File::open(&dump_file)?;
-        let mut decoder = flate2::read::GzDecoder::new(file);
-        let mut content = String::new();
-        decoder.read_to_string(&mut content)?;
-        content
-    } else {
-        fs::read_to_string(&dump_file)?
+    let rewrites = config.rewrites()?;
+    let transforms = config.transforms()?;
+    let restore = PgRestore::new(target.db_url())
+        .extra_args(config.defaults.psql_args(&args.psql_args))
+        .session_settings(config.defaults.restore_timeouts(&args.timeouts))
+        .atomic(args.atomic);
+
+    // Plain backups are read up front so their extensions can be checked
+    // before anything is written
+    let sql = match metadata.format {
+        DumpFormat::Plain if metadata.compressed => {
+            let file = fs::File::open(&dump_file)?;
+            let mut decoder = flate2::read::GzDecoder::new(file);
+            let mut content = String::new();
+            decoder.read_to_string(&mut content)?;
+            Some(content)
+        }
+        DumpFormat::Plain => Some(fs::read_to_string(&dump_file)?),
+        DumpFormat::Custom | DumpFormat::Directory => None,
     };
 
-    // Transform SQL for Supabase compatibility
-    info!("Transforming SQL...");
-    let transformed = SqlTransformer::transform(&sql);
+    if !args.skip_extension_check {
+        let kind = transform_kind(args.target_kind).unless_skipped(&skipped);
+        let wanted: Vec<Extension> = match &sql {
+            Some(sql) => Extension::in_dump(sql),
+            None => Extension::in_toc(&PgRestore::list_archive(&dump_file)?),
+        }
+        .into_iter()
+        .filter(|extension| kind.keeps_extension(&extension.name))
+        .collect();
+        check_extensions(&target.db_url(), &wanted, false, summary).await?;
+    }
 
-    // Restore to target
-    info!("Restoring to target database...");
-    let restore = PgRestore::new(target.db_url());
-    restore.restore_from_string(&transformed)?;
+    // Hooks run against the target, which the SQL file leaves alone
+    if output_sql.is_none() {
+        for file in &pre_sql {
+            restore.run_hook(file)?;
+        }
+    }
+
+    if let Some(sql) = sql {
+        // Transform SQL for the target
+        info!("Transforming SQL...");
+        let mut transformer = LineTransformer::new(transform_kind(args.target_kind))
+            .rewrite(rewrites)
+            .custom(transforms)
+            .script(script)
+            .skip(skipped.clone())
+            .keep_changes(args.transform.transform_report.is_some())
+            .rename_roles(config.role_map.clone());
+        for (from, to) in remaps {
+            transformer = transformer.remap_schema(from, to);
+        }
+        let unknown_roles = config.defaults.unknown_roles(args.unknown_roles);
+        if unknown_roles != UnknownRoles::Keep {
+            transformer = transformer.known_roles(
+                restore.role_names()?,
+                unknown_roles,
+                &config.defaults.fallback_role,
+            );
+        }
+        let sql = transformer.apply(&sql)?;
+
+        if let Some(path) = &output_sql {
+            let written = restore.write_sql(&sql, path)?;
+            summary.stat("sql_bytes", written);
+            summary.artifact(path);
+        } else {
+            // Restore to target
+            info!("Restoring to target database...");
+            restore.restore_from_string(&sql)?;
+            summary.stat("sql_bytes", sql.len() as u64);
+        }
+        report_transforms(
+            &transformer,
+            args.transform.transform_report.as_deref(),
+            summary,
+        )?;
+    } else {
+        if !rewrites.is_empty() {
+            return Err(SupamigrateError::Config(
+                "Rewrites only apply to plain SQL backups".to_string(),
+            )
+            .into());
+        }
+        if !transforms.is_empty() {
+            return Err(SupamigrateError::Config(
+                "[transforms] only apply to plain SQL backups".to_string(),
+            )
+            .into());
+        }
+        if script.is_some() {
+            return Err(SupamigrateError::Config(
+                "transform_script only applies to plain SQL backups".to_string(),
+            )
+            .into());
+        }
+        if !remaps.is_empty() {
+            return Err(SupamigrateError::Config(
+                "--remap-schema only applies to plain SQL backups".to_string(),
+            )
+            .into());
+        }
+        if output_sql.is_some() {
+            return Err(SupamigrateError::Config(
+                "--output-sql only applies to plain SQL backups".to_string(),
+            )
+            .into());
+        }
+        if !config.role_map.is_empty() {
+            return Err(SupamigrateError::Config(
+                "[role_map] only applies to plain SQL backups".to_string(),
+            )
+            .into());
+        }
+        match args.unknown_roles.or(config.defaults.unknown_roles) {
+            Some(UnknownRoles::Keep) => {}
+            Some(_) => {
+                return Err(SupamigrateError::Config(
+                    "unknown_roles only applies to plain SQL backups".to_string(),
+                )
+                .into())
+            }
+            // Remapping is only the default for plain backups
+            None => {
+                println!(
+                    "{} Archives keep the source's roles; statements naming roles missing on the target fail",
+                    style("⚠").yellow()
+                );
+                summary.warn("Archive restored with the source's roles as-is");
+            }
+        }
+        // The same transformations, applied to the archive's table of contents
+        info!(
+            "Restoring archive to target database with {} jobs...",
+            if args.atomic { 1 } else { args.jobs }
+        );
+        match args.target_kind {
+            TargetKind::Supabase if skipped.contains(&TransformRule::SupabaseManaged) => {
+                restore.restore_archive(&dump_file, args.jobs, &[], str::to_string)?;
+            }
+            TargetKind::Supabase => {
+                restore.restore_archive(
+                    &dump_file,
+                    args.jobs,
+                    &[],
+                    SqlTransformer::transform_toc,
+                )?;
+            }
+            TargetKind::Postgres if skipped.contains(&TransformRule::ForPostgres) => {
+                restore.restore_archive(&dump_file, args.jobs, &[], str::to_string)?;
+            }
+            TargetKind::Postgres => restore.restore_archive(
+                &dump_file,
+                args.jobs,
+                &["--no-owner"],
+                SqlTransformer::transform_toc_for_postgres,
+            )?,
+        }
+    }
+
+    if let Some(path) = &output_sql {
+        println!(
+            "{} Transformed SQL written to {}",
+            style("✓").green(),
+            path.display()
+        );
+        summary.end_phase(format!("written to {}", path.display()));
+        return Ok(());
+    }
+
+    for file in &post_sql {
+        restore.run_hook(file)?;
+    }
 
     println!("{} Database restore complete!", style("✓").green());
+    summary.end_phase("restored");
 
     // Storage restore
     if args.include_storage && metadata.include_storage {
         println!("\n{} Restoring storage...", style("📦").bold());
+        summary.begin_phase("storage");
 
         let service_key = target.service_key.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Target project requires service_key for storage restore")
         })?;
 
-        let storage = StorageClient::new(target.api_url(), service_key.clone());
+        let storage = StorageClient::for_project(&target, service_key.clone());
         let storage_dir = args.from.join("storage");
 
+        // Older backups have no buckets.json; their buckets are restored as private
+        let buckets_file = args.from.join("buckets.json");
+        let buckets: Vec<Bucket> = if buckets_file.exists() {
+            serde_json::from_str(&fs::read_to_string(&buckets_file)?)?
+        } else {
+            Vec::new()
+        };
+
         if storage_dir.exists() {
-            let stats = restore_storage(&storage, &storage_dir).await?;
+            let stats = restore_storage(&storage, &storage_dir, &buckets).await?;
             println!("{} Storage restore complete: {}", style("✓").green(), stats);
+            summary.stat("buckets", stats.buckets as u64);
+            summary.stat("objects", stats.objects as u64);
+            summary.stat("bytes", stats.bytes as u64);
+            summary.end_phase(&stats);
         } else {
             println!("{} No storage backup found, skipping", style("⚠️").yellow());
+            summary.warn("No storage backup found");
+            summary.end_phase("skipped");
         }
     }
 
     // Edge Functions restore
     if args.include_functions && metadata.include_functions {
         println!("\n{} Restoring edge functions...", style("⚡").bold());
+        summary.begin_phase("functions");
 
         let service_key = target.service_key.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Target project requires service_key for edge functions restore")
         })?;
 
-        let functions_client =
-            FunctionsClient::new(target.project_ref.clone(), service_key.clone());
+        let functions_client = FunctionsClient::for_project(&target, service_key.clone());
 
         let functions_dir = args.from.join("functions");
 
@@ -180,17 +541,22 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
                 style("✓").green(),
                 stats
             );
+            summary.stat("functions", stats.functions as u64);
+            summary.end_phase(&stats);
         } else {
             println!(
                 "{} No functions backup found, skipping",
                 style("⚠️").yellow()
             );
+            summary.warn("No functions backup found");
+            summary.end_phase("skipped");
         }
     }
 
     // Secrets restore
     if args.include_secrets && metadata.include_secrets {
         println!("\n{} Restoring secrets...", style("🔐").bold());
+        summary.begin_phase("secrets");
 
         let secrets_file = args.from.join("secrets.json");
 
@@ -202,9 +568,9 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
                 println!("{} No secrets in backup, skipping", style("ℹ").blue());
             } else {
                 let count =
-                    restore_secrets(&secrets_backup, &args.to, args.secrets_file.as_deref())
-                        .await?;
+                    restore_secrets(&secrets_backup, &target, args.secrets_file.as_deref()).await?;
 
+                summary.stat("secrets", count as u64);
                 if count > 0 {
                     println!(
                         "{} Secrets restore complete: {} secrets set",
@@ -221,11 +587,13 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
         } else {
             println!("{} No secrets backup found, skipping", style("⚠️").yellow());
         }
+        summary.end_phase("done");
     }
 
     // Vault restore
     if args.include_vault && metadata.include_vault {
         println!("\n{} Restoring vault secrets...", style("🔐").bold());
+        summary.begin_phase("vault");
 
         let vault_file = args.from.join("vault_secrets.json");
 
@@ -236,10 +604,11 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
             if vault_backup.secrets.is_empty() {
                 println!("{} No vault secrets in backup, skipping", style("ℹ").blue());
             } else {
-                match restore_vault(&vault_backup, &args.to) {
+                match restore_vault(&vault_backup, &target) {
                     Ok(count) => {
+                        summary.stat("vault_secrets", count as u64);
                         println!(
-                            "{} Vault restore complete: {} secrets created (skipped {} existing)",
+                            "{} Vault restore complete: {} vault secrets created (skipped {} existing)",
                             style("✓").green(),
                             count,
                             vault_backup.secrets.len() - count
@@ -247,12 +616,14 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
                     }
                     Err(e) => {
                         println!("{} Vault restore failed: {}", style("⚠").yellow(), e);
+                        summary.warn(format!("Vault restore failed: {}", e));
                     }
                 }
             }
         } else {
             println!("{} No vault backup found, skipping", style("⚠️").yellow());
         }
+        summary.end_phase("done");
     }
 
     println!("\n{} Restore completed successfully!", style("🎉").bold());
@@ -287,17 +658,17 @@ async fn restore_functions(
             let entrypoint_path = metadata["entrypoint_path"].as_str().map(String::from);
             let import_map_path = metadata["import_map_path"].as_str().map(String::from);
 
+            // Original bundle, if the backup kept one
+            let bundle = match metadata["bundle"].as_str() {
+                Some(file) => Some(fs::read(func_dir.join(file))?),
+                None => None,
+            };
+
             // Read function files
             let mut files = Vec::new();
             read_function_files(&func_dir, &func_dir, &mut files)?;
 
-            // Filter out metadata.json
-            let files: Vec<FunctionFile> = files
-                .into_iter()
-                .filter(|f| f.name != "metadata.json")
-                .collect();
-
-            if files.is_empty() {
+            if files.is_empty() && bundle.is_none() {
                 continue;
             }
 
@@ -308,6 +679,7 @@ async fn restore_functions(
                 entrypoint_path,
                 import_map_path,
                 files,
+                bundle,
             };
 
             info!("Deploying function: {}", slug);
@@ -337,6 +709,13 @@ fn read_function_files(
                 .to_string_lossy()
                 .to_string();
 
+            // Backup bookkeeping, not function source
+            if ["metadata.json", ESZIP_BUNDLE_FILE, TARBALL_BUNDLE_FILE]
+                .contains(&relative_path.as_str())
+            {
+                continue;
+            }
+
             let content = fs::read_to_string(&path)?;
             files.push(FunctionFile {
                 name: relative_path,
@@ -350,6 +729,7 @@ fn read_function_files(
 async fn restore_storage(
     client: &StorageClient,
     storage_dir: &std::path::Path,
+    buckets: &[Bucket],
 ) -> Result<RestoreStats> {
     use tokio::fs;
 
@@ -357,27 +737,71 @@ async fn restore_storage(
 
     let mut entries = fs::read_dir(storage_dir).await?;
     while let Some(entry) = entries.next_entry().await? {
-        if entry.file_type().await?.is_dir() {
-            let bucket_name = entry.file_name().to_string_lossy().to_string();
-
-            // Create bucket (assume public for now, could store in metadata)
-            client.create_bucket(&bucket_name, false).await?;
-            stats.buckets += 1;
-
-            // Upload files
-            let bucket_dir = entry.path();
-            let mut files = fs::read_dir(&bucket_dir).await?;
-
-            while let Some(file_entry) = files.next_entry().await? {
-                if file_entry.file_type().await?.is_file() {
-                    let file_name = file_entry.file_name().to_string_lossy().to_string();
-                    let data = fs::read(file_entry.path()).await?;
-                    let data_len = data.len();
-
-                    client.upload(&bucket_name, &file_name, data.into()).await?;
-                    stats.objects += 1;
-                    stats.bytes += data_len;
-                }
+        let file_type = entry.file_type().await?;
+        let archive_bucket = archive_bucket_name(&entry.path()).filter(|_| file_type.is_file());
+        if !file_type.is_dir() && archive_bucket.is_none() {
+            continue;
+        }
+
+        let bucket_name =
+            archive_bucket.unwrap_or_else(|| entry.file_name().to_string_lossy().to_string());
+
+        // Create bucket with its recorded settings when available
+        let new_bucket = buckets
+            .iter()
+            .find(|b| b.name == bucket_name)
+            .map_or_else(|| NewBucket::private(&bucket_name), NewBucket::from);
+        client.create_bucket(&new_bucket).await?;
+        stats.buckets += 1;
+
+        let manifest = read_manifest(storage_dir, &bucket_name)?;
+
+        if file_type.is_file() {
+            // Archives are read on a blocking thread and uploaded as they stream out
+            let path = entry.path();
+            let (tx, mut rx) = tokio::sync::mpsc::channel::<(String, Vec<u8>)>(4);
+            let reader = tokio::task::spawn_blocking(move || {
+                read_bucket_archive(&path, |name, data| {
+                    tx.blocking_send((name, data)).map_err(|_| {
+                        crate::error::SupamigrateError::Storage(
+                            "archive upload stopped".to_string(),
+                        )
+                    })
+                })
+            });
+
+            while let Some((name, data)) = rx.recv().await {
+                let data_len = data.len();
+                client
+                    .upload_with_metadata(&new_bucket.id, &name, data.into(), manifest.get(&name))
+                    .await?;
+                stats.objects += 1;
+                stats.bytes += data_len;
+            }
+            reader.await??;
+            continue;
+        }
+
+        // Upload files
+        let bucket_dir = entry.path();
+        let mut files = fs::read_dir(&bucket_dir).await?;
+
+        while let Some(file_entry) = files.next_entry().await? {
+            if file_entry.file_type().await?.is_file() {
+                let file_name = file_entry.file_name().to_string_lossy().to_string();
+                let data = fs::read(file_entry.path()).await?;
+                let data_len = data.len();
+
+                client
+                    .upload_with_metadata(
+                        &new_bucket.id,
+                        &file_name,
+                        data.into(),
+                        manifest.get(&file_name),
+                    )
+                    .await?;
+                stats.objects += 1;
+                stats.bytes += data_len;
             }
         }
     }
@@ -412,3 +836,11 @@ impl std::fmt::Display for FunctionsRestoreStats {
         write!(f, "{} functions deployed", self.functions)
     }
 }
+
+/// How a backup is adapted to this kind of target
+fn transform_kind(target: TargetKind) -> TransformKind {
+    match target {
+        TargetKind::Supabase => TransformKind::Supabase,
+        TargetKind::Postgres => TransformKind::ForPostgres,
+    }
+}
@@ -1,5 +1,6 @@
+use super::migrate::confirm;
 use crate::cli::{SecretsArgs, SecretsCommands};
-use crate::config::Config;
+use crate::config::{Config, ProjectConfig};
 use crate::functions::secrets::{
     generate_env_template, parse_env_file, Secret, SecretsBackup, SecretsClient,
 };
@@ -8,17 +9,26 @@ use console::style;
 use std::io::{self, Write};
 use std::path::Path;
 
-pub async fn run(args: SecretsArgs) -> Result<()> {
+pub async fn run(config: &Config, args: SecretsArgs) -> Result<()> {
     match args.command {
-        SecretsCommands::List { project } => list_secrets(&project).await,
-        SecretsCommands::Export { project, output } => export_secrets(&project, &output).await,
-        SecretsCommands::Import { project, file } => import_secrets(&project, &file).await,
-        SecretsCommands::Copy { from, to } => copy_secrets(&from, &to).await,
+        SecretsCommands::List { project } => list_secrets(config, &project).await,
+        SecretsCommands::Export { project, output } => {
+            export_secrets(config, &project, &output).await
+        }
+        SecretsCommands::Import {
+            project,
+            file,
+            allow_protected,
+        } => import_secrets(config, &project, &file, allow_protected).await,
+        SecretsCommands::Copy {
+            from,
+            to,
+            allow_protected,
+        } => copy_secrets(config, &from, &to, allow_protected).await,
     }
 }
 
-async fn list_secrets(project_name: &str) -> Result<()> {
-    let config = Config::load(None)?;
+async fn list_secrets(config: &Config, project_name: &str) -> Result<()> {
     let project = config.get_project(project_name)?;
 
     let access_token = project
@@ -26,7 +36,7 @@ async fn list_secrets(project_name: &str) -> Result<()> {
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Project requires access_token for secrets operations. Get one at: https://supabase.com/dashboard/account/tokens"))?;
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::for_project(&project, access_token.clone());
     let secrets = client.list_secrets().await?;
 
     println!(
@@ -53,8 +63,7 @@ async fn list_secrets(project_name: &str) -> Result<()> {
     Ok(())
 }
 
-async fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
-    let config = Config::load(None)?;
+async fn export_secrets(config: &Config, project_name: &str, output: &Path) -> Result<()> {
     let project = config.get_project(project_name)?;
 
     let access_token = project
@@ -62,7 +71,7 @@ async fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Project requires access_token for secrets operations"))?;
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::for_project(&project, access_token.clone());
     let secrets = client.list_secrets().await?;
 
     let template = generate_env_template(&secrets);
@@ -83,9 +92,14 @@ async fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
-    let config = Config::load(None)?;
+async fn import_secrets(
+    config: &Config,
+    project_name: &str,
+    file: &Path,
+    allow_protected: bool,
+) -> Result<()> {
     let project = config.get_project(project_name)?;
+    config.ensure_writable(project_name, &project, allow_protected)?;
 
     let access_token = project
         .access_token
@@ -125,18 +139,15 @@ async fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
         println!("  {} {}", style("•").cyan(), secret.name);
     }
 
-    print!("\nProceed? [y/N] ");
-    io::stdout().flush()?;
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-
-    if !input.trim().eq_ignore_ascii_case("y") {
+    let typed_ref = config
+        .looks_like_production(project_name, &project)
+        .then_some(project.project_ref.as_str());
+    if !confirm("Proceed?", typed_ref)? {
         println!("{} Cancelled", style("✗").red());
         return Ok(());
     }
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::for_project(&project, access_token.clone());
 
     let secrets_to_create: Vec<Secret> = secrets_with_values
         .into_iter()
@@ -157,10 +168,15 @@ async fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
-    let config = Config::load(None)?;
+async fn copy_secrets(
+    config: &Config,
+    from_name: &str,
+    to_name: &str,
+    allow_protected: bool,
+) -> Result<()> {
     let source = config.get_project(from_name)?;
     let target = config.get_project(to_name)?;
+    config.ensure_writable(to_name, &target, allow_protected)?;
 
     let source_token = source
         .access_token
@@ -171,7 +187,7 @@ async fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Target project requires access_token"))?;
 
-    let source_client = SecretsClient::new(source.project_ref.clone(), source_token.clone());
+    let source_client = SecretsClient::for_project(&source, source_token.clone());
     let secrets = source_client.list_secrets().await?;
 
     if secrets.is_empty() {
@@ -216,7 +232,7 @@ async fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
         return Ok(());
     }
 
-    let target_client = SecretsClient::new(target.project_ref.clone(), target_token.clone());
+    let target_client = SecretsClient::for_project(&target, target_token.clone());
     target_client.create_secrets(&secrets_to_create).await?;
 
     println!(
@@ -237,15 +253,12 @@ fn read_password() -> Result<String> {
 }
 
 /// Backup secrets from a project (called by backup command)
-pub async fn backup_secrets(project_name: &str) -> Result<Option<SecretsBackup>> {
-    let config = Config::load(None)?;
-    let project = config.get_project(project_name)?;
-
+pub async fn backup_secrets(project: &ProjectConfig) -> Result<Option<SecretsBackup>> {
     let Some(access_token) = project.access_token.as_ref() else {
         return Ok(None);
     };
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::for_project(project, access_token.clone());
     let backup = client.backup().await?;
 
     Ok(Some(backup))
@@ -254,12 +267,9 @@ pub async fn backup_secrets(project_name: &str) -> Result<Option<SecretsBackup>>
 /// Restore secrets from backup with interactive prompts or env file
 pub async fn restore_secrets(
     backup: &SecretsBackup,
-    project_name: &str,
+    project: &ProjectConfig,
     secrets_file: Option<&Path>,
 ) -> Result<usize> {
-    let config = Config::load(None)?;
-    let project = config.get_project(project_name)?;
-
     let access_token = project
         .access_token
         .as_ref()
@@ -317,7 +327,7 @@ pub async fn restore_secrets(
         return Ok(0);
     }
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::for_project(project, access_token.clone());
     client.create_secrets(&secrets_to_create).await?;
 
     Ok(secrets_to_create.len())
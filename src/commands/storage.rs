@@ -1,29 +1,129 @@
 use crate::cli::{StorageArgs, StorageCommands};
-use crate::config::Config;
-use crate::storage::{StorageClient, StorageTransfer};
+use crate::config::{display_name, Config};
+use crate::hooks::{self, Hook};
+use crate::storage::{
+    human_bytes, NewBucket, StorageClient, StorageTransfer, SyncState, SyncStats, TransferEstimate,
+};
+use crate::summary::RunSummary;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use console::style;
+use std::io::{self, Write};
 
-pub async fn run(args: StorageArgs) -> Result<()> {
+pub async fn run(config: &Config, args: StorageArgs, summary: &mut RunSummary) -> Result<()> {
     match args.command {
-        StorageCommands::List { project } => list_buckets(&project).await,
+        StorageCommands::List { project } => list_buckets(config, &project).await,
         StorageCommands::Sync {
             from,
             to,
             bucket,
             parallel,
-        } => sync_storage(&from, &to, bucket.as_deref(), parallel).await,
+            since,
+            since_last_run,
+            max_egress,
+            yes,
+            allow_protected,
+        } => {
+            ensure_writable(config, &to, allow_protected)?;
+            let since = match since {
+                Some(cutoff) => SyncSince::After(cutoff),
+                None if since_last_run => SyncSince::LastRun,
+                None => SyncSince::All,
+            };
+            summary.begin_phase("storage");
+            let stats = sync_storage(
+                config,
+                &from,
+                &to,
+                bucket.as_deref(),
+                parallel,
+                since,
+                EgressBudget {
+                    max_bytes: max_egress,
+                    assume_yes: yes,
+                },
+            )
+            .await?;
+            let Some(stats) = stats else {
+                summary.end_phase("cancelled");
+                return Ok(());
+            };
+            stats.record(summary);
+            summary.end_phase(&stats);
+            hooks::run(Hook::AfterStorageSync, summary).await
+        }
         StorageCommands::Download {
             project,
             output,
             bucket,
-        } => download_storage(&project, &output, bucket.as_deref()).await,
-        StorageCommands::Upload { from, to, bucket } => upload_storage(&from, &to, &bucket).await,
+        } => download_storage(config, &project, &output, bucket.as_deref()).await,
+        StorageCommands::Upload {
+            from,
+            to,
+            bucket,
+            allow_protected,
+        } => {
+            ensure_writable(config, &to, allow_protected)?;
+            upload_storage(config, &from, &to, &bucket).await
+        }
     }
 }
 
-async fn list_buckets(project_name: &str) -> Result<()> {
-    let config = Config::load(None)?;
+fn ensure_writable(config: &Config, to: &str, allow_protected: bool) -> Result<()> {
+    let target = config.get_project(to)?;
+    config.ensure_writable(to, &target, allow_protected)?;
+    Ok(())
+}
+
+/// Egress limit checked before any objects are transferred
+pub struct EgressBudget {
+    pub max_bytes: Option<u64>,
+    /// Abort instead of prompting when the budget is exceeded
+    pub assume_yes: bool,
+}
+
+/// Print the estimated egress and cost of a transfer and enforce the budget.
+/// Returns the estimate, or `None` if the user declined to continue past
+/// the budget.
+pub async fn check_egress(
+    transfer: &StorageTransfer,
+    bucket: Option<&str>,
+    budget: &EgressBudget,
+    cost_per_gb: f64,
+) -> Result<Option<TransferEstimate>> {
+    let estimate = transfer.estimate(bucket).await?;
+    println!(
+        "  Estimated egress: {} (~${:.2})",
+        estimate,
+        estimate.cost(cost_per_gb)
+    );
+
+    let Some(max_bytes) = budget.max_bytes else {
+        return Ok(Some(estimate));
+    };
+    if estimate.bytes <= max_bytes {
+        return Ok(Some(estimate));
+    }
+
+    let message = format!(
+        "Estimated egress {} exceeds --max-egress {}",
+        human_bytes(estimate.bytes),
+        human_bytes(max_bytes)
+    );
+    if budget.assume_yes {
+        anyhow::bail!("{}", message);
+    }
+
+    println!("{} {}", style("⚠").yellow(), message);
+    print!("Continue anyway? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y").then_some(estimate))
+}
+
+async fn list_buckets(config: &Config, project_name: &str) -> Result<()> {
     let project = config.get_project(project_name)?;
 
     let service_key = project
@@ -31,7 +131,7 @@ async fn list_buckets(project_name: &str) -> Result<()> {
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Project requires service_key for storage operations"))?;
 
-    let client = StorageClient::new(project.api_url(), service_key.clone());
+    let client = StorageClient::for_project(&project, service_key.clone());
     let buckets = client.list_buckets().await?;
 
     println!("\n{} Buckets in {}", style("📦").bold(), project_name);
@@ -49,8 +149,24 @@ async fn list_buckets(project_name: &str) -> Result<()> {
     Ok(())
 }
 
-async fn sync_storage(from: &str, to: &str, bucket: Option<&str>, parallel: usize) -> Result<()> {
-    let config = Config::load(None)?;
+/// Which objects an incremental sync copies
+enum SyncSince {
+    All,
+    After(DateTime<Utc>),
+    /// Since the last complete sync between the same projects and bucket
+    LastRun,
+}
+
+/// Returns the transfer statistics, or `None` if the sync was cancelled
+async fn sync_storage(
+    config: &Config,
+    from: &str,
+    to: &str,
+    bucket: Option<&str>,
+    parallel: usize,
+    since: SyncSince,
+    budget: EgressBudget,
+) -> Result<Option<SyncStats>> {
     let source = config.get_project(from)?;
     let target = config.get_project(to)?;
 
@@ -63,8 +179,8 @@ async fn sync_storage(from: &str, to: &str, bucket: Option<&str>, parallel: usiz
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Target project requires service_key"))?;
 
-    let source_client = StorageClient::new(source.api_url(), source_key.clone());
-    let target_client = StorageClient::new(target.api_url(), target_key.clone());
+    let source_client = StorageClient::for_project(&source, source_key.clone());
+    let target_client = StorageClient::for_project(&target, target_key.clone());
 
     println!(
         "\n{} Syncing storage: {} → {}",
@@ -72,30 +188,77 @@ async fn sync_storage(from: &str, to: &str, bucket: Option<&str>, parallel: usiz
         from,
         to
     );
+    hooks::context("source", display_name(from));
+    hooks::context("source_ref", &source.project_ref);
+    hooks::context("target", display_name(to));
+    hooks::context("target_ref", &target.project_ref);
+
+    // Resolve the incremental cutoff before listing anything
+    let started_at = Utc::now();
+    let mut sync_state = SyncState::load()?;
+    let state_key = SyncState::key(&source.project_ref, &target.project_ref, bucket);
+    let since = match since {
+        SyncSince::All => None,
+        SyncSince::After(cutoff) => Some(cutoff),
+        SyncSince::LastRun => {
+            let last = sync_state.last_run(&state_key);
+            if last.is_none() {
+                println!(
+                    "{} No previous sync recorded, running a full sync",
+                    style("ℹ").blue()
+                );
+            }
+            last
+        }
+    };
+    if let Some(cutoff) = since {
+        println!("  Only objects updated since {}", cutoff.to_rfc3339());
+    }
 
     let transfer = StorageTransfer::new(source_client)
         .with_target(target_client)
-        .parallel(parallel);
+        .parallel(parallel)
+        .ranged_threshold(config.defaults.ranged_download_threshold())
+        .since(since);
+
+    if check_egress(
+        &transfer,
+        bucket,
+        &budget,
+        config.defaults.egress_cost_per_gb,
+    )
+    .await?
+    .is_none()
+    {
+        println!("Sync cancelled.");
+        return Ok(None);
+    }
 
     let stats = if let Some(bucket_name) = bucket {
         let target = config.get_project(to)?;
         let target_key = target.service_key.as_ref().unwrap();
-        let target_client = StorageClient::new(target.api_url(), target_key.clone());
+        let target_client = StorageClient::for_project(&target, target_key.clone());
         transfer.sync_bucket(bucket_name, &target_client).await?
     } else {
-        transfer.sync_all().await?
+        transfer.sync_all(None).await?
     };
 
+    // Only advance the cutoff when every object made it across
+    if stats.errors == 0 {
+        sync_state.record_run(state_key, started_at);
+        sync_state.save()?;
+    }
+
     println!("\n{} Sync complete: {}", style("✓").green(), stats);
-    Ok(())
+    Ok(Some(stats))
 }
 
 async fn download_storage(
+    config: &Config,
     project_name: &str,
     output: &std::path::Path,
     bucket: Option<&str>,
 ) -> Result<()> {
-    let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
     let service_key = project
@@ -103,7 +266,7 @@ async fn download_storage(
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Project requires service_key"))?;
 
-    let client = StorageClient::new(project.api_url(), service_key.clone());
+    let client = StorageClient::for_project(&project, service_key.clone());
 
     println!(
         "\n{} Downloading storage from {} to {}",
@@ -114,7 +277,9 @@ async fn download_storage(
 
     std::fs::create_dir_all(output)?;
 
-    let transfer = StorageTransfer::new(client).parallel(config.defaults.parallel_transfers);
+    let transfer = StorageTransfer::new(client)
+        .parallel(config.defaults.parallel_transfers)
+        .ranged_threshold(config.defaults.ranged_download_threshold());
 
     let stats = if let Some(bucket_name) = bucket {
         let buckets = transfer.source.list_buckets().await?;
@@ -131,10 +296,14 @@ async fn download_storage(
     Ok(())
 }
 
-async fn upload_storage(from: &std::path::Path, to: &str, bucket: &str) -> Result<()> {
+async fn upload_storage(
+    config: &Config,
+    from: &std::path::Path,
+    to: &str,
+    bucket: &str,
+) -> Result<()> {
     use tokio::fs;
 
-    let config = Config::load(None)?;
     let project = config.get_project(to)?;
 
     let service_key = project
@@ -142,7 +311,7 @@ async fn upload_storage(from: &std::path::Path, to: &str, bucket: &str) -> Resul
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Project requires service_key"))?;
 
-    let client = StorageClient::new(project.api_url(), service_key.clone());
+    let client = StorageClient::for_project(&project, service_key.clone());
 
     println!(
         "\n{} Uploading {} to {}/{}",
@@ -153,7 +322,7 @@ async fn upload_storage(from: &std::path::Path, to: &str, bucket: &str) -> Resul
     );
 
     // Create bucket if needed
-    client.create_bucket(bucket, false).await?;
+    client.create_bucket(&NewBucket::private(bucket)).await?;
 
     // Upload files
     let mut entries = fs::read_dir(from).await?;
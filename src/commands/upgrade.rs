@@ -0,0 +1,273 @@
+use crate::cli::UpgradeArgs;
+use anyhow::{bail, Context, Result};
+use console::style;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const RELEASES_API: &str = "https://api.github.com/repos/Foodshareclub/supamigrate/releases";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+pub async fn run(args: &UpgradeArgs) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = fetch_release(args.version.as_deref()).await?;
+    let latest = release.tag_name.trim_start_matches('v');
+
+    println!("\n{} Current version: {}", style("ℹ").blue(), current);
+    println!("{} Release version: {}", style("ℹ").blue(), latest);
+
+    if args.version.is_none() && !is_newer(latest, current) {
+        println!("{} supamigrate is up to date", style("✓").green());
+        return Ok(());
+    }
+
+    if args.check {
+        println!(
+            "{} Update available. Run `supamigrate upgrade` to install it.",
+            style("⚠").yellow()
+        );
+        return Ok(());
+    }
+
+    let platform = platform_name().context(
+        "No prebuilt release for this platform; install with `cargo install supamigrate`",
+    )?;
+    let archive_name = format!("supamigrate-{}.{}", platform, archive_extension());
+    let archive = find_asset(&release, &archive_name)?;
+    let checksum = find_asset(&release, &format!("{}.sha256", archive_name))?;
+
+    let current_exe = std::env::current_exe()?;
+    println!("  Install path: {}", current_exe.display());
+
+    if !args.yes {
+        print!("\nReplace the current binary with {}? [y/N] ", latest);
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Upgrade cancelled.");
+            return Ok(());
+        }
+    }
+
+    println!("\n{} Downloading {}...", style("⬇").bold(), archive_name);
+    let data = download(&archive.browser_download_url).await?;
+    let checksum_file = String::from_utf8(download(&checksum.browser_download_url).await?)?;
+    verify_checksum(&data, &checksum_file, &archive_name)?;
+    println!("{} Checksum verified", style("✓").green());
+
+    let staging = tempfile::tempdir()?;
+    let binary = extract_binary(&data, staging.path())?;
+    replace_executable(&binary, &current_exe)?;
+
+    println!(
+        "{} Upgraded supamigrate {} → {}",
+        style("✓").green(),
+        current,
+        latest
+    );
+
+    Ok(())
+}
+
+async fn fetch_release(tag: Option<&str>) -> Result<Release> {
+    let url = match tag {
+        Some(tag) => format!("{}/tags/{}", RELEASES_API, tag),
+        None => format!("{}/latest", RELEASES_API),
+    };
+
+    let response = crate::http::client()
+        .get(&url)
+        .header(
+            "User-Agent",
+            concat!("supamigrate/", env!("CARGO_PKG_VERSION")),
+        )
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        bail!(
+            "Failed to fetch release info: {} - {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        );
+    }
+
+    Ok(response.json().await?)
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let response = crate::http::client()
+        .get(url)
+        .header(
+            "User-Agent",
+            concat!("supamigrate/", env!("CARGO_PKG_VERSION")),
+        )
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        bail!("Failed to download {}: {}", url, response.status());
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Check `data` against a sha256sum-format file ("<hex digest>  <file name>")
+fn verify_checksum(data: &[u8], checksum_file: &str, name: &str) -> Result<()> {
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .context("Empty checksum file")?;
+    let actual = format!("{:x}", Sha256::digest(data));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name,
+            expected,
+            actual
+        );
+    }
+    Ok(())
+}
+
+fn find_asset<'a>(release: &'a Release, name: &str) -> Result<&'a Asset> {
+    release
+        .assets
+        .iter()
+        .find(|a| a.name == name)
+        .with_context(|| format!("Release {} has no asset {}", release.tag_name, name))
+}
+
+/// Release asset name for the running platform, matching the build matrix
+fn platform_name() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") if cfg!(target_env = "musl") => Some("linux-x86_64-musl"),
+        ("linux", "x86_64") => Some("linux-x86_64"),
+        ("linux", "aarch64") => Some("linux-aarch64"),
+        ("macos", "x86_64") => Some("darwin-x86_64"),
+        ("macos", "aarch64") => Some("darwin-aarch64"),
+        ("windows", "x86_64") => Some("windows-x86_64"),
+        _ => None,
+    }
+}
+
+fn archive_extension() -> &'static str {
+    if cfg!(windows) {
+        "zip"
+    } else {
+        "tar.gz"
+    }
+}
+
+/// Unpack the release archive and return the path of the binary in it
+fn extract_binary(data: &[u8], dir: &Path) -> Result<std::path::PathBuf> {
+    let binary_name = if cfg!(windows) {
+        "supamigrate.exe"
+    } else {
+        "supamigrate"
+    };
+
+    if cfg!(windows) {
+        // Windows 10+ ships bsdtar, which reads zip archives
+        let archive = dir.join("release.zip");
+        fs::write(&archive, data)?;
+        let status = std::process::Command::new("tar")
+            .arg("-xf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir)
+            .status()
+            .context("Failed to run tar to unpack the release")?;
+        if !status.success() {
+            bail!("Failed to unpack release archive");
+        }
+    } else {
+        let decoder = flate2::read::GzDecoder::new(data);
+        tar::Archive::new(decoder).unpack(dir)?;
+    }
+
+    let binary = dir.join(binary_name);
+    if !binary.exists() {
+        bail!("Release archive does not contain {}", binary_name);
+    }
+    Ok(binary)
+}
+
+/// Swap in the new binary. The running executable is renamed rather than
+/// overwritten, which works on Windows too, and restored if the swap fails.
+fn replace_executable(new_binary: &Path, current_exe: &Path) -> Result<()> {
+    let dir = current_exe
+        .parent()
+        .context("Cannot determine install directory")?;
+    let staged = dir.join(".supamigrate.new");
+    let previous = dir.join(".supamigrate.old");
+
+    fs::copy(new_binary, &staged).with_context(|| {
+        format!(
+            "Cannot write to {} (try again with sufficient permissions)",
+            dir.display()
+        )
+    })?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    let _ = fs::remove_file(&previous);
+    fs::rename(current_exe, &previous)?;
+    if let Err(e) = fs::rename(&staged, current_exe) {
+        let _ = fs::rename(&previous, current_exe);
+        return Err(e.into());
+    }
+    // A running executable can't be deleted on Windows; it's cleaned up on
+    // the next upgrade instead
+    let _ = fs::remove_file(&previous);
+
+    Ok(())
+}
+
+/// Compare dotted numeric versions, ignoring any pre-release suffix
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Vec<u64> {
+        v.split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parse(candidate) > parse(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.4.0", "0.3.9"));
+        assert!(is_newer("1.0.0", "0.10.0"));
+        assert!(is_newer("0.10.0", "0.9.0"));
+        assert!(!is_newer("0.3.0", "0.3.0"));
+        assert!(!is_newer("0.3.0-beta.1", "0.3.0"));
+    }
+}
@@ -1,5 +1,6 @@
+use super::migrate::confirm;
 use crate::cli::{VaultArgs, VaultCommands};
-use crate::config::Config;
+use crate::config::{Config, ProjectConfig};
 use crate::db::{VaultBackup, VaultClient};
 use anyhow::Result;
 use console::style;
@@ -7,17 +8,24 @@ use std::fs;
 use std::io::{self, Write};
 use std::path::Path;
 
-pub fn run(args: VaultArgs) -> Result<()> {
+pub fn run(config: &Config, args: VaultArgs) -> Result<()> {
     match args.command {
-        VaultCommands::List { project } => list_secrets(&project),
-        VaultCommands::Export { project, output } => export_secrets(&project, &output),
-        VaultCommands::Import { project, file } => import_secrets(&project, &file),
-        VaultCommands::Copy { from, to } => copy_secrets(&from, &to),
+        VaultCommands::List { project } => list_secrets(config, &project),
+        VaultCommands::Export { project, output } => export_secrets(config, &project, &output),
+        VaultCommands::Import {
+            project,
+            file,
+            allow_protected,
+        } => import_secrets(config, &project, &file, allow_protected),
+        VaultCommands::Copy {
+            from,
+            to,
+            allow_protected,
+        } => copy_secrets(config, &from, &to, allow_protected),
     }
 }
 
-fn list_secrets(project_name: &str) -> Result<()> {
-    let config = Config::load(None)?;
+fn list_secrets(config: &Config, project_name: &str) -> Result<()> {
     let project = config.get_project(project_name)?;
 
     let client = VaultClient::new(project.db_url());
@@ -64,8 +72,7 @@ fn list_secrets(project_name: &str) -> Result<()> {
     Ok(())
 }
 
-fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
-    let config = Config::load(None)?;
+fn export_secrets(config: &Config, project_name: &str, output: &Path) -> Result<()> {
     let project = config.get_project(project_name)?;
 
     let client = VaultClient::new(project.db_url());
@@ -118,9 +125,14 @@ fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
     Ok(())
 }
 
-fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
-    let config = Config::load(None)?;
+fn import_secrets(
+    config: &Config,
+    project_name: &str,
+    file: &Path,
+    allow_protected: bool,
+) -> Result<()> {
     let project = config.get_project(project_name)?;
+    config.ensure_writable(project_name, &project, allow_protected)?;
 
     let client = VaultClient::new(project.db_url());
 
@@ -154,13 +166,10 @@ fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
         println!("  {} {} - {}", style("•").cyan(), secret.name, desc);
     }
 
-    print!("\nProceed? [y/N] ");
-    io::stdout().flush()?;
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-
-    if !input.trim().eq_ignore_ascii_case("y") {
+    let typed_ref = config
+        .looks_like_production(project_name, &project)
+        .then_some(project.project_ref.as_str());
+    if !confirm("Proceed?", typed_ref)? {
         println!("{} Import cancelled", style("✗").red());
         return Ok(());
     }
@@ -177,10 +186,15 @@ fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
     Ok(())
 }
 
-fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
-    let config = Config::load(None)?;
+fn copy_secrets(
+    config: &Config,
+    from_name: &str,
+    to_name: &str,
+    allow_protected: bool,
+) -> Result<()> {
     let source = config.get_project(from_name)?;
     let target = config.get_project(to_name)?;
+    config.ensure_writable(to_name, &target, allow_protected)?;
 
     let source_client = VaultClient::new(source.db_url());
     let target_client = VaultClient::new(target.db_url());
@@ -228,13 +242,10 @@ fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
         println!("  {} {} - {}", style("•").cyan(), secret.name, desc);
     }
 
-    print!("\nProceed? [y/N] ");
-    io::stdout().flush()?;
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-
-    if !input.trim().eq_ignore_ascii_case("y") {
+    let typed_ref = config
+        .looks_like_production(to_name, &target)
+        .then_some(target.project_ref.as_str());
+    if !confirm("Proceed?", typed_ref)? {
         println!("{} Copy cancelled", style("✗").red());
         return Ok(());
     }
@@ -252,10 +263,7 @@ fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
 }
 
 /// Backup vault secrets from a project (called by backup command)
-pub fn backup_vault(project_name: &str) -> Result<Option<VaultBackup>> {
-    let config = Config::load(None)?;
-    let project = config.get_project(project_name)?;
-
+pub fn backup_vault(project: &ProjectConfig) -> Result<Option<VaultBackup>> {
     let client = VaultClient::new(project.db_url());
 
     if !client.is_vault_enabled()? {
@@ -271,10 +279,7 @@ pub fn backup_vault(project_name: &str) -> Result<Option<VaultBackup>> {
 }
 
 /// Restore vault secrets from backup
-pub fn restore_vault(backup: &VaultBackup, project_name: &str) -> Result<usize> {
-    let config = Config::load(None)?;
-    let project = config.get_project(project_name)?;
-
+pub fn restore_vault(backup: &VaultBackup, project: &ProjectConfig) -> Result<usize> {
     let client = VaultClient::new(project.db_url());
 
     if !client.is_vault_enabled()? {
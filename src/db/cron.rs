@@ -0,0 +1,169 @@
+use super::native::NativeClient;
+use super::sql::quote_literal;
+use crate::error::{Result, SupamigrateError};
+use serde::Deserialize;
+
+const CRON_INSTALLED_SQL: &str = "SELECT to_json(to_regclass('cron.job') IS NOT NULL)";
+
+const JOBS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'name', jobname,
+    'schedule', schedule,
+    'command', command,
+    'database', database,
+    'current', database = current_database(),
+    'active', active
+) ORDER BY jobid), '[]')
+FROM cron.job";
+
+/// A pg_cron job as stored in `cron.job`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CronJob {
+    pub name: Option<String>,
+    pub schedule: String,
+    pub command: String,
+    pub database: String,
+    /// Runs in the database it was read from
+    pub current: bool,
+    pub active: bool,
+}
+
+impl CronJob {
+    /// Name for reports: the job name, or its schedule and command
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("'{}' {}", self.schedule, self.command),
+        }
+    }
+
+    /// `cron.schedule()` for the job, disabled afterwards when `active` is
+    /// false
+    fn schedule_sql(&self, active: bool) -> String {
+        let schedule = match &self.name {
+            Some(name) => format!(
+                "cron.schedule({}, {}, {})",
+                quote_literal(name),
+                quote_literal(&self.schedule),
+                quote_literal(&self.command)
+            ),
+            None => format!(
+                "cron.schedule({}, {})",
+                quote_literal(&self.schedule),
+                quote_literal(&self.command)
+            ),
+        };
+        if active {
+            format!("SELECT {}", schedule)
+        } else {
+            format!("SELECT cron.alter_job({}, active := false)", schedule)
+        }
+    }
+}
+
+/// What recreating the source's jobs on the target did
+#[derive(Debug, Default)]
+pub struct CronSync {
+    /// Jobs scheduled (or updated, for named jobs that existed)
+    pub scheduled: Vec<String>,
+    /// Of those, the ones left disabled
+    pub disabled: Vec<String>,
+    /// Unnamed jobs the target already runs
+    pub existing: Vec<String>,
+    /// Jobs of other databases, which are left behind
+    pub other_databases: Vec<String>,
+}
+
+/// Jobs on a database, or `None` when pg_cron isn't installed
+async fn cron_jobs(db_url: &str) -> Result<Option<Vec<CronJob>>> {
+    let client = NativeClient::connect(db_url).await?;
+    if !client.query_json::<bool>(CRON_INSTALLED_SQL).await? {
+        return Ok(None);
+    }
+    Ok(Some(client.query_json(JOBS_SQL).await?))
+}
+
+/// Recreate the pg_cron jobs of the source's database on `target_url` with
+/// `cron.schedule()`. Named jobs replace the target's job of the same name;
+/// unnamed jobs are skipped when the target has one with the same schedule
+/// and command. Jobs inactive on the source stay inactive, and with
+/// `disable` every job is imported inactive.
+pub async fn sync_cron_jobs(source_url: &str, target_url: &str, disable: bool) -> Result<CronSync> {
+    let mut sync = CronSync::default();
+    let Some(jobs) = cron_jobs(source_url).await? else {
+        return Ok(sync);
+    };
+    if jobs.is_empty() {
+        return Ok(sync);
+    }
+    let existing = cron_jobs(target_url).await?.ok_or_else(|| {
+        SupamigrateError::Database(
+            "pg_cron isn't enabled on the target; enable it (CREATE EXTENSION pg_cron) first"
+                .to_string(),
+        )
+    })?;
+
+    let mut sql = Vec::new();
+    for job in &jobs {
+        if !job.current {
+            sync.other_databases.push(job.label());
+            continue;
+        }
+        if job.name.is_none()
+            && existing
+                .iter()
+                .any(|e| e.name.is_none() && e.schedule == job.schedule && e.command == job.command)
+        {
+            sync.existing.push(job.label());
+            continue;
+        }
+        let active = job.active && !disable;
+        sql.push(job.schedule_sql(active));
+        sync.scheduled.push(job.label());
+        if !active {
+            sync.disabled.push(job.label());
+        }
+    }
+
+    if !sql.is_empty() {
+        NativeClient::connect(target_url)
+            .await?
+            .execute(&sql.join(";\n"))
+            .await?;
+    }
+    Ok(sync)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_sql() {
+        let job = CronJob {
+            name: Some("nightly-cleanup".to_string()),
+            schedule: "0 3 * * *".to_string(),
+            command: "DELETE FROM app.sessions WHERE expires_at < now() - interval '1 day'"
+                .to_string(),
+            database: "postgres".to_string(),
+            current: true,
+            active: true,
+        };
+        assert_eq!(
+            job.schedule_sql(true),
+            "SELECT cron.schedule('nightly-cleanup', '0 3 * * *', \
+             'DELETE FROM app.sessions WHERE expires_at < now() - interval ''1 day''')"
+        );
+
+        let unnamed = CronJob {
+            name: None,
+            command: "VACUUM".to_string(),
+            ..job
+        };
+        assert_eq!(
+            unnamed.schedule_sql(false),
+            "SELECT cron.alter_job(cron.schedule('0 3 * * *', 'VACUUM'), active := false)"
+        );
+        assert_eq!(unnamed.label(), "'0 3 * * *' VACUUM");
+    }
+}
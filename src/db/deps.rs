@@ -0,0 +1,400 @@
+use super::dump::{glob_match, qualify_pattern};
+use super::restore::PgRestore;
+use crate::error::Result;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// Objects a relation or function needs in order to restore cleanly:
+/// referenced tables (foreign keys, views), sequences and functions used in
+/// column defaults, trigger functions, column types, functions views call
+/// and types functions take or return.
+const DEPENDENCIES_SQL: &str = r"
+SELECT coalesce(json_agg(row_to_json(d)), '[]') FROM (
+    SELECT 'foreign key' AS kind, c.conname::text AS via,
+           sn.nspname || '.' || s.relname AS dependent,
+           tn.nspname || '.' || t.relname AS requires,
+           true AS relation
+    FROM pg_constraint c
+    JOIN pg_class s ON s.oid = c.conrelid
+    JOIN pg_namespace sn ON sn.oid = s.relnamespace
+    JOIN pg_class t ON t.oid = c.confrelid
+    JOIN pg_namespace tn ON tn.oid = t.relnamespace
+    WHERE c.contype = 'f' AND c.conrelid <> c.confrelid
+  UNION ALL
+    SELECT DISTINCT 'view', NULL,
+           vn.nspname || '.' || v.relname,
+           tn.nspname || '.' || t.relname,
+           true
+    FROM pg_rewrite r
+    JOIN pg_depend dp ON dp.classid = 'pg_rewrite'::regclass AND dp.objid = r.oid
+         AND dp.refclassid = 'pg_class'::regclass
+    JOIN pg_class v ON v.oid = r.ev_class
+    JOIN pg_namespace vn ON vn.oid = v.relnamespace
+    JOIN pg_class t ON t.oid = dp.refobjid
+    JOIN pg_namespace tn ON tn.oid = t.relnamespace
+    WHERE v.oid <> t.oid AND t.relkind IN ('r', 'p', 'v', 'm', 'f')
+  UNION ALL
+    SELECT 'sequence default', a.attname::text,
+           sn.nspname || '.' || s.relname,
+           qn.nspname || '.' || q.relname,
+           true
+    FROM pg_attrdef ad
+    JOIN pg_depend dp ON dp.classid = 'pg_attrdef'::regclass AND dp.objid = ad.oid
+         AND dp.refclassid = 'pg_class'::regclass
+    JOIN pg_class q ON q.oid = dp.refobjid AND q.relkind = 'S'
+    JOIN pg_namespace qn ON qn.oid = q.relnamespace
+    JOIN pg_class s ON s.oid = ad.adrelid
+    JOIN pg_namespace sn ON sn.oid = s.relnamespace
+    JOIN pg_attribute a ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+  UNION ALL
+    SELECT 'function default', a.attname::text,
+           sn.nspname || '.' || s.relname,
+           pn.nspname || '.' || p.proname,
+           false
+    FROM pg_attrdef ad
+    JOIN pg_depend dp ON dp.classid = 'pg_attrdef'::regclass AND dp.objid = ad.oid
+         AND dp.refclassid = 'pg_proc'::regclass
+    JOIN pg_proc p ON p.oid = dp.refobjid
+    JOIN pg_namespace pn ON pn.oid = p.pronamespace
+    JOIN pg_class s ON s.oid = ad.adrelid
+    JOIN pg_namespace sn ON sn.oid = s.relnamespace
+    JOIN pg_attribute a ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+  UNION ALL
+    SELECT 'trigger', tg.tgname::text,
+           sn.nspname || '.' || s.relname,
+           pn.nspname || '.' || p.proname,
+           false
+    FROM pg_trigger tg
+    JOIN pg_proc p ON p.oid = tg.tgfoid
+    JOIN pg_namespace pn ON pn.oid = p.pronamespace
+    JOIN pg_class s ON s.oid = tg.tgrelid
+    JOIN pg_namespace sn ON sn.oid = s.relnamespace
+    WHERE NOT tg.tgisinternal
+  UNION ALL
+    SELECT 'column type', a.attname::text,
+           sn.nspname || '.' || s.relname,
+           tn.nspname || '.' || ty.typname,
+           false
+    FROM pg_attribute a
+    JOIN pg_class s ON s.oid = a.attrelid AND s.relkind IN ('r', 'p')
+    JOIN pg_namespace sn ON sn.oid = s.relnamespace
+    JOIN pg_type ty ON ty.oid = a.atttypid
+    JOIN pg_namespace tn ON tn.oid = ty.typnamespace
+    WHERE a.attnum > 0 AND NOT a.attisdropped
+      AND tn.nspname NOT IN ('pg_catalog', 'information_schema')
+  UNION ALL
+    SELECT DISTINCT 'view function', NULL,
+           vn.nspname || '.' || v.relname,
+           pn.nspname || '.' || p.proname,
+           false
+    FROM pg_rewrite r
+    JOIN pg_depend dp ON dp.classid = 'pg_rewrite'::regclass AND dp.objid = r.oid
+         AND dp.refclassid = 'pg_proc'::regclass
+    JOIN pg_class v ON v.oid = r.ev_class
+    JOIN pg_namespace vn ON vn.oid = v.relnamespace
+    JOIN pg_proc p ON p.oid = dp.refobjid
+    JOIN pg_namespace pn ON pn.oid = p.pronamespace
+    WHERE pn.nspname NOT IN ('pg_catalog', 'information_schema')
+  UNION ALL
+    SELECT DISTINCT 'function type', NULL,
+           pn.nspname || '.' || p.proname,
+           tn.nspname || '.' || ty.typname,
+           false
+    FROM pg_proc p
+    JOIN pg_namespace pn ON pn.oid = p.pronamespace
+    JOIN pg_depend dp ON dp.classid = 'pg_proc'::regclass AND dp.objid = p.oid
+         AND dp.refclassid = 'pg_type'::regclass
+    JOIN pg_type ty ON ty.oid = dp.refobjid
+    JOIN pg_namespace tn ON tn.oid = ty.typnamespace
+    WHERE dp.deptype = 'n' AND pn.nspname <> tn.nspname
+      AND tn.nspname NOT IN ('pg_catalog', 'information_schema')
+) d";
+
+/// Relations pg_dump's --exclude-table can match
+const RELATIONS_SQL: &str = r"
+SELECT coalesce(json_agg(n.nspname || '.' || c.relname ORDER BY 1), '[]')
+FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relkind IN ('r', 'p', 'v', 'm', 'S', 'f')";
+
+/// Schemas every database has
+const SYSTEM_SCHEMAS: &[&str] = &["pg_*", "information_schema"];
+
+/// The schemas a partial migration covers, and the ones the target has
+/// anyway
+#[derive(Debug, Clone, Default)]
+pub struct SchemaScope {
+    /// Only these schemas are migrated (all if empty)
+    pub only: Vec<String>,
+    /// Schema patterns left out
+    pub excluded: Vec<String>,
+    /// Schema patterns the target has whether they're migrated or not,
+    /// e.g. those Supabase manages
+    pub on_target: Vec<String>,
+}
+
+impl SchemaScope {
+    fn migrates(&self, schema: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|p| glob_match(p, schema)))
+            && !self.excluded.iter().any(|p| glob_match(p, schema))
+    }
+
+    /// Whether objects in `schema` won't exist on the target after the
+    /// migration
+    fn lacks(&self, schema: &str) -> bool {
+        !self.migrates(schema)
+            && !SYSTEM_SCHEMAS.iter().any(|p| glob_match(p, schema))
+            && !self.on_target.iter().any(|p| glob_match(p, schema))
+    }
+
+    /// Whether some schemas are left out that the target doesn't have
+    pub fn is_partial(&self) -> bool {
+        !self.only.is_empty() || self.excluded.iter().any(|p| !self.on_target.contains(p))
+    }
+}
+
+/// Schema of a qualified name
+fn schema_of(name: &str) -> &str {
+    name.split_once('.').map_or("public", |(schema, _)| schema)
+}
+
+/// One object that another object needs in order to be restored
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dependency {
+    pub kind: String,
+    /// Constraint, column or trigger name the dependency comes from
+    pub via: Option<String>,
+    pub dependent: String,
+    pub requires: String,
+    /// Whether `requires` is a relation (table, view, sequence) rather
+    /// than a function or type
+    pub relation: bool,
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} needs {} ({}",
+            self.dependent, self.requires, self.kind
+        )?;
+        if let Some(via) = &self.via {
+            write!(f, " {}", via)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Outcome of checking a partial migration against the source catalog
+#[derive(Debug, Default)]
+pub struct DependencyReport {
+    /// Concrete relations to pass to pg_dump --exclude-table
+    pub excluded_tables: Vec<String>,
+    /// Excluded relations pulled back in because included objects need them
+    pub auto_included: Vec<String>,
+    /// Dependencies that remain excluded and will likely fail on restore
+    pub missing: Vec<Dependency>,
+    /// Schemas left out of the migration that `missing` refers to
+    pub missing_schemas: Vec<String>,
+}
+
+/// Checks that objects kept in a partial migration don't depend on
+/// excluded tables or on schemas the target won't have
+pub struct DependencyCheck {
+    db: PgRestore,
+}
+
+impl DependencyCheck {
+    pub fn new(db_url: String) -> Self {
+        Self {
+            db: PgRestore::new(db_url),
+        }
+    }
+
+    /// Expand `table_patterns` against the source and find dependencies on
+    /// excluded objects. With `include_dependencies`, needed relations are
+    /// removed from the exclusion list instead of being reported; those in
+    /// schemas outside `schemas` can only be reported.
+    pub fn check(
+        &self,
+        table_patterns: &[String],
+        schemas: &SchemaScope,
+        include_dependencies: bool,
+    ) -> Result<DependencyReport> {
+        let relations: Vec<String> = self.db.query_json(RELATIONS_SQL)?;
+        let edges: Vec<Dependency> = self.db.query_json(DEPENDENCIES_SQL)?;
+        Ok(resolve(
+            &relations,
+            &edges,
+            table_patterns,
+            schemas,
+            include_dependencies,
+        ))
+    }
+}
+
+impl DependencyReport {
+    /// Excluded relations quoted so pg_dump matches them exactly
+    pub fn quoted_exclusions(&self) -> Vec<String> {
+        self.excluded_tables
+            .iter()
+            .map(|name| {
+                let (schema, table) = name.split_once('.').unwrap_or(("public", name));
+                format!("\"{}\".\"{}\"", schema, table)
+            })
+            .collect()
+    }
+
+    /// Whether some missing dependencies are excluded tables, which
+    /// --include-dependencies would migrate
+    pub fn needs_excluded_tables(&self) -> bool {
+        self.missing.iter().any(|dep| {
+            !self
+                .missing_schemas
+                .iter()
+                .any(|s| s == schema_of(&dep.requires))
+        })
+    }
+}
+
+fn resolve(
+    relations: &[String],
+    edges: &[Dependency],
+    table_patterns: &[String],
+    schemas: &SchemaScope,
+    include_dependencies: bool,
+) -> DependencyReport {
+    let qualified: Vec<String> = table_patterns.iter().map(|p| qualify_pattern(p)).collect();
+
+    let mut excluded: BTreeSet<&str> = relations
+        .iter()
+        .map(String::as_str)
+        .filter(|r| qualified.iter().any(|p| glob_match(p, r)))
+        .collect();
+
+    let schema_excluded = |name: &str| !schemas.migrates(schema_of(name));
+
+    let mut auto_included = Vec::new();
+
+    // Pulling a relation back in can expose its own dependencies, so repeat
+    // until nothing changes
+    loop {
+        let is_kept = |name: &str| !schema_excluded(name) && !excluded.contains(name);
+        let needed: Vec<&str> = edges
+            .iter()
+            .filter(|e| e.relation && is_kept(&e.dependent))
+            .map(|e| e.requires.as_str())
+            .filter(|d| excluded.contains(d) && !schema_excluded(d))
+            .collect();
+
+        if !include_dependencies || needed.is_empty() {
+            break;
+        }
+        for name in needed {
+            if excluded.remove(name) {
+                auto_included.push(name.to_string());
+            }
+        }
+    }
+
+    let is_kept = |name: &str| !schema_excluded(name) && !excluded.contains(name);
+    let lacked = |name: &str| schemas.lacks(schema_of(name));
+    let missing: Vec<Dependency> = edges
+        .iter()
+        .filter(|e| is_kept(&e.dependent))
+        .filter(|e| {
+            lacked(&e.requires)
+                || (e.relation
+                    && !schema_excluded(&e.requires)
+                    && excluded.contains(e.requires.as_str()))
+        })
+        .cloned()
+        .collect();
+    let missing_schemas: BTreeSet<&str> = missing
+        .iter()
+        .map(|e| e.requires.as_str())
+        .filter(|name| lacked(name))
+        .map(schema_of)
+        .collect();
+
+    DependencyReport {
+        excluded_tables: excluded.into_iter().map(String::from).collect(),
+        auto_included,
+        missing_schemas: missing_schemas.into_iter().map(String::from).collect(),
+        missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(kind: &str, dependent: &str, dependency: &str, relation: bool) -> Dependency {
+        Dependency {
+            kind: kind.to_string(),
+            via: None,
+            dependent: dependent.to_string(),
+            requires: dependency.to_string(),
+            relation,
+        }
+    }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        let relations: Vec<String> = ["public.orders", "public.countries", "public.audit_log"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let edges = vec![
+            edge("foreign key", "public.orders", "public.countries", true),
+            edge("trigger", "public.orders", "internal.log_change", false),
+        ];
+        let patterns = vec!["countries".to_string(), "audit_*".to_string()];
+        let schemas = SchemaScope {
+            excluded: vec!["internal".to_string()],
+            ..Default::default()
+        };
+
+        let report = resolve(&relations, &edges, &patterns, &schemas, false);
+        assert_eq!(
+            report.excluded_tables,
+            ["public.audit_log", "public.countries"]
+        );
+        assert_eq!(report.missing.len(), 2);
+
+        let report = resolve(&relations, &edges, &patterns, &schemas, true);
+        assert_eq!(report.excluded_tables, ["public.audit_log"]);
+        assert_eq!(report.auto_included, ["public.countries"]);
+        // Schema-level exclusions can't be undone, only reported
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].requires, "internal.log_change");
+        assert_eq!(report.missing_schemas, ["internal"]);
+        assert!(!report.needs_excluded_tables());
+    }
+
+    #[test]
+    fn test_missing_schemas() {
+        let relations: Vec<String> = vec!["public.avatars".to_string()];
+        let edges = vec![
+            edge("foreign key", "public.avatars", "storage.objects", true),
+            edge("view", "public.profiles", "auth.users", true),
+            edge("view", "public.profiles", "pg_catalog.pg_class", true),
+            edge("function type", "public.emit", "app.event", false),
+            edge("foreign key", "app.events", "auth.users", true),
+        ];
+        let mut schemas = SchemaScope {
+            only: vec!["public".to_string()],
+            excluded: vec!["storage".to_string()],
+            on_target: vec!["storage".to_string(), "auth".to_string()],
+        };
+        assert!(schemas.is_partial());
+        let report = resolve(&relations, &edges, &[], &schemas, true);
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing_schemas, ["app"]);
+
+        // Plain Postgres has neither storage nor auth
+        schemas.on_target.clear();
+        let report = resolve(&relations, &edges, &[], &schemas, true);
+        assert_eq!(report.missing_schemas, ["app", "auth", "storage"]);
+    }
+}
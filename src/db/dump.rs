@@ -1,112 +1,346 @@
+use super::history::HISTORY_SCHEMA;
+use super::mask::MaskRule;
+use super::native::NativeClient;
+use super::native_dump;
+use super::probe::with_connection_hint;
+use super::progress;
+use super::relay;
+use super::restore::PgRestore;
+use super::retry::with_retry;
+use super::sql::quote_ident;
+use super::tools;
+use crate::cli::{DumpEngine, DumpFormat};
+use crate::config::display_name;
 use crate::error::{Result, SupamigrateError};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::io::AsyncWrite;
+use tokio_util::io::SyncIoBridge;
 use tracing::{debug, info, warn};
 
+#[derive(Clone)]
 pub struct PgDump {
-    db_url: String,
-    binary_path: PathBuf,
-    excluded_schemas: Vec<String>,
-    excluded_tables: Vec<String>,
-    schema_only: bool,
-    data_only: bool,
-}
-
-/// Query remote server for PostgreSQL major version
-fn get_server_version(db_url: &str) -> Option<u32> {
-    let output = Command::new("psql")
-        .arg(db_url)
-        .arg("-t") // tuples only
-        .arg("-A") // unaligned
-        .arg("-c")
-        .arg("SHOW server_version_num")
-        .output()
+    pub(super) db_url: String,
+    engine: DumpEngine,
+    format: DumpFormat,
+    jobs: usize,
+    pub(super) schemas: Vec<String>,
+    pub(super) excluded_schemas: Vec<String>,
+    pub(super) included_tables: Vec<String>,
+    pub(super) excluded_tables: Vec<String>,
+    pub(super) excluded_table_data: Vec<String>,
+    /// Tables whose rows are skipped whatever else is excluded
+    pub(super) always_excluded_table_data: Vec<String>,
+    /// (`schema.table`, condition) pairs limiting the exported rows
+    pub(super) row_filters: Vec<(String, String)>,
+    /// (`schema.table`, [(column, rule)]) masking exported values
+    pub(super) masks: Vec<(String, Vec<(String, MaskRule)>)>,
+    pub(super) extra_args: Vec<String>,
+    pub(super) schema_only: bool,
+    pub(super) data_only: bool,
+}
+
+/// Tables whose rows are never dumped unless configured otherwise: storage
+/// objects are copied through the Storage API, which creates their rows
+pub const ALWAYS_EXCLUDED_TABLE_DATA: &[&str] = &["storage.objects"];
+
+/// `schema.table` quoted part by part, so pg_dump matches it exactly rather
+/// than as a pattern
+fn quote_table(table: &str) -> String {
+    let (schema, name) = table.split_once('.').unwrap_or(("public", table));
+    format!("{}.{}", quote_ident(schema), quote_ident(name))
+}
+
+/// Fail on a non-zero pg_dump exit
+pub(super) fn check_dump(output: Output) -> Result<Output> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(SupamigrateError::PgDumpFailed(with_connection_hint(
+            &stderr,
+        )))
+    }
+}
+
+/// Query the server's PostgreSQL major version over a native connection
+pub(super) fn get_server_version(db_url: &str) -> Option<u32> {
+    let version = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(async {
+            NativeClient::connect(db_url)
+                .await?
+                .execute("SHOW server_version_num")
+                .await
+        })
+    });
+    let version = match version {
+        Ok(results) => results,
+        Err(e) => {
+            debug!("Server version query failed: {}", e);
+            return None;
+        }
+    };
+
+    // server_version_num is e.g. "150001" for 15.1
+    let version_num: u32 = version
+        .into_iter()
+        .find_map(|result| result.rows.into_iter().next())?
+        .into_iter()
+        .next()??
+        .trim()
+        .parse()
         .ok()?;
+    Some(version_num / 10000)
+}
+
+/// Major version from `pg_dump --version` output, e.g.
+/// "pg_dump (PostgreSQL) 15.4 (Ubuntu 15.4-1.pgdg22.04+1)"
+fn parse_client_major(version: &str) -> Option<u32> {
+    version
+        .split_whitespace()
+        .find(|part| part.starts_with(|c: char| c.is_ascii_digit()))?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
 
+/// Major version of a pg_dump binary
+fn client_major(path: &Path) -> Option<u32> {
+    let output = Command::new(path).arg("--version").output().ok()?;
     if !output.status.success() {
         return None;
     }
+    parse_client_major(&String::from_utf8_lossy(&output.stdout))
+}
 
-    // server_version_num returns e.g. "150001" for 15.0.1
-    // Major version = first 2 digits for PG >= 10
-    let version_str = String::from_utf8_lossy(&output.stdout);
-    let version_num: u32 = version_str.trim().parse().ok()?;
-    Some(version_num / 10000) // 150001 -> 15
-}
-
-/// Find pg_dump binary compatible with server version
-fn find_compatible_pg_dump(server_major: u32) -> PathBuf {
-    // Check versions from exact match up to +3 (pg_dump is forward-compatible)
-    let versions_to_try: Vec<u32> = (server_major..=server_major + 3).collect();
-
-    for version in versions_to_try {
-        let paths = if cfg!(target_os = "macos") {
-            vec![
-                // Apple Silicon Homebrew
-                format!("/opt/homebrew/opt/postgresql@{}/bin/pg_dump", version),
-                // Intel Homebrew
-                format!("/usr/local/opt/postgresql@{}/bin/pg_dump", version),
-                // Postgres.app
-                format!(
-                    "/Applications/Postgres.app/Contents/Versions/{}/bin/pg_dump",
-                    version
-                ),
-            ]
-        } else {
-            // Linux paths
-            vec![
-                format!("/usr/lib/postgresql/{}/bin/pg_dump", version),
-                format!("/usr/pgsql-{}/bin/pg_dump", version),
-            ]
-        };
+/// `<dir>/*/<rest>` for every subdirectory of `dir` whose name starts with
+/// `prefix`
+fn versioned_paths(dir: &str, prefix: &str, rest: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+        .map(|entry| entry.path().join(rest))
+        .collect();
+    paths.sort();
+    paths
+}
 
-        for path in paths {
-            if Path::new(&path).exists() {
-                debug!("Found compatible pg_dump v{} at: {}", version, path);
-                return PathBuf::from(path);
+/// Every pg_dump that might be installed: `pg_dump` and `pg_dump-<major>`
+/// on PATH, Homebrew (opt and Cellar), Postgres.app and
+/// the Debian/RHEL versioned directories
+fn pg_dump_candidates() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+
+    if let Some(path) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&path) {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = entries
+                .flatten()
+                .filter(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    let name = name.trim_end_matches(".exe");
+                    name == "pg_dump"
+                        || name.strip_prefix("pg_dump-").is_some_and(|major| {
+                            !major.is_empty() && major.chars().all(|c| c.is_ascii_digit())
+                        })
+                })
+                .map(|entry| entry.path())
+                .collect();
+            found.sort();
+            candidates.extend(found);
+        }
+    }
+
+    for prefix in ["/opt/homebrew", "/usr/local", "/home/linuxbrew/.linuxbrew"] {
+        for formula in ["postgresql", "libpq"] {
+            candidates.extend(versioned_paths(
+                &format!("{}/opt", prefix),
+                formula,
+                "bin/pg_dump",
+            ));
+            for cellar in versioned_paths(&format!("{}/Cellar", prefix), formula, "") {
+                candidates.extend(versioned_paths(
+                    &cellar.to_string_lossy(),
+                    "",
+                    "bin/pg_dump",
+                ));
             }
         }
     }
+    candidates.extend(versioned_paths(
+        "/Applications/Postgres.app/Contents/Versions",
+        "",
+        "bin/pg_dump",
+    ));
+    candidates.extend(versioned_paths("/usr/lib/postgresql", "", "bin/pg_dump"));
+    candidates.extend(versioned_paths("/usr", "pgsql-", "bin/pg_dump"));
+
+    // Homebrew's opt links and PATH entries often point at the same binary
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|path| path.is_file() && seen.insert(path.canonicalize().ok()));
+    candidates
+}
+
+/// The oldest pg_dump that can dump the server: newer ones work too, but
+/// emit settings older servers reject on restore
+fn select_pg_dump(server_major: u32, installed: &[(PathBuf, u32)]) -> Result<PathBuf> {
+    if let Some((path, _)) = installed
+        .iter()
+        .filter(|(_, major)| *major >= server_major)
+        .min_by_key(|(_, major)| *major)
+    {
+        return Ok(path.clone());
+    }
+
+    match installed.iter().map(|(_, major)| *major).max() {
+        Some(client) => Err(SupamigrateError::PgDumpTooOld {
+            server: server_major,
+            client,
+        }),
+        None => Err(SupamigrateError::PgDumpNotFound),
+    }
+}
 
-    // Fall back to PATH
-    debug!("No version-specific pg_dump found, using PATH");
-    PathBuf::from("pg_dump")
+/// Find the pg_dump that matches the server version
+pub(super) fn find_compatible_pg_dump(server_major: u32) -> Result<PathBuf> {
+    let installed: Vec<(PathBuf, u32)> = pg_dump_candidates()
+        .into_iter()
+        .filter_map(|path| {
+            let major = client_major(&path)?;
+            debug!("Found pg_dump v{} at {}", major, path.display());
+            Some((path, major))
+        })
+        .collect();
+    select_pg_dump(server_major, &installed)
 }
 
 impl PgDump {
     pub fn new(db_url: String) -> Self {
-        // Try to auto-detect compatible pg_dump
-        let binary_path = match get_server_version(&db_url) {
-            Some(major) => {
-                info!("Detected PostgreSQL server version: {}", major);
-                find_compatible_pg_dump(major)
-            }
-            None => {
-                warn!("Could not detect server version, using pg_dump from PATH");
-                PathBuf::from("pg_dump")
-            }
-        };
-
         Self {
             db_url,
-            binary_path,
+            engine: DumpEngine::PgDump,
+            format: DumpFormat::Plain,
+            jobs: 1,
+            schemas: Vec::new(),
             excluded_schemas: Vec::new(),
+            included_tables: Vec::new(),
             excluded_tables: Vec::new(),
+            excluded_table_data: Vec::new(),
+            always_excluded_table_data: ALWAYS_EXCLUDED_TABLE_DATA
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            row_filters: Vec::new(),
+            masks: Vec::new(),
+            extra_args: Vec::new(),
             schema_only: false,
             data_only: false,
         }
     }
 
+    /// Dump with pg_dump, natively over tokio-postgres or with pg_dump in
+    /// Docker
+    pub fn engine(mut self, engine: DumpEngine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Plain SQL, or a custom- or directory-format archive for pg_restore.
+    /// Archives can only be written with `dump_to_file`.
+    pub fn format(mut self, format: DumpFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Tables pg_dump dumps in parallel, for the directory format
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Only dump these schemas (all schemas if empty)
+    pub fn schemas(mut self, schemas: Vec<String>) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
     pub fn exclude_schemas(mut self, schemas: Vec<String>) -> Self {
         self.excluded_schemas = schemas;
         self
     }
 
+    /// Only dump tables matching these patterns (`schema.table`, `*`
+    /// wildcards, `public` if unqualified). Each must match a table on the
+    /// source.
+    pub fn include_tables(mut self, tables: Vec<String>) -> Self {
+        self.included_tables = tables;
+        self
+    }
+
     pub fn exclude_tables(mut self, tables: Vec<String>) -> Self {
         self.excluded_tables = tables;
         self
     }
 
+    /// Only export the rows of these tables (`schema.table`, `public` if
+    /// unqualified) matching their SQL condition. pg_dump skips their data
+    /// and the filtered rows follow the dump as `COPY` blocks, so this needs
+    /// a plain-format dump.
+    pub fn row_filters(mut self, filters: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.row_filters = filters
+            .into_iter()
+            .map(|(table, condition)| (qualify_pattern(&table), condition))
+            .collect();
+        self
+    }
+
+    /// Mask columns of these tables (`schema.table`, `public` if
+    /// unqualified) as their rows are exported. Like row filters, pg_dump
+    /// skips their data and the masked rows follow the dump, so this needs a
+    /// plain-format dump.
+    pub fn masks<C>(mut self, masks: impl IntoIterator<Item = (String, C)>) -> Self
+    where
+        C: IntoIterator<Item = (String, MaskRule)>,
+    {
+        self.masks = masks
+            .into_iter()
+            .map(|(table, columns)| (qualify_pattern(&table), columns.into_iter().collect()))
+            .filter(|(_, columns): &(String, Vec<_>)| !columns.is_empty())
+            .collect();
+        self
+    }
+
+    /// Dump the definition but not the rows of matching tables. Patterns may
+    /// use `*` and `?` (e.g. `public.audit_*`, `*.events`); unqualified names
+    /// refer to the `public` schema.
+    pub fn exclude_table_data(mut self, patterns: Vec<String>) -> Self {
+        self.excluded_table_data = patterns;
+        self
+    }
+
+    /// Skip the rows of these tables (pg_dump patterns) on top of
+    /// `exclude_table_data`, without looking them up first. Defaults to
+    /// `ALWAYS_EXCLUDED_TABLE_DATA`.
+    pub fn always_exclude_table_data(mut self, patterns: Vec<String>) -> Self {
+        self.always_excluded_table_data = patterns;
+        self
+    }
+
+    /// Additional pg_dump arguments appended after the generated ones
+    pub fn extra_args(mut self, args: Vec<String>) -> Self {
+        self.extra_args = args;
+        self
+    }
+
     pub fn schema_only(mut self, value: bool) -> Self {
         self.schema_only = value;
         self
@@ -117,16 +351,31 @@ impl PgDump {
         self
     }
 
+    /// pg_dump binary matching the server version. If the server can't be
+    /// reached pg_dump will report why, so any pg_dump is used.
+    fn binary_path(&self) -> Result<PathBuf> {
+        match get_server_version(&self.db_url) {
+            Some(major) => {
+                info!("Detected PostgreSQL server version: {}", major);
+                find_compatible_pg_dump(major)
+            }
+            None => {
+                warn!("Could not detect server version, using pg_dump from PATH");
+                Ok(PathBuf::from("pg_dump"))
+            }
+        }
+    }
+
     /// Check if pg_dump is available
-    fn check_available(&self) -> Result<()> {
-        let output = Command::new(&self.binary_path).arg("--version").output();
+    fn check_available(binary_path: &Path) -> Result<()> {
+        let output = Command::new(binary_path).arg("--version").output();
 
         match output {
             Ok(o) if o.status.success() => {
                 let version = String::from_utf8_lossy(&o.stdout);
                 debug!(
                     "Using pg_dump: {} ({})",
-                    self.binary_path.display(),
+                    binary_path.display(),
                     version.trim()
                 );
                 Ok(())
@@ -135,18 +384,45 @@ impl PgDump {
         }
     }
 
-    /// Execute pg_dump and write to file
-    #[allow(dead_code)]
-    pub fn dump_to_file(&self, output_path: &Path) -> Result<()> {
-        self.check_available()?;
+    /// pg_dump in the postgres image matching the server version
+    fn docker_command(&self) -> Command {
+        let major = get_server_version(&self.db_url);
+        match major {
+            Some(major) => info!("Detected PostgreSQL server version: {}", major),
+            None => warn!("Could not detect server version, using the default postgres image"),
+        }
+        tools::docker("pg_dump", major, &[])
+    }
 
-        info!("Starting database dump...");
+    /// Build the pg_dump command shared by file, string and streamed output
+    pub(super) fn command(&self) -> Result<Command> {
+        let mut cmd = if self.engine == DumpEngine::Docker {
+            self.docker_command()
+        } else {
+            let binary_path = self.binary_path()?;
+            Self::check_available(&binary_path)?;
+            Command::new(&binary_path)
+        };
+        cmd.arg(&self.db_url).arg("--quote-all-identifiers");
+        // pg_dump refuses to drop objects in a data-only dump
+        if !self.data_only {
+            cmd.arg("--clean").arg("--if-exists");
+        }
+        // Per-object progress, relayed at debug level
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            cmd.arg("--verbose");
+        }
 
-        let mut cmd = Command::new(&self.binary_path);
-        cmd.arg(&self.db_url)
-            .arg("--clean")
-            .arg("--if-exists")
-            .arg("--quote-all-identifiers");
+        match self.format {
+            DumpFormat::Plain => {}
+            DumpFormat::Custom => {
+                cmd.arg("--format=custom");
+            }
+            DumpFormat::Directory => {
+                cmd.arg("--format=directory")
+                    .arg(format!("--jobs={}", self.jobs));
+            }
+        }
 
         // Add schema/data only flags
         if self.schema_only {
@@ -156,33 +432,222 @@ impl PgDump {
             cmd.arg("--data-only");
         }
 
-        // Exclude storage.objects data (always)
-        cmd.arg("--exclude-table-data=storage.objects");
+        for table in &self.always_excluded_table_data {
+            cmd.arg(format!("--exclude-table-data={}", table));
+        }
+
+        // Exclude data of tables matching the requested patterns
+        for table in self.resolve_excluded_table_data()? {
+            cmd.arg(format!("--exclude-table-data={}", table));
+        }
+
+        // Filtered and masked rows are exported separately by `exported_rows`
+        if !self.schema_only {
+            for table in self.rewritten_tables() {
+                cmd.arg(format!("--exclude-table-data={}", quote_table(table)));
+            }
+        }
 
-        // Exclude schemas
+        // Exclude schemas. The migration history belongs to each target, so
+        // it's never carried over whatever the config says.
         if !self.excluded_schemas.is_empty() {
             let schema_pattern = self.excluded_schemas.join("|");
             cmd.arg(format!("--exclude-schema={}", schema_pattern));
         }
+        cmd.arg(format!("--exclude-schema={}", HISTORY_SCHEMA));
 
         // Exclude specific tables
         for table in &self.excluded_tables {
             cmd.arg(format!("--exclude-table={}", table));
         }
 
-        // Include all schemas
-        cmd.arg("--schema=*");
+        // Only the requested tables, if any
+        for table in self.resolve_included_tables()? {
+            cmd.arg(format!("--table={}", table));
+        }
 
-        // Output to file
-        cmd.arg("-f").arg(output_path);
+        // Include the requested schemas, or all of them
+        if self.schemas.is_empty() {
+            cmd.arg("--schema=*");
+        } else {
+            for schema in &self.schemas {
+                cmd.arg(format!("--schema={}", schema));
+            }
+        }
+
+        // User-supplied options the CLI doesn't model
+        cmd.args(&self.extra_args);
+
+        Ok(cmd)
+    }
+
+    /// `schema.table` names of the source's tables
+    fn source_tables(&self) -> Result<Vec<String>> {
+        let catalog = PgRestore::new(self.db_url.clone()).query(
+            "SELECT schemaname || '.' || tablename FROM pg_catalog.pg_tables \
+             ORDER BY 1",
+        )?;
+        Ok(catalog
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Expand the included table patterns against the source catalog into
+    /// exact, quoted names for `--table`
+    fn resolve_included_tables(&self) -> Result<Vec<String>> {
+        if self.included_tables.is_empty() {
+            return Ok(Vec::new());
+        }
+        let catalog = self.source_tables()?;
+        let tables: Vec<&str> = catalog.iter().map(String::as_str).collect();
+        Ok(self
+            .match_included_tables(&tables)?
+            .into_iter()
+            .map(quote_table)
+            .collect())
+    }
+
+    /// Tables matching the included patterns, failing on a pattern that
+    /// matches none so a typo doesn't silently dump less
+    pub(super) fn match_included_tables<'t>(&self, tables: &[&'t str]) -> Result<Vec<&'t str>> {
+        let mut matched = Vec::new();
+        for pattern in &self.included_tables {
+            let qualified = qualify_pattern(pattern);
+            let found: Vec<&str> = tables
+                .iter()
+                .copied()
+                .filter(|table| glob_match(&qualified, table))
+                .collect();
+            if found.is_empty() {
+                return Err(SupamigrateError::Config(format!(
+                    "Included table '{}' doesn't exist on the source",
+                    pattern
+                )));
+            }
+            info!(
+                "Including {} table(s) matching '{}': {}",
+                found.len(),
+                pattern,
+                found.join(", ")
+            );
+            for table in found {
+                if !matched.contains(&table) {
+                    matched.push(table);
+                }
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Expand table-data exclusion patterns against the source catalog so
+    /// the exact set of skipped tables is logged before dumping
+    fn resolve_excluded_table_data(&self) -> Result<Vec<String>> {
+        if self.excluded_table_data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let catalog = self.source_tables()?;
+        let tables: Vec<&str> = catalog.iter().map(String::as_str).collect();
+
+        let mut resolved = Vec::new();
+        for pattern in &self.excluded_table_data {
+            let qualified = qualify_pattern(pattern);
+            let matches: Vec<&str> = tables
+                .iter()
+                .copied()
+                .filter(|table| glob_match(&qualified, table))
+                .collect();
+
+            if matches.is_empty() {
+                warn!("Table data pattern '{}' matched no tables", pattern);
+            } else {
+                info!(
+                    "Excluding data of {} table(s) matching '{}': {}",
+                    matches.len(),
+                    pattern,
+                    matches.join(", ")
+                );
+            }
 
-        debug!("Running: {:?}", cmd);
+            for table in matches {
+                let quoted = quote_table(table);
+                if !resolved.contains(&quoted) {
+                    resolved.push(quoted);
+                }
+            }
+        }
 
-        let output = cmd.output()?;
+        Ok(resolved)
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(SupamigrateError::PgDumpFailed(stderr.to_string()));
+    /// Execute pg_dump and write to file
+    pub fn dump_to_file(&self, output_path: &Path) -> Result<()> {
+        if self.format != DumpFormat::Plain && !self.rewritten_tables().is_empty() {
+            return Err(SupamigrateError::Config(
+                "Row filters and masking need a plain-format dump".to_string(),
+            ));
+        }
+        match self.engine {
+            DumpEngine::Native if self.format != DumpFormat::Plain => {
+                return Err(SupamigrateError::Config(
+                    "The native engine only writes plain SQL dumps".to_string(),
+                ))
+            }
+            DumpEngine::Native => {
+                let file = std::fs::File::create(output_path)?;
+                return self.dump_native(&mut tokio::io::BufWriter::new(
+                    tokio::fs::File::from_std(file),
+                ));
+            }
+            DumpEngine::Docker if self.format == DumpFormat::Directory => {
+                return Err(SupamigrateError::Config(
+                    "The docker engine can't write directory-format dumps; use --format custom"
+                        .to_string(),
+                ))
+            }
+            // The container can't write to host paths, so take its stdout
+            DumpEngine::Docker => {
+                let mut output = Self::run(&mut self.command()?)?.stdout;
+                output.extend_from_slice(self.exported_rows()?.as_bytes());
+                return Ok(std::fs::write(output_path, output)?);
+            }
+            DumpEngine::PgDump => {}
+        }
+
+        let mut cmd = self.command()?;
+
+        info!("Starting database dump...");
+
+        // Output to file
+        cmd.arg("-f").arg(output_path);
+
+        debug!(
+            "Running pg_dump against {} into {}",
+            display_name(&self.db_url),
+            output_path.display()
+        );
+
+        let pb = progress::bytes("Dumping database", None);
+        let result = with_retry("pg_dump", || {
+            // pg_dump only writes into a missing or empty directory, so
+            // clear what a failed attempt left behind
+            if self.format == DumpFormat::Directory && output_path.exists() {
+                std::fs::remove_dir_all(output_path)?;
+            }
+            let _watch = progress::Watch::start(output_path, &pb);
+            check_dump(relay::output("pg_dump", &mut cmd)?)
+        });
+        pb.finish_and_clear();
+        result?;
+
+        let exported = self.exported_rows()?;
+        if !exported.is_empty() {
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(output_path)?
+                .write_all(exported.as_bytes())?;
         }
 
         info!("Database dump completed: {}", output_path.display());
@@ -191,41 +656,243 @@ impl PgDump {
 
     /// Execute pg_dump and return SQL as string
     pub fn dump_to_string(&self) -> Result<String> {
-        self.check_available()?;
+        if self.engine == DumpEngine::Native {
+            let mut sql = Vec::new();
+            self.dump_native(&mut sql)?;
+            return Ok(String::from_utf8_lossy(&sql).into_owned());
+        }
 
-        let mut cmd = Command::new(&self.binary_path);
-        cmd.arg(&self.db_url)
-            .arg("--clean")
-            .arg("--if-exists")
-            .arg("--quote-all-identifiers");
+        let output = Self::run(&mut self.command()?)?;
 
-        if self.schema_only {
-            cmd.arg("--schema-only");
+        let mut sql = String::from_utf8_lossy(&output.stdout).to_string();
+        sql.push_str(&self.exported_rows()?);
+        Ok(sql)
+    }
+}
+
+impl PgDump {
+    /// Run pg_dump with retries, collecting its stdout with a progress bar
+    fn run(cmd: &mut Command) -> Result<Output> {
+        let pb = progress::bytes("Dumping database", None);
+        let result = with_retry("pg_dump", || {
+            pb.set_position(0);
+            check_dump(relay::output_counted("pg_dump", cmd, &pb)?)
+        });
+        pb.finish_and_clear();
+        result
+    }
+
+    /// Tables whose rows are filtered or masked, so pg_dump can't export them
+    pub(super) fn rewritten_tables(&self) -> Vec<&str> {
+        let mut tables: Vec<&str> = self
+            .row_filters
+            .iter()
+            .map(|(table, _)| table.as_str())
+            .chain(self.masks.iter().map(|(table, _)| table.as_str()))
+            .collect();
+        tables.sort_unstable();
+        tables.dedup();
+        tables
+    }
+
+    /// `COPY` blocks with the filtered and masked rows of the tables that
+    /// have row filters or masks, to follow a pg_dump dump (empty without
+    /// either, or with the native engine, which filters and masks itself)
+    pub(super) fn exported_rows(&self) -> Result<String> {
+        if self.rewritten_tables().is_empty()
+            || self.schema_only
+            || self.engine == DumpEngine::Native
+        {
+            return Ok(String::new());
         }
-        if self.data_only {
-            cmd.arg("--data-only");
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(native_dump::exported_rows(self))
+        })
+    }
+
+    /// Run the native engine into `sink` from sync code, which may be on an
+    /// async worker (backup) or a blocking thread (migrate)
+    fn dump_native(&self, sink: &mut (impl AsyncWrite + Unpin)) -> Result<()> {
+        info!("Starting database dump (native engine)...");
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(native_dump::dump(self, sink))
+        })
+    }
+
+    /// Set up the dump for streaming, to start once per attempt
+    pub(super) fn source(&self) -> Result<DumpSource> {
+        if self.engine == DumpEngine::Native {
+            return Ok(DumpSource::Native(Arc::new(self.clone())));
         }
+        let mut cmd = self.command()?;
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        Ok(DumpSource::Command(cmd))
+    }
+}
 
-        cmd.arg("--exclude-table-data=storage.objects");
+/// Where a streamed dump comes from
+pub(super) enum DumpSource {
+    /// pg_dump, or pg_dump in docker
+    Command(Command),
+    /// The native engine, run on the async runtime
+    Native(Arc<PgDump>),
+}
 
-        if !self.excluded_schemas.is_empty() {
-            let schema_pattern = self.excluded_schemas.join("|");
-            cmd.arg(format!("--exclude-schema={}", schema_pattern));
+/// A started dump
+pub(super) enum Dumper {
+    Process(Child, JoinHandle<Vec<u8>>),
+    Native(tokio::task::JoinHandle<Result<()>>),
+}
+
+/// Bytes of SQL the native engine may be ahead of the reader
+const NATIVE_PIPE_CAPACITY: usize = 1024 * 1024;
+
+impl DumpSource {
+    /// Start dumping, returning the SQL to read as it's produced. Must be
+    /// called off the async workers, as reading blocks.
+    pub(super) fn start(&mut self) -> Result<(Box<dyn Read + Send>, Dumper)> {
+        match self {
+            Self::Command(cmd) => {
+                let mut child = cmd.spawn()?;
+                // Drain stderr while streaming so pg_dump doesn't block on a
+                // full pipe
+                let stderr = relay::relay("pg_dump", child.stderr.take());
+                let stdout: Box<dyn Read + Send> = match child.stdout.take() {
+                    Some(stdout) => Box::new(stdout),
+                    None => Box::new(std::io::empty()),
+                };
+                Ok((stdout, Dumper::Process(child, stderr)))
+            }
+            Self::Native(options) => {
+                info!("Starting database dump (native engine)...");
+                let (mut writer, reader) = tokio::io::duplex(NATIVE_PIPE_CAPACITY);
+                let options = Arc::clone(options);
+                // The reader going away ends the dump with a broken pipe
+                let task =
+                    tokio::spawn(async move { native_dump::dump(&options, &mut writer).await });
+                Ok((Box::new(SyncIoBridge::new(reader)), Dumper::Native(task)))
+            }
         }
+    }
+}
 
-        for table in &self.excluded_tables {
-            cmd.arg(format!("--exclude-table={}", table));
+impl Dumper {
+    /// Stop the dump early
+    pub(super) fn kill(&mut self) {
+        match self {
+            Self::Process(child, _) => {
+                let _ = child.kill();
+            }
+            Self::Native(task) => task.abort(),
         }
+    }
 
-        cmd.arg("--schema=*");
+    /// Wait for the dump to end, failing if it did
+    pub(super) fn wait(self) -> Result<()> {
+        match self {
+            Self::Process(mut child, stderr) => {
+                let status = child.wait()?;
+                check_dump(Output {
+                    status,
+                    stdout: Vec::new(),
+                    stderr: stderr.join().unwrap_or_default(),
+                })?;
+                Ok(())
+            }
+            Self::Native(task) => tokio::runtime::Handle::current()
+                .block_on(task)
+                .map_err(std::io::Error::from)?,
+        }
+    }
+}
 
-        let output = cmd.output()?;
+/// Qualify a table pattern with the `public` schema if it has none
+pub fn qualify_pattern(pattern: &str) -> String {
+    if pattern.contains('.') {
+        pattern.to_string()
+    } else {
+        format!("public.{}", pattern)
+    }
+}
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(SupamigrateError::PgDumpFailed(stderr.to_string()));
+/// Match `text` against a pattern where `*` matches any run of characters
+/// and `?` matches exactly one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
         }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_pg_dump() {
+        assert_eq!(
+            parse_client_major("pg_dump (PostgreSQL) 15.4 (Ubuntu 15.4-1.pgdg22.04+1)"),
+            Some(15)
+        );
+        assert_eq!(parse_client_major("pg_dump (PostgreSQL) 17beta1"), Some(17));
+
+        let installed = vec![
+            (PathBuf::from("/usr/bin/pg_dump"), 14),
+            (PathBuf::from("/usr/lib/postgresql/17/bin/pg_dump"), 17),
+            (PathBuf::from("/usr/lib/postgresql/15/bin/pg_dump"), 15),
+        ];
+        assert_eq!(
+            select_pg_dump(15, &installed).unwrap(),
+            PathBuf::from("/usr/lib/postgresql/15/bin/pg_dump")
+        );
+        assert_eq!(
+            select_pg_dump(16, &installed).unwrap(),
+            PathBuf::from("/usr/lib/postgresql/17/bin/pg_dump")
+        );
+        assert!(matches!(
+            select_pg_dump(18, &installed),
+            Err(SupamigrateError::PgDumpTooOld {
+                server: 18,
+                client: 17
+            })
+        ));
+        assert!(matches!(
+            select_pg_dump(15, &[]),
+            Err(SupamigrateError::PgDumpNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("public.audit_*", "public.audit_log"));
+        assert!(glob_match("*.events", "analytics.events"));
+        assert!(glob_match("public.log_??", "public.log_01"));
+        assert!(!glob_match("public.audit_*", "private.audit_log"));
+        assert!(!glob_match("*.events", "public.events_archive"));
+    }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    #[test]
+    fn test_quote_table() {
+        assert_eq!(quote_table("public.users"), r#""public"."users""#);
+        assert_eq!(quote_table("users"), r#""public"."users""#);
+        assert_eq!(quote_table(r#"app.odd"name"#), r#""app"."odd""name""#);
     }
 }
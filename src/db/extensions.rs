@@ -0,0 +1,191 @@
+use super::native::NativeClient;
+use super::sql::quote_ident;
+use crate::error::{Result, SupamigrateError};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Extensions created in the database, leaving out those built into the
+/// server (plpgsql)
+const INSTALLED_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'name', e.extname,
+    'version', e.extversion,
+    'schema', n.nspname
+) ORDER BY e.extname), '[]')
+FROM pg_extension e
+JOIN pg_namespace n ON n.oid = e.extnamespace
+WHERE e.oid >= 16384";
+
+/// The version each extension would have after `CREATE EXTENSION IF NOT
+/// EXISTS`: the installed one, else the default
+const AVAILABLE_SQL: &str = r"
+SELECT coalesce(json_object_agg(name, coalesce(installed_version, default_version)), '{}')
+FROM pg_available_extensions";
+
+/// An extension a dump creates
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Extension {
+    pub name: String,
+    /// Version on the source, when known. Dumps don't record one.
+    pub version: Option<String>,
+    /// Schema on the source, when known
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+impl Extension {
+    /// Extensions installed on a database, which a full dump of it creates
+    pub async fn installed(db_url: &str) -> Result<Vec<Self>> {
+        NativeClient::connect(db_url)
+            .await?
+            .query_json(INSTALLED_SQL)
+            .await
+    }
+
+    /// Extensions a plain SQL dump creates
+    pub fn in_dump(sql: &str) -> Vec<Self> {
+        sql.lines()
+            .filter_map(|line| {
+                let rest = line.strip_prefix("CREATE EXTENSION ")?;
+                let rest = rest.strip_prefix("IF NOT EXISTS ").unwrap_or(rest);
+                Some(Self::named(rest.split_whitespace().next()?))
+            })
+            .collect()
+    }
+
+    /// Extensions an archive's table of contents (`pg_restore --list`)
+    /// creates. Entries look like `3; 3079 16389 EXTENSION - pgcrypto `.
+    pub fn in_toc(list: &str) -> Vec<Self> {
+        list.lines()
+            .filter(|line| !line.starts_with(';'))
+            .filter_map(|line| {
+                let entry = line.splitn(4, ' ').nth(3)?;
+                Some(Self::named(
+                    entry
+                        .strip_prefix("EXTENSION - ")?
+                        .split_whitespace()
+                        .next()?,
+                ))
+            })
+            .collect()
+    }
+
+    fn named(name: &str) -> Self {
+        Self {
+            name: name.trim_end_matches(';').trim_matches('"').to_string(),
+            version: None,
+            schema: None,
+        }
+    }
+
+    /// Create the extensions `target_url` doesn't have yet, each in its
+    /// `schema` (created if needed) and with the extensions it requires.
+    /// Returns the names of those created.
+    pub async fn create_missing(target_url: &str, extensions: &[Self]) -> Result<Vec<String>> {
+        let client = NativeClient::connect(target_url).await?;
+        let installed: Vec<Self> = client.query_json(INSTALLED_SQL).await?;
+        let mut created = Vec::new();
+        for extension in extensions {
+            if installed.iter().any(|e| e.name == extension.name) {
+                continue;
+            }
+            let name = quote_ident(&extension.name);
+            let sql = match &extension.schema {
+                Some(schema) => format!(
+                    "CREATE SCHEMA IF NOT EXISTS {schema};\n\
+                     CREATE EXTENSION IF NOT EXISTS {name} WITH SCHEMA {schema} CASCADE",
+                    schema = quote_ident(schema)
+                ),
+                None => format!("CREATE EXTENSION IF NOT EXISTS {} CASCADE", name),
+            };
+            client.execute(&sql).await.map_err(|e| {
+                SupamigrateError::Database(format!(
+                    "Failed to create extension {}: {}",
+                    extension.name, e
+                ))
+            })?;
+            created.push(extension.name.clone());
+        }
+        Ok(created)
+    }
+}
+
+/// How the extensions a dump creates match what the target offers
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ExtensionCheck {
+    /// Extensions the target can't install at all
+    pub missing: Vec<String>,
+    /// Extensions that would come out in another version than the source's,
+    /// one line each
+    pub other_versions: Vec<String>,
+}
+
+impl ExtensionCheck {
+    /// Compare `wanted` with the extensions available on `target_url`
+    pub async fn run(target_url: &str, wanted: &[Extension]) -> Result<Self> {
+        let available: BTreeMap<String, Option<String>> = NativeClient::connect(target_url)
+            .await?
+            .query_json(AVAILABLE_SQL)
+            .await?;
+        Ok(Self::compare(wanted, &available))
+    }
+
+    fn compare(wanted: &[Extension], available: &BTreeMap<String, Option<String>>) -> Self {
+        let mut check = Self::default();
+        for extension in wanted {
+            match available.get(&extension.name) {
+                None => check.missing.push(extension.name.clone()),
+                Some(target) => {
+                    if let (Some(source), Some(target)) = (&extension.version, target) {
+                        if source != target {
+                            check.other_versions.push(format!(
+                                "{}: {} on the source, {} on the target",
+                                extension.name, source, target
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        check
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_check() {
+        let sql = r#"
+CREATE EXTENSION IF NOT EXISTS "pgcrypto" WITH SCHEMA "extensions";
+-- CREATE EXTENSION IF NOT EXISTS "pg_net" WITH SCHEMA "extensions";
+CREATE EXTENSION postgis WITH SCHEMA public;
+"#;
+        let mut wanted = Extension::in_dump(sql);
+        assert_eq!(
+            wanted.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+            vec!["pgcrypto", "postgis"]
+        );
+
+        let toc = "; Archive created at 2024-06-01\n3; 3079 16389 EXTENSION - pgcrypto \n;4; 3079 16390 EXTENSION - pg_net \n215; 1259 16386 TABLE public todos postgres";
+        assert_eq!(Extension::in_toc(toc), vec![Extension::named("pgcrypto")]);
+
+        wanted.push(Extension {
+            name: "vector".to_string(),
+            version: Some("0.7.0".to_string()),
+            schema: Some("extensions".to_string()),
+        });
+        let available: BTreeMap<String, Option<String>> =
+            [("pgcrypto", Some("1.3")), ("vector", Some("0.5.1"))]
+                .map(|(name, version)| (name.to_string(), version.map(String::from)))
+                .into();
+        assert_eq!(
+            ExtensionCheck::compare(&wanted, &available),
+            ExtensionCheck {
+                missing: vec!["postgis".to_string()],
+                other_versions: vec!["vector: 0.7.0 on the source, 0.5.1 on the target".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use super::native::NativeClient;
+use super::sql::quote_literal;
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Schema holding the migration history on a target. It's excluded from
+/// dumps by default so each database keeps its own history.
+pub const HISTORY_SCHEMA: &str = "supamigrate";
+
+const CREATE_HISTORY: &str = "CREATE SCHEMA IF NOT EXISTS supamigrate;
+CREATE TABLE IF NOT EXISTS supamigrate.migrations (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    source_ref text NOT NULL,
+    source text NOT NULL,
+    started_at timestamptz NOT NULL,
+    finished_at timestamptz NOT NULL,
+    tool_version text NOT NULL,
+    options jsonb NOT NULL DEFAULT '{}',
+    stats jsonb NOT NULL DEFAULT '{}'
+);";
+
+/// A completed migration, as recorded in `supamigrate.migrations` on the
+/// target
+#[derive(Debug, Clone)]
+pub struct MigrationRecord {
+    pub source_ref: String,
+    /// Alias or connection string (password masked) of the source
+    pub source: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Resolved options from the migration plan
+    pub options: Value,
+    /// Statistics by phase
+    pub stats: Value,
+}
+
+impl MigrationRecord {
+    fn insert_sql(&self) -> String {
+        format!(
+            "INSERT INTO supamigrate.migrations \
+             (source_ref, source, started_at, finished_at, tool_version, options, stats) \
+             VALUES ({}, {}, {}, {}, {}, {}::jsonb, {}::jsonb)",
+            quote_literal(&self.source_ref),
+            quote_literal(&self.source),
+            quote_literal(&self.started_at.to_rfc3339()),
+            quote_literal(&self.finished_at.to_rfc3339()),
+            quote_literal(env!("CARGO_PKG_VERSION")),
+            quote_literal(&self.options.to_string()),
+            quote_literal(&self.stats.to_string()),
+        )
+    }
+
+    /// Append this migration to the history table on `db_url`, creating the
+    /// table on first use
+    pub async fn save(&self, db_url: &str) -> Result<()> {
+        let client = NativeClient::connect(db_url).await?;
+        client.execute(CREATE_HISTORY).await?;
+        client.execute(&self.insert_sql()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_insert_sql() {
+        let record = MigrationRecord {
+            source_ref: "abcdefghijklmnop".to_string(),
+            source: "production".to_string(),
+            started_at: DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            finished_at: DateTime::parse_from_rfc3339("2024-01-15T12:05:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            options: serde_json::json!({"Tables": "public.o'rders"}),
+            stats: serde_json::json!({"database": {"dump_bytes": 42}}),
+        };
+        let sql = record.insert_sql();
+        assert!(sql.contains("'2024-01-15T12:00:00+00:00'"));
+        assert!(sql.contains(r#"'{"Tables":"public.o''rders"}'::jsonb"#));
+        assert!(sql.contains(r#"'{"database":{"dump_bytes":42}}'::jsonb"#));
+    }
+}
@@ -0,0 +1,65 @@
+use super::native::NativeClient;
+use super::sql::quote_literal;
+use crate::error::{Result, SupamigrateError};
+use tracing::debug;
+
+/// Advisory lock key shared by every supamigrate run ("supamigr" in ASCII)
+const LOCK_KEY: i64 = 0x7375_7061_6d69_6772;
+
+/// Session advisory lock on a target database, held while migrate or
+/// restore writes to it so two runs can't interleave. Postgres releases it
+/// when the connection closes, so a crashed run never leaves it behind.
+pub struct TargetLock {
+    _client: NativeClient,
+}
+
+impl TargetLock {
+    /// Take the lock, or fail right away naming the run that holds it
+    pub async fn acquire(db_url: &str, command: &str) -> Result<Self> {
+        let client = NativeClient::connect(db_url).await?;
+        client
+            .execute(&format!(
+                "SET application_name = {}",
+                quote_literal(&format!("supamigrate {}", command))
+            ))
+            .await?;
+
+        let acquired = client
+            .execute(&format!("SELECT pg_try_advisory_lock({})", LOCK_KEY))
+            .await?
+            .first()
+            .and_then(|result| result.rows.first())
+            .and_then(|row| row.first().cloned().flatten());
+        if acquired.as_deref() == Some("t") {
+            debug!("Acquired target lock");
+            return Ok(Self { _client: client });
+        }
+
+        // A bigint key shows up in pg_locks split into two 32-bit halves
+        let holder = client
+            .execute(&format!(
+                "SELECT a.application_name, coalesce(host(a.client_addr), 'local'), \
+                        to_char(a.backend_start, 'YYYY-MM-DD HH24:MI:SS TZ') \
+                 FROM pg_locks l JOIN pg_stat_activity a ON a.pid = l.pid \
+                 WHERE l.locktype = 'advisory' AND l.granted \
+                   AND l.classid = {} AND l.objid = {} AND l.objsubid = 1",
+                LOCK_KEY >> 32,
+                LOCK_KEY & 0xffff_ffff
+            ))
+            .await?
+            .into_iter()
+            .next()
+            .and_then(|result| result.rows.into_iter().next())
+            .map(|row| {
+                let field = |i: usize| row.get(i).cloned().flatten().unwrap_or_default();
+                format!(" ({} from {}, started {})", field(0), field(1), field(2))
+            })
+            .unwrap_or_default();
+
+        Err(SupamigrateError::Database(format!(
+            "Another supamigrate run is writing to this database{}. Wait for it to \
+             finish; the lock is released as soon as it exits.",
+            holder
+        )))
+    }
+}
@@ -0,0 +1,133 @@
+use super::sql::quote_literal;
+use serde::{Deserialize, Serialize};
+
+/// First and last names the `name` rule picks from
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Sam", "Jordan", "Taylor", "Morgan", "Casey", "Riley", "Jamie", "Avery", "Quinn",
+    "Robin", "Charlie", "Dana", "Kai", "Noa", "Sasha",
+];
+const LAST_NAMES: &[&str] = &[
+    "Smith", "Garcia", "Kim", "Müller", "Rossi", "Silva", "Novak", "Tanaka", "Dubois", "Cohen",
+    "Jensen", "Okafor", "Larsen", "Moreau", "Kowalski", "Nguyen",
+];
+
+/// How the values of a masked column are replaced on their way out of the
+/// source. Every rule runs as SQL in the `COPY` that exports the rows, so
+/// personal data never leaves the source database, and maps the same input
+/// to the same output, so joins on masked columns still line up. NULLs stay
+/// NULL.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskRule {
+    /// SHA-256 of the value, as hex
+    Hash,
+    /// `user_<hash>@example.com`
+    Email,
+    /// A made-up "First Last" name
+    Name,
+    /// Letters and digits replaced by `*`, keeping punctuation and length
+    Redact,
+    /// NULL
+    Null,
+    /// A fixed SQL literal, from a `[redact]` rule
+    #[serde(skip)]
+    Constant(String),
+}
+
+/// Override for one column in `[redact]`: `"null"`, or a constant the
+/// column is set to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Redaction {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Redaction {
+    pub fn rule(&self) -> MaskRule {
+        match self {
+            Self::Text(text) if text.eq_ignore_ascii_case("null") => MaskRule::Null,
+            Self::Text(text) => MaskRule::Constant(quote_literal(text)),
+            Self::Bool(value) => MaskRule::Constant(value.to_string()),
+            Self::Integer(value) => MaskRule::Constant(value.to_string()),
+            Self::Float(value) => MaskRule::Constant(value.to_string()),
+        }
+    }
+}
+
+impl MaskRule {
+    /// SQL expression replacing `column` (a quoted identifier) of type
+    /// `type_name`
+    pub(super) fn expression(&self, column: &str, type_name: &str) -> String {
+        let text = format!("{}::text", column);
+        let digest = format!("sha256(convert_to({}, 'UTF8'))", text);
+        let masked = match self {
+            Self::Hash => format!("encode({}, 'hex')", digest),
+            Self::Email => format!(
+                "'user_' || left(encode({}, 'hex'), 16) || '@example.com'",
+                digest
+            ),
+            Self::Name => format!(
+                "{} || ' ' || {}",
+                pick(FIRST_NAMES, &digest, 0),
+                pick(LAST_NAMES, &digest, 1)
+            ),
+            Self::Redact => format!("regexp_replace({}, '[[:alnum:]]', '*', 'g')", text),
+            Self::Null => "NULL".to_string(),
+            Self::Constant(literal) => literal.clone(),
+        };
+        format!("({})::{}", masked, type_name)
+    }
+}
+
+/// One of `values`, chosen by byte `byte` of `digest`
+fn pick(values: &[&str], digest: &str, byte: usize) -> String {
+    let array: Vec<String> = values.iter().map(|v| format!("'{}'", v)).collect();
+    format!(
+        "(ARRAY[{}])[1 + get_byte({}, {}) % {}]",
+        array.join(", "),
+        digest,
+        byte,
+        values.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_expression() {
+        assert_eq!(
+            MaskRule::Hash.expression("\"email\"", "text"),
+            "(encode(sha256(convert_to(\"email\"::text, 'UTF8')), 'hex'))::text"
+        );
+        assert_eq!(
+            MaskRule::Redact.expression("phone", "character varying(20)"),
+            "(regexp_replace(phone::text, '[[:alnum:]]', '*', 'g'))::character varying(20)"
+        );
+        assert_eq!(MaskRule::Null.expression("dob", "date"), "(NULL)::date");
+        assert!(MaskRule::Name
+            .expression("full_name", "text")
+            .contains("get_byte(sha256(convert_to(full_name::text, 'UTF8')), 1) % 16]"));
+    }
+
+    #[test]
+    fn test_redaction_rule() {
+        assert_eq!(Redaction::Text("NULL".to_string()).rule(), MaskRule::Null);
+        assert_eq!(
+            Redaction::Text("it's".to_string())
+                .rule()
+                .expression("plan", "text"),
+            "('it''s')::text"
+        );
+        assert_eq!(
+            Redaction::Integer(0)
+                .rule()
+                .expression("credits", "integer"),
+            "(0)::integer"
+        );
+    }
+}
@@ -1,9 +1,50 @@
+mod cron;
+mod deps;
 mod dump;
+mod extensions;
+mod history;
+mod lock;
+mod mask;
+mod native;
+mod native_dump;
+mod objects;
+mod policies;
+mod probe;
+mod progress;
+mod publications;
+mod relay;
 mod restore;
+pub mod retry;
+mod roles;
+mod schema;
+mod sequences;
+mod size;
+pub mod sql;
+mod statements;
+pub mod tools;
 mod transform;
 pub mod vault;
+mod webhooks;
 
-pub use dump::PgDump;
+pub use cron::sync_cron_jobs;
+pub use deps::{DependencyCheck, SchemaScope};
+pub use dump::{PgDump, ALWAYS_EXCLUDED_TABLE_DATA};
+pub use extensions::{Extension, ExtensionCheck};
+pub use history::{MigrationRecord, HISTORY_SCHEMA};
+pub use lock::TargetLock;
+pub use mask::{MaskRule, Redaction};
+pub use native::ResultSet;
+pub use objects::sync_object_rows;
+pub use policies::{diff_policies, reconcile_sql, render_diff, PolicySet};
+pub use probe::{check_database, connect_project, connect_url};
+pub use publications::{sync_publication, REALTIME_PUBLICATION};
 pub use restore::PgRestore;
-pub use transform::SqlTransformer;
-pub use vault::{VaultBackup, VaultClient};
+pub use roles::RoleDump;
+pub use schema::{schema_drift, SchemaSnapshot};
+pub use sequences::{sync_sequences, SequenceAdjustment};
+pub use size::{DatabaseSize, SchemaSize};
+pub use transform::{
+    LineTransformer, Rewrite, SqlTransformer, TransformKind, TransformScript, UserTransforms,
+};
+pub use vault::{VaultBackup, VaultClient, VaultEntry};
+pub use webhooks::sync_webhooks;
@@ -0,0 +1,1168 @@
+use super::dump::{glob_match, qualify_pattern, PgDump};
+use super::history::HISTORY_SCHEMA;
+use super::mask::MaskRule;
+use super::native::NativeClient;
+use super::progress;
+use super::transform::Rewrite;
+use crate::error::{Result, SupamigrateError};
+use bytes::Bytes;
+use indicatif::HumanBytes;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt::Write;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+/// Session settings at the top of the dump, as pg_dump writes them. The
+/// empty search_path also makes the catalog functions qualify every name.
+const HEADER: &str = "--
+-- PostgreSQL database dump (supamigrate native engine)
+--
+
+SET statement_timeout = 0;
+SET lock_timeout = 0;
+SET idle_in_transaction_session_timeout = 0;
+SET client_encoding = 'UTF8';
+SET standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', false);
+SET check_function_bodies = false;
+SET client_min_messages = warning;
+SET row_security = off;
+";
+
+/// Opens the read-only snapshot the catalog and data are read from, like
+/// pg_dump. Quoting every identifier, as `--quote-all-identifiers` does,
+/// lets the schema remaps and role renames find them in the output.
+const SNAPSHOT: &str = "BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY; \
+     SELECT pg_catalog.set_config('search_path', '', false); \
+     SET quote_all_identifiers = on";
+
+/// Condition excluding objects that belong to an extension
+const NOT_EXTENSION_MEMBER: &str =
+    "NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.objid = {oid} AND d.deptype = 'e')";
+
+const SCHEMAS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', n.nspname, 'ident', quote_ident(n.nspname)
+) ORDER BY n.nspname), '[]')
+FROM pg_catalog.pg_namespace n
+WHERE n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:n.oid}";
+
+const EXTENSIONS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', e.extname, 'ident', quote_ident(e.extname),
+    'sql', format('CREATE EXTENSION IF NOT EXISTS %I WITH SCHEMA %I;', e.extname, n.nspname)
+) ORDER BY e.extname), '[]')
+FROM pg_catalog.pg_extension e
+JOIN pg_catalog.pg_namespace n ON n.oid = e.extnamespace
+WHERE e.extname <> 'plpgsql'";
+
+/// Enums, domains and standalone composite types
+const TYPES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', t.typname, 'ident', format('%I.%I', n.nspname, t.typname),
+    'kind', CASE t.typtype WHEN 'd' THEN 'DOMAIN' ELSE 'TYPE' END,
+    'sql', CASE t.typtype
+        WHEN 'e' THEN format('CREATE TYPE %I.%I AS ENUM (%s);', n.nspname, t.typname,
+            (SELECT coalesce(string_agg(quote_literal(e.enumlabel), ', ' ORDER BY e.enumsortorder), '')
+             FROM pg_catalog.pg_enum e WHERE e.enumtypid = t.oid))
+        WHEN 'c' THEN format('CREATE TYPE %I.%I AS (%s);', n.nspname, t.typname,
+            (SELECT coalesce(string_agg(format('%I %s', a.attname, format_type(a.atttypid, a.atttypmod)), ', ' ORDER BY a.attnum), '')
+             FROM pg_catalog.pg_attribute a WHERE a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped))
+        ELSE format('CREATE DOMAIN %I.%I AS %s%s%s%s;', n.nspname, t.typname,
+            format_type(t.typbasetype, t.typtypmod),
+            coalesce(' DEFAULT ' || t.typdefault, ''),
+            CASE WHEN t.typnotnull THEN ' NOT NULL' ELSE '' END,
+            (SELECT coalesce(string_agg(format(' CONSTRAINT %I %s', c.conname, pg_get_constraintdef(c.oid)), '' ORDER BY c.conname), '')
+             FROM pg_catalog.pg_constraint c WHERE c.contypid = t.oid AND c.contype = 'c'))
+    END
+) ORDER BY t.oid), '[]')
+FROM pg_catalog.pg_type t
+JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace
+WHERE (t.typtype IN ('e', 'd')
+       OR (t.typtype = 'c' AND (SELECT c.relkind FROM pg_catalog.pg_class c WHERE c.oid = t.typrelid) = 'c'))
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:t.oid}";
+
+/// Functions and procedures. `late` marks those whose signature uses a
+/// table's row type, so they must follow the tables.
+const FUNCTIONS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', p.proname, 'ident', p.oid::regprocedure::text,
+    'kind', CASE p.prokind WHEN 'p' THEN 'PROCEDURE' ELSE 'FUNCTION' END,
+    'sql', pg_get_functiondef(p.oid) || ';',
+    'late', EXISTS (
+        SELECT 1 FROM pg_catalog.pg_type t
+        WHERE t.oid = ANY (array_append(p.proargtypes::oid[], p.prorettype))
+          AND t.typrelid <> 0
+          AND (SELECT c.relkind FROM pg_catalog.pg_class c WHERE c.oid = t.typrelid) IN ('r', 'p', 'v', 'm'))
+) ORDER BY p.oid), '[]')
+FROM pg_catalog.pg_proc p
+JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace
+WHERE p.prokind IN ('f', 'p')
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:p.oid}";
+
+/// Sequences; identity sequences are created with their column but still
+/// need their value restored
+const SEQUENCES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', s.schemaname, 'name', s.sequencename,
+    'ident', format('%I.%I', s.schemaname, s.sequencename),
+    'sql', format('CREATE SEQUENCE %I.%I AS %s START WITH %s INCREMENT BY %s MINVALUE %s MAXVALUE %s CACHE %s%s;',
+        s.schemaname, s.sequencename, s.data_type, s.start_value, s.increment_by,
+        s.min_value, s.max_value, s.cache_size, CASE WHEN s.cycle THEN ' CYCLE' ELSE '' END),
+    'identity', EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.objid = c.oid AND d.deptype = 'i'),
+    'owned_by', (
+        SELECT format('ALTER SEQUENCE %I.%I OWNED BY %I.%I.%I;', s.schemaname, s.sequencename, tn.nspname, tc.relname, a.attname)
+        FROM pg_catalog.pg_depend d
+        JOIN pg_catalog.pg_class tc ON tc.oid = d.refobjid
+        JOIN pg_catalog.pg_namespace tn ON tn.oid = tc.relnamespace
+        JOIN pg_catalog.pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+        WHERE d.objid = c.oid AND d.classid = 'pg_catalog.pg_class'::regclass
+          AND d.refclassid = 'pg_catalog.pg_class'::regclass AND d.deptype = 'a'
+        LIMIT 1),
+    'setval', CASE WHEN s.last_value IS NOT NULL THEN format('SELECT pg_catalog.setval(%L, %s, true);',
+        format('%I.%I', s.schemaname, s.sequencename), s.last_value) END
+) ORDER BY c.oid), '[]')
+FROM pg_catalog.pg_sequences s
+JOIN pg_catalog.pg_namespace n ON n.nspname = s.schemaname
+JOIN pg_catalog.pg_class c ON c.relnamespace = n.oid AND c.relname = s.sequencename
+WHERE n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:c.oid}";
+
+const TABLES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', c.relname, 'ident', format('%I.%I', n.nspname, c.relname),
+    'partitioned', c.relkind = 'p',
+    'unlogged', c.relpersistence = 'u',
+    'partition_of', CASE WHEN c.relispartition THEN (
+        SELECT format('%I.%I', pn.nspname, pc.relname)
+        FROM pg_catalog.pg_inherits i
+        JOIN pg_catalog.pg_class pc ON pc.oid = i.inhparent
+        JOIN pg_catalog.pg_namespace pn ON pn.oid = pc.relnamespace
+        WHERE i.inhrelid = c.oid) END,
+    'partition_bound', CASE WHEN c.relispartition THEN pg_get_expr(c.relpartbound, c.oid) END,
+    'partition_key', CASE WHEN c.relkind = 'p' THEN pg_get_partkeydef(c.oid) END,
+    'rls', c.relrowsecurity,
+    'force_rls', c.relforcerowsecurity,
+    'comments', array_remove(ARRAY[
+        CASE WHEN obj_description(c.oid, 'pg_class') IS NOT NULL THEN format('COMMENT ON TABLE %I.%I IS %L;',
+            n.nspname, c.relname, obj_description(c.oid, 'pg_class')) END
+    ] || ARRAY(
+        SELECT format('COMMENT ON COLUMN %I.%I.%I IS %L;', n.nspname, c.relname, a.attname, col_description(c.oid, a.attnum))
+        FROM pg_catalog.pg_attribute a
+        WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped AND col_description(c.oid, a.attnum) IS NOT NULL
+        ORDER BY a.attnum
+    ), NULL),
+    'columns', (
+        SELECT coalesce(json_agg(json_build_object(
+            'name', a.attname,
+            'ident', quote_ident(a.attname),
+            'type', format_type(a.atttypid, a.atttypmod),
+            'not_null', a.attnotnull,
+            'default', CASE WHEN a.attgenerated = '' THEN pg_get_expr(ad.adbin, ad.adrelid) END,
+            'generated', CASE WHEN a.attgenerated = 's' THEN pg_get_expr(ad.adbin, ad.adrelid) END,
+            'identity', a.attidentity,
+            'collation', CASE WHEN a.attcollation <> 0 AND a.attcollation <> t.typcollation THEN (
+                SELECT format('%I.%I', cn.nspname, co.collname)
+                FROM pg_catalog.pg_collation co
+                JOIN pg_catalog.pg_namespace cn ON cn.oid = co.collnamespace
+                WHERE co.oid = a.attcollation) END
+        ) ORDER BY a.attnum), '[]')
+        FROM pg_catalog.pg_attribute a
+        JOIN pg_catalog.pg_type t ON t.oid = a.atttypid
+        LEFT JOIN pg_catalog.pg_attrdef ad ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum
+        WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped)
+) ORDER BY c.relispartition, c.oid), '[]')
+FROM pg_catalog.pg_class c
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relkind IN ('r', 'p')
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:c.oid}";
+
+/// Table constraints, foreign keys last. Constraints cloned onto
+/// partitions come with the parent's.
+const CONSTRAINTS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', c.relname,
+    'kind', CASE WHEN con.contype = 'f' THEN 'foreign_key' ELSE 'constraint' END,
+    'sql', format('ALTER TABLE %s%I.%I ADD CONSTRAINT %I %s;',
+        CASE WHEN c.relkind = 'p' THEN '' ELSE 'ONLY ' END,
+        n.nspname, c.relname, con.conname, pg_get_constraintdef(con.oid))
+) ORDER BY con.contype = 'f', con.contype <> 'p', con.oid), '[]')
+FROM pg_catalog.pg_constraint con
+JOIN pg_catalog.pg_class c ON c.oid = con.conrelid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE con.contype IN ('p', 'u', 'c', 'f', 'x')
+  AND con.conislocal AND con.conparentid = 0
+  AND c.relkind IN ('r', 'p')
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:c.oid}";
+
+/// Indexes not created by a constraint; partition indexes come with the
+/// parent's
+const INDEXES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', c.relname,
+    'sql', replace(pg_get_indexdef(i.indexrelid), ' ON ONLY ', ' ON ') || ';'
+) ORDER BY i.indexrelid), '[]')
+FROM pg_catalog.pg_index i
+JOIN pg_catalog.pg_class c ON c.oid = i.indrelid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relkind IN ('r', 'p', 'm')
+  AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_constraint con WHERE con.conindid = i.indexrelid AND con.contype IN ('p', 'u', 'x'))
+  AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_inherits h WHERE h.inhrelid = i.indexrelid)
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:c.oid}";
+
+/// Views and materialized views with the views they read from
+const VIEWS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'oid', c.oid::int8,
+    'schema', n.nspname, 'name', c.relname, 'ident', format('%I.%I', n.nspname, c.relname),
+    'kind', CASE c.relkind WHEN 'm' THEN 'MATERIALIZED VIEW' ELSE 'VIEW' END,
+    'sql', CASE c.relkind
+        WHEN 'm' THEN format(E'CREATE MATERIALIZED VIEW %I.%I AS\n%s\nWITH NO DATA;', n.nspname, c.relname,
+            rtrim(rtrim(pg_get_viewdef(c.oid)), ';'))
+        ELSE format(E'CREATE VIEW %I.%I%s AS\n%s', n.nspname, c.relname,
+            CASE WHEN c.reloptions IS NOT NULL THEN format(' WITH (%s)', array_to_string(c.reloptions, ', ')) ELSE '' END,
+            pg_get_viewdef(c.oid))
+    END,
+    'depends_on', ARRAY(
+        SELECT DISTINCT d.refobjid::int8
+        FROM pg_catalog.pg_rewrite r
+        JOIN pg_catalog.pg_depend d ON d.classid = 'pg_catalog.pg_rewrite'::regclass AND d.objid = r.oid
+        JOIN pg_catalog.pg_class rc ON rc.oid = d.refobjid AND rc.relkind IN ('v', 'm')
+        WHERE r.ev_class = c.oid AND d.refobjid <> c.oid)
+) ORDER BY c.oid), '[]')
+FROM pg_catalog.pg_class c
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relkind IN ('v', 'm')
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:c.oid}";
+
+/// User triggers; clones on partitions come with the parent's
+const TRIGGERS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', c.relname,
+    'sql', pg_get_triggerdef(t.oid) || ';'
+) ORDER BY t.oid), '[]')
+FROM pg_catalog.pg_trigger t
+JOIN pg_catalog.pg_class c ON c.oid = t.tgrelid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE NOT t.tgisinternal
+  AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d
+                  WHERE d.classid = 'pg_catalog.pg_trigger'::regclass AND d.objid = t.oid AND d.deptype IN ('P', 'S'))
+  AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+  AND {not_extension:c.oid}";
+
+const POLICIES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'schema', n.nspname, 'name', c.relname,
+    'sql', format('CREATE POLICY %I ON %I.%I AS %s FOR %s TO %s%s%s;',
+        pol.polname, n.nspname, c.relname,
+        CASE WHEN pol.polpermissive THEN 'PERMISSIVE' ELSE 'RESTRICTIVE' END,
+        CASE pol.polcmd WHEN 'r' THEN 'SELECT' WHEN 'a' THEN 'INSERT' WHEN 'w' THEN 'UPDATE' WHEN 'd' THEN 'DELETE' ELSE 'ALL' END,
+        CASE WHEN pol.polroles = '{0}' THEN 'PUBLIC' ELSE (
+            SELECT string_agg(quote_ident(r.rolname), ', ' ORDER BY r.rolname)
+            FROM pg_catalog.pg_roles r WHERE r.oid = ANY (pol.polroles)) END,
+        coalesce(' USING (' || pg_get_expr(pol.polqual, pol.polrelid) || ')', ''),
+        coalesce(' WITH CHECK (' || pg_get_expr(pol.polwithcheck, pol.polrelid) || ')', ''))
+) ORDER BY pol.oid), '[]')
+FROM pg_catalog.pg_policy pol
+JOIN pg_catalog.pg_class c ON c.oid = pol.polrelid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'";
+
+/// A schema-level object and the statement that creates it
+#[derive(Debug, Deserialize)]
+struct Definition {
+    schema: String,
+    /// Object name, or the table name for table-level objects
+    name: String,
+    #[serde(default)]
+    ident: String,
+    #[serde(default)]
+    kind: String,
+    #[serde(default)]
+    sql: String,
+    #[serde(default)]
+    late: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Sequence {
+    schema: String,
+    name: String,
+    ident: String,
+    sql: String,
+    identity: bool,
+    owned_by: Option<String>,
+    setval: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Table {
+    schema: String,
+    name: String,
+    ident: String,
+    partitioned: bool,
+    unlogged: bool,
+    partition_of: Option<String>,
+    partition_bound: Option<String>,
+    partition_key: Option<String>,
+    rls: bool,
+    force_rls: bool,
+    comments: Vec<String>,
+    columns: Vec<Column>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Column {
+    name: String,
+    ident: String,
+    #[serde(rename = "type")]
+    type_name: String,
+    not_null: bool,
+    default: Option<String>,
+    generated: Option<String>,
+    identity: String,
+    collation: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct View {
+    oid: i64,
+    schema: String,
+    name: String,
+    ident: String,
+    kind: String,
+    sql: String,
+    depends_on: Vec<i64>,
+}
+
+/// Definitions that go after the data, so loading it doesn't check
+/// constraints or fire triggers row by row
+struct PostData {
+    constraints: Vec<Definition>,
+    indexes: Vec<Definition>,
+    views: Vec<View>,
+    functions: Vec<Definition>,
+    triggers: Vec<Definition>,
+    policies: Vec<Definition>,
+}
+
+impl Table {
+    fn drop_sql(&self) -> String {
+        format!("DROP TABLE IF EXISTS {} CASCADE;", self.ident)
+    }
+
+    fn create_sql(&self) -> String {
+        if let (Some(parent), Some(bound)) = (&self.partition_of, &self.partition_bound) {
+            let mut sql = format!(
+                "CREATE TABLE {} PARTITION OF {} {}",
+                self.ident, parent, bound
+            );
+            if let Some(key) = &self.partition_key {
+                let _ = write!(sql, " PARTITION BY {}", key);
+            }
+            sql.push_str(";\n");
+            return sql;
+        }
+
+        let columns: Vec<String> = self.columns.iter().map(Column::definition).collect();
+        let mut sql = format!(
+            "CREATE {}TABLE {} (\n    {}\n)",
+            if self.unlogged { "UNLOGGED " } else { "" },
+            self.ident,
+            columns.join(",\n    ")
+        );
+        if let Some(key) = &self.partition_key {
+            let _ = write!(sql, " PARTITION BY {}", key);
+        }
+        sql.push_str(";\n");
+        sql
+    }
+
+    /// Columns COPY can write; generated columns are computed on insert
+    fn copy_columns(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .filter(|c| c.generated.is_none())
+            .map(|c| c.ident.as_str())
+            .collect()
+    }
+
+    /// The `COPY ... FROM stdin;` line that heads the table's rows in a dump
+    fn copy_in_sql(&self) -> String {
+        format!(
+            "COPY {} ({}) FROM stdin;",
+            self.ident,
+            self.copy_columns().join(", ")
+        )
+    }
+
+    /// `COPY ... TO STDOUT` of the table's rows, or only those matching
+    /// `condition`, with the values of `masks` columns replaced
+    fn copy_out_sql(&self, condition: Option<&str>, masks: &[(String, MaskRule)]) -> String {
+        if condition.is_none() && masks.is_empty() {
+            let columns = self.copy_columns().join(", ");
+            return format!("COPY {} ({}) TO STDOUT", self.ident, columns);
+        }
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .filter(|c| c.generated.is_none())
+            .map(|c| match masks.iter().find(|(name, _)| *name == c.name) {
+                Some((_, rule)) => rule.expression(&c.ident, &c.type_name),
+                None => c.ident.clone(),
+            })
+            .collect();
+        let mut sql = format!("COPY (SELECT {} FROM {}", columns.join(", "), self.ident);
+        if let Some(condition) = condition {
+            let _ = write!(sql, " WHERE {}", condition);
+        }
+        sql.push_str(") TO STDOUT");
+        sql
+    }
+}
+
+impl Column {
+    fn definition(&self) -> String {
+        let mut sql = format!("{} {}", self.ident, self.type_name);
+        if let Some(collation) = &self.collation {
+            let _ = write!(sql, " COLLATE {}", collation);
+        }
+        if let Some(expression) = &self.generated {
+            let _ = write!(sql, " GENERATED ALWAYS AS ({}) STORED", expression);
+        }
+        match self.identity.as_str() {
+            "a" => sql.push_str(" GENERATED ALWAYS AS IDENTITY"),
+            "d" => sql.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+            _ => {}
+        }
+        if let Some(default) = &self.default {
+            let _ = write!(sql, " DEFAULT {}", default);
+        }
+        if self.not_null {
+            sql.push_str(" NOT NULL");
+        }
+        sql
+    }
+}
+
+/// Which objects of the catalog end up in the dump, from the PgDump options
+pub(super) struct Filter<'a> {
+    dump: &'a PgDump,
+    included_tables: Vec<String>,
+    excluded_tables: Vec<String>,
+    excluded_table_data: Vec<String>,
+}
+
+impl<'a> Filter<'a> {
+    pub(super) fn new(dump: &'a PgDump) -> Self {
+        let qualify = |patterns: &[String]| patterns.iter().map(|p| qualify_pattern(p)).collect();
+        let excluded_table_data = [
+            qualify(&dump.excluded_table_data),
+            qualify(&dump.always_excluded_table_data),
+        ]
+        .concat();
+        Self {
+            dump,
+            included_tables: qualify(&dump.included_tables),
+            excluded_tables: qualify(&dump.excluded_tables),
+            excluded_table_data,
+        }
+    }
+
+    fn schema(&self, schema: &str) -> bool {
+        schema != HISTORY_SCHEMA
+            && (self.dump.schemas.is_empty()
+                || self.dump.schemas.iter().any(|p| glob_match(p, schema)))
+            && !self
+                .dump
+                .excluded_schemas
+                .iter()
+                .any(|p| glob_match(p, schema))
+    }
+
+    /// Tables, views and sequences, which `include_tables` and
+    /// `exclude_tables` also apply to
+    pub(super) fn relation(&self, schema: &str, name: &str) -> bool {
+        let qualified = format!("{}.{}", schema, name);
+        self.schema(schema)
+            && (self.included_tables.is_empty()
+                || self
+                    .included_tables
+                    .iter()
+                    .any(|p| glob_match(p, &qualified)))
+            && !self
+                .excluded_tables
+                .iter()
+                .any(|p| glob_match(p, &qualified))
+    }
+
+    /// Condition limiting the exported rows of a table
+    fn row_filter(&self, schema: &str, name: &str) -> Option<&str> {
+        let qualified = format!("{}.{}", schema, name);
+        self.dump
+            .row_filters
+            .iter()
+            .find(|(table, _)| *table == qualified)
+            .map(|(_, condition)| condition.as_str())
+    }
+
+    /// Columns masked as the table's rows are exported
+    fn masks(&self, schema: &str, name: &str) -> &[(String, MaskRule)] {
+        let qualified = format!("{}.{}", schema, name);
+        self.dump
+            .masks
+            .iter()
+            .find(|(table, _)| *table == qualified)
+            .map_or(&[], |(_, columns)| columns.as_slice())
+    }
+
+    pub(super) fn table_data(&self, schema: &str, name: &str) -> bool {
+        let qualified = format!("{}.{}", schema, name);
+        !self
+            .excluded_table_data
+            .iter()
+            .any(|p| glob_match(p, &qualified))
+    }
+}
+
+async fn fetch<T: for<'de> Deserialize<'de>>(client: &NativeClient, sql: &str) -> Result<Vec<T>> {
+    client.query_json(&expand_not_extension(sql)).await
+}
+
+/// All tables of the source, after checking each included table pattern
+/// matches one of them and each masked column exists
+async fn fetch_tables(client: &NativeClient, options: &PgDump) -> Result<Vec<Table>> {
+    let tables: Vec<Table> = fetch(client, TABLES_SQL).await?;
+    let names: Vec<String> = tables
+        .iter()
+        .map(|t| format!("{}.{}", t.schema, t.name))
+        .collect();
+    options.match_included_tables(&names.iter().map(String::as_str).collect::<Vec<_>>())?;
+
+    for (name, columns) in &options.masks {
+        let table = tables
+            .iter()
+            .zip(&names)
+            .find(|(_, qualified)| *qualified == name)
+            .map(|(table, _)| table)
+            .ok_or_else(|| {
+                SupamigrateError::Config(format!(
+                    "Masked table '{}' doesn't exist on the source",
+                    name
+                ))
+            })?;
+        if let Some((column, _)) = columns
+            .iter()
+            .find(|(column, _)| !table.columns.iter().any(|c| c.name == *column))
+        {
+            return Err(SupamigrateError::Config(format!(
+                "Masked column '{}' doesn't exist on {}",
+                column, name
+            )));
+        }
+    }
+    Ok(tables)
+}
+
+/// Expand `{not_extension:<oid expression>}` into the extension membership
+/// check for that object
+fn expand_not_extension(sql: &str) -> String {
+    const MARKER: &str = "{not_extension:";
+    let mut out = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find(MARKER) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + MARKER.len()..];
+        let end = after.find('}').unwrap_or(after.len());
+        out.push_str(&NOT_EXTENSION_MEMBER.replace("{oid}", &after[..end]));
+        rest = after.get(end + 1..).unwrap_or("");
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Views ordered so each follows the views it reads from
+fn sort_views(views: Vec<View>) -> Vec<View> {
+    let oids: HashSet<i64> = views.iter().map(|v| v.oid).collect();
+    let mut pending: Vec<View> = views;
+    let mut done: HashSet<i64> = HashSet::new();
+    let mut sorted = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<View>, Vec<View>) = pending.into_iter().partition(|v| {
+            v.depends_on
+                .iter()
+                .all(|dep| done.contains(dep) || !oids.contains(dep))
+        });
+        if ready.is_empty() {
+            // A cycle can't come from the catalog; keep the remaining order
+            sorted.extend(waiting);
+            break;
+        }
+        done.extend(ready.iter().map(|v| v.oid));
+        sorted.extend(ready);
+        pending = waiting;
+    }
+
+    sorted
+}
+
+/// Dump the database as plain SQL from catalog queries and `COPY TO
+/// STDOUT`, without pg_dump. Covers schemas, extensions, enum/domain/
+/// composite types, sequences, tables (including partitions), views,
+/// functions, constraints, indexes, triggers, RLS policies and comments;
+/// grants, ownership and other object kinds are not dumped. The SQL is
+/// written to `sink` as it's produced, with each table's rows streamed
+/// straight from its `COPY`, so the dump is never held in memory.
+pub async fn dump(options: &PgDump, sink: &mut (impl AsyncWrite + Unpin)) -> Result<()> {
+    let client = NativeClient::connect(&options.db_url).await?;
+    client.execute(SNAPSHOT).await?;
+
+    if !options.extra_args.is_empty() {
+        warn!(
+            "Ignoring pg_dump arguments with the native engine: {}",
+            options.extra_args.join(" ")
+        );
+    }
+
+    let filter = Filter::new(options);
+    let mut out = String::from(HEADER);
+
+    let tables: Vec<Table> = fetch_tables(&client, options)
+        .await?
+        .into_iter()
+        .filter(|t| filter.relation(&t.schema, &t.name))
+        .collect();
+    let sequences: Vec<Sequence> = fetch::<Sequence>(&client, SEQUENCES_SQL)
+        .await?
+        .into_iter()
+        .filter(|s| filter.relation(&s.schema, &s.name))
+        .collect();
+
+    let mut post_data = None;
+    if !options.data_only {
+        let schemas: Vec<Definition> = fetch(&client, SCHEMAS_SQL).await?;
+        let extensions: Vec<Definition> = fetch(&client, EXTENSIONS_SQL).await?;
+        let types: Vec<Definition> = fetch(&client, TYPES_SQL).await?;
+        let functions: Vec<Definition> = fetch(&client, FUNCTIONS_SQL).await?;
+        let views = sort_views(
+            fetch::<View>(&client, VIEWS_SQL)
+                .await?
+                .into_iter()
+                .filter(|v| filter.relation(&v.schema, &v.name))
+                .collect(),
+        );
+        let in_schema = |items: Vec<Definition>| -> Vec<Definition> {
+            items
+                .into_iter()
+                .filter(|d| filter.schema(&d.schema))
+                .collect()
+        };
+        let on_table = |items: Vec<Definition>| -> Vec<Definition> {
+            items
+                .into_iter()
+                .filter(|d| filter.relation(&d.schema, &d.name))
+                .collect()
+        };
+
+        let schemas = in_schema(schemas);
+        let extensions = in_schema(extensions);
+        let types = in_schema(types);
+        let functions = in_schema(functions);
+        let constraints = on_table(fetch(&client, CONSTRAINTS_SQL).await?);
+        let indexes = on_table(fetch(&client, INDEXES_SQL).await?);
+        let triggers = on_table(fetch(&client, TRIGGERS_SQL).await?);
+        let policies = on_table(fetch(&client, POLICIES_SQL).await?);
+
+        info!(
+            "Native dump: {} tables, {} views, {} functions, {} sequences",
+            tables.len(),
+            views.len(),
+            functions.len(),
+            sequences.len()
+        );
+
+        // --clean --if-exists
+        out.push('\n');
+        for view in views.iter().rev() {
+            let _ = writeln!(out, "DROP {} IF EXISTS {} CASCADE;", view.kind, view.ident);
+        }
+        for table in tables.iter().filter(|t| t.partition_of.is_none()) {
+            let _ = writeln!(out, "{}", table.drop_sql());
+        }
+        for sequence in sequences.iter().filter(|s| !s.identity) {
+            let _ = writeln!(out, "DROP SEQUENCE IF EXISTS {} CASCADE;", sequence.ident);
+        }
+        for function in functions.iter().rev() {
+            let _ = writeln!(
+                out,
+                "DROP {} IF EXISTS {} CASCADE;",
+                function.kind, function.ident
+            );
+        }
+        for ty in types.iter().rev() {
+            let _ = writeln!(out, "DROP {} IF EXISTS {} CASCADE;", ty.kind, ty.ident);
+        }
+
+        out.push('\n');
+        for schema in schemas.iter().filter(|s| s.name != "public") {
+            let _ = writeln!(out, "CREATE SCHEMA IF NOT EXISTS {};", schema.ident);
+        }
+        section(
+            &mut out,
+            "Extensions",
+            extensions.iter().map(|e| e.sql.as_str()),
+        );
+        section(&mut out, "Types", types.iter().map(|t| t.sql.as_str()));
+        section(
+            &mut out,
+            "Functions",
+            functions.iter().filter(|f| !f.late).map(|f| f.sql.as_str()),
+        );
+        section(
+            &mut out,
+            "Sequences",
+            sequences
+                .iter()
+                .filter(|s| !s.identity)
+                .map(|s| s.sql.as_str()),
+        );
+
+        out.push_str("\n--\n-- Tables\n--\n\n");
+        for table in &tables {
+            out.push_str(&table.create_sql());
+            for comment in &table.comments {
+                let _ = writeln!(out, "{}", comment);
+            }
+            out.push('\n');
+        }
+        section(
+            &mut out,
+            "Sequence ownership",
+            sequences.iter().filter_map(|s| s.owned_by.as_deref()),
+        );
+
+        post_data = Some(PostData {
+            constraints,
+            indexes,
+            views,
+            functions,
+            triggers,
+            policies,
+        });
+    }
+
+    if !options.schema_only {
+        out.push_str("\n--\n-- Data\n--\n\n");
+        for table in tables
+            .iter()
+            .filter(|t| !t.partitioned && filter.table_data(&t.schema, &t.name))
+        {
+            debug!("Copying {}", table.ident);
+            let _ = writeln!(out, "{}", table.copy_in_sql());
+            write_out(&mut out, sink).await?;
+            client
+                .copy_out_to(
+                    &table.copy_out_sql(
+                        filter.row_filter(&table.schema, &table.name),
+                        filter.masks(&table.schema, &table.name),
+                    ),
+                    sink,
+                )
+                .await?;
+            out.push_str("\\.\n\n");
+        }
+        section(
+            &mut out,
+            "Sequence values",
+            sequences.iter().filter_map(|s| s.setval.as_deref()),
+        );
+    }
+
+    if let Some(PostData {
+        constraints,
+        indexes,
+        views,
+        functions,
+        triggers,
+        policies,
+    }) = post_data
+    {
+        let (foreign_keys, constraints): (Vec<_>, Vec<_>) = constraints
+            .into_iter()
+            .partition(|c| c.kind == "foreign_key");
+        section(
+            &mut out,
+            "Constraints",
+            constraints.iter().map(|c| c.sql.as_str()),
+        );
+        section(&mut out, "Indexes", indexes.iter().map(|i| i.sql.as_str()));
+        section(
+            &mut out,
+            "Foreign keys",
+            foreign_keys.iter().map(|c| c.sql.as_str()),
+        );
+        section(&mut out, "Views", views.iter().map(|v| v.sql.as_str()));
+        section(
+            &mut out,
+            "Functions using table row types",
+            functions.iter().filter(|f| f.late).map(|f| f.sql.as_str()),
+        );
+        if !options.schema_only {
+            let refreshes: Vec<String> = views
+                .iter()
+                .filter(|v| v.kind == "MATERIALIZED VIEW")
+                .map(|v| format!("REFRESH MATERIALIZED VIEW {};", v.ident))
+                .collect();
+            section(
+                &mut out,
+                "Materialized view data",
+                refreshes.iter().map(String::as_str),
+            );
+        }
+
+        let row_security: Vec<String> = tables
+            .iter()
+            .flat_map(|t| {
+                let mut statements = Vec::new();
+                if t.rls {
+                    statements.push(format!(
+                        "ALTER TABLE {} ENABLE ROW LEVEL SECURITY;",
+                        t.ident
+                    ));
+                }
+                if t.force_rls {
+                    statements.push(format!("ALTER TABLE {} FORCE ROW LEVEL SECURITY;", t.ident));
+                }
+                statements
+            })
+            .collect();
+        section(
+            &mut out,
+            "Triggers",
+            triggers.iter().map(|t| t.sql.as_str()),
+        );
+        section(
+            &mut out,
+            "Row level security",
+            row_security
+                .iter()
+                .map(String::as_str)
+                .chain(policies.iter().map(|p| p.sql.as_str())),
+        );
+    }
+
+    client.execute("COMMIT").await?;
+    out.push_str("\n--\n-- PostgreSQL database dump complete\n--\n");
+    write_out(&mut out, sink).await?;
+    sink.flush().await?;
+    Ok(())
+}
+
+/// Write the SQL gathered in `out` to `sink` and start over
+async fn write_out(out: &mut String, sink: &mut (impl AsyncWrite + Unpin)) -> Result<()> {
+    sink.write_all(out.as_bytes()).await?;
+    out.clear();
+    Ok(())
+}
+
+/// The filtered and masked rows of the tables with row filters or masks, as
+/// `COPY` blocks to append to a pg_dump dump that skipped their data.
+/// Foreign keys and triggers are off while they load, since the constraints
+/// already exist by then.
+pub async fn exported_rows(options: &PgDump) -> Result<String> {
+    let client = NativeClient::connect(&options.db_url).await?;
+    client.execute(SNAPSHOT).await?;
+
+    let filter = Filter::new(options);
+    let tables = fetch_tables(&client, options).await?;
+    let mut out = String::from(
+        "\n--\n-- Filtered and masked table data\n--\n\nSET session_replication_role = replica;\n\n",
+    );
+    for name in options.rewritten_tables() {
+        let table = tables
+            .iter()
+            .find(|t| format!("{}.{}", t.schema, t.name) == name)
+            .ok_or_else(|| {
+                SupamigrateError::Config(format!(
+                    "Row filter table '{}' doesn't exist on the source",
+                    name
+                ))
+            })?;
+        if !filter.relation(&table.schema, &table.name)
+            || !filter.table_data(&table.schema, &table.name)
+        {
+            continue;
+        }
+
+        let condition = filter.row_filter(&table.schema, &table.name);
+        let masks = filter.masks(&table.schema, &table.name);
+        if let Some(condition) = condition {
+            info!("Exporting rows of {} where {}", table.ident, condition);
+        }
+        if !masks.is_empty() {
+            let columns: Vec<&str> = masks.iter().map(|(column, _)| column.as_str()).collect();
+            info!("Masking {} of {}", columns.join(", "), table.ident);
+        }
+        let data = client
+            .copy_out(&table.copy_out_sql(condition, masks))
+            .await?;
+        let _ = writeln!(out, "{}", table.copy_in_sql());
+        out.push_str(&String::from_utf8_lossy(&data));
+        out.push_str("\\.\n\n");
+    }
+    out.push_str("RESET session_replication_role;\n");
+    Ok(out)
+}
+
+/// Tables, rows and bytes moved by `copy_data`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CopyStats {
+    pub tables: u64,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+/// Copy the rows of the tables `options` selects straight into the same
+/// tables on `target`, one `COPY` pipe per table from a single snapshot of
+/// the source. Foreign keys and triggers are off on the target session while
+/// loading, like `pg_dump --disable-triggers`; sequence values follow the
+/// data. Rows pass through the `rewrites` for their table on the way.
+pub async fn copy_data(
+    options: &PgDump,
+    rewrites: &[Rewrite],
+    target: &NativeClient,
+) -> Result<CopyStats> {
+    let source = NativeClient::connect(&options.db_url).await?;
+    source.execute(SNAPSHOT).await?;
+    if let Err(e) = target
+        .execute("SET session_replication_role = replica")
+        .await
+    {
+        warn!(
+            "Foreign keys and triggers stay on while copying, so tables load in catalog order: {}",
+            e
+        );
+    }
+
+    let filter = Filter::new(options);
+    let tables: Vec<Table> = fetch_tables(&source, options)
+        .await?
+        .into_iter()
+        .filter(|t| {
+            !t.partitioned
+                && filter.relation(&t.schema, &t.name)
+                && filter.table_data(&t.schema, &t.name)
+        })
+        .collect();
+    let sequences: Vec<Sequence> = fetch::<Sequence>(&source, SEQUENCES_SQL)
+        .await?
+        .into_iter()
+        .filter(|s| filter.relation(&s.schema, &s.name))
+        .collect();
+
+    let mut stats = CopyStats::default();
+    let pb = progress::tables(tables.len() as u64);
+    for table in &tables {
+        let columns = table.copy_columns().join(", ");
+        debug!("Copying {}", table.ident);
+        let qualified = format!("{}.{}", table.schema, table.name);
+        let rewrites: Vec<&Rewrite> = rewrites
+            .iter()
+            .filter(|r| r.applies_to(&qualified))
+            .collect();
+        let mut bytes = 0;
+        let rows = source
+            .copy_into(
+                &table.copy_out_sql(
+                    filter.row_filter(&table.schema, &table.name),
+                    filter.masks(&table.schema, &table.name),
+                ),
+                target,
+                &format!("COPY {} ({}) FROM STDIN", table.ident, columns),
+                |row| rewrite_row(&rewrites, row),
+                |chunk| {
+                    bytes += chunk;
+                    pb.set_message(format!("{} ({})", table.ident, HumanBytes(bytes)));
+                },
+            )
+            .await;
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                pb.abandon();
+                return Err(e);
+            }
+        };
+        stats.tables += 1;
+        stats.rows += rows;
+        stats.bytes += bytes;
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+
+    for setval in sequences.iter().filter_map(|s| s.setval.as_deref()) {
+        target.execute(setval).await?;
+    }
+    target.execute("RESET session_replication_role").await?;
+
+    info!(
+        "Copied {} rows ({}) in {} tables",
+        stats.rows,
+        HumanBytes(stats.bytes),
+        stats.tables
+    );
+    Ok(stats)
+}
+
+/// Empty the tables on `target` whose rows `options` would load, in one
+/// `TRUNCATE` so foreign keys between them don't get in the way. Tables
+/// outside the selection that reference one of them make it fail rather
+/// than being emptied too. Returns the tables truncated.
+pub async fn truncate_tables(options: &PgDump, target: &NativeClient) -> Result<Vec<String>> {
+    let source = NativeClient::connect(&options.db_url).await?;
+    let filter = Filter::new(options);
+    let tables: Vec<String> = fetch_tables(&source, options)
+        .await?
+        .into_iter()
+        .filter(|t| {
+            !t.partitioned
+                && filter.relation(&t.schema, &t.name)
+                && filter.table_data(&t.schema, &t.name)
+        })
+        .map(|t| t.ident)
+        .collect();
+    if !tables.is_empty() {
+        target
+            .execute(&format!("TRUNCATE TABLE {}", tables.join(", ")))
+            .await?;
+    }
+    Ok(tables)
+}
+
+/// A row of `COPY` text with the rewrites applied
+fn rewrite_row(rewrites: &[&Rewrite], row: Bytes) -> Bytes {
+    if rewrites.is_empty() {
+        return row;
+    }
+    let text = String::from_utf8_lossy(&row);
+    let rewritten = rewrites.iter().fold(text.to_string(), |text, rewrite| {
+        rewrite.apply(&text).into_owned()
+    });
+    Bytes::from(rewritten)
+}
+
+/// Append statements under a pg_dump-style comment header, if there are any
+fn section<'s>(out: &mut String, title: &str, statements: impl Iterator<Item = &'s str>) {
+    let mut statements = statements.peekable();
+    if statements.peek().is_none() {
+        return;
+    }
+    let _ = write!(out, "\n--\n-- {}\n--\n\n", title);
+    for statement in statements {
+        out.push_str(statement.trim_end());
+        out.push_str("\n\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::transform::{LineTransformer, TransformKind};
+
+    fn view(oid: i64, depends_on: Vec<i64>) -> View {
+        View {
+            oid,
+            schema: "public".to_string(),
+            name: format!("v{}", oid),
+            ident: format!("public.v{}", oid),
+            kind: "VIEW".to_string(),
+            sql: String::new(),
+            depends_on,
+        }
+    }
+
+    #[test]
+    fn test_sort_views() {
+        // v1 reads v3, v3 reads v2; v9 isn't part of the dump
+        let sorted = sort_views(vec![view(1, vec![3]), view(2, vec![9]), view(3, vec![2])]);
+        let order: Vec<i64> = sorted.iter().map(|v| v.oid).collect();
+        assert_eq!(order, vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn test_filter_history_schema() {
+        let dump = PgDump::new(String::new()).schemas(vec!["*".to_string()]);
+        let filter = Filter::new(&dump);
+        assert!(filter.relation("public", "todos"));
+        assert!(!filter.relation(HISTORY_SCHEMA, "migrations"));
+    }
+
+    #[test]
+    fn test_shadow_schema_native_dump() {
+        // Identifiers as the catalog writes them with quote_all_identifiers
+        let table = Table {
+            schema: "public".to_string(),
+            name: "todos".to_string(),
+            ident: "\"public\".\"todos\"".to_string(),
+            partitioned: false,
+            unlogged: false,
+            partition_of: None,
+            partition_bound: None,
+            partition_key: None,
+            rls: false,
+            force_rls: false,
+            comments: Vec::new(),
+            columns: vec![Column {
+                name: "id".to_string(),
+                ident: "\"id\"".to_string(),
+                type_name: "bigint".to_string(),
+                not_null: true,
+                default: None,
+                generated: None,
+                identity: String::new(),
+                collation: None,
+            }],
+        };
+        let sql = format!(
+            "{}\n{}{}\n1\n\\.\n",
+            table.drop_sql(),
+            table.create_sql(),
+            table.copy_in_sql()
+        );
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .shadow_schema("public", "public_next")
+            .apply(&sql)
+            .unwrap();
+        assert!(result.contains("DROP TABLE IF EXISTS \"public_next\".\"todos\" CASCADE;"));
+        assert!(result.contains("CREATE TABLE \"public_next\".\"todos\" ("));
+        assert!(result.contains("COPY \"public_next\".\"todos\" (\"id\") FROM stdin;"));
+        assert!(!result.contains("\"public\"."));
+    }
+
+    #[test]
+    fn test_column_definition() {
+        let column = Column {
+            name: "id".to_string(),
+            ident: "id".to_string(),
+            type_name: "bigint".to_string(),
+            not_null: true,
+            default: None,
+            generated: None,
+            identity: "a".to_string(),
+            collation: None,
+        };
+        assert_eq!(
+            column.definition(),
+            "id bigint GENERATED ALWAYS AS IDENTITY NOT NULL"
+        );
+    }
+}
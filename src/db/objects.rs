@@ -0,0 +1,102 @@
+use super::native::NativeClient;
+use super::sql::quote_literal;
+use crate::error::Result;
+use serde::Deserialize;
+
+/// Rows read from the source per round trip
+const PAGE_SIZE: usize = 5000;
+
+/// The columns carried over for a page of objects after `(bucket, name)`.
+/// The rest (metadata, version) describe the file as the target stored it.
+fn objects_sql(bucket: &str, name: &str) -> String {
+    format!(
+        r"
+SELECT coalesce(json_agg(json_build_object(
+    'id', id,
+    'bucket_id', bucket_id,
+    'name', name,
+    'owner', owner,
+    'created_at', created_at,
+    'updated_at', updated_at,
+    'last_accessed_at', last_accessed_at
+) ORDER BY bucket_id, name), '[]')
+FROM (
+    SELECT * FROM storage.objects
+    WHERE (bucket_id, name) > ({}, {})
+    ORDER BY bucket_id, name
+    LIMIT {}
+) o",
+        quote_literal(bucket),
+        quote_literal(name),
+        PAGE_SIZE
+    )
+}
+
+/// Give the target's objects at the same paths the columns of `rows` (a JSON
+/// array). Owners are kept only when the user exists on the target.
+fn update_sql(rows: &str) -> String {
+    format!(
+        r"
+WITH source AS (
+    SELECT * FROM json_populate_recordset(NULL::storage.objects, {})
+), updated AS (
+    UPDATE storage.objects t
+    SET id = s.id,
+        owner = CASE
+            WHEN EXISTS (SELECT 1 FROM auth.users u WHERE u.id = s.owner) THEN s.owner
+            ELSE t.owner
+        END,
+        created_at = s.created_at,
+        updated_at = s.updated_at,
+        last_accessed_at = s.last_accessed_at
+    FROM source s
+    WHERE t.bucket_id = s.bucket_id AND t.name = s.name
+    RETURNING 1
+)
+SELECT to_json(count(*)) FROM updated",
+        quote_literal(rows)
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ObjectKey {
+    bucket_id: String,
+    name: String,
+}
+
+/// What bringing the target's object rows in line with the source's did
+#[derive(Debug, Default)]
+pub struct ObjectRowSync {
+    /// Objects given the source's id, owner and timestamps
+    pub updated: u64,
+    /// Source objects with no file at the same path on the target
+    pub missing: u64,
+}
+
+/// Copy the ids, owners and timestamps of the source's `storage.objects`
+/// rows onto the target's rows for the same bucket and path. Files uploaded
+/// through the Storage API get new rows, so without this, tables referencing
+/// objects by id point at nothing.
+pub async fn sync_object_rows(source_url: &str, target_url: &str) -> Result<ObjectRowSync> {
+    let source = NativeClient::connect(source_url).await?;
+    let target = NativeClient::connect(target_url).await?;
+    let mut sync = ObjectRowSync::default();
+    let (mut bucket, mut name) = (String::new(), String::new());
+    loop {
+        let rows: Vec<serde_json::Value> = source.query_json(&objects_sql(&bucket, &name)).await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        let key = ObjectKey::deserialize(last)?;
+        (bucket, name) = (key.bucket_id, key.name);
+
+        let json = serde_json::to_string(&rows)?;
+        let updated: u64 = target.query_json(&update_sql(&json)).await?;
+        sync.updated += updated;
+        sync.missing += rows.len() as u64 - updated;
+        if rows.len() < PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(sync)
+}
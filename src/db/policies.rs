@@ -0,0 +1,372 @@
+use super::restore::PgRestore;
+use super::sql::quote_ident;
+use crate::error::Result;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const POLICIES_SQL: &str = r"
+SELECT coalesce(json_agg(row_to_json(p) ORDER BY p.schemaname, p.tablename, p.policyname), '[]')
+FROM (
+    SELECT schemaname, tablename, policyname, permissive, roles::text[] AS roles,
+           cmd, qual, with_check
+    FROM pg_catalog.pg_policies
+) p";
+
+const RLS_TABLES_SQL: &str = r"
+SELECT coalesce(json_agg(row_to_json(t) ORDER BY t.schemaname, t.tablename), '[]')
+FROM (
+    SELECT n.nspname AS schemaname, c.relname AS tablename,
+           c.relrowsecurity AS enabled, c.relforcerowsecurity AS forced
+    FROM pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.relkind IN ('r', 'p')
+      AND n.nspname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
+) t";
+
+/// A row-level security policy as reported by `pg_policies`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Policy {
+    #[serde(rename = "schemaname")]
+    pub schema: String,
+    #[serde(rename = "tablename")]
+    pub table: String,
+    #[serde(rename = "policyname")]
+    pub name: String,
+    /// PERMISSIVE or RESTRICTIVE
+    pub permissive: String,
+    pub roles: Vec<String>,
+    /// ALL, SELECT, INSERT, UPDATE or DELETE
+    pub cmd: String,
+    #[serde(rename = "qual")]
+    pub using: Option<String>,
+    pub with_check: Option<String>,
+}
+
+/// Whether row-level security is enabled on a table
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TableRls {
+    #[serde(rename = "schemaname")]
+    pub schema: String,
+    #[serde(rename = "tablename")]
+    pub table: String,
+    pub enabled: bool,
+    pub forced: bool,
+}
+
+/// RLS state of one database
+#[derive(Debug, Default)]
+pub struct PolicySet {
+    pub policies: Vec<Policy>,
+    pub tables: Vec<TableRls>,
+}
+
+impl PolicySet {
+    /// Read all policies and table RLS flags from a database
+    pub fn fetch(db_url: &str) -> Result<Self> {
+        let db = PgRestore::new(db_url.to_string());
+        Ok(Self {
+            policies: db.query_json(POLICIES_SQL)?,
+            tables: db.query_json(RLS_TABLES_SQL)?,
+        })
+    }
+
+    /// Keep only objects in the given schemas (all if empty)
+    pub fn retain_schemas(&mut self, schemas: &[String]) {
+        if schemas.is_empty() {
+            return;
+        }
+        self.policies.retain(|p| schemas.contains(&p.schema));
+        self.tables.retain(|t| schemas.contains(&t.schema));
+    }
+}
+
+/// A difference in RLS between source and target, described from the
+/// target's point of view (what it would take to make it match the source)
+#[derive(Debug)]
+pub enum PolicyChange {
+    /// Policy exists on the source only
+    Missing(Policy),
+    /// Policy exists on the target only
+    Extra(Policy),
+    /// Same name, different definition
+    Changed { source: Policy, target: Policy },
+    /// RLS enable/force flags differ on a table present in both
+    Rls { source: TableRls, target: TableRls },
+}
+
+impl PolicyChange {
+    fn table_key(&self) -> String {
+        let (schema, table) = match self {
+            PolicyChange::Missing(p) | PolicyChange::Extra(p) => (&p.schema, &p.table),
+            PolicyChange::Changed { source, .. } => (&source.schema, &source.table),
+            PolicyChange::Rls { source, .. } => (&source.schema, &source.table),
+        };
+        format!("{}.{}", schema, table)
+    }
+}
+
+/// Compare the RLS state of two databases
+pub fn diff_policies(source: &PolicySet, target: &PolicySet) -> Vec<PolicyChange> {
+    let key = |p: &Policy| (p.schema.clone(), p.table.clone(), p.name.clone());
+    let source_policies: BTreeMap<_, _> = source.policies.iter().map(|p| (key(p), p)).collect();
+    let target_policies: BTreeMap<_, _> = target.policies.iter().map(|p| (key(p), p)).collect();
+
+    let mut changes = Vec::new();
+
+    for (k, policy) in &source_policies {
+        match target_policies.get(k) {
+            None => changes.push(PolicyChange::Missing((*policy).clone())),
+            Some(existing) if !same_definition(policy, existing) => {
+                changes.push(PolicyChange::Changed {
+                    source: (*policy).clone(),
+                    target: (*existing).clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    for (k, policy) in &target_policies {
+        if !source_policies.contains_key(k) {
+            changes.push(PolicyChange::Extra((*policy).clone()));
+        }
+    }
+
+    let target_tables: BTreeMap<_, _> = target
+        .tables
+        .iter()
+        .map(|t| ((t.schema.as_str(), t.table.as_str()), t))
+        .collect();
+    for table in &source.tables {
+        if let Some(existing) = target_tables.get(&(table.schema.as_str(), table.table.as_str())) {
+            if table.enabled != existing.enabled || table.forced != existing.forced {
+                changes.push(PolicyChange::Rls {
+                    source: table.clone(),
+                    target: (*existing).clone(),
+                });
+            }
+        }
+    }
+
+    changes.sort_by_key(PolicyChange::table_key);
+    changes
+}
+
+fn same_definition(a: &Policy, b: &Policy) -> bool {
+    let mut a_roles = a.roles.clone();
+    let mut b_roles = b.roles.clone();
+    a_roles.sort();
+    b_roles.sort();
+    a.permissive == b.permissive
+        && a.cmd == b.cmd
+        && a_roles == b_roles
+        && a.using == b.using
+        && a.with_check == b.with_check
+}
+
+/// Human-readable diff grouped by table
+pub fn render_diff(changes: &[PolicyChange]) -> String {
+    let mut out = String::new();
+    let mut current_table = String::new();
+
+    for change in changes {
+        let table = change.table_key();
+        if table != current_table {
+            let _ = writeln!(out, "\n{}", table);
+            current_table = table;
+        }
+        match change {
+            PolicyChange::Missing(p) => {
+                let _ = writeln!(out, "  + {} ({})", p.name, summary(p));
+            }
+            PolicyChange::Extra(p) => {
+                let _ = writeln!(out, "  - {} ({})", p.name, summary(p));
+            }
+            PolicyChange::Changed { source, target } => {
+                let _ = writeln!(out, "  ~ {}", source.name);
+                let fields = [
+                    ("command", Some(&target.cmd), Some(&source.cmd)),
+                    ("mode", Some(&target.permissive), Some(&source.permissive)),
+                    ("using", target.using.as_ref(), source.using.as_ref()),
+                    (
+                        "with check",
+                        target.with_check.as_ref(),
+                        source.with_check.as_ref(),
+                    ),
+                ];
+                for (label, to, from) in fields {
+                    if to != from {
+                        let _ = writeln!(
+                            out,
+                            "      {}: {} → {}",
+                            label,
+                            to.map_or("(none)", String::as_str),
+                            from.map_or("(none)", String::as_str)
+                        );
+                    }
+                }
+                if !same_roles(&source.roles, &target.roles) {
+                    let _ = writeln!(
+                        out,
+                        "      roles: {} → {}",
+                        target.roles.join(", "),
+                        source.roles.join(", ")
+                    );
+                }
+            }
+            PolicyChange::Rls { source, target } => {
+                let _ = writeln!(
+                    out,
+                    "  ~ row level security: {} → {}",
+                    rls_state(target),
+                    rls_state(source)
+                );
+            }
+        }
+    }
+
+    out
+}
+
+fn summary(p: &Policy) -> String {
+    format!("{}, {}, roles: {}", p.cmd, p.permissive, p.roles.join(", "))
+}
+
+fn same_roles(a: &[String], b: &[String]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+fn rls_state(t: &TableRls) -> &'static str {
+    match (t.enabled, t.forced) {
+        (false, _) => "disabled",
+        (true, false) => "enabled",
+        (true, true) => "enabled (forced)",
+    }
+}
+
+/// SQL that makes the target's RLS match the source
+pub fn reconcile_sql(changes: &[PolicyChange]) -> String {
+    let mut sql = String::new();
+
+    for change in changes {
+        match change {
+            PolicyChange::Missing(p) => {
+                let _ = writeln!(sql, "{}", create_policy_sql(p));
+            }
+            PolicyChange::Extra(p) => {
+                let _ = writeln!(sql, "{}", drop_policy_sql(p));
+            }
+            PolicyChange::Changed { source, target } => {
+                // Command and mode can't be altered, so recreate the policy
+                let _ = writeln!(sql, "{}", drop_policy_sql(target));
+                let _ = writeln!(sql, "{}", create_policy_sql(source));
+            }
+            PolicyChange::Rls { source, target } => {
+                let table = qualified(&source.schema, &source.table);
+                if source.enabled != target.enabled {
+                    let action = if source.enabled { "ENABLE" } else { "DISABLE" };
+                    let _ = writeln!(sql, "ALTER TABLE {} {} ROW LEVEL SECURITY;", table, action);
+                }
+                if source.forced != target.forced {
+                    let action = if source.forced { "FORCE" } else { "NO FORCE" };
+                    let _ = writeln!(sql, "ALTER TABLE {} {} ROW LEVEL SECURITY;", table, action);
+                }
+            }
+        }
+    }
+
+    sql
+}
+
+fn create_policy_sql(p: &Policy) -> String {
+    let roles: Vec<String> = p
+        .roles
+        .iter()
+        .map(|r| {
+            if r == "public" {
+                "PUBLIC".to_string()
+            } else {
+                quote_ident(r)
+            }
+        })
+        .collect();
+
+    let mut sql = format!(
+        "CREATE POLICY {} ON {} AS {} FOR {} TO {}",
+        quote_ident(&p.name),
+        qualified(&p.schema, &p.table),
+        p.permissive,
+        p.cmd,
+        roles.join(", ")
+    );
+    if let Some(using) = &p.using {
+        let _ = write!(sql, " USING ({})", using);
+    }
+    if let Some(check) = &p.with_check {
+        let _ = write!(sql, " WITH CHECK ({})", check);
+    }
+    sql.push(';');
+    sql
+}
+
+fn drop_policy_sql(p: &Policy) -> String {
+    format!(
+        "DROP POLICY IF EXISTS {} ON {};",
+        quote_ident(&p.name),
+        qualified(&p.schema, &p.table)
+    )
+}
+
+fn qualified(schema: &str, table: &str) -> String {
+    format!("{}.{}", quote_ident(schema), quote_ident(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(name: &str, using: &str) -> Policy {
+        Policy {
+            schema: "public".to_string(),
+            table: "todos".to_string(),
+            name: name.to_string(),
+            permissive: "PERMISSIVE".to_string(),
+            roles: vec!["authenticated".to_string()],
+            cmd: "SELECT".to_string(),
+            using: Some(using.to_string()),
+            with_check: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_and_reconcile_policies() {
+        let source = PolicySet {
+            policies: vec![
+                policy("read own", "(auth.uid() = user_id)"),
+                policy("new", "true"),
+            ],
+            tables: Vec::new(),
+        };
+        let target = PolicySet {
+            policies: vec![
+                policy("read own", "(auth.uid() = owner_id)"),
+                policy("stale", "true"),
+            ],
+            tables: Vec::new(),
+        };
+
+        let changes = diff_policies(&source, &target);
+        assert_eq!(changes.len(), 3);
+
+        let sql = reconcile_sql(&changes);
+        assert!(sql.contains(
+            "CREATE POLICY \"new\" ON \"public\".\"todos\" AS PERMISSIVE FOR SELECT TO \"authenticated\" USING (true);"
+        ));
+        assert!(sql.contains("DROP POLICY IF EXISTS \"stale\" ON \"public\".\"todos\";"));
+        assert!(sql.contains("USING ((auth.uid() = user_id))"));
+    }
+}
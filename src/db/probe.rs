@@ -0,0 +1,148 @@
+use super::native::NativeClient;
+use crate::config::ProjectConfig;
+use crate::error::{Result, SupamigrateError};
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Explanation appended to connection failures caused by the IPv6-only
+/// direct host
+const IPV6_HINT: &str = "The direct database host (db.<ref>.supabase.co) only resolves to IPv6 \
+     unless the project has the IPv4 add-on. Connect through the session pooler \
+     with `pooler = true` and `pooler_region` in the project config, or pass --pooler.";
+
+/// Check that something accepts TCP connections on `host:port`, trying
+/// every resolved address
+pub fn tcp_probe(host: &str, port: u16) -> io::Result<()> {
+    let mut last_error = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} did not resolve to any address", host),
+    );
+
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                debug!("Probe of {} failed: {}", addr, e);
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Database URL for a project, switching to the session pooler when the
+/// direct host can't be reached and `allow_fallback` is set. Projects
+/// already on the pooler or with a custom host are used as configured.
+pub fn connect_url(project: &ProjectConfig, allow_fallback: bool) -> Result<String> {
+    Ok(connect_project(project, allow_fallback)?.db_url())
+}
+
+/// Project settings to connect with, applying the same session pooler
+/// fallback as [`connect_url`]
+pub fn connect_project(project: &ProjectConfig, allow_fallback: bool) -> Result<ProjectConfig> {
+    if project.uses_pooler() || project.db_host.is_some() {
+        return Ok(project.clone());
+    }
+
+    let host = project.db_host();
+    let Err(e) = tcp_probe(&host, project.db_port()) else {
+        return Ok(project.clone());
+    };
+
+    if !allow_fallback {
+        return Err(SupamigrateError::Database(format!(
+            "Cannot reach {}: {}. {}",
+            host, e, IPV6_HINT
+        )));
+    }
+    if project.pooler_region.is_none() {
+        return Err(SupamigrateError::Database(format!(
+            "Cannot reach {}: {}. {} Set pooler_region to let supamigrate fall back automatically.",
+            host, e, IPV6_HINT
+        )));
+    }
+
+    let mut pooled = project.clone();
+    pooled.pooler = true;
+    warn!(
+        "Cannot reach {} ({}); falling back to the session pooler at {}",
+        host,
+        e,
+        pooled.db_host()
+    );
+    Ok(pooled)
+}
+
+/// Check that a database accepts connections and queries: TCP first, so an
+/// unreachable host fails fast, then `SELECT 1` over a native connection to
+/// verify credentials
+pub async fn check_database(db_url: &str) -> Result<()> {
+    let url = url::Url::parse(db_url)?;
+    let host = url.host_str().unwrap_or("localhost");
+    let port = url.port().unwrap_or(5432);
+
+    tokio::task::block_in_place(|| tcp_probe(host, port)).map_err(|e| {
+        SupamigrateError::Database(with_connection_hint(&format!(
+            "Cannot reach {}:{}: {}",
+            host, port, e
+        )))
+    })?;
+
+    NativeClient::connect(db_url)
+        .await?
+        .execute("SELECT 1")
+        .await?;
+    Ok(())
+}
+
+/// Add a hint to pg_dump/psql connection errors with a recognisable cause
+pub fn with_connection_hint(stderr: &str) -> String {
+    let unreachable = [
+        "No route to host",
+        "Network is unreachable",
+        "timeout expired",
+        "timed out",
+    ];
+    let hint = if unreachable.iter().any(|m| stderr.contains(m)) {
+        Some(IPV6_HINT)
+    } else if stderr.contains("password authentication failed") {
+        Some(
+            "Check db_password in the project config \
+             (Project Settings → Database → Reset database password).",
+        )
+    } else if stderr.contains("Tenant or user not found") {
+        Some("The pooler did not recognise the project; check project_ref and pooler_region.")
+    } else {
+        None
+    };
+
+    match hint {
+        Some(hint) => format!("{}\n{}", stderr.trim_end(), hint),
+        None => stderr.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_hint() {
+        let stderr = "pg_dump: error: connection to server at \"db.abc.supabase.co\" \
+                      (2600:1f18::1), port 5432 failed: No route to host\n";
+        assert!(with_connection_hint(stderr).contains("--pooler"));
+        assert!(with_connection_hint(
+            "FATAL:  password authentication failed for user \"postgres\""
+        )
+        .contains("db_password"));
+        assert_eq!(
+            with_connection_hint("permission denied"),
+            "permission denied"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const TICK: Duration = Duration::from_millis(200);
+
+/// Byte counter for a dump or restore, with throughput and elapsed time. With
+/// a `total` it's drawn as a bar, otherwise as a spinner.
+pub(super) fn bytes(message: &str, total: Option<u64>) -> ProgressBar {
+    let (pb, template) = match total {
+        Some(total) => (
+            ProgressBar::new(total),
+            "{spinner:.green} {msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, {elapsed})",
+        ),
+        None => (
+            ProgressBar::no_length(),
+            "{spinner:.green} {msg} {bytes} ({binary_bytes_per_sec}, {elapsed})",
+        ),
+    };
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message(message.to_string());
+    pb.enable_steady_tick(TICK);
+    pb
+}
+
+/// Statement counter for a psql restore, like "Applied 4,210/18,344
+/// statements"
+pub(super) fn statements(total: u64) -> ProgressBar {
+    let pb = ProgressBar::new(total);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} {msg} [{bar:40.cyan/blue}] {human_pos}/{human_len} statements ({elapsed})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message("Applied");
+    pb.enable_steady_tick(TICK);
+    pb
+}
+
+/// Table counter for a direct copy, with the table in progress as message
+pub(super) fn tables(total: u64) -> ProgressBar {
+    let pb = ProgressBar::new(total);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} tables {msg} ({elapsed})")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.enable_steady_tick(TICK);
+    pb
+}
+
+/// Polls the size of a file a child process writes (or of all files under a
+/// directory) into a progress bar, until dropped
+pub(super) struct Watch {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watch {
+    pub(super) fn start(path: &Path, pb: &ProgressBar) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = Arc::clone(&stop);
+            let path = path.to_path_buf();
+            let pb = pb.clone();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    pb.set_position(size(&path));
+                    std::thread::sleep(TICK);
+                }
+                pb.set_position(size(&path));
+            })
+        };
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Size of a file, or the total of the files under a directory
+fn size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .filter_map(std::result::Result::ok)
+            .map(|entry| size(&entry.path()))
+            .sum()
+    })
+}
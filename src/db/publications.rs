@@ -0,0 +1,110 @@
+use super::dump::PgDump;
+use super::native::NativeClient;
+use super::native_dump::Filter;
+use super::sql::{quote_ident, quote_literal};
+use crate::error::Result;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// Publication Supabase Realtime streams changes from
+pub const REALTIME_PUBLICATION: &str = "supabase_realtime";
+
+/// The publication's tables, and whether it publishes every table anyway
+const PUBLICATION_SQL: &str = r"
+SELECT json_build_object(
+    'exists', EXISTS (SELECT 1 FROM pg_publication WHERE pubname = {name}),
+    'all_tables', coalesce((SELECT puballtables FROM pg_publication WHERE pubname = {name}), false),
+    'tables', coalesce((
+        SELECT json_agg(json_build_array(schemaname, tablename) ORDER BY schemaname, tablename)
+        FROM pg_publication_tables WHERE pubname = {name}
+    ), '[]')
+)";
+
+/// Tables that exist, as `[schema, table]`
+pub(super) const TABLES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_array(n.nspname, c.relname)), '[]')
+FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relkind IN ('r', 'p')";
+
+#[derive(Debug, Deserialize)]
+struct Publication {
+    exists: bool,
+    all_tables: bool,
+    tables: BTreeSet<(String, String)>,
+}
+
+impl Publication {
+    async fn fetch(client: &NativeClient, name: &str) -> Result<Self> {
+        client
+            .query_json(&PUBLICATION_SQL.replace("{name}", &quote_literal(name)))
+            .await
+    }
+}
+
+/// How a publication on the target was brought in line with the source's
+#[derive(Debug, Default)]
+pub struct PublicationSync {
+    /// Tables added, as `schema.table`
+    pub added: Vec<String>,
+    /// Tables published on the source that the target doesn't have
+    pub missing: Vec<String>,
+    /// The target publishes every table already
+    pub all_tables: bool,
+}
+
+/// Add the tables `dump`'s source publishes in `name` to the same
+/// publication on `target_url`, creating it if needed. Tables are chosen with
+/// the same filters as `dump`; tables the target publishes besides are left
+/// alone.
+pub async fn sync_publication(
+    dump: &PgDump,
+    target_url: &str,
+    name: &str,
+) -> Result<PublicationSync> {
+    let filter = Filter::new(dump);
+    let source = NativeClient::connect(&dump.db_url).await?;
+    let published = Publication::fetch(&source, name).await?;
+
+    let client = NativeClient::connect(target_url).await?;
+    let target = Publication::fetch(&client, name).await?;
+    let mut sync = PublicationSync {
+        all_tables: target.all_tables,
+        ..PublicationSync::default()
+    };
+    if !published.exists || target.all_tables {
+        return Ok(sync);
+    }
+
+    let existing: BTreeSet<(String, String)> = client.query_json(TABLES_SQL).await?;
+    let mut tables = Vec::new();
+    for (schema, table) in &published.tables {
+        if !filter.relation(schema, table)
+            || target.tables.contains(&(schema.clone(), table.clone()))
+        {
+            continue;
+        }
+        if existing.contains(&(schema.clone(), table.clone())) {
+            tables.push(format!("{}.{}", quote_ident(schema), quote_ident(table)));
+            sync.added.push(format!("{}.{}", schema, table));
+        } else {
+            sync.missing.push(format!("{}.{}", schema, table));
+        }
+    }
+
+    let mut sql = Vec::new();
+    if !target.exists {
+        sql.push(format!("CREATE PUBLICATION {}", quote_ident(name)));
+    }
+    if !tables.is_empty() {
+        sql.push(format!(
+            "ALTER PUBLICATION {} ADD TABLE {}",
+            quote_ident(name),
+            tables.join(", ")
+        ));
+    }
+    if !sql.is_empty() {
+        client.execute(&sql.join(";\n")).await?;
+    }
+    Ok(sync)
+}
@@ -1,20 +1,84 @@
+use super::dump::PgDump;
+use super::native::{NativeClient, ResultSet};
+use super::native_dump::{self, CopyStats};
+use super::probe::with_connection_hint;
+use super::progress;
+use super::relay::{self, Relay};
+use super::retry::with_retry;
+use super::sql::quote_literal;
+use super::statements::{self, StatementScanner};
+use super::tools;
+use super::transform::{LineTransformer, Rewrite};
+use crate::config::display_name;
 use crate::error::{Result, SupamigrateError};
+use indicatif::{HumanCount, ProgressBar};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Output, Stdio};
 use tracing::{debug, info};
 
 pub struct PgRestore {
     db_url: String,
+    extra_args: Vec<String>,
+    session_settings: Vec<(&'static str, String)>,
+    atomic: bool,
 }
 
 impl PgRestore {
     pub fn new(db_url: String) -> Self {
-        Self { db_url }
+        Self {
+            db_url,
+            extra_args: Vec::new(),
+            session_settings: Vec::new(),
+            atomic: false,
+        }
+    }
+
+    /// Stop at the first error and restore in a single transaction, so a
+    /// failed restore leaves the target as it was
+    pub fn atomic(mut self, value: bool) -> Self {
+        self.atomic = value;
+        self
+    }
+
+    /// psql arguments for an atomic restore of a file or script
+    fn atomic_args(&self) -> &'static [&'static str] {
+        if self.atomic {
+            &["-v", "ON_ERROR_STOP=1", "--single-transaction"]
+        } else {
+            &[]
+        }
+    }
+
+    /// Settings applied to the restore's session (e.g. statement_timeout).
+    /// psql and pg_restore get them through PGOPTIONS; a dump's own header
+    /// resets the timeouts, so those resets are commented out.
+    pub fn session_settings(mut self, settings: Vec<(&'static str, String)>) -> Self {
+        self.session_settings = settings;
+        self
+    }
+
+    /// `SET` statements for the session settings, for where there is no
+    /// process to set PGOPTIONS on: written files and native connections
+    fn set_statements(&self) -> Vec<String> {
+        self.session_settings
+            .iter()
+            .map(|(name, value)| format!("SET {} = {};", name, quote_literal(value)))
+            .collect()
+    }
+
+    /// Additional psql arguments appended to restore commands
+    pub fn extra_args(mut self, args: Vec<String>) -> Self {
+        self.extra_args = args;
+        self
     }
 
     /// Check if psql is available
     pub fn check_available() -> Result<()> {
-        let output = Command::new("psql").arg("--version").output();
+        let output = tools::psql().arg("--version").output();
 
         match output {
             Ok(o) if o.status.success() => {
@@ -26,90 +90,760 @@ impl PgRestore {
         }
     }
 
-    /// Restore from SQL file
-    pub fn restore_from_file(&self, input_path: &Path) -> Result<()> {
-        Self::check_available()?;
+    /// The table of contents of a custom-format archive (`pg_restore --list`)
+    pub fn list_archive(archive: &Path) -> Result<String> {
+        let list = pg_restore(archive, &[])?
+            .arg("--list")
+            .arg(archive_arg(archive)?)
+            .output()
+            .map_err(|_| SupamigrateError::PgRestoreNotFound)?;
+        if !list.status.success() {
+            return Err(SupamigrateError::PgRestoreFailed(with_connection_hint(
+                &String::from_utf8_lossy(&list.stderr),
+            )));
+        }
+        Ok(String::from_utf8_lossy(&list.stdout).into_owned())
+    }
 
-        info!("Starting database restore from {}...", input_path.display());
+    /// Restore a custom-format archive with pg_restore, running `jobs`
+    /// connections in parallel. `filter` gets the archive's table of
+    /// contents (`pg_restore --list`) and returns it with the entries to skip
+    /// commented out.
+    pub fn restore_archive(
+        &self,
+        archive: &Path,
+        jobs: usize,
+        args: &[&str],
+        filter: impl Fn(&str) -> String,
+    ) -> Result<()> {
+        info!("Starting database restore from {}...", archive.display());
 
-        let mut cmd = Command::new("psql");
-        cmd.arg(&self.db_url)
-            .arg("--file")
-            .arg(input_path)
+        let toc = tempfile::NamedTempFile::new()?;
+        std::fs::write(toc.path(), filter(&Self::list_archive(archive)?))?;
+
+        let mut cmd = pg_restore(archive, &[toc.path()])?;
+        cmd.arg("--dbname")
+            .arg(&self.db_url)
+            .arg("--clean")
+            .arg("--if-exists");
+        // pg_restore can't spread a single transaction over several jobs
+        if self.atomic {
+            cmd.arg("--single-transaction").arg("--exit-on-error");
+        } else {
+            cmd.arg(format!("--jobs={}", jobs.max(1)));
+        }
+        cmd.args(args)
+            .arg("--use-list")
+            .arg(toc.path())
+            .arg(archive_arg(archive)?)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        // pg_restore has no -c, so session settings go in through
+        // PGOPTIONS. It resets the timeouts itself when it connects, so
+        // only the other settings hold.
+        self.apply_settings(&mut cmd);
 
         debug!(
-            "Running: psql {} --file {}",
-            &self.db_url,
-            input_path.display()
+            "Running: pg_restore --jobs {} {} into {}",
+            jobs,
+            archive.display(),
+            display_name(&self.db_url)
         );
 
-        let output = cmd.output()?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            // psql often returns warnings that aren't fatal
-            if stderr.contains("ERROR") {
-                return Err(SupamigrateError::PsqlFailed(stderr.to_string()));
-            }
-        }
+        self.attempt("pg_restore", || {
+            check_restore(&relay::output("pg_restore", &mut cmd)?).map_err(|e| match e {
+                SupamigrateError::PsqlFailed(stderr) => SupamigrateError::PgRestoreFailed(stderr),
+                other => other,
+            })
+        })?;
 
         info!("Database restore completed");
         Ok(())
     }
 
+    /// Run a restore, retried only when atomic: a failed atomic restore
+    /// rolled back, but a plain one may have applied part of its input by
+    /// the time a connection fails (pg_dump's after psql's, or a late
+    /// pg_restore worker's)
+    fn attempt<T>(&self, what: &str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        if self.atomic {
+            with_retry(what, f)
+        } else {
+            f()
+        }
+    }
+
+    /// Session settings as libpq `options` (`-c name=value ...`), with
+    /// backslashes and spaces in values escaped
+    fn pg_options(&self) -> String {
+        self.session_settings
+            .iter()
+            .map(|(name, value)| {
+                let value = value.replace('\\', "\\\\").replace(' ', "\\ ");
+                format!("-c {}={}", name, value)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Pass the session settings to `cmd` through PGOPTIONS, after any the
+    /// environment already has
+    fn apply_settings(&self, cmd: &mut std::process::Command) {
+        if self.session_settings.is_empty() {
+            return;
+        }
+        let options = std::env::var("PGOPTIONS")
+            .ok()
+            .filter(|options| !options.trim().is_empty())
+            .into_iter()
+            .chain(Some(self.pg_options()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        cmd.env("PGOPTIONS", options);
+    }
+
+    /// Comments out the `SET <setting> = 0;` lines of a dump's header for
+    /// the configured settings, which would reset them
+    fn settings_guard(&self) -> SettingsGuard<'_> {
+        SettingsGuard {
+            names: self
+                .session_settings
+                .iter()
+                .map(|(name, _)| *name)
+                .collect(),
+            in_header: true,
+        }
+    }
+
     /// Restore from SQL string
     pub fn restore_from_string(&self, sql: &str) -> Result<()> {
         Self::check_available()?;
 
         info!("Starting database restore...");
 
-        let mut cmd = Command::new("psql");
+        let mut cmd = tools::psql();
+        self.apply_settings(&mut cmd);
+        // Reading stdin as a file makes psql prefix errors with their line
         cmd.arg(&self.db_url)
+            .args(self.atomic_args())
+            .arg("--file=-")
+            .args(&self.extra_args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
+        let mut guard = self.settings_guard();
+        let script: Vec<Cow<str>> = sql.lines().map(|line| guard.line(line)).collect();
+        let script: Vec<&str> = script.iter().map(AsRef::as_ref).collect();
+        let mut counter = StatementScanner::new();
+        let total: usize = script.iter().map(|line| counter.line(line)).sum();
+
+        let pb = progress::statements(total as u64);
+        let result = self.attempt("psql", || {
+            pb.set_position(0);
+            let mut scanner = StatementScanner::new();
+            let mut child = cmd.spawn()?;
+            let output = Relay::start("psql", &mut child);
+
+            if let Some(stdin) = child.stdin.take() {
+                // psql exits early if it can't connect; that failure is in
+                // its output, not the broken pipe
+                if let Err(e) = feed(stdin, &script, &mut scanner, &pb) {
+                    if e.kind() != std::io::ErrorKind::BrokenPipe {
+                        return Err(e.into());
+                    }
+                }
+            }
+
+            check_restore(&output.wait(&mut child)?).map_err(|e| locate(e, &scanner, Some(&script)))
+        });
+        pb.finish_and_clear();
+        result?;
+
+        info!("Database restore completed");
+        Ok(())
+    }
+
+    /// Run a pre- or post-restore SQL file, stopping at its first error.
+    /// A failure names the file and the statement, with line numbers from
+    /// the file.
+    pub fn run_hook(&self, path: &Path) -> Result<()> {
+        Self::check_available()?;
+
+        let sql = std::fs::read_to_string(path).map_err(|e| {
+            SupamigrateError::Config(format!("Can't read {}: {}", path.display(), e))
+        })?;
+        let script: Vec<&str> = sql.lines().collect();
+
+        let mut cmd = tools::psql();
+        self.apply_settings(&mut cmd);
+        cmd.arg(&self.db_url)
+            .arg("-v")
+            .arg("ON_ERROR_STOP=1")
+            .arg("--file=-")
+            .args(&self.extra_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // Hooks aren't retried: they may have run in part, and needn't be
+        // safe to run twice
+        info!("Running {}...", path.display());
+        let mut scanner = StatementScanner::new();
         let mut child = cmd.spawn()?;
+        let output = Relay::start("psql", &mut child);
+        if let Some(stdin) = child.stdin.take() {
+            if let Err(e) = feed(stdin, &script, &mut scanner, &ProgressBar::hidden()) {
+                if e.kind() != std::io::ErrorKind::BrokenPipe {
+                    return Err(e.into());
+                }
+            }
+        }
+        let result = check_restore(&output.wait(&mut child)?)
+            .map_err(|e| locate(e, &scanner, Some(&script)));
+        // Name the file where psql says it read the script from
+        result.map_err(|e| match e {
+            SupamigrateError::PsqlFailed(message) => SupamigrateError::PsqlFailed(
+                message.replace("psql:<stdin>:", &format!("psql:{}:", path.display())),
+            ),
+            e => e,
+        })
+    }
 
-        if let Some(stdin) = child.stdin.as_mut() {
-            use std::io::Write;
-            stdin.write_all(sql.as_bytes())?;
+    /// Stream `dump` from pg_dump straight into psql, transforming it line by
+    /// line, so it is never held in memory or written to disk. Returns the
+    /// bytes fed to psql; `transformer` is left holding its changes.
+    pub fn restore_from_dump(
+        &self,
+        dump: &PgDump,
+        transformer: &mut LineTransformer,
+    ) -> Result<u64> {
+        Self::check_available()?;
+
+        let mut source = dump.source()?;
+        let mut psql_cmd = tools::psql();
+        self.apply_settings(&mut psql_cmd);
+        psql_cmd.arg(&self.db_url);
+        // An atomic restore opens its transaction in the stream and only
+        // commits once pg_dump finished, where --single-transaction would
+        // commit whatever arrived before a dump failure
+        if self.atomic {
+            psql_cmd.arg("-v").arg("ON_ERROR_STOP=1");
         }
+        psql_cmd
+            .arg("--file=-")
+            .args(&self.extra_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        // Filtered and masked rows are read up front and follow the
+        // streamed dump
+        let exported = dump.exported_rows()?;
 
-        let output = child.wait_with_output()?;
+        info!("Streaming database dump into restore...");
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("ERROR") {
-                return Err(SupamigrateError::PsqlFailed(stderr.to_string()));
+        let pb = progress::bytes("Streaming database", None);
+        let initial = transformer.clone();
+        let result = self.attempt("pg_dump | psql", || {
+            pb.set_position(0);
+            let mut scanner = StatementScanner::new();
+            let (output, mut dumper) = source.start()?;
+            let mut psql = psql_cmd.spawn()?;
+            // Drain stderr while streaming so psql doesn't block on a full
+            // pipe
+            let psql_stderr = relay::relay("psql", psql.stderr.take());
+
+            let mut stdin = psql.stdin.take();
+            let written = match stdin.as_mut() {
+                Some(sink) => {
+                    // Each attempt starts over, so the changes recorded
+                    // are those of the last one
+                    let mut attempt = initial.clone();
+                    let written =
+                        self.pipe(output, &exported, sink, &mut attempt, &mut scanner, &pb);
+                    *transformer = attempt;
+                    written
+                }
+                None => Ok(0),
+            };
+            let written = match written {
+                Ok(written) => written,
+                // psql exits early if it can't connect; that failure is in
+                // its output, not the broken pipe
+                Err(SupamigrateError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => 0,
+                Err(e) => {
+                    dumper.kill();
+                    let _ = psql.kill();
+                    return Err(e);
+                }
+            };
+
+            let dump_status = dumper.wait();
+            if let Some(mut sink) = stdin.take() {
+                if self.atomic && dump_status.is_ok() {
+                    // psql already stopped if the restore failed
+                    let _ = writeln!(sink, "COMMIT;");
+                }
             }
-        }
+            let psql_status = psql.wait()?;
+            // A failed psql also breaks pg_dump's pipe, so report it first
+            check_restore(&Output {
+                status: psql_status,
+                stdout: Vec::new(),
+                stderr: psql_stderr.join().unwrap_or_default(),
+            })
+            .map_err(|e| locate(e, &scanner, None))?;
+            dump_status?;
+            Ok(written)
+        });
+        pb.finish_and_clear();
+        let written = result?;
 
         info!("Database restore completed");
-        Ok(())
+        Ok(written)
+    }
+
+    /// Write `sql` to `path` as it would be fed to psql: after the session
+    /// settings, and in one transaction when atomic. Returns the bytes
+    /// written.
+    pub fn write_sql(&self, sql: &str, path: &Path) -> Result<u64> {
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        let mut written = 0;
+        let mut guard = self.settings_guard();
+        let header: Vec<String> = self
+            .set_statements()
+            .into_iter()
+            .chain(self.header())
+            .collect();
+        for line in header
+            .iter()
+            .map(|line| Cow::Borrowed(line.as_str()))
+            .chain(sql.lines().map(|line| guard.line(line)))
+            .chain(self.atomic.then_some(Cow::Borrowed("COMMIT;")))
+        {
+            writeln!(writer, "{}", line)?;
+            written += line.len() as u64 + 1;
+        }
+        writer.flush()?;
+        info!("Wrote transformed SQL to {}", path.display());
+        Ok(written)
+    }
+
+    /// Stream `dump` from pg_dump through `transformer` into the file at
+    /// `path` instead of psql, with what `restore_from_dump` would send
+    /// around it. Returns the bytes written.
+    pub fn write_dump(
+        &self,
+        dump: &PgDump,
+        transformer: &mut LineTransformer,
+        path: &Path,
+    ) -> Result<u64> {
+        let mut source = dump.source()?;
+        let exported = dump.exported_rows()?;
+
+        info!("Streaming database dump into {}...", path.display());
+
+        let pb = progress::bytes("Streaming database", None);
+        let initial = transformer.clone();
+        let result = with_retry("pg_dump", || {
+            pb.set_position(0);
+            let mut scanner = StatementScanner::new();
+            let (output, mut dumper) = source.start()?;
+
+            let mut file = std::fs::File::create(path)?;
+            // No psql to take PGOPTIONS, so the settings go in the file
+            let mut settings = 0;
+            for statement in self.set_statements() {
+                writeln!(file, "{}", statement)?;
+                settings += statement.len() as u64 + 1;
+            }
+            let mut attempt = initial.clone();
+            let written = self.pipe(
+                output,
+                &exported,
+                &mut file,
+                &mut attempt,
+                &mut scanner,
+                &pb,
+            );
+            *transformer = attempt;
+            let mut written = match written {
+                Ok(written) => settings + written,
+                Err(e) => {
+                    dumper.kill();
+                    return Err(e);
+                }
+            };
+
+            dumper.wait()?;
+            if self.atomic {
+                writeln!(file, "COMMIT;")?;
+                written += "COMMIT;".len() as u64 + 1;
+            }
+            Ok(written)
+        });
+        pb.finish_and_clear();
+        let written = result?;
+
+        info!("Wrote transformed SQL to {}", path.display());
+        Ok(written)
+    }
+
+    /// What goes before the dump: `BEGIN` when atomic
+    fn header(&self) -> Vec<String> {
+        self.atomic
+            .then(|| "BEGIN;".to_string())
+            .into_iter()
+            .collect()
     }
 
-    /// Execute a single SQL command
-    #[allow(dead_code)]
-    pub fn execute(&self, sql: &str) -> Result<String> {
+    /// Copy a dump and then `tail` into psql's stdin, counting bytes and
+    /// statements on `pb`
+    fn pipe(
+        &self,
+        source: impl Read,
+        tail: &str,
+        sink: impl Write,
+        transformer: &mut LineTransformer,
+        scanner: &mut StatementScanner,
+        pb: &ProgressBar,
+    ) -> Result<u64> {
+        debug!("Transform passes: {}", transformer.passes().join(", "));
+        let mut reader = BufReader::new(source);
+        let mut writer = BufWriter::new(pb.wrap_write(sink));
+        let mut written = 0;
+        let mut write_line = |writer: &mut BufWriter<_>, line: &str| {
+            writeln!(writer, "{}", line)?;
+            written += line.len() as u64 + 1;
+            if scanner.line(line) > 0 {
+                pb.set_message(format!(
+                    "Streaming database, {} statements",
+                    HumanCount(scanner.count() as u64)
+                ));
+            }
+            std::io::Result::Ok(())
+        };
+
+        let header = self.header().into_iter().chain(transformer.preamble());
+        for line in header {
+            write_line(&mut writer, &line)?;
+        }
+
+        let mut guard = self.settings_guard();
+        let mut buf = Vec::new();
+        while reader.read_until(b'\n', &mut buf)? > 0 {
+            let line = String::from_utf8_lossy(&buf);
+            let line = guard.line(line.trim_end_matches(['\n', '\r']));
+            for line in transformer.line(&line)? {
+                write_line(&mut writer, &line)?;
+            }
+            buf.clear();
+        }
+        for line in tail.lines() {
+            for line in transformer.line(line)? {
+                write_line(&mut writer, &line)?;
+            }
+        }
+        for line in transformer.finish() {
+            write_line(&mut writer, &line)?;
+        }
+
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Copy the rows of the tables `dump` selects straight from its database
+    /// into this one with COPY, without a dump file in between. The tables
+    /// must already exist on this side.
+    pub async fn copy_from(&self, dump: &PgDump, rewrites: &[Rewrite]) -> Result<CopyStats> {
+        info!("Copying table data from {}...", display_name(&dump.db_url));
+        let target = NativeClient::connect(&self.db_url).await?;
+        let settings = self.set_statements();
+        if !settings.is_empty() {
+            target.execute(&settings.join(" ")).await?;
+        }
+        native_dump::copy_data(dump, rewrites, &target).await
+    }
+
+    /// Empty the tables here whose rows `dump` carries, before loading it
+    /// with data only. Returns the tables truncated.
+    pub async fn truncate_for(&self, dump: &PgDump) -> Result<Vec<String>> {
+        info!("Truncating target tables...");
+        let target = NativeClient::connect(&self.db_url).await?;
+        native_dump::truncate_tables(dump, &target).await
+    }
+
+    /// Execute SQL over a native connection, without psql, returning a
+    /// result set per statement
+    pub async fn execute(&self, sql: &str) -> Result<Vec<ResultSet>> {
+        NativeClient::connect(&self.db_url)
+            .await?
+            .execute(sql)
+            .await
+    }
+
+    /// Execute a query and return unaligned, tuples-only output
+    pub fn query(&self, sql: &str) -> Result<String> {
         Self::check_available()?;
 
-        let mut cmd = Command::new("psql");
+        let mut cmd = tools::psql();
         cmd.arg(&self.db_url)
+            .arg("-t") // Tuples only (no headers)
+            .arg("-A") // Unaligned output
+            .arg("-v")
+            .arg("ON_ERROR_STOP=1")
             .arg("-c")
             .arg(sql)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        let output = cmd.output()?;
+        let output = with_retry("psql", || check_success(cmd.output()?))?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(SupamigrateError::PsqlFailed(stderr.to_string()));
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Run statements that change data exactly once, fed over stdin in a
+    /// single transaction so payloads of any size fit and a failure leaves
+    /// nothing behind. Unlike `query`, failures aren't retried. Returns
+    /// unaligned, tuples-only output.
+    pub fn apply(&self, sql: &str) -> Result<String> {
+        Self::check_available()?;
+
+        let mut cmd = tools::psql();
+        cmd.arg(&self.db_url)
+            .args([
+                "-q",
+                "-t",
+                "-A",
+                "-v",
+                "ON_ERROR_STOP=1",
+                "--single-transaction",
+            ])
+            .arg("--file=-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = cmd.spawn()?;
+        let output = Relay::start("psql", &mut child);
+        if let Some(mut stdin) = child.stdin.take() {
+            // psql exits early if it can't connect; that failure is in its
+            // output, not the broken pipe
+            if let Err(e) = stdin.write_all(sql.as_bytes()) {
+                if e.kind() != std::io::ErrorKind::BrokenPipe {
+                    return Err(e.into());
+                }
+            }
         }
+        let output = check_success(output.wait(&mut child)?)?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    /// Execute a query that returns a single JSON value
+    pub fn query_json<T: for<'de> Deserialize<'de>>(&self, sql: &str) -> Result<T> {
+        let output = self.query(sql)?;
+        if output.is_empty() {
+            return Err(SupamigrateError::Database("Empty response".to_string()));
+        }
+        serde_json::from_str(&output).map_err(|e| {
+            SupamigrateError::Database(format!("Failed to parse JSON: {} - Output: {}", e, output))
+        })
+    }
+
+    /// Names of the roles that exist on the database
+    pub fn role_names(&self) -> Result<BTreeSet<String>> {
+        Ok(self
+            .query("SELECT rolname FROM pg_roles")?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Run a query and return its rows as a JSON array of objects
+    pub async fn query_rows(&self, sql: &str) -> Result<serde_json::Value> {
+        let sql = sql.trim().trim_end_matches(';');
+        NativeClient::connect(&self.db_url)
+            .await?
+            .query_json(&format!(
+                "SELECT coalesce(json_agg(t), '[]'::json) FROM ({}) t",
+                sql
+            ))
+            .await
+    }
+}
+
+/// pg_restore from PATH. In Docker, `archive` and
+/// `files` are mounted at their absolute paths.
+fn pg_restore(archive: &Path, files: &[&Path]) -> Result<std::process::Command> {
+    if !tools::docker_enabled() {
+        return Ok(std::process::Command::new("pg_restore"));
+    }
+    let archive = archive.canonicalize()?;
+    let mounts: Vec<&Path> = std::iter::once(archive.as_path())
+        .chain(files.iter().copied())
+        .collect();
+    Ok(tools::docker("pg_restore", None, &mounts))
+}
+
+/// Archive path as pg_restore sees it: absolute when it runs in Docker
+fn archive_arg(archive: &Path) -> Result<std::path::PathBuf> {
+    if tools::docker_enabled() {
+        Ok(archive.canonicalize()?)
+    } else {
+        Ok(archive.to_path_buf())
+    }
+}
+
+/// Write script lines into psql, counting finished statements on `pb`
+fn feed(
+    sink: impl Write,
+    script: &[&str],
+    scanner: &mut StatementScanner,
+    pb: &ProgressBar,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(sink);
+    for line in script {
+        writeln!(writer, "{}", line)?;
+        pb.inc(scanner.line(line) as u64);
+    }
+    writer.flush()
+}
+
+/// Lines of a failed statement shown in the error
+const FAILED_STATEMENT_LINES: usize = 10;
+
+/// Point a failed restore at the statement psql reported its first error on,
+/// quoting it when the script is at hand
+fn locate(
+    error: SupamigrateError,
+    scanner: &StatementScanner,
+    script: Option<&[&str]>,
+) -> SupamigrateError {
+    let SupamigrateError::PsqlFailed(stderr) = error else {
+        return error;
+    };
+    let Some((number, (first, last))) =
+        statements::error_line(&stderr).and_then(|line| scanner.statement_at(line))
+    else {
+        return SupamigrateError::PsqlFailed(stderr);
+    };
+
+    let mut message = format!(
+        "{}\nFailed at statement {} (line {}{})",
+        stderr.trim_end(),
+        HumanCount(number as u64),
+        first,
+        if last > first {
+            format!("-{}", last)
+        } else {
+            String::new()
+        }
+    );
+    if let Some(lines) = script.and_then(|script| script.get(first - 1..last)) {
+        message.push_str(":\n");
+        for line in lines.iter().take(FAILED_STATEMENT_LINES) {
+            message.push_str(&line.chars().take(200).collect::<String>());
+            message.push('\n');
+        }
+        if lines.len() > FAILED_STATEMENT_LINES {
+            message.push_str("...\n");
+        }
+    }
+    SupamigrateError::PsqlFailed(message.trim_end().to_string())
+}
+
+/// Fail on any non-zero psql exit
+fn check_success(output: Output) -> Result<Output> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(SupamigrateError::PsqlFailed(with_connection_hint(&stderr)))
+    }
+}
+
+/// Fail a restore on errors in the script or a failed connection (exit code
+/// 2). psql often exits non-zero for warnings that aren't fatal.
+fn check_restore(output: &Output) -> Result<()> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("ERROR") || output.status.code() == Some(2) {
+        return Err(SupamigrateError::PsqlFailed(with_connection_hint(&stderr)));
+    }
+    Ok(())
+}
+
+/// Tracks the header of a dump (its leading `SET`s, comments and psql
+/// meta-commands such as `\restrict`) and
+/// comments out the lines there that set one of `names`, such as pg_dump's
+/// `SET statement_timeout = 0;`
+struct SettingsGuard<'a> {
+    names: Vec<&'a str>,
+    in_header: bool,
+}
+
+impl SettingsGuard<'_> {
+    fn line<'l>(&mut self, line: &'l str) -> Cow<'l, str> {
+        if !self.in_header || self.names.is_empty() {
+            return Cow::Borrowed(line);
+        }
+        let trimmed = line.trim();
+        let Some(setting) = trimmed.strip_prefix("SET ") else {
+            self.in_header = trimmed.is_empty()
+                || trimmed.starts_with("--")
+                || trimmed.starts_with('\\')
+                || trimmed.starts_with("SELECT pg_catalog.set_config(");
+            return Cow::Borrowed(line);
+        };
+        let name = setting.split([' ', '=']).next().unwrap_or_default();
+        if self.names.contains(&name) {
+            Cow::Owned(format!("-- {}", line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_guard() {
+        let restore = PgRestore::new(String::new())
+            .session_settings(vec![("statement_timeout", "5min".to_string())]);
+        let mut guard = restore.settings_guard();
+        let lines: Vec<_> = [
+            "-- PostgreSQL database dump",
+            "\\restrict abc",
+            "",
+            "SET statement_timeout = 0;",
+            "SET lock_timeout = 0;",
+            "CREATE TABLE t (id int);",
+            "SET statement_timeout = 0;",
+        ]
+        .into_iter()
+        .map(|line| guard.line(line).into_owned())
+        .collect();
+        assert_eq!(lines[3], "-- SET statement_timeout = 0;");
+        assert_eq!(lines[4], "SET lock_timeout = 0;");
+        assert_eq!(lines[6], "SET statement_timeout = 0;");
+        assert_eq!(restore.pg_options(), "-c statement_timeout=5min",);
+    }
+
+    #[test]
+    fn test_pg_options_escaping() {
+        let restore = PgRestore::new(String::new()).session_settings(vec![
+            ("search_path", "a b".to_string()),
+            ("application_name", "C:\\dumps\\x y".to_string()),
+        ]);
+        assert_eq!(
+            restore.pg_options(),
+            "-c search_path=a\\ b -c application_name=C:\\\\dumps\\\\x\\ y"
+        );
     }
 }
@@ -0,0 +1,164 @@
+use crate::error::{Result, SupamigrateError};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+/// Failures that happen before a session is established, so re-running the
+/// command can't apply anything twice
+const DEFAULT_RETRY_ON: &[&str] = &[
+    "could not translate host name",
+    "Temporary failure in name resolution",
+    "Connection refused",
+    "Connection timed out",
+    "timeout expired",
+    "No route to host",
+    "Network is unreachable",
+    "the database system is starting up",
+    "too many clients already",
+    "remaining connection slots are reserved",
+    "Max client connections reached",
+];
+
+static POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// How often pg_dump/psql launches are retried after transient connection
+/// failures, with exponential backoff
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff: Duration,
+    patterns: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(2), &[])
+    }
+}
+
+impl RetryPolicy {
+    /// `attempts` includes the first try; `extra_patterns` are matched
+    /// against error output in addition to the built-in connection errors
+    pub fn new(attempts: u32, backoff: Duration, extra_patterns: &[String]) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            backoff,
+            patterns: DEFAULT_RETRY_ON
+                .iter()
+                .map(|p| (*p).to_string())
+                .chain(extra_patterns.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// Only a failure to connect is retried: a pattern must be in the
+    /// tool's own error (`psql: error: connection to server ... failed`),
+    /// and no statement may have failed, which would show the session got
+    /// far enough to run something
+    fn is_retryable(&self, error: &SupamigrateError) -> bool {
+        match error {
+            SupamigrateError::PgDumpFailed(stderr)
+            | SupamigrateError::PsqlFailed(stderr)
+            | SupamigrateError::PgRestoreFailed(stderr) => {
+                !stderr.contains("ERROR:")
+                    && stderr
+                        .lines()
+                        .filter(|line| is_client_error(line))
+                        .any(|line| self.patterns.iter().any(|p| line.contains(p.as_str())))
+            }
+            _ => false,
+        }
+    }
+
+    /// Run `f`, retrying while it fails with a retryable error
+    pub fn run<T>(&self, what: &str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(e) if attempt < self.attempts && self.is_retryable(&e) => {
+                    warn!(
+                        "{} failed (attempt {}/{}), retrying in {}s: {}",
+                        what,
+                        attempt,
+                        self.attempts,
+                        backoff.as_secs(),
+                        e.to_string().lines().next().unwrap_or_default()
+                    );
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether `line` is an error of the client tool itself (`psql: error:
+/// ...`), rather than one reported for a statement of its script
+/// (`psql:<stdin>:12: ERROR: ...`)
+fn is_client_error(line: &str) -> bool {
+    ["psql", "pg_dump", "pg_dumpall", "pg_restore"]
+        .iter()
+        .any(|tool| {
+            line.strip_prefix(tool)
+                .is_some_and(|rest| rest.starts_with(": error:") || rest.starts_with(": could not"))
+        })
+}
+
+/// Set the policy used for all pg_dump/psql launches. Has no effect if one
+/// was already set.
+pub fn configure(policy: RetryPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+/// Run a pg_dump/psql launch under the configured retry policy
+pub fn with_retry<T>(what: &str, f: impl FnMut() -> Result<T>) -> Result<T> {
+    POLICY.get_or_init(RetryPolicy::default).run(what, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retries_connection_errors_only() {
+        let policy = RetryPolicy::new(3, Duration::ZERO, &[]);
+
+        let mut calls = 0;
+        let result = policy.run("psql", || {
+            calls += 1;
+            if calls < 3 {
+                Err(SupamigrateError::PsqlFailed(
+                    "psql: error: could not translate host name \"db.x.supabase.co\"".to_string(),
+                ))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<()> = policy.run("psql", || {
+            calls += 1;
+            Err(SupamigrateError::PsqlFailed(
+                "ERROR:  relation \"todos\" already exists".to_string(),
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+
+        // The script got to run, and its own connection failed
+        let mut calls = 0;
+        let result: Result<()> = policy.run("psql", || {
+            calls += 1;
+            Err(SupamigrateError::PsqlFailed(
+                "psql:<stdin>:40: ERROR:  could not establish connection\nDETAIL:  Connection refused"
+                    .to_string(),
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
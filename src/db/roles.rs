@@ -0,0 +1,257 @@
+use super::dump::{check_dump, find_compatible_pg_dump, get_server_version, glob_match};
+use super::relay;
+use super::retry::with_retry;
+use super::tools;
+use crate::cli::DumpEngine;
+use crate::error::Result;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::info;
+
+/// Roles every Supabase project has, created and owned by the platform
+const MANAGED_ROLES: &[&str] = &[
+    "anon",
+    "authenticated",
+    "authenticator",
+    "dashboard_user",
+    "pgbouncer",
+    "pgsodium_keyholder",
+    "pgsodium_keyiduser",
+    "pgsodium_keymaker",
+    "postgres",
+    "service_role",
+    "supabase_admin",
+    "supabase_auth_admin",
+    "supabase_etl_admin",
+    "supabase_functions_admin",
+    "supabase_read_only_user",
+    "supabase_realtime_admin",
+    "supabase_replication_admin",
+    "supabase_storage_admin",
+];
+
+/// Attributes only a superuser can grant, which the `postgres` role of a
+/// Supabase project isn't
+const SUPERUSER_ATTRIBUTES: &[&str] = &["SUPERUSER", "REPLICATION", "BYPASSRLS"];
+
+/// Custom roles of a database as SQL for the target, from
+/// `pg_dumpall --roles-only`
+#[derive(Debug, Default)]
+pub struct RoleDump {
+    pub sql: String,
+    /// Roles created or updated
+    pub roles: Vec<String>,
+    /// Roles that can log in. Passwords aren't dumped, so these need one set
+    /// on the target.
+    pub login_roles: Vec<String>,
+    /// `role: ATTRIBUTE` pairs dropped because the target can't grant them
+    pub dropped_attributes: Vec<String>,
+}
+
+impl RoleDump {
+    /// Dump the custom roles of `db_url`, leaving out Supabase-managed roles
+    /// and those matching `excluded` (wildcards allowed). For a Supabase
+    /// target, memberships in managed roles are kept, since those roles
+    /// already exist there, and superuser-only attributes are dropped.
+    pub fn fetch(
+        db_url: &str,
+        engine: DumpEngine,
+        excluded: &[String],
+        supabase_target: bool,
+    ) -> Result<Self> {
+        let mut cmd = Self::command(db_url, engine)?;
+        let output = with_retry("pg_dumpall", || {
+            check_dump(relay::output("pg_dumpall", &mut cmd)?)
+        })?;
+        let dump = Self::transform(
+            &String::from_utf8_lossy(&output.stdout),
+            excluded,
+            supabase_target,
+        );
+        info!("Dumped {} custom role(s)", dump.roles.len());
+        Ok(dump)
+    }
+
+    /// pg_dumpall next to the pg_dump that matches the server, or in the
+    /// matching postgres image
+    fn command(db_url: &str, engine: DumpEngine) -> Result<Command> {
+        let major = get_server_version(db_url);
+        let mut cmd = if engine == DumpEngine::Docker {
+            tools::docker("pg_dumpall", major, &[])
+        } else {
+            let pg_dump = match major {
+                Some(major) => find_compatible_pg_dump(major)?,
+                None => PathBuf::from("pg_dump"),
+            };
+            Command::new(
+                pg_dump.with_file_name(format!("pg_dumpall{}", std::env::consts::EXE_SUFFIX)),
+            )
+        };
+        // Supabase doesn't let postgres read pg_authid, so passwords stay behind
+        cmd.arg(format!("--dbname={}", db_url))
+            .arg("--roles-only")
+            .arg("--no-role-passwords")
+            .arg("--quote-all-identifiers");
+        Ok(cmd)
+    }
+
+    fn transform(dump: &str, excluded: &[String], supabase_target: bool) -> Self {
+        let custom = |role: &str| {
+            !MANAGED_ROLES.contains(&role)
+                && !role.starts_with("pg_")
+                && !excluded.iter().any(|pattern| glob_match(pattern, role))
+        };
+
+        let mut result = Self::default();
+        let mut sql = Vec::new();
+        for line in dump.lines() {
+            let idents = quoted_idents(line);
+            if line.starts_with("CREATE ROLE ") {
+                let Some(role) = idents.first().filter(|role| custom(role)) else {
+                    continue;
+                };
+                // Roles that already exist on the target are updated instead
+                sql.push(format!(
+                    "DO $$ BEGIN {} EXCEPTION WHEN duplicate_object THEN NULL; END $$;",
+                    line
+                ));
+                result.roles.push(role.clone());
+            } else if line.starts_with("ALTER ROLE ") || line.starts_with("COMMENT ON ROLE ") {
+                let Some(role) = idents.first().filter(|role| custom(role)) else {
+                    continue;
+                };
+                let mut line = line.to_string();
+                if line.contains(" WITH ") {
+                    let attributes = line.trim_end_matches(';').split_whitespace();
+                    if attributes.clone().any(|word| word == "LOGIN") {
+                        result.login_roles.push(role.clone());
+                    }
+                    if supabase_target {
+                        let kept: Vec<&str> = attributes
+                            .filter(|word| {
+                                let dropped = SUPERUSER_ATTRIBUTES.contains(word);
+                                if dropped {
+                                    result
+                                        .dropped_attributes
+                                        .push(format!("{}: {}", role, word));
+                                }
+                                !dropped
+                            })
+                            .collect();
+                        line = format!("{};", kept.join(" "));
+                    }
+                }
+                sql.push(line);
+            } else if line.starts_with("GRANT ") {
+                // GRANT "role" TO "member" [...] [GRANTED BY "grantor"]
+                let (Some(role), Some(member)) = (idents.first(), idents.get(1)) else {
+                    continue;
+                };
+                let keep = match (custom(role), custom(member)) {
+                    (true, true) => true,
+                    (false, false) => false,
+                    // Only a Supabase target has the managed side
+                    _ => supabase_target,
+                };
+                if keep {
+                    // The grantor may be a role the target user can't act as
+                    let line = match line.find(" GRANTED BY ") {
+                        Some(at) => format!("{};", &line[..at]),
+                        None => line.to_string(),
+                    };
+                    sql.push(line);
+                }
+            } else if !line.starts_with("ALTER ") && !line.starts_with("COMMENT ") {
+                // SET statements, comments and blank lines
+                sql.push(line.to_string());
+            }
+        }
+
+        if !result.roles.is_empty() {
+            result.sql = sql.join("\n") + "\n";
+        }
+        result
+    }
+}
+
+/// Double-quoted identifiers in `line`, unescaped, in order
+fn quoted_idents(line: &str) -> Vec<String> {
+    let mut idents = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut ident = String::new();
+        while let Some(c) = chars.next() {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    ident.push('"');
+                } else {
+                    break;
+                }
+            } else {
+                ident.push(c);
+            }
+        }
+        idents.push(ident);
+    }
+    idents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = r#"--
+-- Roles
+--
+
+SET default_transaction_read_only = off;
+
+CREATE ROLE "anon";
+ALTER ROLE "anon" WITH NOSUPERUSER INHERIT NOCREATEROLE NOCREATEDB NOLOGIN NOREPLICATION NOBYPASSRLS;
+CREATE ROLE "app_user";
+ALTER ROLE "app_user" WITH NOSUPERUSER INHERIT NOCREATEROLE NOCREATEDB LOGIN NOREPLICATION BYPASSRLS;
+ALTER ROLE "app_user" SET "search_path" TO 'app', 'public';
+CREATE ROLE "reporting";
+ALTER ROLE "reporting" WITH NOSUPERUSER INHERIT NOCREATEROLE NOCREATEDB NOLOGIN NOREPLICATION NOBYPASSRLS;
+COMMENT ON ROLE "reporting" IS 'Read-only dashboards';
+CREATE ROLE "temp_ci";
+ALTER ROLE "temp_ci" WITH NOSUPERUSER INHERIT NOCREATEROLE NOCREATEDB LOGIN NOREPLICATION NOBYPASSRLS;
+
+GRANT "reporting" TO "app_user" WITH INHERIT TRUE GRANTED BY "supabase_admin";
+GRANT "reporting" TO "authenticator" GRANTED BY "postgres";
+GRANT "anon" TO "authenticator" GRANTED BY "supabase_admin";
+"#;
+
+    #[test]
+    fn test_transform_roles() {
+        let excluded = vec!["temp_*".to_string()];
+        let roles = RoleDump::transform(DUMP, &excluded, true);
+
+        assert_eq!(roles.roles, vec!["app_user", "reporting"]);
+        assert_eq!(roles.login_roles, vec!["app_user"]);
+        assert_eq!(roles.dropped_attributes, vec!["app_user: BYPASSRLS"]);
+        assert!(!roles.sql.contains("\"anon\" WITH"));
+        assert!(!roles.sql.contains("temp_ci"));
+        assert!(roles.sql.contains(
+            "DO $$ BEGIN CREATE ROLE \"app_user\"; EXCEPTION WHEN duplicate_object THEN NULL; END $$;"
+        ));
+        assert!(roles.sql.contains("LOGIN NOREPLICATION;"));
+        assert!(roles.sql.contains("COMMENT ON ROLE \"reporting\""));
+        assert!(roles
+            .sql
+            .contains("GRANT \"reporting\" TO \"app_user\" WITH INHERIT TRUE;\n"));
+        assert!(roles
+            .sql
+            .contains("GRANT \"reporting\" TO \"authenticator\";"));
+        assert!(!roles.sql.contains("GRANT \"anon\""));
+
+        // A plain Postgres target has no authenticator to grant to
+        let plain = RoleDump::transform(DUMP, &excluded, false);
+        assert!(!plain.sql.contains("authenticator"));
+        assert!(plain.sql.contains("LOGIN NOREPLICATION BYPASSRLS;"));
+    }
+}
@@ -0,0 +1,232 @@
+use super::dump::{glob_match, qualify_pattern};
+use super::restore::PgRestore;
+use crate::error::Result;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+const TABLES_SQL: &str = r"
+SELECT coalesce(json_agg(row_to_json(t) ORDER BY t.schemaname, t.tablename), '[]')
+FROM (
+    SELECT n.nspname AS schemaname, c.relname AS tablename,
+           coalesce((
+               SELECT json_agg(json_build_object(
+                          'name', a.attname,
+                          'data_type', format_type(a.atttypid, a.atttypmod),
+                          'not_null', a.attnotnull,
+                          'has_default', a.atthasdef
+                      ) ORDER BY a.attnum)
+               FROM pg_attribute a
+               WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+           ), '[]') AS columns,
+           coalesce((
+               SELECT json_agg(pg_get_constraintdef(co.oid) ORDER BY 1)
+               FROM pg_constraint co
+               WHERE co.conrelid = c.oid
+           ), '[]') AS constraints
+    FROM pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.relkind IN ('r', 'p')
+      AND n.nspname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
+) t";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ColumnDef {
+    pub name: String,
+    pub data_type: String,
+    pub not_null: bool,
+    pub has_default: bool,
+}
+
+/// Columns and constraint definitions of one table
+#[derive(Debug, Clone, Deserialize)]
+pub struct TableDef {
+    #[serde(rename = "schemaname")]
+    pub schema: String,
+    #[serde(rename = "tablename")]
+    pub table: String,
+    pub columns: Vec<ColumnDef>,
+    pub constraints: Vec<String>,
+}
+
+impl TableDef {
+    fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema, self.table)
+    }
+}
+
+/// Table definitions of a database, used to detect drift before data-only loads
+#[derive(Debug, Default)]
+pub struct SchemaSnapshot {
+    pub tables: Vec<TableDef>,
+}
+
+impl SchemaSnapshot {
+    /// Read table definitions, skipping excluded schemas and tables
+    pub fn fetch(
+        db_url: &str,
+        excluded_schemas: &[String],
+        excluded_tables: &[String],
+    ) -> Result<Self> {
+        let tables: Vec<TableDef> = PgRestore::new(db_url.to_string()).query_json(TABLES_SQL)?;
+        let table_patterns: Vec<String> =
+            excluded_tables.iter().map(|p| qualify_pattern(p)).collect();
+
+        let tables = tables
+            .into_iter()
+            .filter(|t| !excluded_schemas.iter().any(|p| glob_match(p, &t.schema)))
+            .filter(|t| {
+                let name = t.qualified_name();
+                !table_patterns.iter().any(|p| glob_match(p, &name))
+            })
+            .collect();
+
+        Ok(Self { tables })
+    }
+
+    /// Short digest of each table's columns and constraints, keyed by
+    /// qualified name, to tell later whether a table changed
+    pub fn fingerprints(&self) -> BTreeMap<String, String> {
+        self.tables
+            .iter()
+            .map(|t| {
+                let mut hasher = Sha256::new();
+                for column in &t.columns {
+                    hasher.update(format!(
+                        "{} {} {} {}\n",
+                        column.name, column.data_type, column.not_null, column.has_default
+                    ));
+                }
+                for constraint in &t.constraints {
+                    hasher.update(format!("{}\n", constraint));
+                }
+                let digest = format!("{:x}", hasher.finalize());
+                (t.qualified_name(), digest[..16].to_string())
+            })
+            .collect()
+    }
+}
+
+/// Compare the tables that would be loaded from `source` with their
+/// counterparts in `target`. Returns one line per difference.
+pub fn schema_drift(source: &SchemaSnapshot, target: &SchemaSnapshot) -> Vec<String> {
+    let target_tables: BTreeMap<String, &TableDef> = target
+        .tables
+        .iter()
+        .map(|t| (t.qualified_name(), t))
+        .collect();
+
+    let mut drift = Vec::new();
+
+    for table in &source.tables {
+        let name = table.qualified_name();
+        let Some(existing) = target_tables.get(&name) else {
+            drift.push(format!("{}: table missing on target", name));
+            continue;
+        };
+
+        let target_columns: BTreeMap<&str, &ColumnDef> = existing
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c))
+            .collect();
+
+        for column in &table.columns {
+            match target_columns.get(column.name.as_str()) {
+                None => drift.push(format!(
+                    "{}.{}: column missing on target",
+                    name, column.name
+                )),
+                Some(other) if other.data_type != column.data_type => drift.push(format!(
+                    "{}.{}: type {} on source, {} on target",
+                    name, column.name, column.data_type, other.data_type
+                )),
+                Some(other) if other.not_null && !column.not_null => drift.push(format!(
+                    "{}.{}: NOT NULL on target but nullable on source",
+                    name, column.name
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for column in &existing.columns {
+            let on_source = table.columns.iter().any(|c| c.name == column.name);
+            // Extra target columns only break the load if rows can't omit them
+            if !on_source && column.not_null && !column.has_default {
+                drift.push(format!(
+                    "{}.{}: NOT NULL column without default exists only on target",
+                    name, column.name
+                ));
+            }
+        }
+
+        for constraint in &table.constraints {
+            if !existing.constraints.contains(constraint) {
+                drift.push(format!(
+                    "{}: constraint missing on target: {}",
+                    name, constraint
+                ));
+            }
+        }
+        for constraint in &existing.constraints {
+            if !table.constraints.contains(constraint) {
+                drift.push(format!(
+                    "{}: constraint only on target: {}",
+                    name, constraint
+                ));
+            }
+        }
+    }
+
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, not_null: bool) -> ColumnDef {
+        ColumnDef {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            not_null,
+            has_default: false,
+        }
+    }
+
+    fn table(columns: Vec<ColumnDef>, constraints: &[&str]) -> SchemaSnapshot {
+        SchemaSnapshot {
+            tables: vec![TableDef {
+                schema: "public".to_string(),
+                table: "orders".to_string(),
+                columns,
+                constraints: constraints.iter().map(ToString::to_string).collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_schema_drift() {
+        let source = table(
+            vec![column("id", "bigint", true), column("note", "text", false)],
+            &["PRIMARY KEY (id)"],
+        );
+        let same = table(
+            vec![column("id", "bigint", true), column("note", "text", false)],
+            &["PRIMARY KEY (id)"],
+        );
+        assert!(schema_drift(&source, &same).is_empty());
+
+        let drifted = table(
+            vec![
+                column("id", "integer", true),
+                column("note", "text", true),
+                column("tenant", "uuid", true),
+            ],
+            &[],
+        );
+        let drift = schema_drift(&source, &drifted);
+        assert_eq!(drift.len(), 4);
+        assert!(drift[0].contains("type bigint on source, integer on target"));
+    }
+}
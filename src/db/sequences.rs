@@ -0,0 +1,148 @@
+use super::dump::PgDump;
+use super::native::NativeClient;
+use super::native_dump::Filter;
+use super::sql::quote_literal;
+use crate::error::Result;
+use serde::Deserialize;
+
+/// Sequences owned by a table column (serial and identity columns)
+const OWNED_SEQUENCES_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'sequence', format('%I.%I', sn.nspname, s.relname),
+    'schema', tn.nspname,
+    'table', t.relname,
+    'column', a.attname,
+    'max_sql', format('SELECT max(%I)::bigint FROM %I.%I', a.attname, tn.nspname, t.relname),
+    'last_value', ps.last_value
+) ORDER BY sn.nspname, s.relname), '[]')
+FROM pg_class s
+JOIN pg_namespace sn ON sn.oid = s.relnamespace
+JOIN pg_depend d ON d.objid = s.oid
+    AND d.classid = 'pg_class'::regclass
+    AND d.refclassid = 'pg_class'::regclass
+    AND d.deptype IN ('a', 'i')
+JOIN pg_class t ON t.oid = d.refobjid
+JOIN pg_namespace tn ON tn.oid = t.relnamespace
+JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = d.refobjsubid
+JOIN pg_sequences ps ON ps.schemaname = sn.nspname AND ps.sequencename = s.relname
+WHERE s.relkind = 'S'";
+
+#[derive(Debug, Deserialize)]
+struct OwnedSequence {
+    sequence: String,
+    schema: String,
+    table: String,
+    column: String,
+    max_sql: String,
+    /// Unset until the sequence is first used
+    last_value: Option<i64>,
+}
+
+/// A sequence moved forward to the highest value in its column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceAdjustment {
+    pub sequence: String,
+    /// Owning column, as `schema.table.column`
+    pub column: String,
+    pub previous: Option<i64>,
+    /// The column's maximum; the next value handed out is one higher
+    pub value: i64,
+}
+
+/// Move every sequence owned by a migrated table on `target_url` past the
+/// highest value in its column. Rows loaded with explicit ids leave
+/// sequences behind, so the next insert would hit a duplicate key. Tables are
+/// chosen with the same filters as `dump`; sequences already ahead are left
+/// alone.
+pub async fn sync_sequences(target_url: &str, dump: &PgDump) -> Result<Vec<SequenceAdjustment>> {
+    let client = NativeClient::connect(target_url).await?;
+    let filter = Filter::new(dump);
+    let sequences: Vec<OwnedSequence> = client
+        .query_json::<Vec<OwnedSequence>>(OWNED_SEQUENCES_SQL)
+        .await?
+        .into_iter()
+        .filter(|s| filter.relation(&s.schema, &s.table))
+        .collect();
+    if sequences.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let max_sql: Vec<&str> = sequences.iter().map(|s| s.max_sql.as_str()).collect();
+    let maxima = client.execute(&max_sql.join(";\n")).await?;
+
+    let mut adjustments = Vec::new();
+    for (sequence, result) in sequences.iter().zip(maxima) {
+        let max = result
+            .rows
+            .first()
+            .and_then(|row| row.first().cloned().flatten())
+            .and_then(|value| value.parse::<i64>().ok());
+        if let Some(adjustment) = adjustment(sequence, max) {
+            adjustments.push(adjustment);
+        }
+    }
+
+    if !adjustments.is_empty() {
+        let setval: Vec<String> = adjustments
+            .iter()
+            .map(|a| {
+                format!(
+                    "SELECT setval({}, {}, true)",
+                    quote_literal(&a.sequence),
+                    a.value
+                )
+            })
+            .collect();
+        client.execute(&setval.join(";\n")).await?;
+    }
+    Ok(adjustments)
+}
+
+/// The adjustment `sequence` needs when its column's maximum is `max`, if
+/// it's behind. Empty tables need none.
+fn adjustment(sequence: &OwnedSequence, max: Option<i64>) -> Option<SequenceAdjustment> {
+    let max = max?;
+    if sequence.last_value.is_some_and(|last| last >= max) {
+        return None;
+    }
+    Some(SequenceAdjustment {
+        sequence: sequence.sequence.clone(),
+        column: format!("{}.{}.{}", sequence.schema, sequence.table, sequence.column),
+        previous: sequence.last_value,
+        value: max,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_adjustment() {
+        let sequence = OwnedSequence {
+            sequence: "public.orders_id_seq".to_string(),
+            schema: "public".to_string(),
+            table: "orders".to_string(),
+            column: "id".to_string(),
+            max_sql: String::new(),
+            last_value: Some(10),
+        };
+        assert_eq!(adjustment(&sequence, None), None);
+        assert_eq!(adjustment(&sequence, Some(10)), None);
+        assert_eq!(
+            adjustment(&sequence, Some(42)),
+            Some(SequenceAdjustment {
+                sequence: "public.orders_id_seq".to_string(),
+                column: "public.orders.id".to_string(),
+                previous: Some(10),
+                value: 42,
+            })
+        );
+
+        let unused = OwnedSequence {
+            last_value: None,
+            ..sequence
+        };
+        assert_eq!(adjustment(&unused, Some(1)).unwrap().previous, None);
+    }
+}
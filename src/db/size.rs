@@ -0,0 +1,101 @@
+use super::dump::PgDump;
+use super::native::NativeClient;
+use super::native_dump::Filter;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const SIZE_SQL: &str = r"
+SELECT json_build_object(
+    'database_bytes', pg_database_size(current_database()),
+    'tables', coalesce((
+        SELECT json_agg(json_build_object(
+            'schema', n.nspname, 'name', c.relname, 'bytes', pg_table_size(c.oid)))
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.relkind IN ('r', 'p')
+          AND n.nspname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
+    ), '[]'))";
+
+#[derive(Debug, Deserialize)]
+struct TableSize {
+    schema: String,
+    name: String,
+    /// Heap and TOAST, without indexes, which a dump doesn't carry
+    bytes: u64,
+}
+
+/// Tables and data a dump would carry from one schema
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SchemaSize {
+    pub tables: usize,
+    pub bytes: u64,
+}
+
+/// On-disk sizes of a database and its tables, for dry-run estimates
+#[derive(Debug, Deserialize)]
+pub struct DatabaseSize {
+    pub database_bytes: u64,
+    tables: Vec<TableSize>,
+}
+
+impl DatabaseSize {
+    /// Sizes of the database `dump` reads from
+    pub async fn fetch(dump: &PgDump) -> Result<Self> {
+        NativeClient::connect(&dump.db_url)
+            .await?
+            .query_json(SIZE_SQL)
+            .await
+    }
+
+    /// Tables and table data `dump` would export, by schema. Tables whose
+    /// data is excluded count without their bytes.
+    pub fn by_schema(&self, dump: &PgDump) -> BTreeMap<String, SchemaSize> {
+        let filter = Filter::new(dump);
+        let mut schemas: BTreeMap<String, SchemaSize> = BTreeMap::new();
+        for table in &self.tables {
+            if !filter.relation(&table.schema, &table.name) {
+                continue;
+            }
+            let size = schemas.entry(table.schema.clone()).or_default();
+            size.tables += 1;
+            if !dump.schema_only && filter.table_data(&table.schema, &table.name) {
+                size.bytes += table.bytes;
+            }
+        }
+        schemas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_by_schema() {
+        let size: DatabaseSize = serde_json::from_value(serde_json::json!({
+            "database_bytes": 5000,
+            "tables": [
+                {"schema": "public", "name": "orders", "bytes": 1000},
+                {"schema": "public", "name": "audit_log", "bytes": 2000},
+                {"schema": "auth", "name": "users", "bytes": 300},
+                {"schema": "storage", "name": "objects", "bytes": 400},
+            ],
+        }))
+        .unwrap();
+        let dump = PgDump::new(String::new())
+            .exclude_schemas(vec!["storage".to_string()])
+            .exclude_table_data(vec!["audit_*".to_string()]);
+
+        let schemas = size.by_schema(&dump);
+        assert_eq!(schemas.len(), 2);
+        assert_eq!(
+            schemas["public"],
+            SchemaSize {
+                tables: 2,
+                bytes: 1000
+            }
+        );
+        assert_eq!(schemas["auth"].bytes, 300);
+    }
+}
@@ -0,0 +1,20 @@
+/// `ident` as a quoted identifier, with embedded quotes doubled
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// `value` as a string literal, with embedded quotes doubled
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote_ident("my \"table\""), "\"my \"\"table\"\"\"");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+}
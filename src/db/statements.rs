@@ -0,0 +1,257 @@
+/// Lexer state carried from one line to the next
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Normal,
+    SingleQuote,
+    DoubleQuote,
+    /// Inside `$tag$ ... $tag$`
+    Dollar(String),
+    /// Nesting depth of `/* ... */`
+    BlockComment(u32),
+    /// Data lines of `COPY ... FROM stdin`, up to `\.`
+    CopyData,
+}
+
+/// Finds where the SQL statements of a script start and end, one line at a
+/// time, following psql's rules closely enough for pg_dump output: quotes,
+/// dollar quotes, comments, `COPY` data and `BEGIN ATOMIC` bodies don't end
+/// a statement. Lines are numbered from 1, like psql's error messages.
+#[derive(Debug, Clone)]
+pub(crate) struct StatementScanner {
+    state: State,
+    lineno: usize,
+    /// First line of the statement in progress
+    start: Option<usize>,
+    first_word: Option<String>,
+    /// Open `BEGIN ATOMIC` and `CASE` blocks of a `CREATE` statement
+    depth: u32,
+    /// (first, last) line of each finished statement
+    spans: Vec<(usize, usize)>,
+}
+
+impl StatementScanner {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Normal,
+            lineno: 0,
+            start: None,
+            first_word: None,
+            depth: 0,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Scan one line (without its line ending), returning how many statements
+    /// it finished
+    pub(crate) fn line(&mut self, line: &str) -> usize {
+        self.lineno += 1;
+        let finished = self.spans.len();
+
+        if self.state == State::CopyData {
+            if line == "\\." {
+                self.state = State::Normal;
+                // Errors in the data are reported on the COPY
+                if let Some(span) = self.spans.last_mut() {
+                    span.1 = self.lineno;
+                }
+            }
+            return 0;
+        }
+        // psql meta-commands (pg_dump writes \restrict and \connect) aren't
+        // statements
+        if self.start.is_none() && self.state == State::Normal && line.starts_with('\\') {
+            return 0;
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match &mut self.state {
+                State::SingleQuote if c == '\'' => self.state = State::Normal,
+                State::DoubleQuote if c == '"' => self.state = State::Normal,
+                State::SingleQuote | State::DoubleQuote | State::CopyData => {}
+                State::Dollar(tag) => {
+                    if c == '$' && line[byte_offset(&chars, i)..].starts_with(tag.as_str()) {
+                        i += tag.chars().count() - 1;
+                        self.state = State::Normal;
+                    }
+                }
+                State::BlockComment(depth) => {
+                    if c == '*' && chars.get(i + 1) == Some(&'/') {
+                        i += 1;
+                        if *depth == 1 {
+                            self.state = State::Normal;
+                        } else {
+                            *depth -= 1;
+                        }
+                    } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+                        i += 1;
+                        *depth += 1;
+                    }
+                }
+                State::Normal => {
+                    if c == '-' && chars.get(i + 1) == Some(&'-') {
+                        break;
+                    }
+                    if c == '/' && chars.get(i + 1) == Some(&'*') {
+                        i += 1;
+                        self.state = State::BlockComment(1);
+                    } else if c == ';' {
+                        self.begin();
+                        if self.depth == 0 {
+                            self.finish(line);
+                        }
+                    } else if !c.is_whitespace() {
+                        self.begin();
+                        match c {
+                            '\'' => self.state = State::SingleQuote,
+                            '"' => self.state = State::DoubleQuote,
+                            '$' => {
+                                if let Some(tag) = dollar_tag(&chars[i..]) {
+                                    i += tag.chars().count() - 1;
+                                    self.state = State::Dollar(tag);
+                                }
+                            }
+                            c if c.is_alphabetic() || c == '_' => {
+                                let end = (i..chars.len())
+                                    .find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'))
+                                    .unwrap_or(chars.len());
+                                let word: String = chars[i..end].iter().collect();
+                                self.word(&word.to_uppercase());
+                                i = end - 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        self.spans.len() - finished
+    }
+
+    /// Statements finished so far
+    pub(crate) fn count(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Whether a statement started and hasn't ended yet
+    pub(crate) fn in_statement(&self) -> bool {
+        self.start.is_some()
+    }
+
+    /// Whether the next line is a row of a `COPY ... FROM stdin`
+    pub(crate) fn in_copy_data(&self) -> bool {
+        self.state == State::CopyData
+    }
+
+    /// Number (from 1) and line span of the statement that psql was running
+    /// at `line`, the line number psql puts in front of an error
+    pub(crate) fn statement_at(&self, line: usize) -> Option<(usize, (usize, usize))> {
+        let index = self.spans.partition_point(|&(_, last)| last < line);
+        self.spans
+            .get(index)
+            .filter(|&&(first, _)| first <= line)
+            .map(|&span| (index + 1, span))
+    }
+
+    fn begin(&mut self) {
+        if self.start.is_none() {
+            self.start = Some(self.lineno);
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        let Some(first) = &self.first_word else {
+            self.first_word = Some(word.to_string());
+            return;
+        };
+        match word {
+            "BEGIN" if first == "CREATE" => self.depth += 1,
+            "CASE" if self.depth > 0 => self.depth += 1,
+            "END" if self.depth > 0 => self.depth -= 1,
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self, line: &str) {
+        let start = self.start.take().unwrap_or(self.lineno);
+        self.spans.push((start, self.lineno));
+        self.depth = 0;
+        if self.first_word.take().as_deref() == Some("COPY")
+            && line.to_uppercase().contains("FROM STDIN")
+        {
+            self.state = State::CopyData;
+        }
+    }
+}
+
+/// `$tag$` at the start of `chars`, if it is one
+fn dollar_tag(chars: &[char]) -> Option<String> {
+    let end = chars[1..].iter().position(|&c| c == '$')? + 1;
+    let name = &chars[1..end];
+    let valid = name
+        .iter()
+        .enumerate()
+        .all(|(i, &c)| c == '_' || c.is_alphabetic() || (i > 0 && c.is_ascii_digit()));
+    valid.then(|| chars[..=end].iter().collect())
+}
+
+fn byte_offset(chars: &[char], index: usize) -> usize {
+    chars[..index].iter().map(|c| c.len_utf8()).sum()
+}
+
+/// Line psql reported its first error on, from `psql:<stdin>:LINE: ERROR:`
+pub(crate) fn error_line(stderr: &str) -> Option<usize> {
+    stderr.lines().find_map(|line| {
+        let rest = line.strip_prefix("psql:<stdin>:")?;
+        let (number, message) = rest.split_once(':')?;
+        message
+            .trim_start()
+            .starts_with("ERROR")
+            .then(|| number.parse().ok())
+            .flatten()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement_scanner() {
+        let script = "SET statement_timeout = 0;\n\
+            -- comment; not a statement\n\
+            CREATE FUNCTION \"public\".\"f\"() RETURNS integer\n\
+            \x20   LANGUAGE plpgsql\n\
+            \x20   AS $_$ BEGIN RETURN 1; END; $_$;\n\
+            COPY \"public\".\"t\" (\"a\") FROM stdin;\n\
+            a;b\n\
+            \\.\n\
+            CREATE FUNCTION \"public\".\"g\"() RETURNS integer\n\
+            \x20   BEGIN ATOMIC\n\
+            \x20SELECT CASE WHEN true THEN 1 END;\n\
+            END;\n\
+            \\unrestrict abc\n\
+            INSERT INTO \"t\" VALUES ('x;''y'); /* ; */ SELECT 1;";
+
+        let mut scanner = StatementScanner::new();
+        let counts: Vec<usize> = script.lines().map(|line| scanner.line(line)).collect();
+        assert_eq!(counts, vec![1, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 1, 0, 2]);
+        assert_eq!(scanner.count(), 6);
+        assert_eq!(scanner.statement_at(5), Some((2, (3, 5))));
+        assert_eq!(scanner.statement_at(12), Some((4, (9, 12))));
+        assert_eq!(scanner.statement_at(7), Some((3, (6, 8))));
+        assert_eq!(scanner.statement_at(2), None);
+    }
+
+    #[test]
+    fn test_error_line() {
+        let stderr = "psql:<stdin>:3: NOTICE:  skipping\n\
+            psql:<stdin>:42: ERROR:  relation \"t\" does not exist\n";
+        assert_eq!(error_line(stderr), Some(42));
+        assert_eq!(error_line("psql: error: connection failed"), None);
+    }
+}
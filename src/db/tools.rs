@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Image the Docker engine runs client tools from, tagged with the server's
+/// major version
+const DOCKER_IMAGE: &str = "postgres";
+
+/// Image tag when the server version is unknown
+const DOCKER_DEFAULT_MAJOR: u32 = 17;
+
+/// libpq variables passed through to containers (unset ones are skipped)
+const PG_ENV: &[&str] = &[
+    "PGHOST",
+    "PGPORT",
+    "PGUSER",
+    "PGPASSWORD",
+    "PGDATABASE",
+    "PGSSLMODE",
+    "PGSSLROOTCERT",
+    "PGOPTIONS",
+    "PGAPPNAME",
+    "PGCONNECT_TIMEOUT",
+];
+
+/// Set by `engine = "docker"` to run pg_dump and psql in containers, with
+/// the host paths (CA certificates) they need mounted
+static DOCKER_MOUNTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Run pg_dump and psql from the postgres Docker image from now on
+pub fn use_docker(mounts: Vec<PathBuf>) {
+    let _ = DOCKER_MOUNTS.set(mounts);
+}
+
+/// Whether client tools run in Docker
+pub fn docker_enabled() -> bool {
+    DOCKER_MOUNTS.get().is_some()
+}
+
+/// `docker run` of a client tool from the postgres image matching `major`.
+/// Host networking reaches databases on localhost, stdin is attached and
+/// `mounts` are bind-mounted read-only at the same paths.
+pub fn docker(tool: &str, major: Option<u32>, mounts: &[&Path]) -> Command {
+    docker_run(tool, major, mounts, false)
+}
+
+fn docker_run(tool: &str, major: Option<u32>, mounts: &[&Path], tty: bool) -> Command {
+    let mut cmd = Command::new("docker");
+    cmd.args(["run", "--rm", if tty { "-it" } else { "-i" }])
+        .args(["--network", "host"]);
+    for var in PG_ENV {
+        cmd.arg("-e").arg(var);
+    }
+    let configured = DOCKER_MOUNTS.get().map(Vec::as_slice).unwrap_or_default();
+    for path in configured
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(mounts.iter().copied())
+    {
+        cmd.arg("-v")
+            .arg(format!("{}:{}:ro", path.display(), path.display()));
+    }
+    cmd.arg(format!(
+        "{}:{}",
+        DOCKER_IMAGE,
+        major.unwrap_or(DOCKER_DEFAULT_MAJOR)
+    ))
+    .arg(tool);
+    cmd
+}
+
+/// psql from PATH, or in Docker with that engine
+pub fn psql() -> Command {
+    if docker_enabled() {
+        return docker("psql", None, &[]);
+    }
+    Command::new("psql")
+}
+
+/// psql that can read `file`, and the path to pass to `--file`. In Docker
+/// the file is mounted at its absolute path.
+pub fn psql_reading(file: &Path) -> std::io::Result<(Command, PathBuf)> {
+    if !docker_enabled() {
+        return Ok((psql(), file.to_path_buf()));
+    }
+    let file = file.canonicalize()?;
+    Ok((docker("psql", None, &[&file]), file))
+}
+
+/// psql for an interactive session, with a terminal allocated in Docker
+pub fn psql_shell() -> Command {
+    if !docker_enabled() {
+        return psql();
+    }
+    docker_run("psql", None, &[], true)
+}
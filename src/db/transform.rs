@@ -1,51 +1,1485 @@
-use tracing::debug;
+use super::dump::{glob_match, qualify_pattern};
+use super::publications::REALTIME_PUBLICATION;
+use super::sql::quote_ident;
+use super::statements::StatementScanner;
+use crate::cli::{TransformRule, UnknownRoles};
+use crate::error::{Result, SupamigrateError};
+use regex::{NoExpand, Regex};
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
 
-/// Transforms SQL dump to be compatible with Supabase target project
+/// Adapts the table of contents of a custom-format archive to the target;
+/// plain dumps go through `LineTransformer`
 pub struct SqlTransformer;
 
-impl SqlTransformer {
-    /// Apply all Supabase-specific transformations to SQL dump
-    pub fn transform(sql: &str) -> String {
-        let mut result = sql.to_string();
-
-        // Comment out auth schema operations (managed by Supabase)
-        result = Self::comment_line(&result, "DROP SCHEMA IF EXISTS \"auth\";");
-        result = Self::comment_line(&result, "CREATE SCHEMA \"auth\";");
-
-        // Comment out storage schema operations (managed by Supabase)
-        result = Self::comment_line(&result, "DROP SCHEMA IF EXISTS \"storage\";");
-        result = Self::comment_line(&result, "CREATE SCHEMA \"storage\";");
-
-        // Comment out supabase_admin default privileges
-        result = Self::comment_lines_starting_with(
-            &result,
-            "ALTER DEFAULT PRIVILEGES FOR ROLE \"supabase_admin\"",
+/// Extensions that only exist on the Supabase platform
+const SUPABASE_EXTENSIONS: &[&str] = &[
+    "pg_graphql",
+    "pg_net",
+    "pgjwt",
+    "pgsodium",
+    "supabase_vault",
+    "wrappers",
+];
+
+/// Extensions of other hosted platforms that Supabase can't install
+const FOREIGN_EXTENSIONS: &[&str] = &[
+    "aws_commons",
+    "aws_lambda",
+    "aws_s3",
+    "rds_tools",
+    "google_columnar_engine",
+    "google_ml_integration",
+];
+
+/// Schema Heroku installs extensions into; Supabase uses `extensions`
+const HEROKU_EXTENSION_SCHEMA: &str = "heroku_ext";
+
+/// Lines of Supabase-managed schemas that are commented out as a whole
+const MANAGED_SCHEMA_LINES: &[&str] = &[
+    "DROP SCHEMA IF EXISTS \"auth\";",
+    "CREATE SCHEMA \"auth\";",
+    "DROP SCHEMA IF EXISTS \"storage\";",
+    "CREATE SCHEMA \"storage\";",
+];
+
+/// Roles the platform runs its services as. Objects they own and privileges
+/// granted to them are Supabase's to manage.
+const PLATFORM_ROLES: &[&str] = &[
+    "supabase_admin",
+    "supabase_auth_admin",
+    "supabase_etl_admin",
+    "supabase_functions_admin",
+    "supabase_read_only_user",
+    "supabase_realtime_admin",
+    "supabase_replication_admin",
+    "supabase_storage_admin",
+];
+
+/// Roles every Supabase project has: those clients reach the API as, and
+/// those its services connect as besides the platform's
+const SUPABASE_ROLES: &[&str] = &[
+    "anon",
+    "authenticated",
+    "service_role",
+    "authenticator",
+    "dashboard_user",
+    "pgbouncer",
+    "pgsodium_keyholder",
+    "pgsodium_keyiduser",
+    "pgsodium_keymaker",
+];
+
+/// How a dump is adapted on its way between source and target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformKind {
+    /// Supabase to Supabase
+    Supabase,
+    /// Into plain Postgres
+    ForPostgres,
+    /// From a generic Postgres database (Heroku, RDS, ...) into Supabase:
+    /// the source's roles, grants and event triggers are dropped, extensions
+    /// Supabase lacks are skipped, Heroku's extension schema becomes
+    /// `extensions` and schemas that already exist are reused
+    FromPostgres,
+}
+
+impl TransformKind {
+    /// Whether the dump's `CREATE EXTENSION` for `name` survives the
+    /// transformation
+    pub fn keeps_extension(self, name: &str) -> bool {
+        match self {
+            Self::Supabase => true,
+            Self::ForPostgres => !SUPABASE_EXTENSIONS.contains(&name),
+            Self::FromPostgres => !FOREIGN_EXTENSIONS.contains(&name),
+        }
+    }
+
+    /// The built-in transformation adapting dumps between the kinds, if any
+    pub fn rule(self) -> Option<TransformRule> {
+        match self {
+            Self::Supabase => None,
+            Self::ForPostgres => Some(TransformRule::ForPostgres),
+            Self::FromPostgres => Some(TransformRule::FromPostgres),
+        }
+    }
+
+    /// This kind, or `Supabase` (nothing adapted) when its rule is skipped
+    pub fn unless_skipped(self, skipped: &[TransformRule]) -> Self {
+        match self.rule() {
+            Some(rule) if skipped.contains(&rule) => Self::Supabase,
+            _ => self,
+        }
+    }
+
+    /// Schema the transformed dump expects an extension in, given its schema
+    /// on the source
+    pub fn extension_schema(self, schema: &str) -> &str {
+        if self == Self::FromPostgres && schema == HEROKU_EXTENSION_SCHEMA {
+            "extensions"
+        } else {
+            schema
+        }
+    }
+}
+
+/// A search/replace on the rows of a dump's `COPY` blocks, e.g. a
+/// production domain inside jsonb settings becoming the staging one. Rows
+/// are matched in COPY's text form, where tabs separate columns and
+/// backslashes are doubled.
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    pattern: Regex,
+    replacement: String,
+    /// `replacement` is literal rather than using `$1`-style groups
+    literal: bool,
+    /// Qualified table patterns the rewrite is limited to (all if empty)
+    tables: Vec<String>,
+}
+
+impl Rewrite {
+    /// Replace every occurrence of `from` with `to`. Both are escaped the
+    /// way COPY writes them, so they match the values as stored.
+    pub fn literal(from: &str, to: &str) -> Self {
+        Self {
+            pattern: Regex::new(&regex::escape(&copy_escape(from))).expect("escaped pattern"),
+            replacement: copy_escape(to),
+            literal: true,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Replace matches of the regular expression `from` with `to`, which may
+    /// refer to groups as `$1` or `${name}`
+    pub fn regex(from: &str, to: &str) -> Result<Self> {
+        let pattern = Regex::new(from).map_err(|e| {
+            SupamigrateError::Config(format!("Invalid rewrite pattern '{}': {}", from, e))
+        })?;
+        Ok(Self {
+            pattern,
+            replacement: to.to_string(),
+            literal: false,
+            tables: Vec::new(),
+        })
+    }
+
+    /// Only rewrite rows of tables matching these patterns (`schema.table`,
+    /// `*` wildcards, `public` if unqualified)
+    pub fn tables(mut self, tables: &[String]) -> Self {
+        self.tables = tables.iter().map(|t| qualify_pattern(t)).collect();
+        self
+    }
+
+    /// Whether the rewrite applies to `table` (`schema.table`, unquoted)
+    pub fn applies_to(&self, table: &str) -> bool {
+        self.tables.is_empty() || self.tables.iter().any(|p| glob_match(p, table))
+    }
+
+    pub fn apply<'a>(&self, row: &'a str) -> Cow<'a, str> {
+        if self.literal {
+            self.pattern.replace_all(row, NoExpand(&self.replacement))
+        } else {
+            self.pattern.replace_all(row, self.replacement.as_str())
+        }
+    }
+}
+
+/// A value as it appears in COPY's text format
+fn copy_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Project-specific patches to a plain dump (`[transforms]` in the config),
+/// applied to its statements after the built-in transformations. `COPY`
+/// rows are left to `Rewrite`.
+#[derive(Debug, Clone, Default)]
+pub struct UserTransforms {
+    /// Lines commented out wherever they appear, compared without
+    /// surrounding whitespace
+    comment_lines: Vec<String>,
+    /// Statements commented out up to their semicolon when their first line
+    /// matches
+    drop_statements: Vec<Regex>,
+    replacements: Vec<(Regex, String)>,
+}
+
+impl UserTransforms {
+    /// Comment out lines reading exactly `line`
+    pub fn comment_line(mut self, line: &str) -> Self {
+        self.comment_lines.push(line.trim().to_string());
+        self
+    }
+
+    /// Comment out statements whose first line matches the regular
+    /// expression `pattern`
+    pub fn drop_statement(mut self, pattern: &str) -> Result<Self> {
+        self.drop_statements.push(transform_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Replace matches of the regular expression `from` in every line with
+    /// `to`, which may refer to groups as `$1` or `${name}`
+    pub fn replace(mut self, from: &str, to: &str) -> Result<Self> {
+        self.replacements
+            .push((transform_regex(from)?, to.to_string()));
+        Ok(self)
+    }
+
+    fn replace_all<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut line = Cow::Borrowed(line);
+        for (pattern, replacement) in &self.replacements {
+            if let Cow::Owned(replaced) = pattern.replace_all(&line, replacement.as_str()) {
+                line = Cow::Owned(replaced);
+            }
+        }
+        line
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comment_lines.is_empty()
+            && self.drop_statements.is_empty()
+            && self.replacements.is_empty()
+    }
+}
+
+/// A rhai script (`transform_script` in the config) for patches rules can't
+/// express. Its `transform(statement)` function gets each statement of a
+/// plain dump after the other transformations and returns it, changed or
+/// not, or `()` to drop it.
+#[derive(Clone)]
+pub struct TransformScript {
+    path: PathBuf,
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+}
+
+impl std::fmt::Debug for TransformScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransformScript")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Operations a script may run per statement, so a runaway loop fails
+/// instead of stalling the restore
+const SCRIPT_MAX_OPERATIONS: u64 = 1_000_000;
+
+impl TransformScript {
+    /// Compile the script at `path`, which must define `transform(statement)`
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            SupamigrateError::Config(format!(
+                "Cannot read transform script {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Self::compile(path, &source)
+    }
+
+    fn compile(path: &Path, source: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        engine.on_print(|text| info!("transform script: {}", text));
+        let ast = engine.compile(source).map_err(|e| {
+            SupamigrateError::Config(format!("Transform script {}: {}", path.display(), e))
+        })?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "transform" && f.params.len() == 1)
+        {
+            return Err(SupamigrateError::Config(format!(
+                "Transform script {} must define fn transform(statement)",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+        })
+    }
+
+    /// Run `transform` on one statement: `None` drops it
+    fn call(&self, statement: &str) -> Result<Option<String>> {
+        let error = |message: String| {
+            SupamigrateError::TransformScript(format!("{}: {}", self.path.display(), message))
+        };
+        let result: Dynamic = self
+            .engine
+            .call_fn_with_options(
+                CallFnOptions::new().eval_ast(false),
+                &mut Scope::new(),
+                &self.ast,
+                "transform",
+                (statement.to_string(),),
+            )
+            .map_err(|e| error(e.to_string()))?;
+        if result.is_unit() {
+            return Ok(None);
+        }
+        result.into_string().map(Some).map_err(|found| {
+            error(format!(
+                "transform() must return a string or (), not {}",
+                found
+            ))
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn transform_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| {
+        SupamigrateError::Config(format!("Invalid transform pattern '{}': {}", pattern, e))
+    })
+}
+
+/// `schema.table` of a `COPY ... FROM stdin;` line, unquoted
+fn copy_table(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("COPY ")?;
+    if !line.ends_with("FROM stdin;") {
+        return None;
+    }
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quoted = false;
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                part.push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(std::mem::take(&mut part)),
+            ' ' | '(' if !quoted => break,
+            c => part.push(c),
+        }
+    }
+    parts.push(part);
+    match parts.as_slice() {
+        [schema, name] => Some(format!("{}.{}", schema, name)),
+        [name] => Some(format!("public.{}", name)),
+        _ => None,
+    }
+}
+
+/// A line of a dump changed by a transformation, kept so users can audit
+/// what was commented out or rewritten
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransformChange {
+    /// Transformation that made the change
+    pub rule: &'static str,
+    /// Line of the dump, counting from 1
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// One named step of the transformation of a plain dump. Passes see each
+/// line in turn, as the passes before them left it, and keep whatever state
+/// they need from one line to the next (e.g. being inside a statement they
+/// comment out). They return the line borrowed when they leave it alone.
+pub trait TransformPass: std::fmt::Debug + Send {
+    /// Name in the transform report; built-in passes use the names
+    /// `--transform-skip` takes
+    fn name(&self) -> &'static str;
+
+    /// Transform one line (without its line ending)
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str>;
+
+    /// SQL to run before the transformed dump
+    fn preamble(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the lines this pass changes go in the transform report
+    fn reported(&self) -> bool {
+        true
+    }
+
+    /// Whether the pass also gets the rows of `COPY` blocks, up to and
+    /// including the `\.` ending them. Other passes only see statements.
+    fn rows(&self) -> bool {
+        false
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass>;
+}
+
+impl Clone for Box<dyn TransformPass> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Where the built-in passes run, by name. Passes added with
+/// `LineTransformer::pass` run after them.
+const PASS_ORDER: &[&str] = &[
+    ROW_REWRITES,
+    "for-postgres",
+    "from-postgres",
+    "supabase-roles",
+    "supabase-managed",
+    SCHEMA_REMAP,
+    "roles",
+    CUSTOM_TRANSFORMS,
+];
+
+const ROW_REWRITES: &str = "rewrites";
+const SCHEMA_REMAP: &str = "remap-schema";
+const CUSTOM_TRANSFORMS: &str = "transforms";
+
+/// Comments out whole statements whose first line matches, including any
+/// continuation lines up to the terminating semicolon
+#[derive(Debug, Clone, Default)]
+struct StatementComment {
+    in_statement: bool,
+}
+
+impl StatementComment {
+    fn line<'a>(&mut self, line: &'a str, matches: impl Fn(&str) -> bool) -> Cow<'a, str> {
+        if self.in_statement || matches(line.trim()) {
+            self.in_statement = !line.trim_end().ends_with(';');
+            Cow::Owned(format!("-- {}", line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+}
+
+/// Applies the rewrites to the rows of `COPY` blocks, tracking where the
+/// blocks start and end
+#[derive(Debug, Clone)]
+struct RowRewrites {
+    rewrites: Vec<Rewrite>,
+    /// Rewrites for the rows of the `COPY` block in progress, if in one
+    active: Option<Vec<usize>>,
+}
+
+impl TransformPass for RowRewrites {
+    fn name(&self) -> &'static str {
+        ROW_REWRITES
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        match &self.active {
+            Some(_) if line == "\\." => self.active = None,
+            Some(active) => {
+                let mut row = Cow::Borrowed(line);
+                for &index in active {
+                    if let Cow::Owned(rewritten) = self.rewrites[index].apply(&row) {
+                        row = Cow::Owned(rewritten);
+                    }
+                }
+                return row;
+            }
+            None => {
+                if let Some(table) = copy_table(line) {
+                    self.active = Some(
+                        (0..self.rewrites.len())
+                            .filter(|&i| self.rewrites[i].applies_to(&table))
+                            .collect(),
+                    );
+                }
+            }
+        }
+        Cow::Borrowed(line)
+    }
+
+    /// Rows can be many and may hold personal data
+    fn reported(&self) -> bool {
+        false
+    }
+
+    fn rows(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// Comments out what plain Postgres lacks (`TransformKind::ForPostgres`)
+#[derive(Debug, Clone, Default)]
+struct ForPostgres {
+    statement: StatementComment,
+}
+
+impl TransformPass for ForPostgres {
+    fn name(&self) -> &'static str {
+        TransformRule::ForPostgres.name()
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        self.statement.line(line, is_supabase_only)
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// Adapts a generic Postgres dump for Supabase
+/// (`TransformKind::FromPostgres`)
+#[derive(Debug, Clone)]
+struct FromPostgres {
+    statement: StatementComment,
+    /// Source roles renamed to roles of the target, whose ownership and
+    /// grants are kept
+    renamed: BTreeSet<String>,
+    /// `"heroku_ext"`, quoted as in the dump, and the references to it
+    /// that move to the extensions schema
+    heroku_quoted: String,
+    heroku_qualified: String,
+    heroku_with_schema: String,
+    /// First lines of statements on what only the source has: the Heroku
+    /// extension schema and extensions Supabase lacks
+    foreign_creates: Vec<String>,
+}
+
+impl FromPostgres {
+    fn new(renamed: BTreeSet<String>) -> Self {
+        let heroku_quoted = quote_ident(HEROKU_EXTENSION_SCHEMA);
+        let foreign_creates = std::iter::once(format!("CREATE SCHEMA {}", heroku_quoted))
+            .chain(
+                FOREIGN_EXTENSIONS
+                    .iter()
+                    .map(|ext| format!("CREATE EXTENSION IF NOT EXISTS {}", quote_ident(ext))),
+            )
+            .collect();
+        Self {
+            statement: StatementComment::default(),
+            renamed,
+            heroku_qualified: format!("{}.", heroku_quoted),
+            heroku_with_schema: format!("WITH SCHEMA {}", heroku_quoted),
+            heroku_quoted,
+            foreign_creates,
+        }
+    }
+}
+
+impl TransformPass for FromPostgres {
+    fn name(&self) -> &'static str {
+        TransformRule::FromPostgres.name()
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        let commented = self.statement.line(line, |line| {
+            is_foreign_only(line, &self.foreign_creates) && !only_roles(line, &self.renamed)
+        });
+        if !commented.contains(&self.heroku_quoted) && !commented.starts_with("CREATE SCHEMA \"") {
+            return commented;
+        }
+        let line = commented
+            .replace(&self.heroku_qualified, "\"extensions\".")
+            .replace(&self.heroku_with_schema, "WITH SCHEMA \"extensions\"");
+        Cow::Owned(match line.strip_prefix("CREATE SCHEMA \"") {
+            Some(rest) => format!("CREATE SCHEMA IF NOT EXISTS \"{}", rest),
+            None => line,
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// Comments out what Supabase manages on every project
+#[derive(Debug, Clone)]
+struct SupabaseManaged {
+    /// In a managed statement
+    in_statement: bool,
+    /// Starts of statements on the Realtime publication
+    realtime: Vec<String>,
+}
+
+impl SupabaseManaged {
+    fn new() -> Self {
+        let publication = format!("PUBLICATION {}", quote_ident(REALTIME_PUBLICATION));
+        Self {
+            in_statement: false,
+            realtime: ["CREATE ", "ALTER ", "COMMENT ON "]
+                .iter()
+                .map(|verb| format!("{}{} ", verb, publication))
+                .collect(),
+        }
+    }
+}
+
+impl TransformPass for SupabaseManaged {
+    fn name(&self) -> &'static str {
+        TransformRule::SupabaseManaged.name()
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        if !self.in_statement && !is_supabase_managed(line, &self.realtime) {
+            return Cow::Borrowed(line);
+        }
+        self.in_statement = !line.trim_end().ends_with(';');
+        Cow::Owned(format!("-- {}", line))
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// Drops the grantees of privileges that are Supabase roles the target
+/// can't grant to: the platform's everywhere, and all of them on plain
+/// Postgres. Statements left without grantees are commented out, as are
+/// default privileges for such a role.
+#[derive(Debug, Clone, Default)]
+struct SupabaseRoles {
+    /// The target is plain Postgres, which has none of Supabase's roles
+    plain: bool,
+    /// In a statement dropped for its roles
+    in_statement: bool,
+}
+
+impl SupabaseRoles {
+    fn drops(&self, role: &str) -> bool {
+        PLATFORM_ROLES.contains(&role)
+            || glob_match("supabase_*_admin", role)
+            || (self.plain && SUPABASE_ROLES.contains(&role))
+    }
+
+    fn comment<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        self.in_statement = !line.trim_end().ends_with(';');
+        Cow::Owned(format!("-- {}", line))
+    }
+}
+
+impl TransformPass for SupabaseRoles {
+    fn name(&self) -> &'static str {
+        TransformRule::SupabaseRoles.name()
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        if self.in_statement {
+            return self.comment(line);
+        }
+        let default_privileges = line.starts_with("ALTER DEFAULT PRIVILEGES ");
+        if !line.starts_with("GRANT ") && !line.starts_with("REVOKE ") && !default_privileges {
+            return Cow::Borrowed(line);
+        }
+        let name =
+            |ident: &Range<usize>| line[ident.start + 1..ident.end - 1].replace("\"\"", "\"");
+
+        let mut spans = role_spans(line);
+        if line.starts_with("ALTER DEFAULT PRIVILEGES FOR ROLE ") {
+            let owners = spans.remove(0);
+            if quoted_idents(line, owners)
+                .iter()
+                .any(|ident| self.drops(&name(ident)))
+            {
+                return self.comment(line);
+            }
+        }
+        let Some(span) = spans.pop() else {
+            return Cow::Borrowed(line);
+        };
+        // Grantees end where the options do
+        let end = [" WITH GRANT OPTION", " GRANTED BY ", ";"]
+            .iter()
+            .filter_map(|keyword| line[span.start..].find(keyword).map(|at| span.start + at))
+            .min()
+            .unwrap_or(span.end);
+        let grantees = quoted_idents(line, span.start..end);
+        if !grantees.iter().any(|ident| self.drops(&name(ident))) {
+            return Cow::Borrowed(line);
+        }
+        // PUBLIC isn't quoted, and stays a grantee
+        let mut text = line[span.start..end].to_string();
+        for ident in grantees.iter().rev() {
+            text.replace_range(ident.start - span.start..ident.end - span.start, "");
+        }
+        let kept: Vec<&str> = grantees
+            .iter()
+            .filter(|ident| !self.drops(&name(ident)))
+            .map(|ident| &line[ident.clone()])
+            .chain(text.contains("PUBLIC").then_some("PUBLIC"))
+            .collect();
+        if kept.is_empty() {
+            return self.comment(line);
+        }
+        Cow::Owned(format!(
+            "{}{}{}",
+            &line[..span.start],
+            kept.join(", "),
+            &line[end..]
+        ))
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// Moves the objects of schemas into others. The schemas themselves aren't
+/// dropped or created (the preamble creates the targets), and neither are
+/// the objects `--clean` would drop first, which in the target schema may
+/// be the target's own: a conflict makes their `CREATE` fail instead.
+#[derive(Debug, Clone, Default)]
+struct SchemaRemap {
+    /// Quoted schema names, from source to target
+    remaps: Vec<(String, String)>,
+    /// Quoted source schemas whose objects are still dropped first, as
+    /// their target is a copy of the tool's own
+    replaced: Vec<String>,
+    statement: StatementComment,
+}
+
+impl SchemaRemap {
+    /// Whether the statement on `line`, which names a remapped schema,
+    /// drops or creates that schema, or drops what the target may have
+    fn comments(&self, line: &str) -> bool {
+        let names = |from: &String| {
+            let remap = [(from.clone(), String::new())];
+            remap_schemas(line, &remap) != line
+        };
+        let schema = ["CREATE SCHEMA ", "DROP SCHEMA ", "ALTER SCHEMA "]
+            .iter()
+            .any(|verb| line.starts_with(verb));
+        let clean = line.starts_with("DROP ")
+            || (line.starts_with("ALTER ")
+                && (line.contains(" DROP CONSTRAINT ") || line.ends_with(" DROP DEFAULT;")));
+        self.remaps
+            .iter()
+            .any(|(from, _)| names(from) && (schema || (clean && !self.replaced.contains(from))))
+    }
+}
+
+impl TransformPass for SchemaRemap {
+    fn name(&self) -> &'static str {
+        SCHEMA_REMAP
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        if !self.statement.in_statement
+            && !self
+                .remaps
+                .iter()
+                .any(|(from, _)| line.contains(from.as_str()))
+        {
+            return Cow::Borrowed(line);
+        }
+        let comments = !self.statement.in_statement && self.comments(line);
+        let commented = self.statement.line(line, |_| comments);
+        if matches!(commented, Cow::Owned(_)) {
+            return commented;
+        }
+        Cow::Owned(remap_schemas(line, &self.remaps))
+    }
+
+    /// The target schemas, which may not exist yet
+    fn preamble(&self) -> Option<String> {
+        let targets: BTreeSet<&String> = self.remaps.iter().map(|(_, to)| to).collect();
+        (!targets.is_empty()).then(|| {
+            targets
+                .into_iter()
+                .map(|to| format!("CREATE SCHEMA IF NOT EXISTS {};", to))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// How the roles a dump refers to become roles of the target
+#[derive(Debug, Clone, Default)]
+struct RoleMap {
+    /// Source roles known by another name on the target
+    renames: BTreeMap<String, String>,
+    /// Roles that exist on the target, when checking for unknown ones
+    known: Option<BTreeSet<String>>,
+    policy: UnknownRoles,
+    /// Role that takes over from unknown ones when remapping
+    fallback: String,
+    /// Unknown roles already reported
+    reported: BTreeSet<String>,
+    /// In a statement dropped for its roles
+    in_statement: bool,
+}
+
+impl TransformPass for RoleMap {
+    fn name(&self) -> &'static str {
+        TransformRule::Roles.name()
+    }
+
+    /// Rename role references, and remap or drop those to roles the target
+    /// doesn't have
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        if line.starts_with("-- ") {
+            return Cow::Borrowed(line);
+        }
+        if self.in_statement {
+            // The rest of a statement dropped for its roles
+            self.in_statement = !line.trim_end().ends_with(';');
+            return Cow::Owned(format!("-- {}", line));
+        }
+
+        let mut replacements = Vec::new();
+        let mut unknown = false;
+        for span in role_spans(line) {
+            for ident in quoted_idents(line, span) {
+                let source = line[ident.start + 1..ident.end - 1].replace("\"\"", "\"");
+                let role = self.renames.get(&source).unwrap_or(&source);
+                if self
+                    .known
+                    .as_ref()
+                    .is_some_and(|known| !known.contains(role))
+                {
+                    unknown = true;
+                    if self.reported.insert(role.clone()) {
+                        match self.policy {
+                            UnknownRoles::Remap => warn!(
+                                "Role {} doesn't exist on the target; remapping its ownership and grants to {}",
+                                role, self.fallback
+                            ),
+                            UnknownRoles::Drop | UnknownRoles::Keep => warn!(
+                                "Role {} doesn't exist on the target; dropping its ownership and grants",
+                                role
+                            ),
+                        }
+                    }
+                    replacements.push((ident, self.fallback.clone()));
+                } else if *role != source {
+                    replacements.push((ident, role.clone()));
+                }
+            }
+        }
+
+        let revoke = line.starts_with("REVOKE ")
+            || (line.starts_with("ALTER DEFAULT PRIVILEGES ") && !line.contains(" GRANT "));
+        if unknown && (self.policy == UnknownRoles::Drop || revoke) {
+            self.in_statement = !line.trim_end().ends_with(';');
+            return Cow::Owned(format!("-- {}", line));
+        }
+        if replacements.is_empty() {
+            return Cow::Borrowed(line);
+        }
+
+        let mut mapped = line.to_string();
+        for (ident, role) in replacements.iter().rev() {
+            mapped.replace_range(ident.clone(), &quote_ident(role));
+        }
+        Cow::Owned(mapped)
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// The project's own transforms, for lines outside `COPY` rows
+#[derive(Debug, Clone)]
+struct CustomTransforms {
+    transforms: UserTransforms,
+    /// In a statement being dropped
+    in_dropped: bool,
+}
+
+impl TransformPass for CustomTransforms {
+    fn name(&self) -> &'static str {
+        CUSTOM_TRANSFORMS
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        // Its rows follow, so a COPY is never dropped
+        if copy_table(line).is_some() {
+            return self.transforms.replace_all(line);
+        }
+        if line.starts_with("-- ") {
+            return Cow::Borrowed(line);
+        }
+        if self.in_dropped
+            || self
+                .transforms
+                .drop_statements
+                .iter()
+                .any(|pattern| pattern.is_match(line))
+        {
+            self.in_dropped = !line.trim_end().ends_with(';');
+            return Cow::Owned(format!("-- {}", line));
+        }
+        if self
+            .transforms
+            .comment_lines
+            .iter()
+            .any(|l| *l == line.trim())
+        {
+            return Cow::Owned(format!("-- {}", line));
+        }
+        self.transforms.replace_all(line)
+    }
+
+    fn box_clone(&self) -> Box<dyn TransformPass> {
+        Box::new(self.clone())
+    }
+}
+
+/// Applies a transformation one line at a time, so a dump can stream from
+/// pg_dump to psql without being held in memory. Each line goes through an
+/// ordered pipeline of `TransformPass`es, then whole statements go through
+/// the user's script, if any.
+#[derive(Debug, Clone)]
+pub struct LineTransformer {
+    passes: Vec<Box<dyn TransformPass>>,
+    /// Built-in passes left out, even when configured later
+    skipped: Vec<TransformRule>,
+    /// Schema remaps so far, which make up one pass
+    remaps: Vec<(String, String)>,
+    /// Remapped schemas restored as shadow copies
+    replaced: Vec<String>,
+    /// Role renames and checks so far, which make up one pass
+    roles: RoleMap,
+    script: Option<TransformScript>,
+    /// Where the statements the script gets start and end
+    statements: StatementScanner,
+    /// Lines of the statement in progress for the script, after the passes
+    pending: Vec<String>,
+    /// In the rows of a `COPY` block
+    in_copy: bool,
+    /// In the rows of a `COPY` whose statement the script dropped
+    in_dropped_copy: bool,
+    /// Lines seen so far
+    line_number: usize,
+    /// Lines changed, by rule
+    counts: BTreeMap<&'static str, u64>,
+    /// Whether each change is kept for the transform report
+    keep_changes: bool,
+    changes: Vec<TransformChange>,
+}
+
+impl LineTransformer {
+    pub fn new(kind: TransformKind) -> Self {
+        let transformer = Self {
+            passes: Vec::new(),
+            skipped: Vec::new(),
+            remaps: Vec::new(),
+            replaced: Vec::new(),
+            roles: RoleMap::default(),
+            script: None,
+            statements: StatementScanner::new(),
+            pending: Vec::new(),
+            in_copy: false,
+            in_dropped_copy: false,
+            line_number: 0,
+            counts: BTreeMap::new(),
+            keep_changes: false,
+            changes: Vec::new(),
+        };
+        let transformer = transformer.pass(SupabaseRoles {
+            plain: kind == TransformKind::ForPostgres,
+            in_statement: false,
+        });
+        match kind {
+            TransformKind::Supabase => transformer.pass(SupabaseManaged::new()),
+            TransformKind::ForPostgres => transformer.pass(ForPostgres::default()),
+            TransformKind::FromPostgres => transformer
+                .pass(FromPostgres::new(BTreeSet::new()))
+                .pass(SupabaseManaged::new()),
+        }
+    }
+
+    /// Add a pass, or replace the one of the same name. Built-in passes go
+    /// in their place in the pipeline, others after them in the order
+    /// added.
+    pub fn pass(mut self, pass: impl TransformPass + 'static) -> Self {
+        let name = pass.name();
+        if self.skipped.iter().any(|rule| rule.name() == name) {
+            return self;
+        }
+        let rank = |name: &str| {
+            PASS_ORDER
+                .iter()
+                .position(|n| *n == name)
+                .unwrap_or(PASS_ORDER.len())
+        };
+        match self.passes.iter().position(|p| p.name() == name) {
+            Some(at) => self.passes[at] = Box::new(pass),
+            None => {
+                let at = self
+                    .passes
+                    .partition_point(|p| rank(p.name()) <= rank(name));
+                self.passes.insert(at, Box::new(pass));
+            }
+        }
+        self
+    }
+
+    /// Names of the passes, in the order they run
+    pub fn passes(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Also pass each statement through a user script, last
+    pub fn script(mut self, script: Option<TransformScript>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// Leave out these built-in transformations
+    pub fn skip(mut self, rules: Vec<TransformRule>) -> Self {
+        self.passes
+            .retain(|pass| !rules.iter().any(|rule| rule.name() == pass.name()));
+        self.skipped = rules;
+        self
+    }
+
+    /// Also apply the project's own transforms, after everything else
+    pub fn custom(self, transforms: UserTransforms) -> Self {
+        if transforms.is_empty() {
+            return self;
+        }
+        self.pass(CustomTransforms {
+            transforms,
+            in_dropped: false,
+        })
+    }
+
+    /// Also move every object of schema `from` into schema `to`. Only
+    /// quoted, schema-qualified references (as written by pg_dump with
+    /// --quote-all-identifiers) are rewritten; function bodies that name the
+    /// schema unquoted are left as they are. Several remaps apply in one
+    /// pass, so schemas can swap names.
+    pub fn remap_schema(mut self, from: &str, to: &str) -> Self {
+        self.remaps.push((quote_ident(from), quote_ident(to)));
+        self.schema_remap()
+    }
+
+    /// Like `remap_schema`, into a shadow copy of `from`: the dump still
+    /// drops the objects it restores first, replacing an earlier copy
+    pub fn shadow_schema(mut self, from: &str, to: &str) -> Self {
+        self.replaced.push(quote_ident(from));
+        self.remap_schema(from, to)
+    }
+
+    fn schema_remap(self) -> Self {
+        let remap = SchemaRemap {
+            remaps: self.remaps.clone(),
+            replaced: self.replaced.clone(),
+            statement: StatementComment::default(),
+        };
+        self.pass(remap)
+    }
+
+    /// Also search and replace in the rows of the tables each rewrite
+    /// applies to
+    pub fn rewrite(self, rewrites: Vec<Rewrite>) -> Self {
+        if rewrites.is_empty() {
+            return self;
+        }
+        self.pass(RowRewrites {
+            rewrites,
+            active: None,
+        })
+    }
+
+    /// Also check the roles that ownership, grants, default privileges and
+    /// policies refer to against the roles the target has (`known`): with
+    /// `Remap` unknown ones become `fallback`, with `Drop` the statement is
+    /// commented out. Revokes from unknown roles are always commented out,
+    /// since remapped they'd take privileges from the fallback. Only quoted
+    /// identifiers are recognized.
+    pub fn known_roles(
+        mut self,
+        known: BTreeSet<String>,
+        policy: UnknownRoles,
+        fallback: &str,
+    ) -> Self {
+        self.roles.known = (policy != UnknownRoles::Keep).then_some(known);
+        self.roles.policy = policy;
+        self.roles.fallback = fallback.to_string();
+        let roles = self.roles.clone();
+        self.pass(roles)
+    }
+
+    /// Also rename the roles that ownership, grants, default privileges and
+    /// policies refer to, from source name to target name. Renamed roles are
+    /// what `known_roles` checks.
+    pub fn rename_roles(mut self, renames: BTreeMap<String, String>) -> Self {
+        if renames.is_empty() {
+            return self;
+        }
+        // The source's ownership and grants are dropped coming from plain
+        // Postgres, but not those of roles given a target role
+        let from_postgres = TransformRule::FromPostgres.name();
+        if self.passes.iter().any(|pass| pass.name() == from_postgres) {
+            self = self.pass(FromPostgres::new(renames.keys().cloned().collect()));
+        }
+        self.roles.renames = renames;
+        let roles = self.roles.clone();
+        self.pass(roles)
+    }
+
+    /// Keep every changed line for the transform report, not only the
+    /// number of lines each rule changed
+    pub fn keep_changes(mut self, keep: bool) -> Self {
+        self.keep_changes = keep;
+        self
+    }
+
+    /// SQL to run before the transformed dump
+    pub fn preamble(&self) -> Option<String> {
+        let preambles: Vec<String> = self.passes.iter().filter_map(|p| p.preamble()).collect();
+        (!preambles.is_empty()).then(|| preambles.join("\n"))
+    }
+
+    /// The lines changed so far, outside the rows of `COPY` blocks, when
+    /// kept (`keep_changes`)
+    pub fn changes(&self) -> &[TransformChange] {
+        &self.changes
+    }
+
+    /// The number of lines each rule changed so far
+    pub fn counts(&self) -> &BTreeMap<&'static str, u64> {
+        &self.counts
+    }
+
+    fn record(&mut self, rule: &'static str, line: usize, before: &str, after: &str) {
+        if before == after {
+            return;
+        }
+        *self.counts.entry(rule).or_default() += 1;
+        if self.keep_changes {
+            self.changes.push(TransformChange {
+                rule,
+                line,
+                before: before.to_string(),
+                after: after.to_string(),
+            });
+        }
+    }
+
+    /// Transform one line of the dump (without its line ending). With a
+    /// script, the lines of a statement are held back until it ends and
+    /// then come out together.
+    pub fn line(&mut self, line: &str) -> Result<Vec<String>> {
+        let line = self.transform_line(line);
+        if self.script.is_none() {
+            return Ok(vec![line]);
+        }
+        // The script gets statements, not the rows of a COPY
+        if self.statements.in_copy_data() {
+            self.statements.line(&line);
+            if !self.in_dropped_copy {
+                return Ok(vec![line]);
+            }
+            self.in_dropped_copy = line != "\\.";
+            return Ok(vec![format!("-- {}", line)]);
+        }
+        let finished = self.statements.line(&line);
+        if self.pending.is_empty() && finished == 0 && !self.statements.in_statement() {
+            return Ok(vec![line]);
+        }
+        self.pending.push(line);
+        if self.statements.in_statement() {
+            return Ok(Vec::new());
+        }
+        self.run_script()
+    }
+
+    /// The lines still held back at the end of the dump: a statement
+    /// without its semicolon, which the script doesn't get
+    pub fn finish(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Pass the statement held back to the script
+    fn run_script(&mut self) -> Result<Vec<String>> {
+        let Some(script) = self.script.clone() else {
+            return Ok(self.finish());
+        };
+        let lines = self.finish();
+        let before = lines.join("\n");
+        let after = match script.call(&before)? {
+            Some(statement) => statement,
+            None => {
+                self.in_dropped_copy = self.statements.in_copy_data();
+                lines
+                    .iter()
+                    .map(|line| format!("-- {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+        self.record(
+            "script",
+            self.line_number + 1 - lines.len(),
+            &before,
+            &after,
         );
+        Ok(after.split('\n').map(str::to_string).collect())
+    }
 
-        debug!("Applied SQL transformations for Supabase compatibility");
+    /// The passes over one line. The rows of a `COPY` block only go through
+    /// the passes that work on rows, and are never reported: they can be
+    /// many and hold personal data.
+    fn transform_line(&mut self, line: &str) -> String {
+        self.line_number += 1;
+        let row = self.in_copy;
+        self.in_copy = if row {
+            line != "\\."
+        } else {
+            copy_table(line).is_some()
+        };
+        let mut result = line.to_string();
+        for at in 0..self.passes.len() {
+            let pass = &mut self.passes[at];
+            if row && !pass.rows() {
+                continue;
+            }
+            let Cow::Owned(changed) = pass.line(&result) else {
+                continue;
+            };
+            let (name, reported) = (pass.name(), pass.reported());
+            if reported && !row {
+                self.record(name, self.line_number, &result, &changed);
+            }
+            result = changed;
+        }
         result
     }
 
-    /// Comment out a specific line
-    fn comment_line(sql: &str, target: &str) -> String {
-        sql.lines()
-            .map(|line| {
-                if line.trim() == target {
-                    format!("-- {}", line)
-                } else {
-                    line.to_string()
+    /// Transform a whole dump
+    pub fn apply(&mut self, sql: &str) -> Result<String> {
+        let mut lines: Vec<String> = self.preamble().into_iter().collect();
+        for line in sql.lines() {
+            lines.extend(self.line(line)?);
+        }
+        lines.extend(self.finish());
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Ownership, grants to Supabase roles, RLS policies, event triggers and
+/// platform extensions, which plain Postgres lacks
+fn is_supabase_only(line: &str) -> bool {
+    line.starts_with("GRANT ")
+        || line.starts_with("REVOKE ")
+        || line.starts_with("ALTER DEFAULT PRIVILEGES ")
+        || line.starts_with("CREATE POLICY ")
+        || line.starts_with("CREATE EVENT TRIGGER ")
+        || (line.starts_with("ALTER ") && line.contains(" OWNER TO "))
+        || SUPABASE_EXTENSIONS.iter().any(|ext| {
+            line.starts_with(&format!("CREATE EXTENSION IF NOT EXISTS \"{}\"", ext))
+                || line.starts_with(&format!("COMMENT ON EXTENSION \"{}\"", ext))
+        })
+}
+
+/// The source's roles, grants and event triggers, extensions Supabase lacks
+/// and schemas Supabase already has
+fn is_foreign_only(line: &str, foreign_creates: &[String]) -> bool {
+    line.starts_with("GRANT ")
+        || line.starts_with("REVOKE ")
+        || line.starts_with("ALTER DEFAULT PRIVILEGES ")
+        || line.starts_with("CREATE EVENT TRIGGER ")
+        || line.starts_with("COMMENT ON EXTENSION ")
+        || line.starts_with("COMMENT ON SCHEMA \"public\"")
+        || (line.starts_with("ALTER ") && line.contains(" OWNER TO "))
+        || foreign_creates
+            .iter()
+            .any(|create| line.starts_with(create.as_str()))
+}
+
+/// Parts of a statement's first line that list roles: owners, grantees,
+/// grantors, the role of default privileges and the roles of a policy
+fn role_spans(line: &str) -> Vec<Range<usize>> {
+    let line = line.trim_end();
+    let after = |keyword: &str| line.rfind(keyword).map(|at| at + keyword.len()..line.len());
+    let mut spans = Vec::new();
+    if line.starts_with("ALTER DEFAULT PRIVILEGES FOR ROLE ") {
+        let end = [" IN SCHEMA ", " GRANT ", " REVOKE "]
+            .iter()
+            .filter_map(|keyword| line.find(keyword))
+            .min()
+            .unwrap_or(line.len());
+        spans.push("ALTER DEFAULT PRIVILEGES FOR ROLE".len()..end);
+    }
+    if line.starts_with("GRANT ")
+        || (line.starts_with("ALTER DEFAULT PRIVILEGES ") && line.contains(" GRANT "))
+    {
+        spans.extend(after(" TO "));
+    } else if line.starts_with("REVOKE ") || line.starts_with("ALTER DEFAULT PRIVILEGES ") {
+        spans.extend(after(" FROM "));
+    } else if line.starts_with("CREATE POLICY ") {
+        if let Some(start) = line.find(" TO ") {
+            let end = [" USING ", " WITH CHECK "]
+                .iter()
+                .filter_map(|keyword| line[start..].find(keyword).map(|at| start + at))
+                .min()
+                .unwrap_or(line.len());
+            spans.push(start..end);
+        }
+    } else if line.starts_with("ALTER ") && line.contains(" OWNER TO ") {
+        spans.extend(after(" OWNER TO "));
+    }
+    spans
+}
+
+/// Ranges of the double-quoted identifiers within `span` of `line`, quotes
+/// included
+fn quoted_idents(line: &str, span: Range<usize>) -> Vec<Range<usize>> {
+    let mut idents = Vec::new();
+    let bytes = line.as_bytes();
+    let mut at = span.start;
+    while at < span.end {
+        if bytes[at] != b'"' {
+            at += 1;
+            continue;
+        }
+        let start = at;
+        at += 1;
+        while at < bytes.len() {
+            if bytes[at] == b'"' {
+                if bytes.get(at + 1) == Some(&b'"') {
+                    at += 2;
+                    continue;
                 }
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+                break;
+            }
+            at += 1;
+        }
+        if at < bytes.len() {
+            idents.push(start..at + 1);
+        }
+        at += 1;
+    }
+    idents
+}
+
+/// First lines of statements on objects Supabase manages itself: the auth
+/// and storage schemas, event triggers, extension comments, the Realtime
+/// publication (statements starting with one of `realtime`), and objects
+/// the platform's roles own. Privileges of those roles are left to
+/// `SupabaseRoles`.
+fn is_supabase_managed(line: &str, realtime: &[String]) -> bool {
+    let line = line.trim();
+    MANAGED_SCHEMA_LINES.contains(&line)
+        || line.starts_with("CREATE EVENT TRIGGER ")
+        || line.starts_with("ALTER EVENT TRIGGER ")
+        || line.starts_with("COMMENT ON EVENT TRIGGER ")
+        || line.starts_with("COMMENT ON EXTENSION ")
+        || realtime
+            .iter()
+            .any(|start| line.starts_with(start.as_str()))
+        || (line.starts_with("ALTER ") && line.contains(" OWNER TO ") && names_platform_role(line))
+}
+
+/// Rewrite the quoted schema names of `remap` (source to target) where they
+/// qualify an object or follow `SCHEMA`, in one pass so a schema renamed to
+/// another's old name isn't renamed again
+fn remap_schemas(line: &str, remap: &[(String, String)]) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    'scan: while !rest.is_empty() {
+        if rest.starts_with('"') {
+            let names_schema = ["SCHEMA ", "SCHEMA IF EXISTS ", "SCHEMA IF NOT EXISTS "]
+                .iter()
+                .any(|prefix| result.ends_with(prefix));
+            for (from, to) in remap {
+                if let Some(after) = rest.strip_prefix(from.as_str()) {
+                    if names_schema || after.starts_with('.') {
+                        result.push_str(to);
+                        rest = after;
+                        continue 'scan;
+                    }
+                }
+            }
+        }
+        let next = rest.chars().next().map_or(1, char::len_utf8);
+        result.push_str(&rest[..next]);
+        rest = &rest[next..];
     }
+    result
+}
+
+/// Whether the statement on `line` lists roles (e.g. its owner or
+/// grantees), all of them `roles`
+fn only_roles(line: &str, roles: &BTreeSet<String>) -> bool {
+    let spans = role_spans(line);
+    !spans.is_empty()
+        && spans.into_iter().all(|span| {
+            let idents = quoted_idents(line, span);
+            !idents.is_empty()
+                && idents.iter().all(|ident| {
+                    roles.contains(&line[ident.start + 1..ident.end - 1].replace("\"\"", "\""))
+                })
+        })
+}
 
-    /// Comment out all lines starting with a pattern
-    fn comment_lines_starting_with(sql: &str, pattern: &str) -> String {
-        sql.lines()
+/// Whether the roles the statement on `line` lists (e.g. its owner) include
+/// one of the platform's roles
+fn names_platform_role(line: &str) -> bool {
+    role_spans(line).into_iter().any(|span| {
+        quoted_idents(line, span).into_iter().any(|ident| {
+            let role = line[ident.start + 1..ident.end - 1].replace("\"\"", "\"");
+            PLATFORM_ROLES.contains(&role.as_str())
+        })
+    })
+}
+
+impl SqlTransformer {
+    /// The Supabase transformations for a custom-format archive: skips the
+    /// same objects in its table of contents (`pg_restore --list`)
+    pub fn transform_toc(list: &str) -> String {
+        Self::comment_toc_entries(list, |entry| {
+            let owner = entry.trim_end().rsplit(' ').next().unwrap_or_default();
+            entry.starts_with("SCHEMA - auth ")
+                || entry.starts_with("SCHEMA - storage ")
+                || entry.starts_with("EVENT TRIGGER ")
+                || entry.starts_with("COMMENT - EXTENSION ")
+                || entry.starts_with(&format!("PUBLICATION - {} ", REALTIME_PUBLICATION))
+                || (entry.starts_with("PUBLICATION TABLE ")
+                    && entry.split(' ').nth(3) == Some(REALTIME_PUBLICATION))
+                || (entry.starts_with("DEFAULT ACL ") && PLATFORM_ROLES.contains(&owner))
+        })
+    }
+
+    /// The plain Postgres transformations for a custom-format archive.
+    /// Ownership isn't in the table of contents; restore with `--no-owner`.
+    pub fn transform_toc_for_postgres(list: &str) -> String {
+        Self::comment_toc_entries(list, |entry| {
+            entry.starts_with("ACL ")
+                || entry.starts_with("DEFAULT ACL ")
+                || entry.starts_with("POLICY ")
+                || entry.starts_with("EVENT TRIGGER ")
+                || SUPABASE_EXTENSIONS.iter().any(|ext| {
+                    entry.starts_with(&format!("EXTENSION - {} ", ext))
+                        || entry.starts_with(&format!("COMMENT - EXTENSION {} ", ext))
+                })
+        })
+    }
+
+    /// Comment out table-of-contents entries whose description matches. Lines
+    /// look like `215; 1259 16386 TABLE public todos postgres`; `matches` gets
+    /// the part after the ids.
+    fn comment_toc_entries(list: &str, matches: impl Fn(&str) -> bool) -> String {
+        list.lines()
             .map(|line| {
-                if line.trim().starts_with(pattern) {
-                    format!("-- {}", line)
+                let entry = line.splitn(4, ' ').nth(3).unwrap_or_default();
+                if !line.starts_with(';') && matches(entry) {
+                    format!(";{}", line)
                 } else {
                     line.to_string()
                 }
@@ -59,6 +1493,35 @@ impl SqlTransformer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rewrite_copy_rows() {
+        let input = "COPY \"public\".\"settings\" (\"key\", \"value\") FROM stdin;\n\
+            site\t{\"url\": \"https://app.example.com/a\\\\b\"}\n\
+            \\.\n\
+            COPY public.users (id, email) FROM stdin;\n\
+            1\tme@app.example.com\n\
+            \\.\n\
+            -- https://app.example.com";
+        let rewrites = vec![
+            Rewrite::literal(
+                "https://app.example.com/a\\b",
+                "https://staging.example.com/",
+            )
+            .tables(&["settings".to_string()]),
+            Rewrite::regex(r"@app\.(example\.com)", "@staging.$1").unwrap(),
+        ];
+        let mut transformer = LineTransformer::new(TransformKind::Supabase)
+            .keep_changes(true)
+            .rewrite(rewrites);
+        let result = transformer.apply(input).unwrap();
+        assert!(result.contains("site\t{\"url\": \"https://staging.example.com/\"}"));
+        assert!(result.contains("1\tme@staging.example.com"));
+        assert!(result.ends_with("-- https://app.example.com"));
+        // Rows may hold personal data, so they never go in the report
+        assert!(transformer.changes().is_empty());
+        assert!(Rewrite::regex("(", "").is_err());
+    }
+
     #[test]
     fn test_comment_auth_schema() {
         let input = r#"
@@ -66,30 +1529,523 @@ DROP SCHEMA IF EXISTS "auth";
 CREATE SCHEMA "auth";
 DROP SCHEMA IF EXISTS "public";
 "#;
-        let result = SqlTransformer::transform(input);
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .apply(input)
+            .unwrap();
         assert!(result.contains("-- DROP SCHEMA IF EXISTS \"auth\";"));
         assert!(result.contains("-- CREATE SCHEMA \"auth\";"));
         assert!(result.contains("DROP SCHEMA IF EXISTS \"public\";"));
     }
 
+    #[test]
+    fn test_transform_toc() {
+        let list = "\
+; Archive created at 2026-10-16 17:00:00 UTC
+5; 2615 16384 SCHEMA - auth supabase_admin
+6; 2615 2200 SCHEMA - public pg_database_owner
+4; 3079 16400 EXTENSION - pg_graphql 
+215; 1259 16386 TABLE public todos postgres
+3512; 3256 16390 POLICY public todos own todos postgres
+3600; 0 0 ACL public TABLE todos postgres
+3601; 826 16500 DEFAULT ACL - DEFAULT PRIVILEGES FOR TABLES supabase_admin
+3602; 826 16501 DEFAULT ACL - DEFAULT PRIVILEGES FOR TABLES postgres
+3603; 0 0 COMMENT - EXTENSION pgcrypto 
+3604; 3466 16510 EVENT TRIGGER - pgrst_ddl_watch supabase_admin
+3586; 6104 22126 PUBLICATION - supabase_realtime postgres
+3587; 6106 22127 PUBLICATION TABLE public supabase_realtime todos postgres";
+
+        let supabase = SqlTransformer::transform_toc(list);
+        assert!(supabase.contains(";5; 2615 16384 SCHEMA - auth supabase_admin"));
+        assert!(supabase.contains("\n6; 2615 2200 SCHEMA - public"));
+        assert!(supabase.contains(";3601; 826 16500 DEFAULT ACL"));
+        assert!(supabase.contains("\n3512; 3256 16390 POLICY"));
+        assert!(supabase.contains("\n3602; 826 16501 DEFAULT ACL"));
+        for id in ["3603", "3604", "3586", "3587"] {
+            assert!(supabase.contains(&format!("\n;{}; ", id)));
+        }
+
+        let postgres = SqlTransformer::transform_toc_for_postgres(list);
+        assert!(postgres.contains(";4; 3079 16400 EXTENSION - pg_graphql"));
+        assert!(postgres.contains(";3512; 3256 16390 POLICY"));
+        assert!(postgres.contains(";3600; 0 0 ACL"));
+        assert!(postgres.contains("\n215; 1259 16386 TABLE public todos"));
+    }
+
+    #[test]
+    fn test_comment_managed_objects() {
+        let input = r#"
+CREATE EVENT TRIGGER "pgrst_ddl_watch" ON "ddl_command_end"
+         EXECUTE FUNCTION "extensions"."pgrst_ddl_watch"();
+ALTER EVENT TRIGGER "pgrst_ddl_watch" OWNER TO "supabase_admin";
+COMMENT ON EXTENSION "pgcrypto" IS 'cryptographic functions';
+ALTER SCHEMA "graphql" OWNER TO "supabase_admin";
+ALTER SCHEMA "app" OWNER TO "postgres";
+GRANT ALL ON TABLE "public"."todos" TO "supabase_auth_admin";
+GRANT ALL ON TABLE "public"."todos" TO "anon";
+REVOKE ALL ON FUNCTION "public"."f"() FROM "supabase_storage_admin";
+ALTER DEFAULT PRIVILEGES FOR ROLE "postgres" IN SCHEMA "public" GRANT ALL ON TABLES TO "supabase_auth_admin";
+CREATE PUBLICATION "supabase_realtime" WITH (publish = 'insert, update, delete, truncate');
+ALTER PUBLICATION "supabase_realtime" OWNER TO "postgres";
+ALTER PUBLICATION "supabase_realtime" ADD TABLE ONLY "public"."todos";
+CREATE PUBLICATION "app_changes" WITH (publish = 'insert');
+CREATE POLICY "auth reads" ON "public"."todos" TO "supabase_auth_admin" USING (true);
+"#;
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .apply(input)
+            .unwrap();
+        let kept: Vec<&str> = result
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with("-- "))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                r#"ALTER SCHEMA "app" OWNER TO "postgres";"#,
+                r#"GRANT ALL ON TABLE "public"."todos" TO "anon";"#,
+                r#"CREATE PUBLICATION "app_changes" WITH (publish = 'insert');"#,
+                r#"CREATE POLICY "auth reads" ON "public"."todos" TO "supabase_auth_admin" USING (true);"#,
+            ]
+        );
+    }
+
     #[test]
     fn test_comment_storage_schema() {
         let input = r#"
 DROP SCHEMA IF EXISTS "storage";
 CREATE SCHEMA "storage";
 "#;
-        let result = SqlTransformer::transform(input);
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .apply(input)
+            .unwrap();
         assert!(result.contains("-- DROP SCHEMA IF EXISTS \"storage\";"));
         assert!(result.contains("-- CREATE SCHEMA \"storage\";"));
     }
 
+    #[test]
+    fn test_transform_for_postgres() {
+        let input = r#"
+CREATE SCHEMA "auth";
+CREATE EXTENSION IF NOT EXISTS "pg_graphql" WITH SCHEMA "graphql";
+CREATE EXTENSION IF NOT EXISTS "pgcrypto" WITH SCHEMA "extensions";
+ALTER TABLE "public"."todos" OWNER TO "postgres";
+CREATE POLICY "own todos" ON "public"."todos" TO "authenticated"
+  USING (("user_id" = "auth"."uid"()));
+ALTER TABLE "public"."todos" ENABLE ROW LEVEL SECURITY;
+GRANT ALL ON TABLE "public"."todos" TO "anon";
+"#;
+        let result = LineTransformer::new(TransformKind::ForPostgres)
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("\nCREATE SCHEMA \"auth\";"));
+        assert!(result.contains("-- CREATE EXTENSION IF NOT EXISTS \"pg_graphql\""));
+        assert!(result.contains("\nCREATE EXTENSION IF NOT EXISTS \"pgcrypto\""));
+        assert!(result.contains("-- ALTER TABLE \"public\".\"todos\" OWNER TO"));
+        assert!(result.contains("--   USING ((\"user_id\""));
+        assert!(result.contains("\nALTER TABLE \"public\".\"todos\" ENABLE ROW LEVEL SECURITY;"));
+        assert!(result.contains("-- GRANT ALL"));
+
+        let skipped = LineTransformer::new(TransformKind::ForPostgres)
+            .skip(vec![
+                TransformRule::ForPostgres,
+                TransformRule::SupabaseRoles,
+            ])
+            .apply(input)
+            .unwrap();
+        assert_eq!(skipped, input.trim_end_matches('\n'));
+        // Grants are kept, but not to roles plain Postgres lacks
+        let grants = LineTransformer::new(TransformKind::ForPostgres)
+            .skip(vec![TransformRule::ForPostgres])
+            .apply(input)
+            .unwrap();
+        assert!(grants.contains("-- GRANT ALL"));
+        assert!(grants.contains("\nALTER TABLE \"public\".\"todos\" OWNER TO"));
+        let managed = LineTransformer::new(TransformKind::Supabase)
+            .skip(vec![TransformRule::SupabaseManaged])
+            .apply(input)
+            .unwrap();
+        assert!(managed.contains("\nCREATE SCHEMA \"auth\";"));
+    }
+
+    #[test]
+    fn test_transform_from_postgres() {
+        let input = r#"
+CREATE SCHEMA "heroku_ext";
+CREATE SCHEMA "app";
+CREATE EXTENSION IF NOT EXISTS "pgcrypto" WITH SCHEMA "heroku_ext";
+CREATE EXTENSION IF NOT EXISTS "aws_s3" WITH SCHEMA "public";
+ALTER TABLE "app"."orders" OWNER TO "u8d9f2k1";
+CREATE TABLE "app"."orders" ("id" uuid DEFAULT "heroku_ext"."gen_random_uuid"());
+GRANT SELECT ON TABLE "app"."orders" TO "reporting";
+"#;
+        let result = LineTransformer::new(TransformKind::FromPostgres)
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("-- CREATE SCHEMA \"heroku_ext\";"));
+        assert!(result.contains("\nCREATE SCHEMA IF NOT EXISTS \"app\";"));
+        assert!(result.contains("\"pgcrypto\" WITH SCHEMA \"extensions\";"));
+        assert!(result.contains("-- CREATE EXTENSION IF NOT EXISTS \"aws_s3\""));
+        assert!(result.contains("-- ALTER TABLE \"app\".\"orders\" OWNER TO"));
+        assert!(result.contains("DEFAULT \"extensions\".\"gen_random_uuid\"()"));
+        assert!(result.contains("-- GRANT SELECT"));
+    }
+
+    #[test]
+    fn test_remap_schema() {
+        let input = r#"
+DROP TABLE IF EXISTS "public"."todos";
+DROP SCHEMA IF EXISTS "public";
+CREATE SCHEMA "public";
+CREATE TABLE "public"."todos" ("id" bigint DEFAULT nextval('"public"."todos_id_seq"'::regclass));
+GRANT USAGE ON SCHEMA "public" TO "anon";
+CREATE TABLE "publicity"."ads" ("id" bigint);
+COPY "public"."todos" ("note") FROM stdin;
+see "public".todos
+\.
+"#;
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .remap_schema("public", "public_next")
+            .apply(input)
+            .unwrap();
+        assert!(result.starts_with("CREATE SCHEMA IF NOT EXISTS \"public_next\";"));
+        // The target's own objects and schema are never dropped
+        assert!(result.contains("-- DROP TABLE IF EXISTS \"public\".\"todos\";"));
+        assert!(result.contains("-- DROP SCHEMA IF EXISTS \"public\";"));
+        assert!(result.contains("-- CREATE SCHEMA \"public\";"));
+        assert!(result.contains("CREATE TABLE \"public_next\".\"todos\""));
+        assert!(result.contains("nextval('\"public_next\".\"todos_id_seq\"'"));
+        assert!(result.contains("ON SCHEMA \"public_next\" TO \"anon\""));
+        assert!(result.contains("\"publicity\".\"ads\""));
+        assert!(result.contains("COPY \"public_next\".\"todos\""));
+        assert!(result.contains("\nsee \"public\".todos\n"));
+
+        // A shadow copy replaces the last one
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .shadow_schema("public", "public_next")
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("\nDROP TABLE IF EXISTS \"public_next\".\"todos\";"));
+        assert!(result.contains("-- DROP SCHEMA IF EXISTS \"public\";"));
+    }
+
+    #[test]
+    fn test_remap_schema_with_quote() {
+        let input = "CREATE TABLE \"we\"\"ird\".\"todos\" (\"id\" bigint);\n";
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .remap_schema("we\"ird", "app\"next")
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("CREATE TABLE \"app\"\"next\".\"todos\""));
+    }
+
+    #[test]
+    fn test_remap_several_schemas() {
+        let input = r#"
+CREATE TABLE "legacy"."users" ("id" bigint);
+ALTER TABLE "public"."users" ADD CONSTRAINT "users_id_fkey" FOREIGN KEY ("id") REFERENCES "legacy"."users"("id");
+COMMENT ON SCHEMA "legacy" IS 'old app';
+SELECT "legacy"."users"."id" FROM "legacy_archive"."users";
+"#;
+        let mut transformer = LineTransformer::new(TransformKind::Supabase)
+            .keep_changes(true)
+            .remap_schema("legacy", "public")
+            .remap_schema("public", "legacy");
+        let result = transformer.apply(input).unwrap();
+        assert!(result.starts_with(
+            "CREATE SCHEMA IF NOT EXISTS \"legacy\";\nCREATE SCHEMA IF NOT EXISTS \"public\";"
+        ));
+        assert!(result.contains("CREATE TABLE \"public\".\"users\""));
+        assert!(result.contains(
+            "ALTER TABLE \"legacy\".\"users\" ADD CONSTRAINT \"users_id_fkey\" FOREIGN KEY (\"id\") REFERENCES \"public\".\"users\"(\"id\");"
+        ));
+        assert!(result.contains("COMMENT ON SCHEMA \"public\" IS"));
+        assert!(result
+            .contains("SELECT \"public\".\"users\".\"id\" FROM \"legacy_archive\".\"users\";"));
+        assert_eq!(transformer.changes().len(), 4);
+    }
+
     #[test]
     fn test_comment_supabase_admin() {
         let input = r#"
 ALTER DEFAULT PRIVILEGES FOR ROLE "supabase_admin" IN SCHEMA "public" GRANT ALL ON TABLES TO "postgres";
 ALTER DEFAULT PRIVILEGES FOR ROLE "supabase_admin" IN SCHEMA "public" GRANT ALL ON SEQUENCES TO "postgres";
 "#;
-        let result = SqlTransformer::transform(input);
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("-- ALTER DEFAULT PRIVILEGES FOR ROLE \"supabase_admin\""));
+    }
+
+    #[test]
+    fn test_unknown_roles() {
+        let input = r#"
+ALTER TABLE "public"."orders" OWNER TO "app_owner";
+ALTER TABLE "public"."users" OWNER TO "postgres";
+GRANT SELECT ON TABLE "public"."orders" TO "reporting";
+GRANT ALL ON TABLE "public"."orders" TO "anon", "app_owner";
+REVOKE ALL ON FUNCTION "public"."f"() FROM PUBLIC;
+REVOKE ALL ON TABLE "public"."orders" FROM "app_owner";
+ALTER DEFAULT PRIVILEGES FOR ROLE "app_owner" IN SCHEMA "public" GRANT SELECT ON TABLES TO "anon";
+CREATE POLICY "own rows" ON "public"."orders" FOR SELECT TO "reporting" USING (("owner" = CURRENT_USER)
+  OR ("owner" = 'reporting'));
+CREATE TABLE "public"."app_owner" ("id" bigint);
+COPY "public"."audit" ("statement") FROM stdin;
+GRANT SELECT ON TABLE "public"."orders" TO "reporting";
+\.
+"#;
+        let known: BTreeSet<String> = ["postgres", "anon"].map(String::from).into();
+
+        let remapped = LineTransformer::new(TransformKind::Supabase)
+            .known_roles(known.clone(), UnknownRoles::Remap, "postgres")
+            .apply(input)
+            .unwrap();
+        assert!(remapped.contains("\"orders\" OWNER TO \"postgres\";"));
+        assert!(remapped.contains("TO \"postgres\";\nGRANT ALL"));
+        assert!(remapped.contains("TO \"anon\", \"postgres\";"));
+        assert!(remapped.contains("FOR ROLE \"postgres\" IN SCHEMA"));
+        assert!(remapped.contains("FOR SELECT TO \"postgres\" USING"));
+        assert!(remapped.contains("'reporting'"));
+        assert!(remapped.contains("-- REVOKE ALL ON TABLE"));
+        assert!(remapped.contains("CREATE TABLE \"public\".\"app_owner\""));
+
+        let dropped = LineTransformer::new(TransformKind::Supabase)
+            .known_roles(known.clone(), UnknownRoles::Drop, "postgres")
+            .apply(input)
+            .unwrap();
+        assert!(dropped.contains("-- ALTER TABLE \"public\".\"orders\" OWNER TO"));
+        assert!(dropped.contains("\nALTER TABLE \"public\".\"users\" OWNER TO"));
+        assert!(dropped.contains("-- GRANT ALL"));
+        assert!(dropped.contains("\nREVOKE ALL"));
+        assert!(dropped.contains("-- ALTER DEFAULT PRIVILEGES"));
+        assert!(dropped.contains("-- CREATE POLICY"));
+        assert!(dropped.contains("--   OR (\"owner\""));
+        assert!(dropped.contains("\nCREATE TABLE"));
+        // Rows are data, even when they read like a grant
+        assert!(
+            dropped.contains("\nGRANT SELECT ON TABLE \"public\".\"orders\" TO \"reporting\";\n")
+        );
+
+        let kept = LineTransformer::new(TransformKind::Supabase)
+            .known_roles(known, UnknownRoles::Keep, "postgres")
+            .apply(input)
+            .unwrap();
+        assert_eq!(kept, input.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn test_user_transforms() {
+        let input = r#"
+CREATE EXTENSION IF NOT EXISTS "pg_stat_monitor" WITH SCHEMA "public";
+CREATE TRIGGER "legacy_audit" AFTER INSERT ON "old_schema"."orders"
+  FOR EACH ROW EXECUTE FUNCTION "old_schema"."audit"();
+CREATE TABLE "old_schema"."orders" ("id" bigint);
+COPY "old_schema"."orders" ("id") FROM stdin;
+"old_schema".1
+\.
+"#;
+        let transforms = UserTransforms::default()
+            .comment_line(
+                r#"CREATE EXTENSION IF NOT EXISTS "pg_stat_monitor" WITH SCHEMA "public";"#,
+            )
+            .drop_statement(r#"^CREATE TRIGGER "legacy_audit" "#)
+            .unwrap()
+            .replace(r#""old_schema"\."#, r#""new_schema"."#)
+            .unwrap();
+        let mut transformer = LineTransformer::new(TransformKind::Supabase)
+            .keep_changes(true)
+            .custom(transforms);
+        let result = transformer.apply(input).unwrap();
+        assert!(result.contains("-- CREATE EXTENSION IF NOT EXISTS \"pg_stat_monitor\""));
+        assert!(result.contains("-- CREATE TRIGGER \"legacy_audit\""));
+        assert!(result.contains("--   FOR EACH ROW EXECUTE FUNCTION \"old_schema\""));
+        assert!(result.contains("\nCREATE TABLE \"new_schema\".\"orders\""));
+        assert!(result.contains("COPY \"new_schema\".\"orders\""));
+        assert!(result.contains("\n\"old_schema\".1\n"));
+        let changes = transformer.changes();
+        assert_eq!(changes.len(), 5);
+        assert_eq!(
+            changes[1],
+            TransformChange {
+                rule: "transforms",
+                line: 3,
+                before: r#"CREATE TRIGGER "legacy_audit" AFTER INSERT ON "old_schema"."orders""#
+                    .to_string(),
+                after: r#"-- CREATE TRIGGER "legacy_audit" AFTER INSERT ON "old_schema"."orders""#
+                    .to_string(),
+            }
+        );
+
+        assert!(UserTransforms::default().drop_statement("(").is_err());
+    }
+
+    #[test]
+    fn test_transform_script() {
+        let input = r#"
+CREATE FUNCTION "public"."touch"() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$begin new.updated_at = now(); return new; end;$$;
+CREATE TABLE "public"."legacy_log" ("id" bigint);
+COPY "public"."legacy_log" ("id") FROM stdin;
+1
+\.
+CREATE TABLE "public"."orders" ("id" bigint);
+"#;
+        let script = TransformScript::compile(
+            Path::new("fixups.rhai"),
+            r#"
+fn transform(statement) {
+    if statement.contains("legacy_log") { return (); }
+    statement.replace("plpgsql", "sql");
+    statement
+}
+"#,
+        )
+        .unwrap();
+        let mut transformer = LineTransformer::new(TransformKind::Supabase)
+            .keep_changes(true)
+            .script(Some(script));
+        let result = transformer.apply(input).unwrap();
+        assert!(result.contains("\n    LANGUAGE sql\n    AS $$begin new.updated_at = now();"));
+        assert!(result.contains("\n-- CREATE TABLE \"public\".\"legacy_log\""));
+        assert!(result.contains("\n-- COPY \"public\".\"legacy_log\""));
+        assert!(result.contains("\n-- 1\n-- \\.\n"));
+        assert!(result.contains("\nCREATE TABLE \"public\".\"orders\""));
+        assert_eq!(result.lines().count(), input.lines().count());
+        let changes = transformer.changes();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].rule, "script");
+        assert_eq!(changes[0].line, 2);
+
+        let bad = TransformScript::compile(Path::new("bad.rhai"), "fn transform(s) { 42 }");
+        let mut transformer = LineTransformer::new(TransformKind::Supabase).script(bad.ok());
+        assert!(transformer.apply("SELECT 1;").is_err());
+        assert!(TransformScript::compile(Path::new("empty.rhai"), "let x = 1;").is_err());
+    }
+
+    #[test]
+    fn test_rename_roles() {
+        let input = r#"
+ALTER TABLE "public"."orders" OWNER TO "app_rw";
+GRANT SELECT ON TABLE "public"."orders" TO "app_ro", "anon";
+CREATE POLICY "readers" ON "public"."orders" FOR SELECT TO "app_ro" USING (true);
+"#;
+        let renames: BTreeMap<String, String> = [("app_rw", "writer"), ("app_ro", "reader")]
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .into();
+        let known: BTreeSet<String> = ["anon", "postgres", "writer"].map(String::from).into();
+        let result = LineTransformer::new(TransformKind::FromPostgres)
+            .rename_roles(renames.clone())
+            .apply(input)
+            .unwrap();
+        // Coming from plain Postgres, only ownership and grants of renamed
+        // roles are kept
+        assert!(result.contains("\nALTER TABLE \"public\".\"orders\" OWNER TO \"writer\";"));
+        assert!(result.contains("-- GRANT SELECT"));
+        assert!(result.contains("FOR SELECT TO \"reader\" USING"));
+
+        let result = LineTransformer::new(TransformKind::Supabase)
+            .rename_roles(renames)
+            .known_roles(known, UnknownRoles::Remap, "postgres")
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("OWNER TO \"writer\";"));
+        assert!(result.contains("TO \"postgres\", \"anon\";"));
+        assert!(result.contains("FOR SELECT TO \"postgres\" USING"));
+    }
+
+    #[test]
+    fn test_transform_passes() {
+        #[derive(Debug, Clone)]
+        struct Unlogged;
+
+        impl TransformPass for Unlogged {
+            fn name(&self) -> &'static str {
+                "unlogged"
+            }
+
+            fn line<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+                match line.strip_prefix("CREATE TABLE ") {
+                    Some(rest) => Cow::Owned(format!("CREATE UNLOGGED TABLE {}", rest)),
+                    None => Cow::Borrowed(line),
+                }
+            }
+
+            fn box_clone(&self) -> Box<dyn TransformPass> {
+                Box::new(self.clone())
+            }
+        }
+
+        let mut transformer = LineTransformer::new(TransformKind::FromPostgres)
+            .keep_changes(true)
+            .pass(Unlogged)
+            .skip(vec![TransformRule::Roles])
+            .remap_schema("app", "app_next")
+            .rename_roles([("a".to_string(), "b".to_string())].into());
+        assert_eq!(
+            transformer.passes(),
+            [
+                "from-postgres",
+                "supabase-roles",
+                "supabase-managed",
+                "remap-schema",
+                "unlogged"
+            ]
+        );
+
+        let result = transformer
+            .apply("CREATE TABLE \"app\".\"users\" (\"id\" bigint);")
+            .unwrap();
+        assert!(result.contains("CREATE UNLOGGED TABLE \"app_next\".\"users\""));
+        let rules: Vec<_> = transformer.changes().iter().map(|c| c.rule).collect();
+        assert_eq!(rules, ["remap-schema", "unlogged"]);
+    }
+
+    #[test]
+    fn test_supabase_role_grants() {
+        let input = r#"
+GRANT ALL ON TABLE "public"."todos" TO "anon";
+GRANT ALL ON TABLE "public"."todos" TO "supabase_admin";
+GRANT SELECT ON TABLE "public"."todos" TO "supabase_admin", "app_ro", PUBLIC WITH GRANT OPTION;
+REVOKE ALL ON TABLE "public"."todos" FROM "authenticated", "app_ro";
+ALTER DEFAULT PRIVILEGES FOR ROLE "supabase_admin" IN SCHEMA "public" GRANT ALL ON TABLES TO "anon";
+ALTER DEFAULT PRIVILEGES FOR ROLE "postgres" IN SCHEMA "public" GRANT ALL ON TABLES TO "supabase_storage_admin";
+COPY "public"."audit" ("statement") FROM stdin;
+GRANT ALL ON TABLE "public"."todos" TO "supabase_admin";
+\.
+"#;
+        let mut transformer = LineTransformer::new(TransformKind::Supabase);
+        let result = transformer.apply(input).unwrap();
+        assert!(result.contains("\nGRANT ALL ON TABLE \"public\".\"todos\" TO \"anon\";"));
+        // Without a report, only the counts are kept
+        assert!(transformer.changes().is_empty());
+        // Rows are data, even when they read like a grant
+        assert!(result
+            .contains("\nGRANT ALL ON TABLE \"public\".\"todos\" TO \"supabase_admin\";\n\\."));
+        assert!(
+            result.contains("-- GRANT ALL ON TABLE \"public\".\"todos\" TO \"supabase_admin\";")
+        );
+        assert!(result.contains("TO \"app_ro\", PUBLIC WITH GRANT OPTION;"));
+        assert!(result.contains(
+            "\nREVOKE ALL ON TABLE \"public\".\"todos\" FROM \"authenticated\", \"app_ro\";"
+        ));
         assert!(result.contains("-- ALTER DEFAULT PRIVILEGES FOR ROLE \"supabase_admin\""));
+        assert!(result.contains("-- ALTER DEFAULT PRIVILEGES FOR ROLE \"postgres\""));
+        assert_eq!(
+            transformer.counts().keys().collect::<Vec<_>>(),
+            [&"supabase-roles"]
+        );
+        // Skipping the rule keeps the grants to the platform's roles
+        let kept = LineTransformer::new(TransformKind::Supabase)
+            .skip(vec![TransformRule::SupabaseRoles])
+            .apply(input)
+            .unwrap();
+        assert!(kept
+            .contains("\nGRANT ALL ON TABLE \"public\".\"todos\" TO \"supabase_admin\";\nGRANT"));
+
+        let result = LineTransformer::new(TransformKind::ForPostgres)
+            .skip(vec![TransformRule::ForPostgres])
+            .apply(input)
+            .unwrap();
+        assert!(result.contains("-- GRANT ALL ON TABLE \"public\".\"todos\" TO \"anon\";"));
+        assert!(result.contains("\nREVOKE ALL ON TABLE \"public\".\"todos\" FROM \"app_ro\";"));
     }
 }
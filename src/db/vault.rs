@@ -1,6 +1,8 @@
+use super::sql::quote_literal;
+use super::tools;
 use crate::error::{Result, SupamigrateError};
 use serde::{Deserialize, Serialize};
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 use tracing::debug;
 
 /// A secret stored in Supabase Vault
@@ -15,6 +17,14 @@ pub struct VaultSecret {
     pub updated_at: String,
 }
 
+/// A secret's name and description, without its value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
 /// Backup structure for vault secrets
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VaultBackup {
@@ -34,7 +44,7 @@ impl VaultClient {
 
     /// Execute a SQL query and return the output
     fn query(&self, sql: &str) -> Result<String> {
-        let mut cmd = Command::new("psql");
+        let mut cmd = tools::psql();
         cmd.arg(&self.db_url)
             .arg("-t") // Tuples only (no headers)
             .arg("-A") // Unaligned output
@@ -100,6 +110,23 @@ impl VaultClient {
         self.query_json(sql)
     }
 
+    /// Names and descriptions of the vault's secrets, read without
+    /// decrypting them, for when the key doesn't allow it
+    pub fn inventory(&self) -> Result<Vec<VaultEntry>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object('name', name, 'description', description)
+                    ORDER BY name
+                ),
+                '[]'::json
+            )::text
+            FROM vault.secrets
+        ";
+
+        self.query_json(sql)
+    }
+
     /// Create a new secret in the vault
     pub fn create_secret(
         &self,
@@ -107,20 +134,18 @@ impl VaultClient {
         value: &str,
         description: Option<&str>,
     ) -> Result<String> {
-        // Escape single quotes in values
-        let name_escaped = name.replace('\'', "''");
-        let value_escaped = value.replace('\'', "''");
-
         let sql = if let Some(desc) = description {
-            let desc_escaped = desc.replace('\'', "''");
             format!(
-                "SELECT vault.create_secret('{}', '{}', '{}')::text",
-                name_escaped, value_escaped, desc_escaped
+                "SELECT vault.create_secret({}, {}, {})::text",
+                quote_literal(name),
+                quote_literal(value),
+                quote_literal(desc)
             )
         } else {
             format!(
-                "SELECT vault.create_secret('{}', '{}')::text",
-                name_escaped, value_escaped
+                "SELECT vault.create_secret({}, {})::text",
+                quote_literal(name),
+                quote_literal(value)
             )
         };
 
@@ -136,19 +161,15 @@ impl VaultClient {
         new_name: Option<&str>,
         new_description: Option<&str>,
     ) -> Result<()> {
-        let value_escaped = new_value.replace('\'', "''");
-        let name_part = new_name.map_or_else(
-            || "NULL".to_string(),
-            |n| format!("'{}'", n.replace('\'', "''")),
-        );
-        let desc_part = new_description.map_or_else(
-            || "NULL".to_string(),
-            |d| format!("'{}'", d.replace('\'', "''")),
-        );
+        let name_part = new_name.map_or_else(|| "NULL".to_string(), quote_literal);
+        let desc_part = new_description.map_or_else(|| "NULL".to_string(), quote_literal);
 
         let sql = format!(
-            "SELECT vault.update_secret('{}', '{}', {}, {})",
-            id, value_escaped, name_part, desc_part
+            "SELECT vault.update_secret({}, {}, {}, {})",
+            quote_literal(id),
+            quote_literal(new_value),
+            name_part,
+            desc_part
         );
 
         self.query(&sql)?;
@@ -171,8 +192,8 @@ impl VaultClient {
         for secret in &backup.secrets {
             // Check if secret with same name exists
             let check_sql = format!(
-                "SELECT COUNT(*) FROM vault.decrypted_secrets WHERE name = '{}'",
-                secret.name.replace('\'', "''")
+                "SELECT COUNT(*) FROM vault.decrypted_secrets WHERE name = {}",
+                quote_literal(&secret.name)
             );
             let exists = self.query(&check_sql)?.parse::<i32>().unwrap_or(0) > 0;
 
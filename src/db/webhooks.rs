@@ -0,0 +1,139 @@
+use super::dump::PgDump;
+use super::native::NativeClient;
+use super::native_dump::Filter;
+use super::publications::TABLES_SQL;
+use super::sql::quote_ident;
+use crate::error::{Result, SupamigrateError};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// Triggers calling `supabase_functions.http_request()`, which is what a
+/// Database Webhook set up in the dashboard is
+const WEBHOOKS_SQL: &str = r"
+SELECT coalesce(json_agg(json_build_object(
+    'name', t.tgname,
+    'schema', n.nspname,
+    'table', c.relname,
+    'definition', pg_get_triggerdef(t.oid)
+) ORDER BY n.nspname, c.relname, t.tgname), '[]')
+FROM pg_trigger t
+JOIN pg_class c ON c.oid = t.tgrelid
+JOIN pg_namespace n ON n.oid = c.relnamespace
+JOIN pg_proc p ON p.oid = t.tgfoid
+JOIN pg_namespace pn ON pn.oid = p.pronamespace
+WHERE NOT t.tgisinternal
+  AND pn.nspname = 'supabase_functions'
+  AND p.proname = 'http_request'";
+
+const HTTP_REQUEST_SQL: &str =
+    "SELECT to_json(to_regproc('supabase_functions.http_request') IS NOT NULL)";
+
+/// A Database Webhook: a trigger on a table posting its rows to a URL
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Webhook {
+    pub name: String,
+    pub schema: String,
+    pub table: String,
+    /// `CREATE TRIGGER` statement, with the URL, method, headers, params and
+    /// timeout as arguments
+    pub definition: String,
+}
+
+impl Webhook {
+    /// Name for reports, as `schema.table: name`
+    pub fn label(&self) -> String {
+        format!("{}.{}: {}", self.schema, self.table, self.name)
+    }
+
+    /// Replace the target's trigger of the same name with this one, its
+    /// arguments passed through `rewrite`
+    fn recreate_sql(&self, rewrite: &impl Fn(&str) -> String) -> String {
+        format!(
+            "DROP TRIGGER IF EXISTS {} ON {}.{};\n{}",
+            quote_ident(&self.name),
+            quote_ident(&self.schema),
+            quote_ident(&self.table),
+            rewrite(&self.definition)
+        )
+    }
+}
+
+/// What copying the source's webhooks to the target did
+#[derive(Debug, Default)]
+pub struct WebhookSync {
+    pub created: Vec<String>,
+    /// Webhooks on tables the target doesn't have
+    pub missing: Vec<String>,
+}
+
+/// Recreate the Database Webhooks of `dump`'s source on `target_url`, with
+/// `rewrite` applied to their definitions so URLs and keys of the source
+/// project point at the target. Webhooks are chosen with the same filters as
+/// `dump`, and replace any trigger of the same name the restore created.
+pub async fn sync_webhooks(
+    dump: &PgDump,
+    target_url: &str,
+    rewrite: impl Fn(&str) -> String,
+) -> Result<WebhookSync> {
+    let filter = Filter::new(dump);
+    let webhooks: Vec<Webhook> = NativeClient::connect(&dump.db_url)
+        .await?
+        .query_json::<Vec<Webhook>>(WEBHOOKS_SQL)
+        .await?
+        .into_iter()
+        .filter(|w| filter.relation(&w.schema, &w.table))
+        .collect();
+    let mut sync = WebhookSync::default();
+    if webhooks.is_empty() {
+        return Ok(sync);
+    }
+
+    let client = NativeClient::connect(target_url).await?;
+    if !client.query_json::<bool>(HTTP_REQUEST_SQL).await? {
+        return Err(SupamigrateError::Database(
+            "Database Webhooks aren't enabled on the target; enable them in the dashboard \
+             (Database > Webhooks) first"
+                .to_string(),
+        ));
+    }
+    let existing: BTreeSet<(String, String)> = client.query_json(TABLES_SQL).await?;
+
+    let mut sql = Vec::new();
+    for webhook in &webhooks {
+        if existing.contains(&(webhook.schema.clone(), webhook.table.clone())) {
+            sql.push(webhook.recreate_sql(&rewrite));
+            sync.created.push(webhook.label());
+        } else {
+            sync.missing.push(webhook.label());
+        }
+    }
+    if !sql.is_empty() {
+        client.execute(&sql.join(";\n")).await?;
+    }
+    Ok(sync)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recreate_sql() {
+        let webhook = Webhook {
+            name: "notify-orders".to_string(),
+            schema: "public".to_string(),
+            table: "orders".to_string(),
+            definition: "CREATE TRIGGER \"notify-orders\" AFTER INSERT ON public.orders \
+                         FOR EACH ROW EXECUTE FUNCTION supabase_functions.http_request(\
+                         'https://prodref.supabase.co/functions/v1/notify', 'POST', \
+                         '{\"Content-type\":\"application/json\"}', '{}', '5000')"
+                .to_string(),
+        };
+        let sql = webhook.recreate_sql(&|s: &str| s.replace("prodref", "stagingref"));
+        assert!(
+            sql.starts_with("DROP TRIGGER IF EXISTS \"notify-orders\" ON \"public\".\"orders\";\n")
+        );
+        assert!(sql.contains("'https://stagingref.supabase.co/functions/v1/notify'"));
+        assert_eq!(webhook.label(), "public.orders: notify-orders");
+    }
+}
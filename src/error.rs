@@ -6,24 +6,44 @@ pub enum SupamigrateError {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Transform script error: {0}")]
+    TransformScript(String),
+
     #[error("Project not found: {0}")]
     ProjectNotFound(String),
 
+    #[error("{0} is a protected project; pass --allow-protected to write to it anyway")]
+    ProtectedProject(String),
+
     #[error("Database error: {0}")]
     Database(String),
 
+    #[error("Postgres error {code}: {message}")]
+    Postgres { code: String, message: String },
+
     #[error("pg_dump not found. Please install PostgreSQL client tools.")]
     PgDumpNotFound,
 
+    #[error(
+        "pg_dump {client} cannot dump a PostgreSQL {server} server. Install pg_dump {server} or newer, or run `supamigrate doctor --download`."
+    )]
+    PgDumpTooOld { server: u32, client: u32 },
+
     #[error("psql not found. Please install PostgreSQL client tools.")]
     PsqlNotFound,
 
+    #[error("pg_restore not found. Please install PostgreSQL client tools.")]
+    PgRestoreNotFound,
+
     #[error("pg_dump failed: {0}")]
     PgDumpFailed(String),
 
     #[error("psql failed: {0}")]
     PsqlFailed(String),
 
+    #[error("pg_restore failed: {0}")]
+    PgRestoreFailed(String),
+
     #[error("Storage error: {0}")]
     Storage(String),
 
@@ -39,6 +59,12 @@ pub enum SupamigrateError {
     #[error("Vault error: {0}")]
     Vault(String),
 
+    #[error("Auth error: {0}")]
+    Auth(String),
+
+    #[error("Branch error: {0}")]
+    Branch(String),
+
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
@@ -1,13 +1,14 @@
+use crate::config::ProjectConfig;
 use crate::error::{Result, SupamigrateError};
+use crate::http::SendLimited;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
-const SUPABASE_API_URL: &str = "https://api.supabase.com";
-
 #[derive(Debug, Clone)]
 pub struct FunctionsClient {
     client: Client,
+    api_url: String,
     project_ref: String,
     service_key: String,
 }
@@ -52,6 +53,37 @@ pub struct FunctionBackup {
     pub entrypoint_path: Option<String>,
     pub import_map_path: Option<String>,
     pub files: Vec<FunctionFile>,
+    /// Raw bundle as served by the source (eszip or gzipped tarball), kept
+    /// so deploys can re-upload it byte for byte
+    #[serde(skip)]
+    pub bundle: Option<Vec<u8>>,
+}
+
+impl FunctionBackup {
+    /// File name the bundle is stored under in backups
+    pub fn bundle_file_name(&self) -> Option<&'static str> {
+        self.bundle.as_deref().map(|b| {
+            if is_eszip(b) {
+                ESZIP_BUNDLE_FILE
+            } else {
+                TARBALL_BUNDLE_FILE
+            }
+        })
+    }
+}
+
+/// Backup file names for original function bundles
+pub const ESZIP_BUNDLE_FILE: &str = "bundle.eszip";
+pub const TARBALL_BUNDLE_FILE: &str = "bundle.tar.gz";
+
+/// Source of a function: extracted files plus the original bundle bytes
+pub struct FunctionSource {
+    pub files: Vec<FunctionFile>,
+    pub bundle: Option<Vec<u8>>,
+}
+
+fn is_eszip(data: &[u8]) -> bool {
+    data.starts_with(b"ESZIP")
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,10 +93,11 @@ pub struct FunctionFile {
 }
 
 impl FunctionsClient {
-    pub fn new(project_ref: String, service_key: String) -> Self {
+    pub fn for_project(project: &ProjectConfig, service_key: String) -> Self {
         Self {
-            client: Client::new(),
-            project_ref,
+            client: crate::http::client(),
+            api_url: project.management_api_url(),
+            project_ref: project.project_ref.clone(),
             service_key,
         }
     }
@@ -77,7 +110,7 @@ impl FunctionsClient {
     pub async fn list_functions(&self) -> Result<Vec<EdgeFunction>> {
         let url = format!(
             "{}/v1/projects/{}/functions",
-            SUPABASE_API_URL, self.project_ref
+            self.api_url, self.project_ref
         );
         debug!("Listing edge functions: {}", url);
 
@@ -85,7 +118,7 @@ impl FunctionsClient {
             .client
             .get(&url)
             .header("Authorization", self.auth_header())
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -106,7 +139,7 @@ impl FunctionsClient {
     pub async fn get_function(&self, slug: &str) -> Result<EdgeFunctionBody> {
         let url = format!(
             "{}/v1/projects/{}/functions/{}/body",
-            SUPABASE_API_URL, self.project_ref, slug
+            self.api_url, self.project_ref, slug
         );
         debug!("Getting function body: {}", url);
 
@@ -114,7 +147,7 @@ impl FunctionsClient {
             .client
             .get(&url)
             .header("Authorization", self.auth_header())
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -130,11 +163,11 @@ impl FunctionsClient {
         Ok(function)
     }
 
-    /// Download function source as a tarball and extract files
-    pub async fn download_function_source(&self, slug: &str) -> Result<Vec<FunctionFile>> {
+    /// Download function source and extract files, keeping the original bundle
+    pub async fn download_function_source(&self, slug: &str) -> Result<FunctionSource> {
         let url = format!(
             "{}/v1/projects/{}/functions/{}/body",
-            SUPABASE_API_URL, self.project_ref, slug
+            self.api_url, self.project_ref, slug
         );
         debug!("Downloading function source: {}", url);
 
@@ -143,7 +176,7 @@ impl FunctionsClient {
             .get(&url)
             .header("Authorization", self.auth_header())
             .header("Accept", "application/octet-stream")
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -165,21 +198,27 @@ impl FunctionsClient {
         if content_type.contains("application/json") {
             // Single file function returned as JSON
             let body: EdgeFunctionBody = response.json().await?;
-            if let Some(source) = body.body {
-                return Ok(vec![FunctionFile {
+            let files = body.body.map_or_else(Vec::new, |source| {
+                vec![FunctionFile {
                     name: body
                         .entrypoint_path
                         .unwrap_or_else(|| "index.ts".to_string()),
                     content: source,
-                }]);
-            }
-            return Ok(vec![]);
+                }]
+            });
+            return Ok(FunctionSource {
+                files,
+                bundle: None,
+            });
         }
 
-        // Tarball - extract files
+        // Bundle - extract files for readability, keep the bytes for deploys
         let bytes = response.bytes().await?;
         let files = extract_tarball(&bytes)?;
-        Ok(files)
+        Ok(FunctionSource {
+            files,
+            bundle: Some(bytes.to_vec()),
+        })
     }
 
     /// Create or update an edge function
@@ -191,15 +230,21 @@ impl FunctionsClient {
             .iter()
             .any(|f| f.slug == backup.slug);
 
+        // Re-upload an original eszip as-is rather than rebuilding it from
+        // extracted files, which changes the bundle
+        if let Some(bundle) = backup.bundle.as_deref().filter(|b| is_eszip(b)) {
+            return self.deploy_eszip(backup, bundle, exists).await;
+        }
+
         let url = if exists {
             format!(
                 "{}/v1/projects/{}/functions/{}",
-                SUPABASE_API_URL, self.project_ref, backup.slug
+                self.api_url, self.project_ref, backup.slug
             )
         } else {
             format!(
                 "{}/v1/projects/{}/functions",
-                SUPABASE_API_URL, self.project_ref
+                self.api_url, self.project_ref
             )
         };
 
@@ -232,7 +277,7 @@ impl FunctionsClient {
         let response = request
             .header("Authorization", self.auth_header())
             .multipart(form)
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -247,6 +292,68 @@ impl FunctionsClient {
         Ok(())
     }
 
+    /// Create or update a function from an eszip bundle
+    async fn deploy_eszip(
+        &self,
+        backup: &FunctionBackup,
+        bundle: &[u8],
+        exists: bool,
+    ) -> Result<()> {
+        let url = if exists {
+            format!(
+                "{}/v1/projects/{}/functions/{}",
+                self.api_url, self.project_ref, backup.slug
+            )
+        } else {
+            format!(
+                "{}/v1/projects/{}/functions",
+                self.api_url, self.project_ref
+            )
+        };
+
+        debug!(
+            "Deploying function '{}' from original eszip bundle (exists: {})",
+            backup.slug, exists
+        );
+
+        let mut query = vec![
+            ("slug", backup.slug.clone()),
+            ("name", backup.name.clone()),
+            ("verify_jwt", backup.verify_jwt.to_string()),
+        ];
+        if let Some(path) = &backup.entrypoint_path {
+            query.push(("entrypoint_path", path.clone()));
+        }
+        if let Some(path) = &backup.import_map_path {
+            query.push(("import_map_path", path.clone()));
+        }
+
+        let request = if exists {
+            self.client.patch(&url)
+        } else {
+            self.client.post(&url)
+        };
+
+        let response = request
+            .header("Authorization", self.auth_header())
+            .header("Content-Type", "application/vnd.denoland.eszip")
+            .query(&query)
+            .body(bundle.to_vec())
+            .send_limited()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SupamigrateError::Functions(format!(
+                "Failed to deploy function '{}' bundle: {} - {}",
+                backup.slug, status, body
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Backup all edge functions
     pub async fn backup_all(&self) -> Result<Vec<FunctionBackup>> {
         let functions = self.list_functions().await?;
@@ -254,7 +361,7 @@ impl FunctionsClient {
 
         for func in functions {
             debug!("Backing up function: {}", func.slug);
-            let files = self.download_function_source(&func.slug).await?;
+            let source = self.download_function_source(&func.slug).await?;
 
             backups.push(FunctionBackup {
                 slug: func.slug,
@@ -262,7 +369,8 @@ impl FunctionsClient {
                 verify_jwt: func.verify_jwt,
                 entrypoint_path: func.entrypoint_path,
                 import_map_path: func.import_map_path,
-                files,
+                files: source.files,
+                bundle: source.bundle,
             });
         }
 
@@ -1,13 +1,14 @@
+use crate::config::ProjectConfig;
 use crate::error::{Result, SupamigrateError};
+use crate::http::SendLimited;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
-const SUPABASE_API_URL: &str = "https://api.supabase.com";
-
 #[derive(Debug, Clone)]
 pub struct SecretsClient {
     client: Client,
+    api_url: String,
     project_ref: String,
     access_token: String,
 }
@@ -34,10 +35,11 @@ pub struct SecretsBackup {
 }
 
 impl SecretsClient {
-    pub fn new(project_ref: String, access_token: String) -> Self {
+    pub fn for_project(project: &ProjectConfig, access_token: String) -> Self {
         Self {
-            client: Client::new(),
-            project_ref,
+            client: crate::http::client(),
+            api_url: project.management_api_url(),
+            project_ref: project.project_ref.clone(),
             access_token,
         }
     }
@@ -48,17 +50,14 @@ impl SecretsClient {
 
     /// List all secrets (names only, values are not exposed)
     pub async fn list_secrets(&self) -> Result<Vec<SecretMetadata>> {
-        let url = format!(
-            "{}/v1/projects/{}/secrets",
-            SUPABASE_API_URL, self.project_ref
-        );
+        let url = format!("{}/v1/projects/{}/secrets", self.api_url, self.project_ref);
         debug!("Listing secrets: {}", url);
 
         let response = self
             .client
             .get(&url)
             .header("Authorization", self.auth_header())
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -80,10 +79,7 @@ impl SecretsClient {
             return Ok(());
         }
 
-        let url = format!(
-            "{}/v1/projects/{}/secrets",
-            SUPABASE_API_URL, self.project_ref
-        );
+        let url = format!("{}/v1/projects/{}/secrets", self.api_url, self.project_ref);
         debug!("Creating {} secrets", secrets.len());
 
         let response = self
@@ -92,7 +88,7 @@ impl SecretsClient {
             .header("Authorization", self.auth_header())
             .header("Content-Type", "application/json")
             .json(secrets)
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -114,10 +110,7 @@ impl SecretsClient {
             return Ok(());
         }
 
-        let url = format!(
-            "{}/v1/projects/{}/secrets",
-            SUPABASE_API_URL, self.project_ref
-        );
+        let url = format!("{}/v1/projects/{}/secrets", self.api_url, self.project_ref);
         debug!("Deleting {} secrets", names.len());
 
         let response = self
@@ -126,7 +119,7 @@ impl SecretsClient {
             .header("Authorization", self.auth_header())
             .header("Content-Type", "application/json")
             .json(names)
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -0,0 +1,142 @@
+use crate::config::HooksConfig;
+use crate::summary::RunSummary;
+use anyhow::Result;
+use console::style;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::process::Command;
+use tracing::debug;
+
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+
+/// Points of a run where a `[hooks]` command can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    BeforeMigrate,
+    AfterStorageSync,
+    AfterMigrate,
+    BeforeBackup,
+    AfterBackup,
+    OnFailure,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BeforeMigrate => "before_migrate",
+            Self::AfterStorageSync => "after_storage_sync",
+            Self::AfterMigrate => "after_migrate",
+            Self::BeforeBackup => "before_backup",
+            Self::AfterBackup => "after_backup",
+            Self::OnFailure => "on_failure",
+        }
+    }
+
+    /// Whether a failing command stops the run. Later hooks only warn, as
+    /// the work they follow is already done.
+    fn aborts(self) -> bool {
+        matches!(self, Self::BeforeMigrate | Self::BeforeBackup)
+    }
+
+    fn command(self, config: &HooksConfig) -> Option<&str> {
+        match self {
+            Self::BeforeMigrate => config.before_migrate.as_deref(),
+            Self::AfterStorageSync => config.after_storage_sync.as_deref(),
+            Self::AfterMigrate => config.after_migrate.as_deref(),
+            Self::BeforeBackup => config.before_backup.as_deref(),
+            Self::AfterBackup => config.after_backup.as_deref(),
+            Self::OnFailure => config.on_failure.as_deref(),
+        }
+    }
+}
+
+/// Configured hook commands and the context passed to them
+struct Hooks {
+    config: HooksConfig,
+    command: String,
+    /// `SUPAMIGRATE_*` variables set by the running command
+    env: Mutex<BTreeMap<String, String>>,
+}
+
+/// Enable hooks for this run
+pub fn init(config: &HooksConfig, command: &str) {
+    let _ = HOOKS.set(Hooks {
+        config: config.clone(),
+        command: command.to_string(),
+        env: Mutex::new(BTreeMap::new()),
+    });
+}
+
+/// Pass `value` to later hooks as `SUPAMIGRATE_<KEY>`
+pub fn context(key: &str, value: impl std::fmt::Display) {
+    if let Some(hooks) = HOOKS.get() {
+        if let Ok(mut env) = hooks.env.lock() {
+            env.insert(
+                format!("SUPAMIGRATE_{}", key.to_uppercase()),
+                value.to_string(),
+            );
+        }
+    }
+}
+
+/// Run the command configured for `hook`, if any. The statistics recorded
+/// in `summary` so far are passed as JSON in `SUPAMIGRATE_STATS`.
+pub async fn run(hook: Hook, summary: &mut RunSummary) -> Result<()> {
+    let Some(hooks) = HOOKS.get() else {
+        return Ok(());
+    };
+    let Some(command) = hook.command(&hooks.config) else {
+        return Ok(());
+    };
+
+    println!("\n{} Running {} hook...", style("🪝").bold(), hook.name());
+    match hooks.execute(hook, command, summary).await {
+        Ok(()) => {
+            println!("{} {} hook complete", style("✓").green(), hook.name());
+            Ok(())
+        }
+        Err(e) if hook.aborts() => Err(e),
+        Err(e) => {
+            println!("{} {:#}", style("⚠").yellow(), e);
+            summary.warn(format!("{:#}", e));
+            Ok(())
+        }
+    }
+}
+
+impl Hooks {
+    async fn execute(&self, hook: Hook, command: &str, summary: &RunSummary) -> Result<()> {
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C");
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c");
+            cmd
+        };
+        cmd.arg(command)
+            .env("SUPAMIGRATE_HOOK", hook.name())
+            .env("SUPAMIGRATE_COMMAND", &self.command)
+            .env(
+                "SUPAMIGRATE_STATS",
+                serde_json::to_string(&summary.phase_stats())?,
+            );
+        if let Ok(env) = self.env.lock() {
+            cmd.envs(env.iter());
+        }
+        if let Some(error) = summary.errors.last() {
+            cmd.env("SUPAMIGRATE_ERROR", error);
+        }
+
+        debug!("Running {} hook: {}", hook.name(), command);
+        let status = cmd
+            .status()
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not run {} hook: {}", hook.name(), e))?;
+        if !status.success() {
+            anyhow::bail!("{} hook failed ({}): {}", hook.name(), status, command);
+        }
+        Ok(())
+    }
+}
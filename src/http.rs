@@ -0,0 +1,161 @@
+use crate::config::{AuthHeaderStyle, HttpConfig};
+use crate::error::{Result, SupamigrateError};
+use reqwest::{Client, RequestBuilder, Response};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::debug;
+
+static SHARED_CLIENT: OnceLock<Client> = OnceLock::new();
+static RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+/// Spaces requests evenly so no more than a fixed number start per second
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next request may start
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Sending through the shared rate limiter, so parallel transfers can't
+/// exceed the configured request rate
+pub trait SendLimited {
+    fn send_limited(self) -> impl Future<Output = reqwest::Result<Response>> + Send;
+}
+
+impl SendLimited for RequestBuilder {
+    async fn send_limited(self) -> reqwest::Result<Response> {
+        if let Some(limiter) = RATE_LIMITER.get() {
+            limiter.acquire().await;
+        }
+        self.send().await
+    }
+}
+
+/// Service key headers for the Supabase REST APIs, in the project's style
+pub trait ServiceKeyAuth {
+    fn service_key_auth(self, service_key: &str, style: AuthHeaderStyle) -> Self;
+}
+
+impl ServiceKeyAuth for RequestBuilder {
+    fn service_key_auth(self, service_key: &str, style: AuthHeaderStyle) -> Self {
+        let request = match style {
+            AuthHeaderStyle::Apikey => self,
+            _ => self.bearer_auth(service_key),
+        };
+        match style {
+            AuthHeaderStyle::Bearer => request,
+            _ => request.header("apikey", service_key),
+        }
+    }
+}
+
+/// Build a tuned HTTP client from settings, trusting the given PEM CA
+/// certificates in addition to the built-in roots
+pub fn build_client(settings: &HttpConfig, ca_certificates: &[PathBuf]) -> Result<Client> {
+    let mut builder = Client::builder()
+        .pool_max_idle_per_host(settings.pool_max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(settings.pool_idle_timeout_secs))
+        .tcp_keepalive(Duration::from_secs(settings.tcp_keepalive_secs))
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs));
+
+    if let Some(secs) = settings.timeout_secs {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+
+    if settings.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+
+    // Without an explicit proxy, reqwest reads the *_PROXY variables itself
+    if let Some(proxy_url) = &settings.proxy {
+        let no_proxy = settings
+            .no_proxy
+            .clone()
+            .or_else(|| std::env::var("NO_PROXY").ok())
+            .or_else(|| std::env::var("no_proxy").ok());
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| SupamigrateError::Config(format!("Invalid proxy '{}': {}", proxy_url, e)))?
+            .no_proxy(no_proxy.as_deref().and_then(reqwest::NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+
+    for path in ca_certificates {
+        let pem = std::fs::read(path).map_err(|e| {
+            SupamigrateError::Config(format!(
+                "Cannot read CA certificate {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        for certificate in reqwest::Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Initialize the shared HTTP client used by all API clients.
+/// Has no effect if the client was already created.
+pub fn init(settings: &HttpConfig, ca_certificates: &[PathBuf]) -> Result<()> {
+    if SHARED_CLIENT.get().is_none() {
+        let client = build_client(settings, ca_certificates)?;
+        // Proxy URLs can carry credentials
+        let mut logged = settings.clone();
+        if logged.proxy.is_some() {
+            logged.proxy = Some("<redacted>".to_string());
+        }
+        debug!("Initialized shared HTTP client: {:?}", logged);
+        let _ = SHARED_CLIENT.set(client);
+    }
+    if let Some(rps) = settings.requests_per_second.filter(|rps| *rps > 0.0) {
+        let _ = RATE_LIMITER.set(RateLimiter::new(rps));
+    }
+    Ok(())
+}
+
+/// Get the shared HTTP client, creating one with default settings if needed.
+/// Cloning a `Client` is cheap and shares the underlying connection pool.
+pub fn client() -> Client {
+    SHARED_CLIENT
+        .get_or_init(|| build_client(&HttpConfig::default(), &[]).unwrap_or_default())
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter_spacing() {
+        let limiter = RateLimiter::new(50.0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        // First request is immediate, the next two wait 20ms each
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
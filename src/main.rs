@@ -6,40 +6,189 @@
 #![allow(clippy::too_many_lines)]
 #![allow(clippy::single_match_else)]
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+mod auth;
+mod branches;
+mod checkpoint;
 mod cli;
 mod commands;
 mod config;
 mod db;
 mod error;
 mod functions;
+mod hooks;
+mod http;
+mod notify;
 mod storage;
+mod summary;
 
 use cli::{Cli, Commands};
+use summary::RunSummary;
+use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
+    // Load .env before parsing so env-backed flags and ${VAR} references in
+    // the config file can come from it. Existing variables take precedence.
+    load_env_file()?;
+
+    let cli = Cli::parse();
+
+    // Initialize tracing. --verbose also shows pg_dump's per-object progress.
+    let level = if cli.verbose {
+        "supamigrate=debug"
+    } else {
+        "supamigrate=info"
+    };
+    // A JSON plan owns stdout, so logs go to stderr
+    let writer = match &cli.command {
+        Commands::Migrate(args) if args.plan_format == cli::PlanFormat::Json => {
+            BoxMakeWriter::new(std::io::stderr)
+        }
+        _ => BoxMakeWriter::new(std::io::stdout),
+    };
     tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::from_default_env().add_directive("supamigrate=info".parse()?))
+        .with(fmt::layer().with_writer(writer))
+        .with(EnvFilter::from_default_env().add_directive(level.parse()?))
         .init();
 
-    let cli = Cli::parse();
+    if cli.pooler {
+        config::force_pooler();
+    }
+    if let Some(database) = cli.database.clone() {
+        config::override_database(database);
+    }
+
+    // Commands get the same config the transport settings below come from
+    let config = if cli.command.reads_config() {
+        config::Config::load(cli.config.as_deref()).with_context(|| match &cli.config {
+            Some(path) => format!("Failed to load config {}", path.display()),
+            None => "Failed to load config".to_string(),
+        })?
+    } else {
+        config::Config::default()
+    };
+
+    // Share one tuned HTTP client across all API clients. Custom CAs of
+    // self-hosted projects also secure their API endpoints.
+    let ca_certificates: Vec<_> = config
+        .projects
+        .values()
+        .filter_map(config::ProjectConfig::sslrootcert_path)
+        .collect();
+    http::init(&config.http, &ca_certificates)?;
 
-    match cli.command {
-        Commands::Migrate(args) => commands::migrate::run(args).await,
-        Commands::Backup(args) => commands::backup::run(args).await,
-        Commands::Restore(args) => commands::restore::run(args).await,
-        Commands::Storage(args) => commands::storage::run(args).await,
-        Commands::Secrets(args) => commands::secrets::run(args).await,
-        Commands::Vault(args) => commands::vault::run(args),
-        Commands::Config(args) => commands::config::run(args),
+    // With the docker engine, restores also run psql in a container
+    let engine = match &cli.command {
+        Commands::Migrate(args) => args.engine,
+        Commands::Plan(args) => args.migrate.engine,
+        Commands::Backup(args) => args.engine,
+        _ => None,
+    };
+    if config.defaults.engine(engine) == cli::DumpEngine::Docker {
+        db::tools::use_docker(ca_certificates.clone());
+    }
+    db::retry::configure(db::retry::RetryPolicy::new(
+        config.defaults.retry_attempts,
+        std::time::Duration::from_secs(config.defaults.retry_backoff_secs),
+        &config.defaults.retry_on,
+    ));
+
+    // Long-running commands can report that they're still alive
+    if matches!(cli.command, Commands::Migrate(_) | Commands::Backup(_)) {
+        notify::init(&config.notify, cli.command.name());
+    }
+    let heartbeat = notify::spawn_heartbeat();
+
+    // migrate/backup/restore are recorded under runs/ for `supamigrate report`
+    let recorded = matches!(
+        cli.command,
+        Commands::Migrate(_) | Commands::Backup(_) | Commands::Restore(_)
+    );
+    if recorded || matches!(cli.command, Commands::Storage(_)) {
+        hooks::init(&config.hooks, cli.command.name());
+    }
+    let mut summary = RunSummary::start(cli.command.name());
+
+    let result = match cli.command {
+        Commands::Migrate(args) => {
+            Box::pin(commands::migrate::run(&config, args, &mut summary)).await
+        }
+        Commands::Plan(args) => Box::pin(commands::plan::run(&config, args, &mut summary)).await,
+        Commands::Backup(args) => commands::backup::run(&config, args, &mut summary).await,
+        Commands::Restore(args) => commands::restore::run(&config, args, &mut summary).await,
+        Commands::Storage(args) => commands::storage::run(&config, args, &mut summary).await,
+        Commands::Secrets(args) => commands::secrets::run(&config, args).await,
+        Commands::Vault(args) => commands::vault::run(&config, args),
+        Commands::Auth(args) => commands::auth::run(&config, args).await,
+        Commands::Config(args) => commands::config::run(&config, args),
+        Commands::Report(args) => commands::report::run(&args),
+        Commands::Diff(args) => commands::diff::run(&config, args),
+        Commands::Db(args) => commands::db::run(&config, args).await,
+        Commands::Cutover(args) => commands::cutover::run(&config, &args),
         Commands::Doctor(args) => {
             commands::doctor::run(commands::doctor::DoctorArgs { fix: args.fix })
         }
+        Commands::Upgrade(args) => commands::upgrade::run(&args).await,
+    };
+
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.abort();
     }
+    summary.finish(&result);
+    if result.is_err() {
+        // on_failure only warns, so it can't replace the command's error
+        hooks::run(hooks::Hook::OnFailure, &mut summary).await?;
+    }
+
+    if recorded {
+        match summary.save() {
+            Ok(path) => info!("Run summary saved to: {}", path.display()),
+            Err(e) => warn!("Could not save run summary: {}", e),
+        }
+    }
+
+    if let Some(path) = &cli.summary_file {
+        if let Err(e) = summary.write(path) {
+            // Don't mask the command's own error with a summary write failure
+            if result.is_ok() {
+                return Err(e.into());
+            }
+            warn!("Could not write summary file {}: {}", path.display(), e);
+        }
+    }
+
+    result
+}
+
+/// Load the dotenv file named by `--env-file` / `SUPAMIGRATE_ENV_FILE`, or
+/// `./.env` when it exists. Runs before clap so it scans the raw arguments.
+fn load_env_file() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let mut explicit = std::env::var_os("SUPAMIGRATE_ENV_FILE").map(std::path::PathBuf::from);
+    while let Some(arg) = args.next() {
+        if arg == "--env-file" {
+            explicit = args.next().map(std::path::PathBuf::from);
+        } else if let Some(path) = arg.strip_prefix("--env-file=") {
+            explicit = Some(path.into());
+        }
+    }
+
+    match explicit {
+        Some(path) => {
+            dotenvy::from_path(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to load {}: {}", path.display(), e))?;
+        }
+        None => match dotenvy::dotenv() {
+            Ok(_) => {}
+            Err(e) if e.not_found() => {}
+            Err(e) => return Err(anyhow::anyhow!("Failed to load .env: {}", e)),
+        },
+    }
+
+    Ok(())
 }
@@ -0,0 +1,160 @@
+use crate::config::NotifyConfig;
+use crate::http::SendLimited;
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+/// Posts JSON notifications about a running command to a webhook. The
+/// payload carries a `text` field so Slack-style incoming webhooks display
+/// it as-is.
+pub struct Notifier {
+    webhook_url: String,
+    command: String,
+    heartbeat_interval: Option<Duration>,
+    percent_step: Option<u64>,
+    started: Instant,
+    state: Mutex<NotifierState>,
+}
+
+#[derive(Default)]
+struct NotifierState {
+    phase: Option<String>,
+    /// Last percent step reported for the current progress label
+    reported: Option<(String, u64)>,
+}
+
+#[derive(Debug, Serialize)]
+struct Heartbeat<'a> {
+    text: String,
+    event: &'a str,
+    command: &'a str,
+    phase: Option<&'a str>,
+    elapsed_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress_percent: Option<u64>,
+}
+
+/// Enable notifications for this run. Has no effect without a webhook URL.
+pub fn init(config: &NotifyConfig, command: &str) {
+    let Some(webhook_url) = config.webhook_url.clone() else {
+        return;
+    };
+    let _ = NOTIFIER.set(Notifier {
+        webhook_url,
+        command: command.to_string(),
+        heartbeat_interval: config
+            .heartbeat_minutes
+            .filter(|m| *m > 0)
+            .map(|m| Duration::from_secs(m * 60)),
+        percent_step: config.heartbeat_percent.filter(|p| (1..100).contains(p)),
+        started: Instant::now(),
+        state: Mutex::new(NotifierState::default()),
+    });
+}
+
+/// Record the phase now running, reported in subsequent heartbeats
+pub fn phase(name: &str) {
+    if let Some(notifier) = NOTIFIER.get() {
+        if let Ok(mut state) = notifier.state.lock() {
+            state.phase = Some(name.to_string());
+            state.reported = None;
+        }
+    }
+}
+
+/// Report progress of a long-running step. A heartbeat is sent each time
+/// it crosses another `heartbeat_percent` step.
+pub fn progress(label: &str, done: u64, total: u64) {
+    let Some(notifier) = NOTIFIER.get() else {
+        return;
+    };
+    let Some(step) = notifier.percent_step else {
+        return;
+    };
+    if total == 0 {
+        return;
+    }
+
+    let percent = done * 100 / total;
+    let reached = percent / step * step;
+    {
+        let Ok(mut state) = notifier.state.lock() else {
+            return;
+        };
+        let last = match &state.reported {
+            Some((reported_label, last)) if reported_label == label => *last,
+            _ => 0,
+        };
+        if reached <= last || reached >= 100 {
+            return;
+        }
+        state.reported = Some((label.to_string(), reached));
+    }
+
+    let text = format!("{}: {}% ({}/{})", label, reached, done, total);
+    tokio::spawn(notifier.send("progress", text, Some(reached)));
+}
+
+/// Start periodic heartbeats, if configured. Abort the handle when the
+/// command finishes.
+pub fn spawn_heartbeat() -> Option<JoinHandle<()>> {
+    let notifier = NOTIFIER.get()?;
+    let interval = notifier.heartbeat_interval?;
+
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick fires immediately; the run has only just started
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            notifier
+                .send("heartbeat", "still running".to_string(), None)
+                .await;
+        }
+    }))
+}
+
+impl Notifier {
+    async fn send(&self, event: &str, message: String, progress_percent: Option<u64>) {
+        let phase = self.state.lock().ok().and_then(|s| s.phase.clone());
+        let elapsed = self.started.elapsed().as_secs();
+
+        let payload = Heartbeat {
+            text: format!(
+                "supamigrate {} {} after {}m{:02}s{}",
+                self.command,
+                message,
+                elapsed / 60,
+                elapsed % 60,
+                phase
+                    .as_deref()
+                    .map(|p| format!(" (phase: {})", p))
+                    .unwrap_or_default()
+            ),
+            event,
+            command: &self.command,
+            phase: phase.as_deref(),
+            elapsed_secs: elapsed,
+            progress_percent,
+        };
+
+        debug!("Sending {} notification", event);
+        let result = crate::http::client()
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send_limited()
+            .await;
+
+        match result {
+            Ok(response) if !response.status().is_success() => {
+                warn!("Notification webhook returned {}", response.status());
+            }
+            Err(e) => warn!("Could not send notification: {}", e),
+            Ok(_) => {}
+        }
+    }
+}
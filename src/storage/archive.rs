@@ -0,0 +1,123 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// Extension of per-bucket archives in storage backups
+pub const ARCHIVE_EXTENSION: &str = "tar.zst";
+
+/// One object stored in a bucket archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Writes a bucket as a single zstd-compressed tarball, plus a JSON index
+/// (`<bucket>.index.json`) listing its objects so the archive doesn't have
+/// to be decompressed to see what's in it
+pub struct BucketArchiveWriter {
+    builder: tar::Builder<zstd::Encoder<'static, BufWriter<File>>>,
+    index_path: PathBuf,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl BucketArchiveWriter {
+    pub fn create(output_dir: &Path, bucket_name: &str) -> Result<Self> {
+        let file = File::create(archive_path(output_dir, bucket_name))?;
+        let encoder = zstd::Encoder::new(BufWriter::new(file), 0)?;
+        Ok(Self {
+            builder: tar::Builder::new(encoder),
+            index_path: output_dir.join(format!("{}.index.json", bucket_name)),
+            entries: Vec::new(),
+        })
+    }
+
+    pub fn append(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp().unsigned_abs());
+        self.builder.append_data(&mut header, name, data)?;
+        self.entries.push(ArchiveEntry {
+            name: name.to_string(),
+            size: data.len() as u64,
+        });
+        Ok(())
+    }
+
+    /// Flush the archive and write its index
+    pub fn finish(self) -> Result<Vec<ArchiveEntry>> {
+        self.builder.into_inner()?.finish()?;
+        std::fs::write(
+            &self.index_path,
+            serde_json::to_string_pretty(&self.entries)?,
+        )?;
+        Ok(self.entries)
+    }
+}
+
+/// Path of a bucket's archive inside a storage backup directory
+pub fn archive_path(output_dir: &Path, bucket_name: &str) -> PathBuf {
+    output_dir.join(format!("{}.{}", bucket_name, ARCHIVE_EXTENSION))
+}
+
+/// Bucket name of an archive file, or None if the path isn't an archive
+pub fn archive_bucket_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    file_name
+        .strip_suffix(&format!(".{}", ARCHIVE_EXTENSION))
+        .map(String::from)
+}
+
+/// Read every object of a bucket archive, calling `f` with its name and bytes
+pub fn read_bucket_archive(
+    path: &Path,
+    mut f: impl FnMut(String, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    let decoder = zstd::Decoder::new(BufReader::new(File::open(path)?))?;
+    let mut archive = tar::Archive::new(decoder);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut data = Vec::with_capacity(usize::try_from(entry.size()).unwrap_or(0));
+        entry.read_to_end(&mut data)?;
+        f(name, data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_archive_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut writer = BucketArchiveWriter::create(dir.path(), "avatars").unwrap();
+        writer.append("users/1.png", b"png").unwrap();
+        writer.append("users/2.png", b"second").unwrap();
+        let entries = writer.finish().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let path = archive_path(dir.path(), "avatars");
+        assert_eq!(archive_bucket_name(&path).as_deref(), Some("avatars"));
+        assert!(dir.path().join("avatars.index.json").exists());
+
+        let mut objects = Vec::new();
+        read_bucket_archive(&path, |name, data| {
+            objects.push((name, data));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(objects[0], ("users/1.png".to_string(), b"png".to_vec()));
+        assert_eq!(objects[1].1, b"second");
+    }
+}
@@ -1,5 +1,9 @@
+use crate::config::{AuthHeaderStyle, ProjectConfig};
 use crate::error::{Result, SupamigrateError};
+use crate::http::{SendLimited, ServiceKeyAuth};
+use base64::Engine;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
@@ -7,8 +11,9 @@ use tracing::debug;
 #[derive(Debug, Clone)]
 pub struct StorageClient {
     client: Client,
-    api_url: String,
+    storage_url: String,
     service_key: String,
+    auth_style: AuthHeaderStyle,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +21,10 @@ pub struct Bucket {
     pub id: String,
     pub name: String,
     pub public: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_mime_types: Option<Vec<String>>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -26,44 +35,121 @@ pub struct StorageObject {
     pub id: Option<String>,
     #[serde(default)]
     pub metadata: Option<serde_json::Value>,
+    /// Custom metadata set by the uploader (newer storage API versions only)
+    #[serde(default)]
+    pub user_metadata: Option<serde_json::Value>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-struct CreateBucketRequest {
-    name: String,
-    public: bool,
+/// Object attributes that aren't part of its bytes, kept so uploads can
+/// reproduce them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObjectMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_metadata: Option<serde_json::Value>,
+    /// Recorded for reference only; storage sets its own timestamp on upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
-impl StorageClient {
-    pub fn new(api_url: String, service_key: String) -> Self {
+impl StorageObject {
+    /// Object size in bytes as reported in the storage metadata
+    pub fn size(&self) -> Option<u64> {
+        self.metadata.as_ref()?.get("size")?.as_u64()
+    }
+
+    /// Content type, cache control and custom metadata of the object
+    pub fn object_metadata(&self) -> ObjectMetadata {
+        let field = |key: &str| {
+            self.metadata
+                .as_ref()
+                .and_then(|m| m.get(key))
+                .and_then(|v| v.as_str())
+                .map(String::from)
+        };
+        ObjectMetadata {
+            content_type: field("mimetype"),
+            cache_control: field("cacheControl"),
+            user_metadata: self
+                .user_metadata
+                .clone()
+                .filter(|m| m.as_object().is_some_and(|o| !o.is_empty())),
+            updated_at: self.updated_at.clone(),
+        }
+    }
+
+    /// Last modification time, if reported and parseable
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        let updated_at = self.updated_at.as_deref()?;
+        DateTime::parse_from_rfc3339(updated_at)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// Settings for creating a bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct NewBucket {
+    pub id: String,
+    pub name: String,
+    pub public: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_mime_types: Option<Vec<String>>,
+}
+
+impl NewBucket {
+    /// A private bucket with no limits whose id equals its name
+    pub fn private(name: &str) -> Self {
         Self {
-            client: Client::new(),
-            api_url,
-            service_key,
+            id: name.to_string(),
+            name: name.to_string(),
+            public: false,
+            file_size_limit: None,
+            allowed_mime_types: None,
         }
     }
+}
 
-    fn storage_url(&self) -> String {
-        format!("{}/storage/v1", self.api_url)
+impl From<&Bucket> for NewBucket {
+    fn from(bucket: &Bucket) -> Self {
+        Self {
+            id: bucket.id.clone(),
+            name: bucket.name.clone(),
+            public: bucket.public,
+            file_size_limit: bucket.file_size_limit,
+            allowed_mime_types: bucket.allowed_mime_types.clone(),
+        }
     }
+}
 
-    fn auth_header(&self) -> String {
-        format!("Bearer {}", self.service_key)
+impl StorageClient {
+    /// Client for a project's storage API, authenticated with its service key
+    pub fn for_project(project: &ProjectConfig, service_key: String) -> Self {
+        Self {
+            client: crate::http::client(),
+            storage_url: project.storage_url(),
+            service_key,
+            auth_style: project.auth_header_style,
+        }
     }
 
     /// List all buckets
     pub async fn list_buckets(&self) -> Result<Vec<Bucket>> {
-        let url = format!("{}/bucket", self.storage_url());
+        let url = format!("{}/bucket", self.storage_url);
         debug!("Listing buckets: {}", url);
 
         let response = self
             .client
             .get(&url)
-            .header("Authorization", self.auth_header())
-            .header("apikey", &self.service_key)
-            .send()
+            .service_key_auth(&self.service_key, self.auth_style)
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -79,21 +165,17 @@ impl StorageClient {
         Ok(buckets)
     }
 
-    /// Create a bucket
-    pub async fn create_bucket(&self, name: &str, public: bool) -> Result<()> {
-        let url = format!("{}/bucket", self.storage_url());
-        debug!("Creating bucket: {}", name);
+    /// Create a bucket with an explicit id (which may differ from its name)
+    pub async fn create_bucket(&self, bucket: &NewBucket) -> Result<()> {
+        let url = format!("{}/bucket", self.storage_url);
+        debug!("Creating bucket: {} (id: {})", bucket.name, bucket.id);
 
         let response = self
             .client
             .post(&url)
-            .header("Authorization", self.auth_header())
-            .header("apikey", &self.service_key)
-            .json(&CreateBucketRequest {
-                name: name.to_string(),
-                public,
-            })
-            .send()
+            .service_key_auth(&self.service_key, self.auth_style)
+            .json(bucket)
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -103,7 +185,7 @@ impl StorageClient {
             if !body.contains("already exists") {
                 return Err(SupamigrateError::Storage(format!(
                     "Failed to create bucket '{}': {} - {}",
-                    name, status, body
+                    bucket.name, status, body
                 )));
             }
         }
@@ -117,7 +199,7 @@ impl StorageClient {
         bucket: &str,
         prefix: Option<&str>,
     ) -> Result<Vec<StorageObject>> {
-        let url = format!("{}/object/list/{}", self.storage_url(), bucket);
+        let url = format!("{}/object/list/{}", self.storage_url, bucket);
         debug!("Listing objects in bucket: {}", bucket);
 
         let mut body = serde_json::json!({
@@ -132,10 +214,9 @@ impl StorageClient {
         let response = self
             .client
             .post(&url)
-            .header("Authorization", self.auth_header())
-            .header("apikey", &self.service_key)
+            .service_key_auth(&self.service_key, self.auth_style)
             .json(&body)
-            .send()
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -153,15 +234,14 @@ impl StorageClient {
 
     /// Download an object
     pub async fn download(&self, bucket: &str, path: &str) -> Result<Bytes> {
-        let url = format!("{}/object/{}/{}", self.storage_url(), bucket, path);
+        let url = format!("{}/object/{}/{}", self.storage_url, bucket, path);
         debug!("Downloading: {}/{}", bucket, path);
 
         let response = self
             .client
             .get(&url)
-            .header("Authorization", self.auth_header())
-            .header("apikey", &self.service_key)
-            .send()
+            .service_key_auth(&self.service_key, self.auth_style)
+            .send_limited()
             .await?;
 
         if !response.status().is_success() {
@@ -179,18 +259,41 @@ impl StorageClient {
 
     /// Upload an object
     pub async fn upload(&self, bucket: &str, path: &str, data: Bytes) -> Result<()> {
-        let url = format!("{}/object/{}/{}", self.storage_url(), bucket, path);
+        self.upload_with_metadata(bucket, path, data, None).await
+    }
+
+    /// Upload an object, restoring its content type, cache control and
+    /// custom metadata when given
+    pub async fn upload_with_metadata(
+        &self,
+        bucket: &str,
+        path: &str,
+        data: Bytes,
+        metadata: Option<&ObjectMetadata>,
+    ) -> Result<()> {
+        let url = format!("{}/object/{}/{}", self.storage_url, bucket, path);
         debug!("Uploading: {}/{}", bucket, path);
 
-        let response = self
+        let content_type = metadata
+            .and_then(|m| m.content_type.as_deref())
+            .unwrap_or("application/octet-stream");
+
+        let mut request = self
             .client
             .post(&url)
-            .header("Authorization", self.auth_header())
-            .header("apikey", &self.service_key)
-            .header("Content-Type", "application/octet-stream")
-            .body(data)
-            .send()
-            .await?;
+            .service_key_auth(&self.service_key, self.auth_style)
+            .header("Content-Type", content_type);
+
+        if let Some(cache_control) = metadata.and_then(|m| m.cache_control.as_deref()) {
+            request = request.header("Cache-Control", cache_control);
+        }
+        if let Some(user_metadata) = metadata.and_then(|m| m.user_metadata.as_ref()) {
+            let encoded = base64::engine::general_purpose::STANDARD
+                .encode(serde_json::to_vec(user_metadata)?);
+            request = request.header("x-metadata", encoded);
+        }
+
+        let response = request.body(data).send_limited().await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -203,4 +306,47 @@ impl StorageClient {
 
         Ok(())
     }
+
+    /// Download a byte range of an object (`start` and `end` are inclusive)
+    pub async fn download_range(
+        &self,
+        bucket: &str,
+        path: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Bytes> {
+        let url = format!("{}/object/{}/{}", self.storage_url, bucket, path);
+        debug!("Downloading range {}-{}: {}/{}", start, end, bucket, path);
+
+        let response = self
+            .client
+            .get(&url)
+            .service_key_auth(&self.service_key, self.auth_style)
+            .header("Range", format!("bytes={}-{}", start, end))
+            .send_limited()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SupamigrateError::Storage(format!(
+                "Failed to download range {}-{} of '{}/{}': {} - {}",
+                start, end, bucket, path, status, body
+            )));
+        }
+
+        let bytes = response.bytes().await?;
+        let expected = end - start + 1;
+        if bytes.len() as u64 != expected {
+            return Err(SupamigrateError::Storage(format!(
+                "Short range read for '{}/{}': expected {} bytes, got {}",
+                bucket,
+                path,
+                expected,
+                bytes.len()
+            )));
+        }
+
+        Ok(bytes)
+    }
 }
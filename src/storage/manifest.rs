@@ -0,0 +1,34 @@
+use crate::error::Result;
+use crate::storage::client::ObjectMetadata;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Object metadata of one bucket in a storage backup, keyed by object name
+pub type BucketManifest = BTreeMap<String, ObjectMetadata>;
+
+/// Path of a bucket's metadata manifest inside a storage backup directory
+pub fn manifest_path(output_dir: &Path, bucket_name: &str) -> PathBuf {
+    output_dir.join(format!("{}.metadata.json", bucket_name))
+}
+
+pub fn write_manifest(
+    output_dir: &Path,
+    bucket_name: &str,
+    manifest: &BucketManifest,
+) -> Result<()> {
+    std::fs::write(
+        manifest_path(output_dir, bucket_name),
+        serde_json::to_string_pretty(manifest)?,
+    )?;
+    Ok(())
+}
+
+/// Read a bucket's manifest; backups made before manifests existed have
+/// none, in which case objects are restored without metadata
+pub fn read_manifest(output_dir: &Path, bucket_name: &str) -> Result<BucketManifest> {
+    let path = manifest_path(output_dir, bucket_name);
+    if !path.exists() {
+        return Ok(BucketManifest::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
@@ -1,5 +1,9 @@
+pub mod archive;
 mod client;
+pub mod manifest;
+mod state;
 mod transfer;
 
-pub use client::StorageClient;
-pub use transfer::StorageTransfer;
+pub use client::{Bucket, NewBucket, StorageClient};
+pub use state::SyncState;
+pub use transfer::{human_bytes, StorageTransfer, SyncStats, TransferEstimate};
@@ -0,0 +1,53 @@
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const STATE_PATH: &str = "~/.config/supamigrate/storage-sync-state.json";
+
+/// Timestamps of previous successful storage syncs, keyed by source/target/bucket
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub runs: HashMap<String, DateTime<Utc>>,
+}
+
+impl SyncState {
+    fn path() -> PathBuf {
+        PathBuf::from(shellexpand::tilde(STATE_PATH).as_ref())
+    }
+
+    /// Load sync state, or an empty state if none was recorded yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Persist sync state
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Key identifying a sync between two projects (optionally a single bucket)
+    pub fn key(from: &str, to: &str, bucket: Option<&str>) -> String {
+        format!("{}->{}:{}", from, to, bucket.unwrap_or("*"))
+    }
+
+    pub fn last_run(&self, key: &str) -> Option<DateTime<Utc>> {
+        self.runs.get(key).copied()
+    }
+
+    pub fn record_run(&mut self, key: String, started_at: DateTime<Utc>) {
+        self.runs.insert(key, started_at);
+    }
+}
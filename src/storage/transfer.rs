@@ -1,16 +1,39 @@
+use crate::checkpoint::Checkpoint;
 use crate::error::Result;
-use crate::storage::client::{Bucket, StorageClient, StorageObject};
-use futures::stream::{self, StreamExt};
+use crate::storage::archive::BucketArchiveWriter;
+use crate::storage::client::{Bucket, NewBucket, StorageClient, StorageObject};
+use crate::storage::manifest::{write_manifest, BucketManifest};
+use crate::summary::RunSummary;
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Serialize;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
-use tracing::info;
+use tracing::{debug, info, warn};
+
+/// Objects larger than this are downloaded in parallel byte ranges
+pub const DEFAULT_RANGED_THRESHOLD: u64 = 256 * 1024 * 1024;
+
+/// Size of each byte range for ranged downloads
+const RANGE_PART_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Number of byte ranges fetched concurrently for a single object
+const RANGE_PART_PARALLEL: usize = 4;
+
+/// Attempts per byte range before the whole object is failed
+const RANGE_PART_RETRIES: u32 = 3;
 
 pub struct StorageTransfer {
     pub source: StorageClient,
     target: Option<StorageClient>,
     parallel: usize,
+    ranged_threshold: u64,
+    since: Option<DateTime<Utc>>,
+    archive: bool,
 }
 
 impl StorageTransfer {
@@ -19,6 +42,9 @@ impl StorageTransfer {
             source,
             target: None,
             parallel: 4,
+            ranged_threshold: DEFAULT_RANGED_THRESHOLD,
+            since: None,
+            archive: false,
         }
     }
 
@@ -32,8 +58,28 @@ impl StorageTransfer {
         self
     }
 
-    /// Sync all buckets from source to target
-    pub async fn sync_all(&self) -> Result<SyncStats> {
+    /// Set the object size (in bytes) above which ranged downloads are used
+    pub fn ranged_threshold(mut self, bytes: u64) -> Self {
+        self.ranged_threshold = bytes;
+        self
+    }
+
+    /// Only transfer objects updated after this time
+    pub fn since(mut self, cutoff: Option<DateTime<Utc>>) -> Self {
+        self.since = cutoff;
+        self
+    }
+
+    /// Write each downloaded bucket as a single archive instead of one file
+    /// per object
+    pub fn archive(mut self, enabled: bool) -> Self {
+        self.archive = enabled;
+        self
+    }
+
+    /// Sync all buckets from source to target. Buckets the checkpoint has
+    /// as done are skipped, and buckets synced without errors are added.
+    pub async fn sync_all(&self, checkpoint: Option<&Checkpoint>) -> Result<SyncStats> {
         let target = self
             .target
             .as_ref()
@@ -45,10 +91,18 @@ impl StorageTransfer {
         let mut stats = SyncStats::default();
 
         for bucket in buckets {
+            let step = format!("bucket:{}", bucket.name);
+            if checkpoint.is_some_and(|c| c.is_done(&step)) {
+                info!("Skipping bucket {} (synced before)", bucket.name);
+                continue;
+            }
             let bucket_stats = self.sync_bucket(&bucket.name, target).await?;
             stats.buckets += 1;
             stats.objects += bucket_stats.objects;
             stats.bytes += bucket_stats.bytes;
+            if let Some(checkpoint) = checkpoint.filter(|_| bucket_stats.errors == 0) {
+                checkpoint.complete(&step)?;
+            }
         }
 
         Ok(stats)
@@ -71,11 +125,58 @@ impl StorageTransfer {
                 crate::error::SupamigrateError::BucketNotFound(bucket_name.to_string())
             })?;
 
-        target.create_bucket(&bucket.name, bucket.public).await?;
+        // Preserve the bucket id so data referencing it keeps working
+        target.create_bucket(&NewBucket::from(bucket)).await?;
 
-        // List and transfer objects
-        let objects = self.source.list_objects(bucket_name, None).await?;
-        self.transfer_objects(bucket_name, &objects, target).await
+        // List and transfer objects (object routes are keyed by bucket id)
+        let objects = self.select_objects(bucket).await?;
+        self.transfer_objects(&bucket.id, &objects, target).await
+    }
+
+    /// List the objects of a bucket that a sync would transfer
+    async fn select_objects(&self, bucket: &Bucket) -> Result<Vec<StorageObject>> {
+        let mut objects = self.source.list_objects(&bucket.id, None).await?;
+        if let Some(cutoff) = self.since {
+            let total = objects.len();
+            // Objects without a parseable timestamp are transferred to be safe
+            objects.retain(|obj| obj.updated_at().map_or(true, |t| t > cutoff));
+            info!(
+                "{} of {} objects in {} changed since {}",
+                objects.len(),
+                total,
+                bucket.name,
+                cutoff.to_rfc3339()
+            );
+        }
+        Ok(objects)
+    }
+
+    /// Estimate how much a sync would download from the source, either for
+    /// one bucket or for all of them. Nothing is transferred.
+    pub async fn estimate(&self, bucket_name: Option<&str>) -> Result<TransferEstimate> {
+        let buckets = self.source.list_buckets().await?;
+        let buckets: Vec<&Bucket> = match bucket_name {
+            Some(name) => vec![buckets
+                .iter()
+                .find(|b| b.name == name)
+                .ok_or_else(|| crate::error::SupamigrateError::BucketNotFound(name.to_string()))?],
+            None => buckets.iter().collect(),
+        };
+
+        let mut estimate = TransferEstimate::default();
+        for bucket in buckets {
+            let objects = self.select_objects(bucket).await?;
+            estimate.buckets += 1;
+            estimate.objects += objects.len();
+            for obj in &objects {
+                match obj.size() {
+                    Some(size) => estimate.bytes += size,
+                    None => estimate.unknown_sizes += 1,
+                }
+            }
+        }
+
+        Ok(estimate)
     }
 
     /// Transfer objects with progress
@@ -107,13 +208,20 @@ impl StorageTransfer {
                 let target = Arc::clone(&target);
                 let bucket = bucket.clone();
                 let name = obj.name.clone();
+                let object_size = obj.size();
+                let metadata = obj.object_metadata();
+                let threshold = self.ranged_threshold;
                 let pb = pb.clone();
 
                 async move {
-                    let data = source.download(&bucket, &name).await?;
+                    let data =
+                        fetch_object(&source, &bucket, &name, object_size, threshold).await?;
                     let size = data.len();
-                    target.upload(&bucket, &name, data).await?;
+                    target
+                        .upload_with_metadata(&bucket, &name, data, Some(&metadata))
+                        .await?;
                     pb.inc(1);
+                    crate::notify::progress(&bucket, pb.position(), pb.length().unwrap_or(0));
                     Ok(size)
                 }
             })
@@ -159,11 +267,24 @@ impl StorageTransfer {
     /// Download a bucket to local directory
     pub async fn download_bucket(&self, bucket: &Bucket, output_dir: &Path) -> Result<SyncStats> {
         let bucket_dir = output_dir.join(&bucket.name);
-        fs::create_dir_all(&bucket_dir).await?;
-
-        let objects = self.source.list_objects(&bucket.name, None).await?;
+        let mut archive = if self.archive {
+            Some(BucketArchiveWriter::create(output_dir, &bucket.name)?)
+        } else {
+            fs::create_dir_all(&bucket_dir).await?;
+            None
+        };
+        let archive_mode = archive.is_some();
+
+        let objects = self.source.list_objects(&bucket.id, None).await?;
         info!("Downloading {} objects from {}", objects.len(), bucket.name);
 
+        // Bytes alone lose content type, cache control and custom metadata
+        let manifest: BucketManifest = objects
+            .iter()
+            .map(|obj| (obj.name.clone(), obj.object_metadata()))
+            .collect();
+        write_manifest(output_dir, &bucket.name, &manifest)?;
+
         let multi = MultiProgress::new();
         let pb = multi.add(ProgressBar::new(objects.len() as u64));
         pb.set_style(
@@ -175,40 +296,47 @@ impl StorageTransfer {
         pb.set_message(format!("Downloading {}", bucket.name));
 
         let source = Arc::new(self.source.clone());
-        let bucket_name = bucket.name.clone();
+        let bucket_id = bucket.id.clone();
 
         let mut stats = SyncStats::default();
 
-        let results: Vec<Result<usize>> = stream::iter(objects.iter())
+        // Objects are fetched concurrently; in archive mode their bytes are
+        // handed back and appended to the archive one at a time
+        let mut results = stream::iter(objects.iter())
             .map(|obj| {
                 let source = Arc::clone(&source);
-                let bucket_name = bucket_name.clone();
+                let bucket_id = bucket_id.clone();
                 let bucket_dir = bucket_dir.clone();
                 let name = obj.name.clone();
-                let pb = pb.clone();
+                let object_size = obj.size();
+                let threshold = self.ranged_threshold;
 
                 async move {
-                    let data = source.download(&bucket_name, &name).await?;
+                    let data =
+                        fetch_object(&source, &bucket_id, &name, object_size, threshold).await?;
+
                     let size = data.len();
+                    if archive_mode {
+                        return Ok((name, Some(data), size));
+                    }
 
                     let file_path = bucket_dir.join(&name);
                     if let Some(parent) = file_path.parent() {
                         fs::create_dir_all(parent).await?;
                     }
                     fs::write(&file_path, &data).await?;
-
-                    pb.inc(1);
-                    Ok(size)
+                    Ok((name, None, size))
                 }
             })
-            .buffer_unordered(self.parallel)
-            .collect()
-            .await;
-
-        pb.finish_with_message("Done");
-
-        for result in results {
-            match result {
+            .buffer_unordered(self.parallel);
+
+        while let Some(result) = results.next().await {
+            let result: Result<(String, Option<Bytes>, usize)> = result;
+            let written = result.and_then(|(name, data, size)| match (&mut archive, data) {
+                (Some(writer), Some(data)) => writer.append(&name, &data).map(|()| size),
+                _ => Ok(size),
+            });
+            match written {
                 Ok(size) => {
                     stats.objects += 1;
                     stats.bytes += size;
@@ -218,12 +346,131 @@ impl StorageTransfer {
                     tracing::warn!("Download error: {}", e);
                 }
             }
+            pb.inc(1);
+            crate::notify::progress(&bucket.name, pb.position(), objects.len() as u64);
+        }
+
+        pb.finish_with_message("Done");
+
+        if let Some(writer) = archive {
+            writer.finish()?;
         }
 
         Ok(stats)
     }
 }
 
+/// Download an object, splitting it into parallel byte ranges when it is large
+async fn fetch_object(
+    source: &StorageClient,
+    bucket: &str,
+    name: &str,
+    size: Option<u64>,
+    threshold: u64,
+) -> Result<Bytes> {
+    match size {
+        Some(size) if size > threshold => download_ranged(source, bucket, name, size).await,
+        _ => source.download(bucket, name).await,
+    }
+}
+
+/// Download an object in byte ranges and reassemble it in order
+async fn download_ranged(
+    source: &StorageClient,
+    bucket: &str,
+    name: &str,
+    size: u64,
+) -> Result<Bytes> {
+    let parts = size.div_ceil(RANGE_PART_SIZE);
+    info!(
+        "Downloading {}/{} in {} ranges ({})",
+        bucket,
+        name,
+        parts,
+        human_bytes(size)
+    );
+
+    let chunks: Vec<Bytes> = stream::iter(0..parts)
+        .map(|part| {
+            let start = part * RANGE_PART_SIZE;
+            let end = (start + RANGE_PART_SIZE).min(size) - 1;
+            download_part(source, bucket, name, start, end)
+        })
+        .buffered(RANGE_PART_PARALLEL)
+        .try_collect()
+        .await?;
+
+    let mut data = BytesMut::with_capacity(usize::try_from(size).unwrap_or(0));
+    for chunk in chunks {
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data.freeze())
+}
+
+/// Download a single byte range, retrying with backoff on failure
+async fn download_part(
+    source: &StorageClient,
+    bucket: &str,
+    name: &str,
+    start: u64,
+    end: u64,
+) -> Result<Bytes> {
+    let mut attempt = 1;
+    loop {
+        match source.download_range(bucket, name, start, end).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if attempt < RANGE_PART_RETRIES => {
+                warn!(
+                    "Range {}-{} of {}/{} failed (attempt {}/{}): {}",
+                    start, end, bucket, name, attempt, RANGE_PART_RETRIES, e
+                );
+                tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                debug!(
+                    "Giving up on range {}-{} of {}/{}",
+                    start, end, bucket, name
+                );
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Pre-transfer estimate of storage egress
+#[derive(Debug, Default, Serialize)]
+pub struct TransferEstimate {
+    pub buckets: usize,
+    pub objects: usize,
+    pub bytes: u64,
+    /// Objects whose size isn't reported by the listing (not counted in `bytes`)
+    pub unknown_sizes: usize,
+}
+
+impl TransferEstimate {
+    /// Egress cost in dollars at the given price per GB
+    pub fn cost(&self, cost_per_gb: f64) -> f64 {
+        self.bytes as f64 / GB as f64 * cost_per_gb
+    }
+}
+
+impl std::fmt::Display for TransferEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} buckets, {} objects, {}",
+            self.buckets,
+            self.objects,
+            human_bytes(self.bytes)
+        )?;
+        if self.unknown_sizes > 0 {
+            write!(f, " (+{} objects of unknown size)", self.unknown_sizes)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct SyncStats {
     pub buckets: usize,
@@ -232,6 +479,19 @@ pub struct SyncStats {
     pub errors: usize,
 }
 
+impl SyncStats {
+    /// Record these stats on the current phase of a run summary
+    pub fn record(&self, summary: &mut RunSummary) {
+        summary.stat("buckets", self.buckets as u64);
+        summary.stat("objects", self.objects as u64);
+        summary.stat("bytes", self.bytes as u64);
+        summary.stat("errors", self.errors as u64);
+        if self.errors > 0 {
+            summary.warn(format!("{} storage transfer errors", self.errors));
+        }
+    }
+}
+
 impl std::fmt::Display for SyncStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -239,7 +499,7 @@ impl std::fmt::Display for SyncStats {
             "{} buckets, {} objects, {} bytes transferred",
             self.buckets,
             self.objects,
-            human_bytes(self.bytes)
+            human_bytes(self.bytes as u64)
         )?;
         if self.errors > 0 {
             write!(f, " ({} errors)", self.errors)?;
@@ -248,11 +508,11 @@ impl std::fmt::Display for SyncStats {
     }
 }
 
-fn human_bytes(bytes: usize) -> String {
-    const KB: usize = 1024;
-    const MB: usize = KB * 1024;
-    const GB: usize = MB * 1024;
+const KB: u64 = 1024;
+const MB: u64 = KB * 1024;
+const GB: u64 = MB * 1024;
 
+pub fn human_bytes(bytes: u64) -> String {
     if bytes >= GB {
         format!("{:.2} GB", bytes as f64 / GB as f64)
     } else if bytes >= MB {
@@ -0,0 +1,271 @@
+mod render;
+
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub use render::{render_html, render_markdown};
+
+const RUNS_DIR: &str = "~/.config/supamigrate/runs";
+
+/// Version of the summary JSON layout. Bump only on breaking changes
+/// (removed or renamed fields); new fields are additive.
+pub const SCHEMA_VERSION: u32 = 1;
+
+fn default_schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Final outcome of a command run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Running,
+    Success,
+    Failed,
+    Cancelled,
+}
+
+/// Status of a single phase within a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Timing and statistics for one phase (database, storage, functions, ...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseSummary {
+    pub name: String,
+    pub status: PhaseStatus,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub stats: BTreeMap<String, u64>,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+/// Structured record of a command run, used for reports and CI summaries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSummary {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    pub command: String,
+    pub tool_version: String,
+    pub outcome: Outcome,
+    pub started_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_secs: f64,
+    /// Resolved options shown in the plan (ordered as displayed)
+    #[serde(default)]
+    pub plan: Vec<(String, String)>,
+    #[serde(default)]
+    pub phases: Vec<PhaseSummary>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    #[serde(default)]
+    pub errors: Vec<String>,
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl RunSummary {
+    pub fn start(command: &str) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            command: command.to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            outcome: Outcome::Running,
+            started_at: Utc::now(),
+            finished_at: None,
+            duration_secs: 0.0,
+            plan: Vec::new(),
+            phases: Vec::new(),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            artifacts: Vec::new(),
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// Record a resolved plan option
+    pub fn plan(&mut self, key: &str, value: impl std::fmt::Display) {
+        self.plan.push((key.to_string(), value.to_string()));
+    }
+
+    /// Start timing a new phase
+    pub fn begin_phase(&mut self, name: &str) {
+        crate::notify::phase(name);
+        self.phases.push(PhaseSummary {
+            name: name.to_string(),
+            status: PhaseStatus::Running,
+            started_at: Utc::now(),
+            duration_secs: 0.0,
+            detail: None,
+            stats: BTreeMap::new(),
+            started: Some(Instant::now()),
+        });
+    }
+
+    /// Record a statistic on the current phase
+    pub fn stat(&mut self, key: &str, value: u64) {
+        if let Some(phase) = self.current_phase() {
+            phase.stats.insert(key.to_string(), value);
+        }
+    }
+
+    /// Mark the current phase completed with a short description of the result
+    pub fn end_phase(&mut self, detail: impl std::fmt::Display) {
+        if let Some(phase) = self.current_phase() {
+            phase.status = PhaseStatus::Completed;
+            phase.detail = Some(detail.to_string());
+            phase.duration_secs = phase.started.map_or(0.0, |s| s.elapsed().as_secs_f64());
+        }
+    }
+
+    pub fn warn(&mut self, message: impl std::fmt::Display) {
+        self.warnings.push(message.to_string());
+    }
+
+    pub fn artifact(&mut self, path: &Path) {
+        self.artifacts.push(path.display().to_string());
+    }
+
+    /// Mark the run as cancelled by the user
+    pub fn cancel(&mut self) {
+        self.outcome = Outcome::Cancelled;
+    }
+
+    /// Record the final outcome of the run
+    pub fn finish<T>(&mut self, result: &anyhow::Result<T>) {
+        if let Err(e) = result {
+            // A failed concurrent phase already merged its error in
+            let message = format!("{:#}", e);
+            if !self.errors.contains(&message) {
+                self.errors.push(message);
+            }
+            if let Some(phase) = self.current_phase() {
+                phase.status = PhaseStatus::Failed;
+                phase.duration_secs = phase.started.map_or(0.0, |s| s.elapsed().as_secs_f64());
+            }
+            self.outcome = Outcome::Failed;
+        } else if self.outcome == Outcome::Running {
+            self.outcome = Outcome::Success;
+        }
+        self.finished_at = Some(Utc::now());
+        self.duration_secs = self.started.map_or(0.0, |s| s.elapsed().as_secs_f64());
+    }
+
+    /// Fold in phases, warnings, errors and artifacts recorded by a
+    /// concurrently run phase
+    pub fn merge(&mut self, other: RunSummary) {
+        self.phases.extend(other.phases);
+        self.warnings.extend(other.warnings);
+        self.errors.extend(other.errors);
+        self.artifacts.extend(other.artifacts);
+    }
+
+    /// Statistics of every phase, by phase name
+    pub fn phase_stats(&self) -> BTreeMap<&str, &BTreeMap<String, u64>> {
+        self.phases
+            .iter()
+            .map(|phase| (phase.name.as_str(), &phase.stats))
+            .collect()
+    }
+
+    fn current_phase(&mut self) -> Option<&mut PhaseSummary> {
+        self.phases
+            .iter_mut()
+            .rev()
+            .find(|p| p.status == PhaseStatus::Running)
+    }
+
+    fn runs_dir() -> PathBuf {
+        PathBuf::from(shellexpand::tilde(RUNS_DIR).as_ref())
+    }
+
+    /// Save the run to the runs directory and return its path
+    pub fn save(&self) -> Result<PathBuf> {
+        let dir = Self::runs_dir();
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "{}_{}.json",
+            self.started_at.format("%Y%m%d_%H%M%S"),
+            self.command.replace(' ', "-")
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Write the summary to an explicit path (`--summary-file`)
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Path of the most recently recorded run
+    pub fn latest_path() -> Result<Option<PathBuf>> {
+        let dir = Self::runs_dir();
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let mut runs: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        runs.sort();
+        Ok(runs.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_json_schema() {
+        let mut summary = RunSummary::start("backup");
+        summary.begin_phase("database");
+        summary.stat("dump_bytes", 42);
+        summary.end_phase("done");
+        summary.warn("vault skipped");
+        summary.finish::<()>(&Ok(()));
+
+        let json: serde_json::Value = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["outcome"], "success");
+        assert_eq!(json["phases"][0]["status"], "completed");
+        assert_eq!(json["phases"][0]["stats"]["dump_bytes"], 42);
+        assert_eq!(json["warnings"][0], "vault skipped");
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let mut summary = RunSummary::start("migrate");
+        let mut storage = RunSummary::start("storage");
+        let result: anyhow::Result<()> = Err(anyhow::anyhow!("bucket missing"));
+        storage.finish(&result);
+        summary.merge(storage);
+        summary.finish(&result);
+        assert_eq!(summary.errors, ["bucket missing"]);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::summary::{Outcome, PhaseStatus, RunSummary};
+use std::fmt::Write;
+
+fn outcome_label(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Running => "running",
+        Outcome::Success => "success",
+        Outcome::Failed => "failed",
+        Outcome::Cancelled => "cancelled",
+    }
+}
+
+fn phase_label(status: PhaseStatus) -> &'static str {
+    match status {
+        PhaseStatus::Running => "running",
+        PhaseStatus::Completed => "completed",
+        PhaseStatus::Failed => "failed",
+    }
+}
+
+fn format_stats(stats: &std::collections::BTreeMap<String, u64>) -> String {
+    stats
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render a run summary as a Markdown report
+pub fn render_markdown(run: &RunSummary) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# supamigrate {} report\n", run.command);
+    let _ = writeln!(out, "| | |\n|---|---|");
+    let _ = writeln!(out, "| Outcome | **{}** |", outcome_label(run.outcome));
+    let _ = writeln!(out, "| Started | {} |", run.started_at.to_rfc3339());
+    if let Some(finished) = run.finished_at {
+        let _ = writeln!(out, "| Finished | {} |", finished.to_rfc3339());
+    }
+    let _ = writeln!(out, "| Duration | {:.1}s |", run.duration_secs);
+    let _ = writeln!(out, "| Tool version | {} |", run.tool_version);
+
+    if !run.plan.is_empty() {
+        let _ = writeln!(out, "\n## Plan\n");
+        for (key, value) in &run.plan {
+            let _ = writeln!(out, "- **{}**: {}", key, value);
+        }
+    }
+
+    if !run.phases.is_empty() {
+        let _ = writeln!(out, "\n## Phases\n");
+        let _ = writeln!(out, "| Phase | Status | Duration | Result | Stats |");
+        let _ = writeln!(out, "|---|---|---|---|---|");
+        for phase in &run.phases {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {:.1}s | {} | {} |",
+                phase.name,
+                phase_label(phase.status),
+                phase.duration_secs,
+                phase.detail.as_deref().unwrap_or(""),
+                format_stats(&phase.stats)
+            );
+        }
+    }
+
+    for (title, items) in [
+        ("Warnings", &run.warnings),
+        ("Errors", &run.errors),
+        ("Artifacts", &run.artifacts),
+    ] {
+        if !items.is_empty() {
+            let _ = writeln!(out, "\n## {}\n", title);
+            for item in items {
+                let _ = writeln!(out, "- {}", item);
+            }
+        }
+    }
+
+    out
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render a run summary as a standalone HTML report
+pub fn render_html(run: &RunSummary) -> String {
+    let mut out = String::new();
+    let title = format!("supamigrate {} report", escape_html(&run.command));
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:960px;margin:2em auto}}\
+         table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style>\n\
+         </head><body>\n<h1>{title}</h1>"
+    );
+
+    let _ = writeln!(out, "<table>");
+    let _ = writeln!(
+        out,
+        "<tr><th>Outcome</th><td><strong>{}</strong></td></tr>",
+        outcome_label(run.outcome)
+    );
+    let _ = writeln!(
+        out,
+        "<tr><th>Started</th><td>{}</td></tr>",
+        run.started_at.to_rfc3339()
+    );
+    if let Some(finished) = run.finished_at {
+        let _ = writeln!(
+            out,
+            "<tr><th>Finished</th><td>{}</td></tr>",
+            finished.to_rfc3339()
+        );
+    }
+    let _ = writeln!(
+        out,
+        "<tr><th>Duration</th><td>{:.1}s</td></tr>",
+        run.duration_secs
+    );
+    let _ = writeln!(
+        out,
+        "<tr><th>Tool version</th><td>{}</td></tr>\n</table>",
+        escape_html(&run.tool_version)
+    );
+
+    if !run.plan.is_empty() {
+        let _ = writeln!(out, "<h2>Plan</h2>\n<ul>");
+        for (key, value) in &run.plan {
+            let _ = writeln!(
+                out,
+                "<li><strong>{}</strong>: {}</li>",
+                escape_html(key),
+                escape_html(value)
+            );
+        }
+        let _ = writeln!(out, "</ul>");
+    }
+
+    if !run.phases.is_empty() {
+        let _ = writeln!(
+            out,
+            "<h2>Phases</h2>\n<table>\n<tr><th>Phase</th><th>Status</th><th>Duration</th><th>Result</th><th>Stats</th></tr>"
+        );
+        for phase in &run.phases {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{:.1}s</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&phase.name),
+                phase_label(phase.status),
+                phase.duration_secs,
+                escape_html(phase.detail.as_deref().unwrap_or("")),
+                escape_html(&format_stats(&phase.stats))
+            );
+        }
+        let _ = writeln!(out, "</table>");
+    }
+
+    for (title, items) in [
+        ("Warnings", &run.warnings),
+        ("Errors", &run.errors),
+        ("Artifacts", &run.artifacts),
+    ] {
+        if !items.is_empty() {
+            let _ = writeln!(out, "<h2>{}</h2>\n<ul>", title);
+            for item in items {
+                let _ = writeln!(out, "<li>{}</li>", escape_html(item));
+            }
+            let _ = writeln!(out, "</ul>");
+        }
+    }
+
+    let _ = writeln!(out, "</body></html>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_run() -> RunSummary {
+        let mut run = RunSummary::start("migrate");
+        run.plan("Source", "production (abc)");
+        run.begin_phase("database");
+        run.stat("bytes", 42);
+        run.end_phase("restored");
+        run.begin_phase("storage");
+        run.warn("bucket <avatars> skipped");
+        let result: anyhow::Result<()> = Err(anyhow::anyhow!("upload failed"));
+        run.finish(&result);
+        run
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let report = render_markdown(&sample_run());
+        assert!(report.contains("| Outcome | **failed** |"));
+        assert!(report.contains("- **Source**: production (abc)"));
+        assert!(report.contains("| database | completed |"));
+        assert!(report.contains("bytes: 42"));
+        assert!(report.contains("| storage | failed |"));
+        assert!(report.contains("- upload failed"));
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let report = render_html(&sample_run());
+        assert!(report.contains("bucket &lt;avatars&gt; skipped"));
+        assert!(report.starts_with("<!DOCTYPE html>"));
+    }
+}